@@ -2,7 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 mod local_cache;
+mod signing;
 mod synchronizer;
 
 pub use local_cache::*;
+pub use signing::{
+    load_signing_key, load_verifying_key, IndexSignatureInvalid, SigningKey, VerifyingKey,
+};
 pub use synchronizer::*;
@@ -0,0 +1,122 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Signer, Verifier};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// The trailer in an index commit message that carries the signature over the index.
+pub(crate) const SIGNATURE_TRAILER: &str = "Index-Signature";
+
+/// A fetched index that is unsigned, or whose signature does not verify against the configured public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexSignatureInvalid {
+    pub reference_name: String,
+    pub reason: String,
+}
+
+impl Display for IndexSignatureInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Index {} failed signature verification: {}",
+            self.reference_name, self.reason
+        )
+    }
+}
+
+impl std::error::Error for IndexSignatureInvalid {}
+
+fn read_key_bytes(path: &Path) -> Result<[u8; 32]> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading key from {}", path.display()))?;
+    let bytes = hex::decode(contents.trim())
+        .with_context(|| format!("Decoding hex key in {}", path.display()))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        anyhow::anyhow!(
+            "Key in {} is {} bytes long, but ed25519 keys are 32 bytes",
+            path.display(),
+            bytes.len()
+        )
+    })
+}
+
+/// Load an ed25519 signing key stored as 64 hex digits in the file at `path`.
+pub fn load_signing_key(path: &Path) -> Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&read_key_bytes(path)?))
+}
+
+/// Load an ed25519 public key stored as 64 hex digits in the file at `path`.
+pub fn load_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&read_key_bytes(path)?).with_context(|| {
+        format!(
+            "Key in {} is not a valid ed25519 public key",
+            path.display()
+        )
+    })
+}
+
+/// The bytes that are signed for an index. The keyset ID is included so that a signed index cannot be presented as the index for another keyset.
+fn signed_payload(keyset_id: &str, checksum: &str) -> Vec<u8> {
+    format!("{}\n{}", keyset_id, checksum).into_bytes()
+}
+
+/// Sign the index for `keyset_id` with entries summarized by `checksum`, returning the signature as hex.
+pub(crate) fn sign(key: &SigningKey, keyset_id: &str, checksum: &str) -> String {
+    hex::encode(key.sign(&signed_payload(keyset_id, checksum)).to_bytes())
+}
+
+/// Check that `signature` (as hex) was made by `key` over the index for `keyset_id` with entries summarized by `checksum`. The error describes why verification failed.
+pub(crate) fn verify(
+    key: &VerifyingKey,
+    keyset_id: &str,
+    checksum: &str,
+    signature: &str,
+) -> std::result::Result<(), String> {
+    let bytes = hex::decode(signature).map_err(|e| format!("malformed signature: {}", e))?;
+    let signature =
+        Signature::from_slice(&bytes).map_err(|e| format!("malformed signature: {}", e))?;
+    key.verify(&signed_payload(keyset_id, checksum), &signature)
+        .map_err(|_| String::from("signature does not match the public key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_loaded_and_signatures_verified() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let signing_key_path = dir.path().join("index.key");
+        let verifying_key_path = dir.path().join("index.pub");
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        std::fs::write(
+            &signing_key_path,
+            format!("{}\n", hex::encode(signing_key.to_bytes())),
+        )?;
+        std::fs::write(
+            &verifying_key_path,
+            hex::encode(signing_key.verifying_key().to_bytes()),
+        )?;
+
+        let signing_key = load_signing_key(&signing_key_path)?;
+        let verifying_key = load_verifying_key(&verifying_key_path)?;
+        let signature = sign(&signing_key, "keyset", "checksum");
+        assert_eq!(
+            verify(&verifying_key, "keyset", "checksum", &signature),
+            Ok(())
+        );
+        assert!(verify(&verifying_key, "other-keyset", "checksum", &signature).is_err());
+        assert!(verify(&verifying_key, "keyset", "other-checksum", &signature).is_err());
+        assert!(verify(&verifying_key, "keyset", "checksum", "not hex").is_err());
+
+        std::fs::write(&signing_key_path, "abcd")?;
+        assert!(load_signing_key(&signing_key_path).is_err());
+
+        Ok(())
+    }
+}
@@ -1,18 +1,20 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::signing::{self, IndexSignatureInvalid, SigningKey, VerifyingKey, SIGNATURE_TRAILER};
 use crate::{Cache, CacheKey, CacheKeyKind, CompositeKey};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 use core::fmt;
 use focus_util::{app::App, git_helper};
 use git2::Oid;
 use git2::{Commit, Repository};
 use regex::Regex;
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Display};
 use std::ops::AddAssign;
 use std::{collections::HashSet, path::PathBuf, str::FromStr, sync::Arc};
-use tracing::{info, instrument, warn};
+use tracing::{debug, info, instrument, warn};
 
 pub type Keyset = HashSet<(CacheKeyKind, CacheKey)>;
 pub type KeysetID = Oid;
@@ -28,6 +30,77 @@ pub fn tag_fmt(namespace: impl Display, tag_name: impl Display) -> String {
     format!("refs/tags/{}/{}", namespace, tag_name)
 }
 
+/// The tag under which the named snapshot `name` is stored. Snapshots live apart from keyset tags so that they are never mistaken for keysets.
+pub fn snapshot_tag_fmt(namespace: impl Display, name: impl Display) -> String {
+    format!("refs/tags/{}/snapshots/{}", namespace, name)
+}
+
+/// The trailer in an index commit message that records the checksum of the index entries.
+const CHECKSUM_TRAILER: &str = "Index-Checksum";
+
+/// A fetched index whose entries do not match the checksum recorded when it was shared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexChecksumMismatch {
+    pub reference_name: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Display for IndexChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Index {} failed checksum verification (expected {}, got {})",
+            self.reference_name, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for IndexChecksumMismatch {}
+
+/// An index remote that rejected a test write, so sharing to it would fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteReadOnly {
+    pub remote: String,
+    pub reason: String,
+}
+
+impl Display for RemoteReadOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Index remote {} is read-only: a test write was rejected ({})",
+            self.remote, self.reason
+        )
+    }
+}
+
+impl std::error::Error for RemoteReadOnly {}
+
+/// Compute the checksum of an index from the names of its entries and the hashes of their contents. `entries` is ordered by name so that the checksum does not depend on the order in which the tree was built.
+fn index_checksum(entries: &BTreeMap<String, Oid>) -> Result<Oid> {
+    let manifest: String = entries
+        .iter()
+        .map(|(name, content_hash)| format!("{} {}\n", name, content_hash))
+        .collect();
+    Oid::hash_object(git2::ObjectType::Blob, manifest.as_bytes())
+        .context("Computing index checksum")
+}
+
+/// The value of `trailer` in an index commit message, if present.
+fn trailer_from_message<'a>(message: &'a str, trailer: &str) -> Option<&'a str> {
+    let prefix = format!("{}: ", trailer);
+    message
+        .lines()
+        .find_map(|line| line.strip_prefix(prefix.as_str()))
+        .map(str::trim)
+}
+
+/// The keyset ID an index commit message says the index was written for.
+fn keyset_id_from_message(message: &str) -> Option<&str> {
+    message.lines().next()?.strip_prefix("index for ")
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct PopulateResult {
     pub entry_count: usize,
@@ -83,6 +156,15 @@ pub trait CacheSynchronizer: Debug {
         previous_keyset_id: Option<KeysetID>,
     ) -> Result<git2::Oid>;
     fn available_remote_keysets(&self) -> Result<HashSet<KeysetID>>;
+
+    /// The first of `candidates` that is available on the remote, if any.
+    fn find_remote_keyset(&self, candidates: &[KeysetID]) -> Result<Option<KeysetID>> {
+        let available = self.available_remote_keysets()?;
+        Ok(candidates
+            .iter()
+            .find(|keyset_id| available.contains(keyset_id))
+            .copied())
+    }
 }
 
 /// Synchronize using Git as a key-value store. Keysets are pushed as tags to
@@ -98,6 +180,8 @@ pub struct GitBackedCacheSynchronizer {
     username: String,
     namespace: String,
     parse_tags_regex: Regex,
+    signing_key: Option<SigningKey>,
+    verifying_key: Option<VerifyingKey>,
 }
 
 impl fmt::Debug for GitBackedCacheSynchronizer {
@@ -134,29 +218,313 @@ impl GitBackedCacheSynchronizer {
             username,
             namespace,
             parse_tags_regex,
+            signing_key: None,
+            verifying_key: None,
         })
     }
-}
 
-impl CacheSynchronizer for GitBackedCacheSynchronizer {
-    #[instrument]
-    fn fetch(&self, keyset_id: KeysetID) -> Result<KeysetID> {
+    /// Sign the indexes this synchronizer shares with `key`.
+    pub fn with_signing_key(self, key: SigningKey) -> Self {
+        Self {
+            signing_key: Some(key),
+            ..self
+        }
+    }
+
+    /// Refuse to populate from fetched indexes unless they are signed by the private half of `key`.
+    pub fn with_verifying_key(self, key: VerifyingKey) -> Self {
+        Self {
+            verifying_key: Some(key),
+            ..self
+        }
+    }
+
+    /// Share `keyset` as the named snapshot `name`. Snapshots are immutable: if one with this name already exists on the remote, this fails unless `force` is set.
+    #[instrument(skip(keyset, cache))]
+    pub fn share_snapshot(
+        &self,
+        name: &str,
+        keyset_id: KeysetID,
+        keyset: &Keyset,
+        cache: &dyn Cache,
+        force: bool,
+    ) -> Result<git2::Oid> {
+        let tag = self.snapshot_tag(name)?;
+        if !force && self.remote_snapshot_exists(name)? {
+            bail!(
+                "Index snapshot '{}' already exists on the remote (use --force to overwrite it)",
+                name
+            );
+        }
+
+        let commit_oid = self.write_keyset_commit(keyset_id, keyset, cache, &[])?;
+        self.repo
+            .reference(&tag, commit_oid, true, "Index snapshot")
+            .context("updating snapshot reference")?;
+        let refspec = format!("{}{}:{}", if force { "+" } else { "" }, tag, tag);
+        git_helper::push_refs(
+            self.path.as_path(),
+            vec![refspec],
+            self.remote.as_str(),
+            self.app.clone(),
+        )
+        .with_context(|| format!("Pushing index snapshot '{}'", name))?;
+        Ok(commit_oid)
+    }
+
+    /// Fetch the named snapshot `name` and populate `dest_cache` with its contents.
+    #[instrument(skip(dest_cache))]
+    pub fn fetch_and_populate_snapshot(
+        &self,
+        name: &str,
+        dest_cache: &dyn Cache,
+    ) -> Result<PopulateResult> {
+        let tag = self.snapshot_tag(name)?;
         git_helper::fetch_refs(
             self.path.as_path(),
-            [refspec_fmt(&self.namespace, keyset_id)].iter(),
+            [format!("+{}:{}", tag, tag)].iter(),
             self.remote.as_str(),
             self.app.clone(),
             Some(1),
         )
-        .context("Fetching")
-        .map(|_x| keyset_id)
+        .with_context(|| format!("Fetching index snapshot '{}'", name))?;
+        self.verify_or_discard(&tag, None)?;
+        let populate_result = self
+            .populate_from_reference(&tag, dest_cache)
+            .with_context(|| format!("Populating cache from index snapshot '{}'", name))?;
+        if !populate_result.is_noop() {
+            info!(?populate_result, snapshot = %name, "Populated index");
+        }
+        Ok(populate_result)
     }
 
-    #[instrument]
-    fn populate(&self, keyset_id: &KeysetID, dest_cache: &dyn Cache) -> Result<PopulateResult> {
+    /// Whether the named snapshot `name` exists on the remote.
+    pub fn remote_snapshot_exists(&self, name: &str) -> Result<bool> {
+        let tag = self.snapshot_tag(name)?;
+        let result = git_helper::ls_remote(&self.remote, self.app.clone())?;
+        Ok(result
+            .lines()
+            .any(|line| line.split_once('\t').map(|(_, refname)| refname) == Some(tag.as_str())))
+    }
+
+    /// Check that the remote accepts pushes by pushing a throwaway tag and deleting it again. Fails with `RemoteReadOnly` if the push is rejected.
+    pub fn check_remote_is_writable(&self) -> Result<()> {
+        let signature = git2::Signature::now(&self.username, &self.email)?;
+        let empty_tree_oid = self
+            .repo
+            .treebuilder(None)
+            .context("initializing new TreeBuilder")?
+            .write()
+            .context("writing empty tree")?;
+        let commit_oid = self.repo.commit(
+            None,
+            &signature,
+            &signature,
+            "index remote write check",
+            &self.repo.find_tree(empty_tree_oid)?,
+            &[],
+        )?;
+        let tag = format!("refs/tags/{}/write-check/{}", self.namespace, commit_oid);
+        let mut reference = self
+            .repo
+            .reference(&tag, commit_oid, true, "Index remote write check")
+            .context("creating write check reference")?;
+
+        let result = git_helper::push_refs(
+            self.path.as_path(),
+            vec![format!("{}:{}", tag, tag)],
+            self.remote.as_str(),
+            self.app.clone(),
+        );
+        reference
+            .delete()
+            .context("deleting write check reference")?;
+        if let Err(e) = result {
+            return Err(anyhow::Error::new(RemoteReadOnly {
+                remote: self.remote.clone(),
+                reason: format!("{:#}", e),
+            }));
+        }
+
+        if let Err(e) = git_helper::push_refs(
+            self.path.as_path(),
+            vec![format!(":{}", tag)],
+            self.remote.as_str(),
+            self.app.clone(),
+        ) {
+            warn!(?e, %tag, "Failed to delete the write check tag from the remote");
+        }
+        Ok(())
+    }
+
+    fn snapshot_tag(&self, name: &str) -> Result<String> {
+        let tag = snapshot_tag_fmt(&self.namespace, name);
+        if name.is_empty() || !git2::Reference::is_valid_name(&tag) {
+            bail!("'{}' is not a valid index snapshot name", name);
+        }
+        Ok(tag)
+    }
+
+    /// Write a commit whose tree holds the entries of `keyset`.
+    fn write_keyset_commit(
+        &self,
+        keyset_id: KeysetID,
+        keyset: &Keyset,
+        cache: &dyn Cache,
+        parents: &[&Commit],
+    ) -> Result<git2::Oid> {
+        let mut kv_tree = self
+            .repo
+            .treebuilder(None)
+            .context("initializing new TreeBuilder")?;
+
+        let mut entries = BTreeMap::new();
+        for (kind, key) in keyset.iter() {
+            let payload = cache.get(*kind, *key)?.unwrap();
+            let value_oid = self
+                .repo
+                .blob(&payload)
+                .context("writing DependencyValue as blob")?;
+
+            let name = CompositeKey {
+                key: *key,
+                kind: *kind,
+            }
+            .to_string();
+            kv_tree
+                .insert(&name, value_oid, git2::FileMode::Blob.into())
+                .context("adding entry to tree")?;
+            entries.insert(name, value_oid);
+        }
+
+        let kv_tree_oid = kv_tree.write().context("writing new tree")?;
+        let checksum = index_checksum(&entries)?.to_string();
+        let mut message = format!(
+            "index for {}\n\n{}: {}",
+            keyset_id, CHECKSUM_TRAILER, checksum
+        );
+        if let Some(signing_key) = &self.signing_key {
+            let index_signature = signing::sign(signing_key, &keyset_id.to_string(), &checksum);
+            message.push_str(&format!("\n{}: {}", SIGNATURE_TRAILER, index_signature));
+        }
+        let signature = git2::Signature::now(&self.username, &self.email)?;
+        let commit_oid = self.repo.commit(
+            None,
+            &signature,
+            &signature,
+            &message,
+            &self.repo.find_tree(kv_tree_oid)?,
+            parents,
+        )?;
+        Ok(commit_oid)
+    }
+
+    /// Check the entries of the index at `reference_name` against the checksum recorded in its commit message. The content of every entry is hashed again rather than trusting the object IDs in the tree. Indexes shared before checksums were recorded are accepted as-is, unless a verifying key is set, in which case the index must be signed for `expected_keyset_id` (if given).
+    fn verify_reference(
+        &self,
+        reference_name: &str,
+        expected_keyset_id: Option<&str>,
+    ) -> Result<()> {
+        let commit = self
+            .repo
+            .find_reference(reference_name)
+            .context("Resolving reference")?
+            .peel_to_commit()
+            .context("Resolving commit")?;
+        let message = commit.message().unwrap_or_default();
+        if let Some(verifying_key) = &self.verifying_key {
+            Self::verify_signature(verifying_key, message, expected_keyset_id).map_err(
+                |reason| IndexSignatureInvalid {
+                    reference_name: reference_name.to_owned(),
+                    reason,
+                },
+            )?;
+        }
+
+        let expected = match trailer_from_message(message, CHECKSUM_TRAILER) {
+            Some(expected) => expected.to_owned(),
+            None => {
+                debug!(%reference_name, "Index has no checksum, skipping verification");
+                return Ok(());
+            }
+        };
+
+        let kv_tree = commit.tree().context("Resolving tree")?;
+        let mut entries = BTreeMap::new();
+        for tree_entry in kv_tree.iter() {
+            let name = String::from_utf8_lossy(tree_entry.name_bytes()).into_owned();
+            let content_hash = match tree_entry
+                .to_object(&self.repo)
+                .ok()
+                .and_then(|object| object.into_blob().ok())
+            {
+                Some(blob) => Oid::hash_object(git2::ObjectType::Blob, blob.content())?,
+                None => Oid::zero(),
+            };
+            entries.insert(name, content_hash);
+        }
+
+        let actual = index_checksum(&entries)?.to_string();
+        if actual != expected {
+            return Err(IndexChecksumMismatch {
+                reference_name: reference_name.to_owned(),
+                expected,
+                actual,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn verify_signature(
+        verifying_key: &VerifyingKey,
+        message: &str,
+        expected_keyset_id: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let keyset_id = keyset_id_from_message(message)
+            .ok_or_else(|| String::from("the index does not name its keyset"))?;
+        if let Some(expected_keyset_id) = expected_keyset_id {
+            if keyset_id != expected_keyset_id {
+                return Err(format!(
+                    "the index was signed for keyset {}, not {}",
+                    keyset_id, expected_keyset_id
+                ));
+            }
+        }
+        let checksum = trailer_from_message(message, CHECKSUM_TRAILER)
+            .ok_or_else(|| String::from("the index has no checksum"))?;
+        let signature = trailer_from_message(message, SIGNATURE_TRAILER)
+            .ok_or_else(|| String::from("the index is not signed"))?;
+        signing::verify(verifying_key, keyset_id, checksum, signature)
+    }
+
+    /// Verify the fetched index at `reference_name`, deleting the reference if verification fails so that its contents are never used.
+    fn verify_or_discard(
+        &self,
+        reference_name: &str,
+        expected_keyset_id: Option<&str>,
+    ) -> Result<()> {
+        if let Err(e) = self.verify_reference(reference_name, expected_keyset_id) {
+            if let Err(delete_error) = self
+                .repo
+                .find_reference(reference_name)
+                .and_then(|mut reference| reference.delete())
+            {
+                warn!(%reference_name, ?delete_error, "Failed to discard index");
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn populate_from_reference(
+        &self,
+        reference_name: &str,
+        dest_cache: &dyn Cache,
+    ) -> Result<PopulateResult> {
         let commit = self
             .repo
-            .find_reference(&tag_fmt(&self.namespace, keyset_id))
+            .find_reference(reference_name)
             .context("Resolving reference")?;
         let kv_tree = commit.peel_to_tree().context("Resolving tree")?;
 
@@ -229,6 +597,26 @@ impl CacheSynchronizer for GitBackedCacheSynchronizer {
             failed_entry_count,
         })
     }
+}
+
+impl CacheSynchronizer for GitBackedCacheSynchronizer {
+    #[instrument]
+    fn fetch(&self, keyset_id: KeysetID) -> Result<KeysetID> {
+        git_helper::fetch_refs(
+            self.path.as_path(),
+            [refspec_fmt(&self.namespace, keyset_id)].iter(),
+            self.remote.as_str(),
+            self.app.clone(),
+            Some(1),
+        )
+        .context("Fetching")
+        .map(|_x| keyset_id)
+    }
+
+    #[instrument]
+    fn populate(&self, keyset_id: &KeysetID, dest_cache: &dyn Cache) -> Result<PopulateResult> {
+        self.populate_from_reference(&tag_fmt(&self.namespace, keyset_id), dest_cache)
+    }
 
     fn fetch_and_populate(
         &self,
@@ -236,6 +624,10 @@ impl CacheSynchronizer for GitBackedCacheSynchronizer {
         dest_cache: &dyn Cache,
     ) -> Result<(PopulateResult, KeysetID)> {
         let fetched_keyset_id = self.fetch(keyset_id).context("Fetching index updates")?;
+        self.verify_or_discard(
+            &tag_fmt(&self.namespace, fetched_keyset_id),
+            Some(&fetched_keyset_id.to_string()),
+        )?;
 
         let populate_result = self
             .populate(&fetched_keyset_id, dest_cache)
@@ -255,33 +647,6 @@ impl CacheSynchronizer for GitBackedCacheSynchronizer {
         cache: &dyn Cache,
         previous_keyset_id: Option<KeysetID>,
     ) -> Result<KeysetID> {
-        let mut kv_tree = self
-            .repo
-            .treebuilder(None)
-            .context("initializing new TreeBuilder")?;
-
-        for (kind, key) in keyset.iter() {
-            let payload = cache.get(*kind, *key)?.unwrap();
-            let value_oid = self
-                .repo
-                .blob(&payload)
-                .context("writing DependencyValue as blob")?;
-
-            kv_tree
-                .insert(
-                    CompositeKey {
-                        key: *key,
-                        kind: *kind,
-                    }
-                    .to_string(),
-                    value_oid,
-                    git2::FileMode::Blob.into(),
-                )
-                .context("adding entry to tree")?;
-        }
-
-        let kv_tree_oid = kv_tree.write().context("writing new tree")?;
-        let signature = git2::Signature::now(&self.username, &self.email)?;
         let prev_commit_vec = match previous_keyset_id {
             Some(prev_commit_oid) => vec![self
                 .repo
@@ -290,15 +655,8 @@ impl CacheSynchronizer for GitBackedCacheSynchronizer {
             None => vec![],
         };
         let vec_of_prev_commit_references: Vec<&Commit> = prev_commit_vec.iter().collect();
-
-        let commit_oid = self.repo.commit(
-            None,
-            &signature,
-            &signature,
-            &format!("index for {}", keyset_id)[..],
-            &self.repo.find_tree(kv_tree_oid)?,
-            &vec_of_prev_commit_references[..],
-        )?;
+        let commit_oid =
+            self.write_keyset_commit(keyset_id, keyset, cache, &vec_of_prev_commit_references[..])?;
 
         let refspecs = vec![refspec_fmt(&self.namespace, keyset_id)];
 
@@ -352,7 +710,7 @@ mod tests {
 
     use anyhow::{Context, Result};
 
-    use crate::{tag_fmt, Cache, CompositeKey, RocksDBCache};
+    use crate::{tag_fmt, Cache, CompositeKey, RocksDBCache, SigningKey};
     use crate::{CacheKey, CacheKeyKind, CacheSynchronizer, Keyset};
     use focus_util::app::App;
 
@@ -591,6 +949,190 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_snapshot_share_fetch() -> anyhow::Result<()> {
+        let (_server_dir, server_path) = setup_server_repo_locally().unwrap();
+        let server_string = format!("file://{}", server_path.display());
+        let (_git_cache_dir_1, memo_cache_sync_1) =
+            setup_local_git_cache("fairly-local", server_string.as_str());
+        let (_git_cache_dir_2, memo_cache_sync_2) =
+            setup_local_git_cache("fairly-local2", server_string.as_str());
+        let (_rocks_dir_1, memo_cache_1) = setup_rocks_db("cache-rocks1");
+        let (_rocks_dir_2, memo_cache_2) = setup_rocks_db("cache-rocks2");
+
+        let kind = kind();
+        let commit_1_keys = populate_demo_hashset(&memo_cache_1, kind);
+        assert!(!memo_cache_sync_1.remote_snapshot_exists("release-1")?);
+        memo_cache_sync_1.share_snapshot(
+            "release-1",
+            keyset_id_1(),
+            &commit_1_keys,
+            &memo_cache_1,
+            false,
+        )?;
+        assert!(memo_cache_sync_2.remote_snapshot_exists("release-1")?);
+
+        // Snapshots are not keysets.
+        assert!(memo_cache_sync_2.available_remote_keysets()?.is_empty());
+
+        // Snapshots cannot be overwritten without forcing.
+        let commit_2_keys = populate_demo_hashset(&memo_cache_1, kind);
+        assert!(memo_cache_sync_1
+            .share_snapshot(
+                "release-1",
+                keyset_id_2(),
+                &commit_2_keys,
+                &memo_cache_1,
+                false
+            )
+            .is_err());
+
+        let results = memo_cache_sync_2.fetch_and_populate_snapshot("release-1", &memo_cache_2)?;
+        assert_eq!(results.new_entry_count, RANDOM_KEY_COUNT);
+        assert_caches_match(commit_1_keys, &memo_cache_1, &memo_cache_2);
+        assert_cache_doesnt_contain(commit_2_keys.clone(), &memo_cache_2);
+
+        memo_cache_sync_1.share_snapshot(
+            "release-1",
+            keyset_id_2(),
+            &commit_2_keys,
+            &memo_cache_1,
+            true,
+        )?;
+        memo_cache_sync_2.fetch_and_populate_snapshot("release-1", &memo_cache_2)?;
+        assert_caches_match(commit_2_keys, &memo_cache_1, &memo_cache_2);
+
+        assert!(memo_cache_sync_2
+            .fetch_and_populate_snapshot("release-2", &memo_cache_2)
+            .is_err());
+        assert!(memo_cache_sync_2
+            .remote_snapshot_exists("bad..name")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_discards_corrupted_index() -> anyhow::Result<()> {
+        let (_server_dir, server_path) = setup_server_repo_locally().unwrap();
+        let server_string = server_path.clone().into_os_string().into_string().unwrap();
+        let (_git_cache_dir_1, memo_cache_sync_1) =
+            setup_local_sync_cache("fairly-local", server_string.as_str());
+        let (_git_cache_dir_2, memo_cache_sync_2) =
+            setup_local_git_cache("fairly-local2", server_string.as_str());
+        let (_rocks_dir_1, memo_cache_1) = setup_rocks_db("cache-rocks1");
+        let (_rocks_dir_2, memo_cache_2) = setup_rocks_db("cache-rocks2");
+
+        let keyset_id = keyset_id_1();
+        let commit_keys = populate_demo_hashset(&memo_cache_1, kind());
+        memo_cache_sync_1.share(keyset_id, &commit_keys, &memo_cache_1, None)?;
+
+        // Replace one value on the server while keeping the original commit message and its checksum.
+        let tag = tag_fmt("cache", keyset_id);
+        {
+            let server_repo = Repository::open(&server_path)?;
+            let commit = server_repo.find_reference(&tag)?.peel_to_commit()?;
+            let tree = commit.tree()?;
+            let corrupted_name = tree.iter().next().unwrap().name().unwrap().to_owned();
+            let corrupted_blob = server_repo.blob(b"corrupted")?;
+            let mut builder = server_repo.treebuilder(Some(&tree))?;
+            builder.insert(&corrupted_name, corrupted_blob, git2::FileMode::Blob.into())?;
+            let corrupted_tree = server_repo.find_tree(builder.write()?)?;
+            let signature = commit.author();
+            let corrupted_commit = server_repo.commit(
+                None,
+                &signature,
+                &signature,
+                commit.message().unwrap(),
+                &corrupted_tree,
+                &[],
+            )?;
+            server_repo.reference(&tag, corrupted_commit, true, "corrupt index")?;
+        }
+
+        let error = memo_cache_sync_2
+            .fetch_and_populate(keyset_id, &memo_cache_2)
+            .unwrap_err();
+        assert!(error.downcast_ref::<IndexChecksumMismatch>().is_some());
+        assert_cache_doesnt_contain(commit_keys, &memo_cache_2);
+        assert!(memo_cache_sync_2.repo.find_reference(&tag).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed_fetch() -> anyhow::Result<()> {
+        let (_server_dir, server_path) = setup_server_repo_locally().unwrap();
+        let server_string = server_path.into_os_string().into_string().unwrap();
+        let signing_key = SigningKey::from_bytes(&[1; 32]);
+        let other_key = SigningKey::from_bytes(&[2; 32]);
+        let (_git_cache_dir_1, memo_cache_sync_1) =
+            setup_local_git_cache("fairly-local", server_string.as_str());
+        let memo_cache_sync_1 = memo_cache_sync_1.with_signing_key(signing_key.clone());
+        let (_git_cache_dir_2, memo_cache_sync_2) =
+            setup_local_git_cache("fairly-local2", server_string.as_str());
+        let memo_cache_sync_2 = memo_cache_sync_2.with_verifying_key(other_key.verifying_key());
+        let (_git_cache_dir_3, memo_cache_sync_3) =
+            setup_local_git_cache("fairly-local3", server_string.as_str());
+        let memo_cache_sync_3 = memo_cache_sync_3.with_verifying_key(signing_key.verifying_key());
+        let (_rocks_dir_1, memo_cache_1) = setup_rocks_db("cache-rocks1");
+        let (_rocks_dir_2, memo_cache_2) = setup_rocks_db("cache-rocks2");
+        let (_rocks_dir_3, memo_cache_3) = setup_rocks_db("cache-rocks3");
+
+        let commit_keys = populate_demo_hashset(&memo_cache_1, kind());
+        memo_cache_sync_1.share(keyset_id_1(), &commit_keys, &memo_cache_1, None)?;
+
+        // The index was not signed by the key the consumer trusts.
+        let error = memo_cache_sync_2
+            .fetch_and_populate(keyset_id_1(), &memo_cache_2)
+            .unwrap_err();
+        assert!(error.downcast_ref::<IndexSignatureInvalid>().is_some());
+        assert_cache_doesnt_contain(commit_keys.clone(), &memo_cache_2);
+
+        memo_cache_sync_3.fetch_and_populate(keyset_id_1(), &memo_cache_3)?;
+        assert_caches_match(commit_keys, &memo_cache_1, &memo_cache_3);
+
+        // Unsigned indexes are refused when a key is configured.
+        let (_git_cache_dir_4, memo_cache_sync_4) =
+            setup_local_git_cache("fairly-local4", server_string.as_str());
+        let unsigned_keys = populate_demo_hashset(&memo_cache_1, kind());
+        memo_cache_sync_4.share(keyset_id_2(), &unsigned_keys, &memo_cache_1, None)?;
+        let error = memo_cache_sync_3
+            .fetch_and_populate(keyset_id_2(), &memo_cache_3)
+            .unwrap_err();
+        assert!(error.downcast_ref::<IndexSignatureInvalid>().is_some());
+        assert_cache_doesnt_contain(unsigned_keys, &memo_cache_3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_remote_is_writable() -> anyhow::Result<()> {
+        let (_server_dir, server_path) = setup_server_repo_locally()?;
+        let remote = format!("file://{}", server_path.display());
+        let (_dir, synchronizer) = setup_local_git_cache("writable", &remote);
+        synchronizer.check_remote_is_writable()?;
+        let server_repo = Repository::open_bare(&server_path)?;
+        assert_eq!(server_repo.references()?.count(), 0);
+
+        // A remote that rejects every push, like a read-only mirror.
+        let (_read_only_dir, read_only_path) = setup_server_repo_locally()?;
+        let hook_path = read_only_path.join("hooks").join("pre-receive");
+        std::fs::write(
+            &hook_path,
+            "#!/bin/sh\necho 'this mirror is read-only' >&2\nexit 1\n",
+        )?;
+        std::fs::set_permissions(
+            &hook_path,
+            std::os::unix::fs::PermissionsExt::from_mode(0o755),
+        )?;
+        let remote = format!("file://{}", read_only_path.display());
+        let (_dir, synchronizer) = setup_local_git_cache("read-only", &remote);
+        let error = synchronizer.check_remote_is_writable().unwrap_err();
+        let read_only = error.downcast_ref::<RemoteReadOnly>().unwrap();
+        assert_eq!(read_only.remote, remote);
+        assert!(error.to_string().contains("is read-only"));
+
+        Ok(())
+    }
+
     #[test]
     pub fn refspec_formatting() {
         assert_eq!(refspec_fmt("cache", keyset_id_1()), String::from("+refs/tags/cache/abcd1abcd1abcd1abcd100000000000000000000:refs/tags/cache/abcd1abcd1abcd1abcd100000000000000000000"));
@@ -607,5 +1149,9 @@ mod tests {
             String::from("refs/tags/cache/abcd1abcd1abcd1abcd100000000000000000000"),
         );
         assert_eq!(tag_fmt("cache", "foo"), String::from("refs/tags/cache/foo"));
+        assert_eq!(
+            snapshot_tag_fmt("cache", "release-1"),
+            String::from("refs/tags/cache/snapshots/release-1")
+        );
     }
 }
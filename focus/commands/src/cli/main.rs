@@ -7,7 +7,7 @@ use std::{
     path::{Path, PathBuf},
     sync::Arc,
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use anyhow::{bail, Context, Result};
@@ -20,11 +20,18 @@ use focus_util::{
     app::{App, ExitCode},
     git_helper::{self, GitVersion},
     lock_file::LockFile,
-    paths, sandbox,
+    paths,
+    progress::ProgressMode,
+    sandbox,
     time::FocusTime,
 };
 
-use focus_internals::{target::TargetTypes, tracker::Tracker};
+use focus_internals::{
+    model::selection::{AddOptions, Interpretation},
+    target::TargetTypes,
+    target_resolver::BazelOptions,
+    tracker::Tracker,
+};
 use focus_operations::{
     clone::{CloneArgs, ClonedRepoTemplate},
     maintenance::{self, ScheduleOpts},
@@ -34,7 +41,7 @@ use focus_operations::{
 };
 use strum::VariantNames;
 use termion::{color, style};
-use tracing::{debug, debug_span, error, info};
+use tracing::{debug, debug_span, error, info, metadata::LevelFilter};
 
 #[derive(Parser, Clone, Debug)]
 struct NewArgs {
@@ -54,6 +61,14 @@ struct NewArgs {
     #[clap(long, default_value = "90")]
     days_of_history: u64,
 
+    /// Keep the history since this date (formatted as YYYY-MM-DD) instead of `--days-of-history`.
+    #[clap(long, value_name = "DATE", parse(try_from_str = focus_operations::clone::parse_shallow_since_date))]
+    shallow_since: Option<chrono::NaiveDate>,
+
+    /// Keep this many commits of history from the tip of each branch instead of `--days-of-history`. Cannot be combined with `--shallow-since`.
+    #[clap(long, value_name = "N", conflicts_with = "shallow-since")]
+    depth: Option<u32>,
+
     /// Copy only the specified branch rather than all local branches.
     #[clap(long, parse(try_from_str), default_value = "true")]
     copy_branches: bool,
@@ -63,6 +78,59 @@ struct NewArgs {
 
     #[clap(long, possible_values = ClonedRepoTemplate::VARIANTS)]
     template: Option<ClonedRepoTemplate>,
+
+    /// Do not fetch from or generate into the index while cloning. The initial selection is materialized by resolving targets directly, which may be slower. Index settings in the repository's configuration still apply to later syncs.
+    #[clap(long)]
+    no_index: bool,
+
+    /// Make the clone resumable. If it fails, the partial clone is kept, and running the same command again with --resume continues it, skipping the phases it already completed.
+    #[clap(long)]
+    resume: bool,
+
+    /// Print how long each phase of the clone took once it finishes, and record the timings in telemetry.
+    #[clap(long)]
+    measure: bool,
+
+    /// Report the progress of the initial sync: a progress bar on a terminal, otherwise periodic log lines.
+    #[clap(long)]
+    progress: bool,
+
+    /// Seed the resolution cache from that of an existing focused repo before the initial sync. Only results whose dependencies are unchanged are copied.
+    #[clap(long, parse(from_os_str), value_name = "PATH")]
+    resolution_cache_from: Option<PathBuf>,
+
+    /// Leave a directory, given as a `directory:` target like `directory:project_a/generated`, out of the sparse checkout even if the selection includes it. Excluded directories are recorded in `.focus` and left out of later syncs too. May be repeated.
+    #[clap(long = "exclude", value_name = "TARGET", multiple_occurrences = true)]
+    excludes: Vec<String>,
+
+    /// Borrow objects from an existing local checkout of the same repository when cloning from a remote, so that only objects it lacks are downloaded. The new repository does not depend on the mirror afterwards. Git cannot borrow from a checkout with shallow history, as focus clones usually have, so such a mirror is skipped with a warning.
+    #[clap(long, parse(from_os_str), value_name = "PATH")]
+    mirror_from: Option<PathBuf>,
+
+    /// Borrow objects from a local repository, such as an object cache on a shared volume, when cloning from a remote. The new repository keeps borrowing from it (see `git clone --reference-if-able`) unless `--dissociate` is given. A repository that cannot be borrowed from is skipped.
+    #[clap(
+        long,
+        parse(from_os_str),
+        value_name = "PATH",
+        conflicts_with = "mirror-from"
+    )]
+    reference: Option<PathBuf>,
+
+    /// Copy the objects borrowed from `--reference` so that the new repository does not depend on it.
+    #[clap(long, requires = "reference")]
+    dissociate: bool,
+
+    /// Also fetch this branch when cloning from a remote, and configure the `origin` remote to keep fetching it. Only `--branch` is fetched otherwise. May be repeated.
+    #[clap(
+        long = "additional-branch",
+        value_name = "BRANCH",
+        multiple_occurrences = true
+    )]
+    additional_branches: Vec<String>,
+
+    /// If the repository being cloned has no commits yet, start the new repository with an empty commit on `--branch` so that it has a HEAD to work from. Ignored when the repository has history.
+    #[clap(long)]
+    initial_commit: bool,
 }
 
 #[derive(Parser, Clone, Debug)]
@@ -83,6 +151,96 @@ enum Subcommand {
         /// Sync in one-shot, skipping the cache and invoking the underlying resolver once.
         #[clap(long = "one-shot")]
         one_shot: bool,
+
+        /// A Bazel startup option (e.g. `--output_base=/tmp/focus`) to pass when resolving targets. May be repeated.
+        #[clap(long = "bazel-startup-opt", multiple_occurrences = true)]
+        bazel_startup_opts: Vec<String>,
+
+        /// An option (e.g. `--noshow_progress`) to pass to `bazel query` when resolving targets. May be repeated.
+        #[clap(long = "bazel-query-opt", multiple_occurrences = true)]
+        bazel_query_opts: Vec<String>,
+
+        /// A Bazel config from the repository's `.bazelrc` (passed as `--config=NAME`) to apply when resolving targets.
+        #[clap(long = "bazel-config", value_name = "NAME")]
+        bazel_config: Option<String>,
+
+        /// Leave out selected files that match the repository's ignore rules, such as build artifacts committed by mistake.
+        #[clap(long = "dedupe-with-gitignore")]
+        dedupe_with_gitignore: bool,
+
+        /// Fail if a directory target does not exist in the repository instead of warning.
+        #[clap(long)]
+        strict: bool,
+
+        /// The number of parallel workers Git uses to update the working tree (sets `checkout.workers`). Defaults to Git's configuration.
+        #[clap(long = "checkout-threads", value_name = "N")]
+        checkout_threads: Option<usize>,
+
+        /// Update the working tree all-or-nothing: if the checkout fails or is interrupted, the previous sparse checkout is restored.
+        #[clap(long)]
+        atomic: bool,
+
+        /// Report progress while resolving targets and applying the sparse checkout: a progress bar on a terminal, otherwise periodic log lines.
+        #[clap(long)]
+        progress: bool,
+
+        /// Leave this selected project out of this sync without removing it from the selection. May be repeated.
+        #[clap(
+            long = "exclude-project",
+            value_name = "NAME",
+            multiple_occurrences = true
+        )]
+        exclude_projects: Vec<String>,
+
+        /// Leave a directory, given as a `directory:` target like `directory:project_a/generated`, out of the sparse checkout. Excluded directories are recorded in `.focus` and left out of later syncs too. An exclusion takes precedence over the selection: a directory within an excluded one stays out even if it is selected explicitly. May be repeated.
+        #[clap(long = "exclude", value_name = "TARGET", multiple_occurrences = true)]
+        excludes: Vec<String>,
+
+        /// Forget the directories excluded by earlier syncs, so that they are checked out again if selected. Directories given with `--exclude` are still excluded.
+        #[clap(long)]
+        clear_excludes: bool,
+
+        /// Write the paths this sync would add to and remove from the working tree to a file, one per line, prefixed with `+` or `-`, without changing the working tree. The file is replaced on every run.
+        #[clap(
+            long = "diff-only",
+            value_name = "PATH",
+            parse(from_os_str),
+            conflicts_with_all(&["excludes", "clear-excludes"])
+        )]
+        diff_only: Option<PathBuf>,
+
+        /// Fail without changing the working tree if the selection resolves to a path outside PATH, relative to the repository root. May be repeated to allow several roots.
+        #[clap(
+            long = "assert-subset-of",
+            value_name = "PATH",
+            parse(from_os_str),
+            multiple_occurrences = true
+        )]
+        assert_subset_of: Vec<PathBuf>,
+
+        /// What to do when files with local changes would be removed from the working tree: `abort` leaves everything as it was, `stash` stashes the changes to those files first, and `discard` drops them (requires `--yes`).
+        #[clap(
+            long,
+            possible_values = focus_operations::sync::ConflictResolution::VARIANTS,
+            default_value = "abort"
+        )]
+        conflict_resolution: focus_operations::sync::ConflictResolution,
+
+        /// Confirm that local changes may be discarded by `--conflict-resolution=discard`.
+        #[clap(long)]
+        yes: bool,
+
+        /// Reuse the patterns the last sync resolved instead of resolving targets if no file relevant to the build graph changed between REF and HEAD or since those patterns were resolved. Otherwise targets are resolved as usual.
+        #[clap(long, value_name = "REF")]
+        since: Option<String>,
+
+        /// Resolve targets afresh instead of using results cached by earlier syncs of the same build graph. The fresh results still replace the cached ones.
+        #[clap(long, conflicts_with = "since")]
+        no_cache: bool,
+
+        /// Run CMD with the shell after a successful sync, for example to refresh an IDE's project model. The sparse repo and the numbers of paths added and removed are passed in the `FOCUS_SPARSE_REPO`, `FOCUS_SYNC_PATHS_ADDED`, and `FOCUS_SYNC_PATHS_REMOVED` environment variables, and the changed paths are written to its standard input as `+path` and `-path` lines (also saved to the file named by `FOCUS_SYNC_CHANGED_PATHS_FILE`). A failing command is reported but does not fail the sync. Defaults to the `focus.sync.post-sync-hook` Git config value, or `focus.hooks.post-sync` if that is unset.
+        #[clap(long, value_name = "CMD")]
+        post_sync_hook: Option<String>,
     },
 
     /// Interact with repos configured on this system. Run `focus repo help` for more information.
@@ -93,7 +251,7 @@ enum Subcommand {
 
     /// Add projects and targets to the selection.
     Add {
-        /// Project and targets to add to the selection.
+        /// Project and targets to add to the selection. Directory targets starting with `./` or `../` (like `directory:./config`) are relative to the current directory.
         projects_and_targets: Vec<String>,
 
         /// Select projects to add interactively.
@@ -108,6 +266,26 @@ enum Subcommand {
         /// Add the immediate targets and projects of projects to the selection, not the projects themselves.
         #[clap(long = "unroll")]
         unroll: bool,
+
+        /// Leave out selected files that match the repository's ignore rules, such as build artifacts committed by mistake.
+        #[clap(long = "dedupe-with-gitignore")]
+        dedupe_with_gitignore: bool,
+
+        /// Allow adding targets like `bazel://...` that would materialize more of the repository than `focus.selection.max-repo-percentage` permits.
+        #[clap(long = "allow-full-repo")]
+        allow_full_repo: bool,
+
+        /// Add the smallest project that contains each target instead of the target itself, falling back to the target when no project contains it.
+        #[clap(long = "project-from-label", conflicts_with("interactive"))]
+        project_from_label: bool,
+
+        /// Treat every argument as the name of a project, even if it looks like a target.
+        #[clap(long = "project", conflicts_with_all(&["target", "interactive", "project-from-label"]))]
+        project: bool,
+
+        /// Treat every argument as a target. Arguments that are not valid targets are an error.
+        #[clap(long = "target", conflicts_with("interactive"))]
+        target: bool,
     },
 
     /// Remove projects and targets from the selection.
@@ -119,6 +297,14 @@ enum Subcommand {
         /// Remove all targets and projects from the selection
         #[clap(short = 'a', long = "all")]
         all: bool,
+
+        /// Treat every argument as the name of a project, even if it looks like a target.
+        #[clap(long = "project", conflicts_with("target"))]
+        project: bool,
+
+        /// Treat every argument as a target. Arguments that are not valid targets are an error.
+        #[clap(long = "target")]
+        target: bool,
     },
 
     /// Display which projects and targets are selected.
@@ -130,10 +316,40 @@ enum Subcommand {
         //Include only the types of targets specified
         #[clap(short = 't', long = "types", arg_enum)]
         target_types: Vec<TargetTypes>,
+
+        /// Output format. `json` emits a versioned document describing the selection and working tree and ignores `--targets` and `--types`.
+        #[clap(
+            long,
+            possible_values = focus_operations::status::StatusFormat::VARIANTS,
+            default_value = "text"
+        )]
+        format: focus_operations::status::StatusFormat,
+
+        /// Print the ages in seconds of the last index fetch, sync, and maintenance run as JSON, for monitoring. Other options are ignored.
+        #[clap(long)]
+        machine_age: bool,
+
+        /// Re-check the status every SECS seconds and print it again whenever it changes, until interrupted.
+        #[clap(long, value_name = "SECS", conflicts_with("machine-age"))]
+        watch_interval: Option<u64>,
+
+        /// Print nothing and exit successfully if the named project is selected; otherwise explain which projects are missing and exit with an error. May be repeated. Other options are ignored.
+        #[clap(
+            long = "assert-project-selected",
+            value_name = "NAME",
+            multiple_occurrences = true
+        )]
+        assert_project_selected: Vec<String>,
     },
 
+    /// Check the sparse repo for common problems and suggest fixes. Exits with an error if any critical problem is found.
+    Doctor {},
+
     /// List available projects.
-    Projects {},
+    Projects {
+        #[clap(subcommand)]
+        subcommand: Option<ProjectsSubcommand>,
+    },
 
     /// Interact with project definitions
     Project {
@@ -193,18 +409,36 @@ enum Subcommand {
     /// that the chrome://tracing viewer can understand and display. This is a convenient way
     /// to analyze the timing and call tree of a git command.
     ///
+    /// The input may hold one event per line, as GIT_TRACE2_EVENT writes, or a JSON array of
+    /// events. If it is a directory, the events in every file within it are combined.
+    ///
     /// For example, to analyze git gc:
     /// ```
     /// $ GIT_TRACE2_EVENT=/tmp/gc.json git gc
     /// $ focus git-trace /tmp/gc.json /tmp/chrome-trace.json
     /// ````
     /// Then open chrome://tracing in your browser and load the /tmp/chrome-trace.json flie.
-    GitTrace { input: PathBuf, output: PathBuf },
+    GitTrace {
+        input: PathBuf,
+        output: PathBuf,
+
+        /// Only convert the events git recorded on this thread, such as `main` or `th02:preload_thread`.
+        #[clap(long, value_name = "ID")]
+        thread: Option<String>,
+
+        /// Only convert the events whose names in the trace viewer contain SUBSTRING. May be repeated; events must match every filter given, including `--thread`.
+        #[clap(long, value_name = "SUBSTRING", multiple_occurrences = true)]
+        name_filter: Vec<String>,
+    },
 
     /// Upgrade the repository by running outstanding migration steps.
     Upgrade {
         #[clap(long, parse(from_os_str), default_value = ".")]
         repo: PathBuf,
+
+        /// List the pending migrations in the order they would run, without applying them.
+        #[clap(long, visible_alias = "dry-run")]
+        list: bool,
     },
 
     /// Interact with the on-disk focus index.
@@ -220,12 +454,27 @@ enum Subcommand {
     },
 
     /// Called by a git hook to trigger certain actions after a git event such as
-    /// merge completion or checkout
-    Event { args: Vec<String> },
+    /// merge completion or checkout. `post-checkout` and `post-merge` warn when the build graph changed since the last sync, or sync automatically when passed `--auto-sync` or when `focus.post-checkout.auto-sync` or `focus.post-merge.auto-sync` respectively is set in the Git config.
+    Event {
+        #[clap(allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
 
     /// Print the version of Focus
     Version,
 
+    /// View and change the `focus.*` settings in the repository's Git configuration
+    Config {
+        #[clap(subcommand)]
+        subcommand: ConfigSubcommand,
+    },
+
+    /// Manage the Git hooks that run `focus event` after checkouts and merges
+    Hooks {
+        #[clap(subcommand)]
+        subcommand: HooksSubcommand,
+    },
+
     /// Control automatic background synchronization
     Background {
         #[clap(subcommand)]
@@ -239,6 +488,12 @@ enum Subcommand {
         #[clap(subcommand)]
         subcommand: SelectionSubcommand,
     },
+
+    /// Inspect the sandboxes that focus commands leave behind.
+    Sandbox {
+        #[clap(subcommand)]
+        subcommand: SandboxSubcommand,
+    },
     #[clap(hide = true)]
     /// Update the sparse checkout to only contain selections
     On {
@@ -251,6 +506,37 @@ enum Subcommand {
     Off {},
 }
 
+/// Format `age` coarsely, e.g. `3d4h` or `25m`.
+fn format_age(age: chrono::Duration) -> String {
+    let minutes = age.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Format a size in bytes using binary units, e.g. `1.5 MiB`.
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64;
+    let mut unit = "B";
+    for next_unit in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
 /// Helper method to extract subcommand name. Tool insights client uses this to set
 /// feature name.
 fn feature_name_for(subcommand: &Subcommand) -> String {
@@ -261,17 +547,24 @@ fn feature_name_for(subcommand: &Subcommand) -> String {
             RepoSubcommand::List { .. } => "repo-list".to_string(),
             RepoSubcommand::Repair { .. } => "repo-repair".to_string(),
             RepoSubcommand::Register { .. } => "repo-register".to_string(),
+            RepoSubcommand::Gc { .. } => "repo-gc".to_string(),
+            RepoSubcommand::Remove { .. } => "repo-remove".to_string(),
         },
         Subcommand::Add { .. } => "add".to_string(),
         Subcommand::Remove { .. } => "remove".to_string(),
         Subcommand::Status { .. } => "status".to_string(),
-        Subcommand::Projects { .. } => "projects".to_string(),
+        Subcommand::Doctor { .. } => "doctor".to_string(),
+        Subcommand::Projects { subcommand } => match subcommand {
+            None => "projects".to_string(),
+            Some(ProjectsSubcommand::Show { .. }) => "projects-show".to_string(),
+        },
         Subcommand::Project { subcommand } => match subcommand {
             ProjectSubcommand::Lint { .. } => "project-lint".to_string(),
         },
         Subcommand::DetectBuildGraphChanges { .. } => "detect-build-graph-changes".to_string(),
         Subcommand::Refs { subcommand, .. } => match subcommand {
             RefsSubcommand::Delete { .. } => "refs-delete".to_string(),
+            RefsSubcommand::PruneEmpty { .. } => "refs-prune-empty".to_string(),
             RefsSubcommand::ListExpired { .. } => "refs-list-expired".to_string(),
             RefsSubcommand::ListCurrent { .. } => "refs-list-current".to_string(),
         },
@@ -289,6 +582,7 @@ fn feature_name_for(subcommand: &Subcommand) -> String {
             MaintenanceSubcommand::SandboxCleanup { .. } => {
                 "maintenance-sandbox-cleanup".to_string()
             }
+            MaintenanceSubcommand::Status { .. } => "maintenance-status".to_string(),
             MaintenanceSubcommand::Schedule { subcommand } => match subcommand {
                 MaintenanceScheduleSubcommand::Enable { .. } => {
                     "maintenance-schedule-enable".to_string()
@@ -305,6 +599,8 @@ fn feature_name_for(subcommand: &Subcommand) -> String {
             IndexSubcommand::CalculateChurn { .. } => "index-calculate-churn".to_string(),
             IndexSubcommand::Fetch { .. } => "index-fetch".to_string(),
             IndexSubcommand::Get { .. } => "index-get".to_string(),
+            IndexSubcommand::Stats { .. } => "index-stats".to_string(),
+            IndexSubcommand::Verify { .. } => "index-verify".to_string(),
             IndexSubcommand::Generate { .. } => "index-generate".to_string(),
             IndexSubcommand::Hash { .. } => "index-hash".to_string(),
             IndexSubcommand::Push { .. } => "index-push".to_string(),
@@ -319,6 +615,15 @@ fn feature_name_for(subcommand: &Subcommand) -> String {
             temp_args.join("-")
         }
         Subcommand::Version => "version".to_string(),
+        Subcommand::Config { subcommand } => match subcommand {
+            ConfigSubcommand::Get { .. } => "config-get".to_string(),
+            ConfigSubcommand::Set { .. } => "config-set".to_string(),
+            ConfigSubcommand::List { .. } => "config-list".to_string(),
+        },
+        Subcommand::Hooks { subcommand } => match subcommand {
+            HooksSubcommand::Install { .. } => "hooks-install".to_string(),
+            HooksSubcommand::Uninstall { .. } => "hooks-uninstall".to_string(),
+        },
         Subcommand::Background { subcommand } => match subcommand {
             BackgroundSubcommand::Enable { .. } => "background-enable".to_string(),
             BackgroundSubcommand::Disable { .. } => "background-disable".to_string(),
@@ -327,12 +632,57 @@ fn feature_name_for(subcommand: &Subcommand) -> String {
         Subcommand::Pull => "pull".to_string(),
         Subcommand::Selection { subcommand } => match subcommand {
             SelectionSubcommand::Save { .. } => "selection-save".to_string(),
+            SelectionSubcommand::Validate { .. } => "selection-validate".to_string(),
+            SelectionSubcommand::History { .. } => "selection-history".to_string(),
+            SelectionSubcommand::Profile { subcommand } => match subcommand {
+                SelectionProfileSubcommand::Save { .. } => "selection-profile-save".to_string(),
+                SelectionProfileSubcommand::Switch { .. } => "selection-profile-switch".to_string(),
+                SelectionProfileSubcommand::List {} => "selection-profile-list".to_string(),
+            },
+        },
+        Subcommand::Sandbox { subcommand } => match subcommand {
+            SandboxSubcommand::List {} => "sandbox-list".to_string(),
         },
         Subcommand::On { .. } => "filter-on".to_string(),
         Subcommand::Off {} => "filter-off".to_string(),
     }
 }
 
+/// How the arguments of `add` and `remove` are interpreted given their `--project` and `--target` flags.
+fn interpretation(project: bool, target: bool) -> Interpretation {
+    match (project, target) {
+        (true, _) => Interpretation::Project,
+        (_, true) => Interpretation::Target,
+        _ => Interpretation::Inferred,
+    }
+}
+
+/// Whether `cmd` was asked to report progress with `--progress`.
+fn progress_requested(cmd: &Subcommand) -> bool {
+    match cmd {
+        Subcommand::New(NewArgs { progress, .. })
+        | Subcommand::Clone(NewArgs { progress, .. })
+        | Subcommand::Sync { progress, .. } => *progress,
+        _ => false,
+    }
+}
+
+/// A description of `cmd` if it always contacts a remote, so that it can be refused up front when offline. Commands that only sometimes need the network check for themselves.
+fn network_operation(cmd: &Subcommand) -> Option<&'static str> {
+    match cmd {
+        Subcommand::Branch { .. } => Some("Working with remote branches"),
+        Subcommand::Pull => Some("Pulling"),
+        Subcommand::Index {
+            subcommand: IndexSubcommand::Fetch { .. },
+        } => Some("Fetching the index"),
+        Subcommand::Index {
+            subcommand: IndexSubcommand::Push { dry_run: false, .. },
+        } => Some("Pushing the index"),
+        Subcommand::ProjectCache { .. } => Some("Pushing to the project cache"),
+        _ => None,
+    }
+}
+
 #[derive(Parser, Clone, Debug)]
 enum MaintenanceSubcommand {
     /// Runs global (i.e. system-wide) git maintenance tasks on repositories listed in
@@ -363,6 +713,18 @@ enum MaintenanceSubcommand {
             env = "FOCUS_TIME_PERIOD"
         )]
         time_period: focus_operations::maintenance::TimePeriod,
+
+        /// List the repos and tasks that would be run for the time period without running them.
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Print how long each task took in each repo when the run finishes. Timings are always written to the sandbox.
+        #[clap(long, conflicts_with = "dry-run")]
+        timings: bool,
+
+        /// Exit without running any tasks if the host is on battery power, recording the skip.
+        #[clap(long)]
+        skip_on_battery: bool,
     },
 
     SetDefaultConfig {},
@@ -380,6 +742,9 @@ enum MaintenanceSubcommand {
         subcommand: MaintenanceScheduleSubcommand,
     },
 
+    /// Report whether maintenance jobs are registered with this host's scheduler (launchd, systemd, cron, or the Task Scheduler), when they will next run, and when each last completed.
+    Status {},
+
     SandboxCleanup {
         /// Sandboxes older than this many hours will be deleted automatically.
         /// if 0 then time based cleanup is not performed and we just go by
@@ -392,12 +757,20 @@ enum MaintenanceSubcommand {
         /// than preserve_hours
         #[clap(long)]
         max_num_sandboxes: Option<u32>,
+
+        /// The name of a sandbox directory to preserve regardless of its age. May be repeated.
+        #[clap(long, value_name = "NAME", multiple_occurrences = true)]
+        keep: Vec<String>,
+
+        /// Log the sandboxes that would be deleted, and why, without deleting them.
+        #[clap(long)]
+        dry_run: bool,
     },
 }
 
 #[derive(Parser, Clone, Debug)]
 enum MaintenanceScheduleSubcommand {
-    /// Set up a system-appropriate periodic job (launchctl, systemd, etc.) for running
+    /// Set up a system-appropriate periodic job (launchctl, systemd, cron, etc.) for running
     /// maintenance tasks on hourly, daily, and weekly bases
     Enable {
         /// The time period of job to schedule
@@ -430,6 +803,14 @@ enum MaintenanceScheduleSubcommand {
         /// Add a flag to the maintenance cmdline that will run the tasks against all focus tracked repos
         #[clap(long, env = "FOCUS_TRACKED")]
         tracked: bool,
+
+        /// What scheduled runs do when the host is on battery power: `skip` makes them check the power source (with `pmset` on macOS, or `/sys/class/power_supply` on Linux) and exit early on battery.
+        #[clap(
+            long,
+            possible_values = maintenance::power::OnBattery::VARIANTS,
+            default_value = "run"
+        )]
+        on_battery: maintenance::power::OnBattery,
     },
 
     /// Unload all the scheduled jobs from the system scheduler (if loaded).
@@ -442,17 +823,67 @@ enum MaintenanceScheduleSubcommand {
 
 #[derive(Parser, Clone, Debug)]
 enum RepoSubcommand {
-    /// List registered repositories
-    List {},
+    /// List registered repositories with their size, when they were last synced, and their migration version
+    List {
+        /// Output format
+        #[clap(
+            long,
+            possible_values=focus_operations::repo::RepoListFormat::VARIANTS,
+            default_value="text"
+        )]
+        format: focus_operations::repo::RepoListFormat,
+    },
 
     /// Attempt to repair the registry of repositories
-    Repair {},
+    Repair {
+        /// Discard the registry and rebuild it from the focused repos found by searching this directory.
+        #[clap(long, parse(from_os_str), value_name = "ROOT")]
+        rebuild_from_disk: Option<PathBuf>,
+
+        /// How many directory levels below ROOT to search for focused repos.
+        #[clap(long, default_value = "4", requires = "rebuild-from-disk")]
+        max_depth: usize,
+
+        /// Only report registered repositories that no longer exist on disk instead of removing them.
+        #[clap(long, conflicts_with = "rebuild-from-disk")]
+        keep_missing: bool,
+    },
 
     /// Register (or fix the current registation of) the of the specified repository
     Register {
         #[clap(parse(from_os_str), default_value = ".")]
         sparse_repo: PathBuf,
     },
+
+    /// Remove a repository from the registry so that it is no longer maintained
+    Remove {
+        /// Path to the repository.
+        #[clap(parse(from_os_str))]
+        sparse_repo: PathBuf,
+
+        /// Also delete the repository from disk.
+        #[clap(long)]
+        delete: bool,
+    },
+
+    /// Run the maintenance tasks in a single repository now, printing git's output as it goes
+    Gc {
+        /// Path to the repository.
+        #[clap(long, parse(from_os_str), default_value = ".")]
+        repo: PathBuf,
+
+        /// The time period whose maintenance tasks to run
+        #[clap(
+            long,
+            possible_values=focus_operations::maintenance::TimePeriod::VARIANTS,
+            default_value="weekly"
+        )]
+        time_period: focus_operations::maintenance::TimePeriod,
+
+        /// Print how long each task took when the run finishes.
+        #[clap(long)]
+        timings: bool,
+    },
 }
 
 #[derive(Parser, Clone, Debug)]
@@ -479,6 +910,15 @@ enum BranchSubcommand {
     Add { name: String },
 }
 
+#[derive(Parser, Clone, Debug)]
+enum ProjectsSubcommand {
+    /// Show a project's description, the projects it includes, and the targets it expands to.
+    Show {
+        /// Name of the project to show.
+        name: String,
+    },
+}
+
 #[derive(Parser, Clone, Debug)]
 enum ProjectSubcommand {
     /// Load projects and then try to parse targets
@@ -499,12 +939,51 @@ enum SelectionSubcommand {
         #[clap(long, short = 'd')]
         project_description: Option<String>,
     },
+
+    /// Check that the stored selection's targets parse, that its projects are defined, and that included projects do not form a cycle. Exits non-zero if problems are found.
+    Validate {},
+
+    /// List recent changes to the selection, newest first, with the projects and targets each added and removed.
+    History {
+        /// Show at most this many changes.
+        #[clap(long)]
+        limit: Option<usize>,
+
+        /// Print the changes as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Manage named selection profiles, which store the selected projects and targets so that you can switch between working sets. Run `focus selection profile help` for more information.
+    Profile {
+        #[clap(subcommand)]
+        subcommand: SelectionProfileSubcommand,
+    },
+}
+
+#[derive(Parser, Clone, Debug)]
+enum SelectionProfileSubcommand {
+    /// Store the current selection as a named profile, replacing any profile of that name.
+    Save {
+        /// Name of the profile
+        name: String,
+    },
+
+    /// Replace the selection with a named profile and sync.
+    Switch {
+        /// Name of the profile
+        name: String,
+    },
+
+    /// List the stored selection profiles.
+    List {},
 }
 
 #[derive(Parser, Clone, Debug)]
 enum RefsSubcommand {
     /// Expires refs that are outside the window of "current refs"
     Delete {
+        /// Refs older than this are expired. Either a date like `2021-01-01`, an RFC3339 timestamp like `2021-01-01T12:00:00Z`, or a duration before now like `90d`, `12w`, or `6m`.
         #[clap(long, default_value = "2021-01-01")]
         cutoff_date: String,
 
@@ -518,7 +997,18 @@ enum RefsSubcommand {
         check_merge_base: bool,
     },
 
+    /// Delete refs whose target commit no longer exists, such as after garbage collection
+    PruneEmpty {
+        /// Only print the refs that would be deleted.
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(long)]
+        use_transaction: bool,
+    },
+
     ListExpired {
+        /// Refs older than this are expired. Either a date like `2021-01-01`, an RFC3339 timestamp like `2021-01-01T12:00:00Z`, or a duration before now like `90d`, `12w`, or `6m`.
         #[clap(long, default_value = "2021-01-01")]
         cutoff_date: String,
 
@@ -531,6 +1021,7 @@ enum RefsSubcommand {
 
     /// Output a list of still current (I.e. non-expired) refs
     ListCurrent {
+        /// Refs older than this are expired. Either a date like `2021-01-01`, an RFC3339 timestamp like `2021-01-01T12:00:00Z`, or a duration before now like `90d`, `12w`, or `6m`.
         #[clap(long, default_value = "2021-01-01")]
         cutoff_date: String,
 
@@ -539,6 +1030,14 @@ enum RefsSubcommand {
         /// included in master
         #[clap(short = 'm', long = "check-merge-base")]
         check_merge_base: bool,
+
+        /// Output format. `json` emits each ref's name, target commit, committer timestamp, and merge base check result.
+        #[clap(
+            long,
+            possible_values = focus_operations::refs::RefListFormat::VARIANTS,
+            default_value = "text"
+        )]
+        format: focus_operations::refs::RefListFormat,
     },
 }
 
@@ -563,6 +1062,32 @@ enum IndexSubcommand {
         num_commits: usize,
     },
 
+    /// Report how many index entries the repository's projects use and how large they are.
+    Stats {
+        /// Break the figures down by project, largest first.
+        #[clap(long)]
+        by_project: bool,
+
+        /// Print the statistics as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Recompute the content hashes of the repository's project targets and report those whose index entries are missing or unreadable. Exits non-zero if any are found.
+    Verify {
+        /// Check only this many targets, chosen at random.
+        #[clap(long, value_name = "N")]
+        sample: Option<usize>,
+
+        /// Resolve the stale targets again and replace their entries, rather than only reporting them.
+        #[clap(long)]
+        fix: bool,
+
+        /// Print the report as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
     /// Fetch the pre-computed index for the repository.
     Fetch {
         /// Path to the sparse repository.
@@ -574,9 +1099,25 @@ enum IndexSubcommand {
         #[clap(short = 'f', long = "force")]
         force: bool,
 
-        /// Override the remote provided in the config.
+        /// Override the remote provided in the config. Several remotes may be given separated by commas, such as a nearby mirror followed by the canonical remote; each is tried in order until one serves the index. Remotes starting with `cas+`, like `cas+https://artifacts.example.com/focus-index`, name a content-addressable store read over HTTP (authenticated with the token in `FOCUS_INDEX_STORE_TOKEN`, if set) instead of a Git remote.
         #[clap(long)]
         remote: Option<String>,
+
+        /// Fetch the named index snapshot instead of the index for the current commit.
+        #[clap(long, value_name = "NAME")]
+        tag: Option<String>,
+
+        /// Refuse indexes that are not signed by the private half of the ed25519 public key in this file (stored as hex).
+        #[clap(long, parse(from_os_str), value_name = "PATH")]
+        verify_key: Option<PathBuf>,
+
+        /// How many times to retry contacting the remote after a transient network error. Authentication and not-found errors are never retried.
+        #[clap(long, default_value = "3")]
+        retries: u32,
+
+        /// The delay before the first retry in milliseconds, which doubles with each further retry.
+        #[clap(long, default_value = "500", value_name = "MS")]
+        retry_base_ms: u64,
     },
 
     Get {
@@ -592,6 +1133,14 @@ enum IndexSubcommand {
         /// If index keys are found to be missing, pause for debugging.
         #[clap(long)]
         break_on_missing_keys: bool,
+
+        /// Write a JSON manifest of the generated entries (project, target, content hash, and size) to this path.
+        #[clap(long, parse(from_os_str), value_name = "PATH")]
+        output_manifest: Option<PathBuf>,
+
+        /// The number of threads to resolve and hash targets with. Defaults to the number given by `--resolution-threads`.
+        #[clap(long, value_name = "N")]
+        jobs: Option<usize>,
     },
 
     /// Calculate and print the content hashes of the provided targets.
@@ -602,6 +1151,10 @@ enum IndexSubcommand {
 
         /// The targets to hash.
         targets: Vec<String>,
+
+        /// Also print every input contributing to each hash: the hashing version and each path in the tree with the hash of its contents. Useful for finding why hashes differ between machines.
+        #[clap(long)]
+        include_env: bool,
     },
 
     /// Generate and push the pre-computed index to the remote store for others
@@ -611,7 +1164,7 @@ enum IndexSubcommand {
         #[clap(parse(from_os_str), default_value = ".")]
         sparse_repo: PathBuf,
 
-        /// The Git remote to push to.
+        /// The remote to push to: a Git remote, or a content-addressable store written over HTTP for remotes starting with `cas+` (authenticated with the token in `FOCUS_INDEX_STORE_TOKEN`, if set).
         #[clap(long, default_value = focus_operations::index::INDEX_DEFAULT_REMOTE)]
         remote: String,
 
@@ -623,6 +1176,22 @@ enum IndexSubcommand {
         /// If index keys are found to be missing, pause for debugging.
         #[clap(long)]
         break_on_missing_keys: bool,
+
+        /// Also publish the index as an immutable snapshot with this name, which can be fetched with `focus index fetch --tag`.
+        #[clap(long, value_name = "NAME")]
+        tag: Option<String>,
+
+        /// Overwrite the snapshot named by `--tag` if it already exists.
+        #[clap(long, requires = "tag")]
+        force: bool,
+
+        /// Sign the index with the ed25519 private key in this file (stored as hex), so that consumers can verify it with `focus index fetch --verify-key`.
+        #[clap(long, parse(from_os_str), value_name = "PATH")]
+        sign_key: Option<PathBuf>,
+
+        /// Before generating the index, check that the remote accepts pushes by pushing and deleting a test tag (or writing a test object to a content-addressable store), and fail early if it is read-only.
+        #[clap(long, conflicts_with = "dry-run")]
+        read_only_check: bool,
     },
 
     /// Resolve the targets to their resulting pattern sets.
@@ -632,6 +1201,27 @@ enum IndexSubcommand {
         /// If index keys are found to be missing, pause for debugging.
         #[clap(long)]
         break_on_missing_keys: bool,
+
+        /// A Bazel startup option to pass when resolving targets. May be repeated.
+        #[clap(long = "bazel-startup-opt", multiple_occurrences = true)]
+        bazel_startup_opts: Vec<String>,
+
+        /// An option to pass to `bazel query` when resolving targets. May be repeated.
+        #[clap(long = "bazel-query-opt", multiple_occurrences = true)]
+        bazel_query_opts: Vec<String>,
+
+        /// A Bazel config from the repository's `.bazelrc` to apply when resolving targets.
+        #[clap(long = "bazel-config", value_name = "NAME")]
+        bazel_config: Option<String>,
+
+        /// Exit with an error listing any resolved path outside PATH, relative to the repository root. May be repeated to allow several roots.
+        #[clap(
+            long = "assert-subset-of",
+            value_name = "PATH",
+            parse(from_os_str),
+            multiple_occurrences = true
+        )]
+        assert_subset_of: Vec<PathBuf>,
     },
 }
 
@@ -693,6 +1283,66 @@ enum BackgroundSubcommand {
     },
 }
 
+#[derive(Parser, Clone, Debug)]
+enum ConfigSubcommand {
+    /// Print the value of a setting, exiting with status 1 if it is not set.
+    Get {
+        /// Path to the sparse repository.
+        #[clap(long, parse(from_os_str), default_value = ".")]
+        sparse_repo: PathBuf,
+
+        /// The key to read. The `focus.` prefix may be left out.
+        key: String,
+    },
+
+    /// Change a setting in the repository's local Git configuration.
+    Set {
+        /// Path to the sparse repository.
+        #[clap(long, parse(from_os_str), default_value = ".")]
+        sparse_repo: PathBuf,
+
+        /// The key to set. The `focus.` prefix may be left out.
+        key: String,
+
+        /// The value to set it to.
+        value: String,
+
+        /// Change a key that focus manages itself, like `focus.uuid`.
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Print the settings that are set, with descriptions of the ones focus reads, followed by the known settings that are not set.
+    List {
+        /// Path to the sparse repository.
+        #[clap(long, parse(from_os_str), default_value = ".")]
+        sparse_repo: PathBuf,
+    },
+}
+
+#[derive(Parser, Clone, Debug)]
+enum HooksSubcommand {
+    /// Add `post-checkout` and `post-merge` hooks that run `focus event` to the directory Git runs hooks from, keeping the existing content of those hook scripts. Installing again changes nothing.
+    Install {
+        /// Path to the sparse repository.
+        #[clap(parse(from_os_str), default_value = ".")]
+        sparse_repo: PathBuf,
+    },
+
+    /// Remove the lines added by `focus hooks install` from the hook scripts, deleting scripts left empty.
+    Uninstall {
+        /// Path to the sparse repository.
+        #[clap(parse(from_os_str), default_value = ".")]
+        sparse_repo: PathBuf,
+    },
+}
+
+#[derive(Parser, Clone, Debug)]
+enum SandboxSubcommand {
+    /// List sandboxes with their age, size on disk, and the command that created them, oldest first. The oldest are the first to be removed by `focus maintenance sandbox-cleanup`.
+    List {},
+}
+
 #[derive(Parser, Clone, Debug)]
 #[clap(about = "Focused Development Tools")]
 struct FocusOpts {
@@ -718,10 +1368,46 @@ struct FocusOpts {
     #[clap(long, global = true, env = "NO_COLOR")]
     no_color: bool,
 
+    /// Forbid network access. Operations that need to contact a remote fail instead, the index and project cache are not fetched while syncing, and `focus new` requires a local dense repo and skips the fetch that normally follows cloning.
+    #[clap(long, global = true, env = "FOCUS_OFFLINE")]
+    offline: bool,
+
+    /// Terminate Git commands, such as the fetches made by clone, sync, and index, that run longer than this many seconds. A timed out fetch of the index is retried. Commands run with a timeout cannot prompt on the terminal, so credentials and SSH passphrases must come from a credential helper or agent.
+    #[clap(long, global = true, env = "FOCUS_GIT_TIMEOUT")]
+    timeout: Option<u64>,
+
+    /// Print more detailed logs: `-v` for debug and `-vv` for trace level. Overrides `RUST_LOG`.
+    #[clap(short = 'v', long, global = true, parse(from_occurrences))]
+    verbose: u8,
+
+    /// Only print warnings and errors. Overrides `RUST_LOG`.
+    #[clap(short = 'q', long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The format of the log file written to the sandbox. `json` writes one JSON object per line, including the subcommand being run, for log aggregation. Console output is unaffected.
+    #[clap(
+        long,
+        global = true,
+        possible_values = focus_tracing::LogFormat::VARIANTS,
+        default_value = "text",
+        env = "FOCUS_LOG_FORMAT"
+    )]
+    log_format: focus_tracing::LogFormat,
+
     #[clap(subcommand)]
     cmd: Subcommand,
 }
 
+/// The console log level selected by the `--verbose` and `--quiet` flags, or `None` if neither was given.
+fn console_level_for(verbose: u8, quiet: bool) -> Option<LevelFilter> {
+    match (verbose, quiet) {
+        (_, true) => Some(LevelFilter::WARN),
+        (0, false) => None,
+        (1, false) => Some(LevelFilter::DEBUG),
+        (_, false) => Some(LevelFilter::TRACE),
+    }
+}
+
 fn ensure_directories_exist(tracker: &Tracker) -> Result<()> {
     tracker
         .ensure_directories_exist()
@@ -772,6 +1458,10 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
         return Ok(ExitCode(1));
     };
 
+    if let Some(operation) = network_operation(&options.cmd) {
+        app.require_network(operation)?;
+    }
+
     if let Subcommand::Clone(_) = &options.cmd {
         eprintln!(
             "{}{}The command `focus clone` is deprecated; use `focus new` instead!{}",
@@ -787,18 +1477,44 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             sparse_repo,
             branch,
             days_of_history,
+            shallow_since,
+            depth,
             copy_branches,
             projects_and_targets,
             template,
+            no_index,
+            resume,
+            measure,
+            progress: _,
+            resolution_cache_from,
+            excludes,
+            mirror_from,
+            reference,
+            dissociate,
+            additional_branches,
+            initial_commit,
         })
         | Subcommand::Clone(NewArgs {
             dense_repo,
             sparse_repo,
             branch,
             days_of_history,
+            shallow_since,
+            depth,
             copy_branches,
             projects_and_targets,
             template,
+            no_index,
+            resume,
+            measure,
+            progress: _,
+            resolution_cache_from,
+            excludes,
+            mirror_from,
+            reference,
+            dissociate,
+            additional_branches,
+            initial_commit,
         }) => {
             let origin = focus_operations::clone::Origin::try_from(dense_repo.as_str())?;
             let sparse_repo = {
@@ -821,12 +1537,33 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 origin: Some(origin),
                 branch,
                 days_of_history,
+                shallow_since,
+                depth,
                 copy_branches,
                 projects_and_targets,
+                use_index: !no_index,
+                offline: app.is_offline(),
+                resume,
+                resolution_cache_from: resolution_cache_from
+                    .map(|path| paths::expand_tilde(path.as_path()))
+                    .transpose()?,
+                excluded_paths: excludes
+                    .iter()
+                    .map(|exclude| focus_operations::sync::parse_excluded_path(exclude))
+                    .collect::<Result<_>>()?,
+                mirror_from: mirror_from
+                    .map(|path| paths::expand_tilde(path.as_path()))
+                    .transpose()?,
+                reference: reference
+                    .map(|path| paths::expand_tilde(path.as_path()))
+                    .transpose()?,
+                dissociate,
+                additional_branches,
+                initial_commit,
                 ..Default::default()
             };
 
-            focus_operations::clone::run(
+            let mut timings = focus_operations::clone::run(
                 sparse_repo.clone(),
                 clone_args,
                 template,
@@ -834,15 +1571,58 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 app.clone(),
             )?;
 
-            perform_pending_migrations(&sparse_repo, app)
+            timings
+                .time("migrations", || {
+                    perform_pending_migrations(&sparse_repo, app)
+                })
                 .context("Performing initial migrations after clone")?;
 
+            if measure {
+                println!("{}", timings.breakdown().trim_end());
+                for (phase, elapsed) in timings.phases() {
+                    ti_client.get_context().add_to_custom_map(
+                        format!("clone_{}_secs", phase),
+                        format!("{:.3}", elapsed.as_secs_f64()),
+                    );
+                }
+            }
+
             Ok(ExitCode(0))
         }
         Subcommand::Sync {
             sparse_repo,
             one_shot,
+            bazel_startup_opts,
+            bazel_query_opts,
+            bazel_config,
+            dedupe_with_gitignore,
+            strict,
+            checkout_threads,
+            atomic,
+            progress: _,
+            exclude_projects,
+            excludes,
+            clear_excludes,
+            diff_only,
+            assert_subset_of,
+            conflict_resolution,
+            yes,
+            since,
+            no_cache,
+            post_sync_hook,
         } => {
+            let bazel_options = BazelOptions::new(bazel_startup_opts, bazel_query_opts)?
+                .with_config(bazel_config)?;
+            if let Some(checkout_threads) = checkout_threads {
+                focus_internals::model::repo::validate_checkout_workers(checkout_threads)?;
+            }
+            let excluded_paths = excludes
+                .iter()
+                .map(|exclude| focus_operations::sync::parse_excluded_path(exclude))
+                .collect::<Result<Vec<_>>>()?;
+            if conflict_resolution == focus_operations::sync::ConflictResolution::Discard && !yes {
+                bail!("Discarding local changes requires --yes");
+            }
             // TODO: Add total number of paths in repo to TI.
             let sparse_repo =
                 paths::find_repo_root_from(app.clone(), paths::expand_tilde(sparse_repo)?)?;
@@ -854,7 +1634,24 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             } else {
                 SyncMode::Incremental
             };
-            focus_operations::sync::run(&SyncRequest::new(&sparse_repo, mode), app)?;
+            focus_operations::sync::run(
+                &SyncRequest::new(&sparse_repo, mode)
+                    .with_bazel_options(bazel_options)
+                    .with_dedupe_with_gitignore(dedupe_with_gitignore)
+                    .with_strict_directories(strict)
+                    .with_checkout_workers(checkout_threads)
+                    .with_atomic(atomic)
+                    .with_excluded_projects(exclude_projects)
+                    .with_excluded_paths(excluded_paths)
+                    .with_clear_excluded_paths(clear_excludes)
+                    .with_diff_path(diff_only)
+                    .with_allowed_roots(assert_subset_of)
+                    .with_conflict_resolution(conflict_resolution)
+                    .with_since(since)
+                    .with_no_cache(no_cache)
+                    .with_post_sync_hook(post_sync_hook),
+                app,
+            )?;
             Ok(ExitCode(0))
         }
 
@@ -870,7 +1667,7 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                     use_transaction,
                     check_merge_base,
                 } => {
-                    let cutoff = FocusTime::parse_date(cutoff_date)?;
+                    let cutoff = FocusTime::parse_cutoff(cutoff_date)?;
                     focus_operations::refs::expire_old_refs(
                         &repo,
                         cutoff,
@@ -881,21 +1678,38 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                     Ok(ExitCode(0))
                 }
 
+                RefsSubcommand::PruneEmpty {
+                    dry_run,
+                    use_transaction,
+                } => {
+                    let pruned = focus_operations::refs::prune_empty_refs(
+                        &repo,
+                        dry_run,
+                        use_transaction,
+                        app,
+                    )?;
+                    println!("{}", pruned.join("\n"));
+                    Ok(ExitCode(0))
+                }
+
                 RefsSubcommand::ListExpired {
                     cutoff_date,
                     check_merge_base,
                 } => {
-                    let cutoff = FocusTime::parse_date(cutoff_date)?;
-                    let focus_operations::refs::PartitionedRefNames {
+                    let cutoff = FocusTime::parse_cutoff(cutoff_date)?;
+                    let focus_operations::refs::PartitionedRefs {
                         current: _,
                         expired,
-                    } = focus_operations::refs::PartitionedRefNames::for_repo(
+                    } = focus_operations::refs::PartitionedRefs::for_repo(
                         &repo,
                         cutoff,
                         check_merge_base,
                     )?;
 
-                    println!("{}", expired.join("\n"));
+                    focus_operations::refs::print_refs(
+                        &expired,
+                        focus_operations::refs::RefListFormat::Text,
+                    )?;
 
                     Ok(ExitCode(0))
                 }
@@ -903,18 +1717,19 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 RefsSubcommand::ListCurrent {
                     cutoff_date,
                     check_merge_base,
+                    format,
                 } => {
-                    let cutoff = FocusTime::parse_date(cutoff_date)?;
-                    let focus_operations::refs::PartitionedRefNames {
+                    let cutoff = FocusTime::parse_cutoff(cutoff_date)?;
+                    let focus_operations::refs::PartitionedRefs {
                         current,
                         expired: _,
-                    } = focus_operations::refs::PartitionedRefNames::for_repo(
+                    } = focus_operations::refs::PartitionedRefs::for_repo(
                         &repo,
                         cutoff,
                         check_merge_base,
                     )?;
 
-                    println!("{}", current.join("\n"));
+                    focus_operations::refs::print_refs(&current, format)?;
 
                     Ok(ExitCode(0))
                 }
@@ -943,12 +1758,47 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
         }
 
         Subcommand::Repo { subcommand } => match subcommand {
-            RepoSubcommand::List {} => {
-                focus_operations::repo::list(tracker)?;
+            RepoSubcommand::List { format } => {
+                let summaries = focus_operations::repo::list(tracker, app, |path, app| {
+                    focus_migrations::production::current_version(path, app)
+                        .map(|version| version.to_string())
+                })?;
+                match format {
+                    focus_operations::repo::RepoListFormat::Text => {
+                        let now = chrono::Utc::now().timestamp();
+                        for summary in summaries {
+                            println!(
+                                "{:>10} {:>8} {:>4} {} {}",
+                                format_size(summary.size),
+                                summary
+                                    .last_sync
+                                    .map(|timestamp| format_age(chrono::Duration::seconds(
+                                        now - timestamp
+                                    )))
+                                    .unwrap_or_else(|| "never".to_string()),
+                                summary.migration_version.as_deref().unwrap_or("-"),
+                                summary.path.display(),
+                                summary.identifier
+                            );
+                        }
+                    }
+                    focus_operations::repo::RepoListFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&summaries)?);
+                    }
+                }
                 Ok(ExitCode(0))
             }
-            RepoSubcommand::Repair {} => {
-                focus_operations::repo::repair(tracker, app)?;
+            RepoSubcommand::Repair {
+                rebuild_from_disk,
+                max_depth,
+                keep_missing,
+            } => {
+                if let Some(root) = rebuild_from_disk {
+                    let root = paths::expand_tilde(root)?;
+                    focus_operations::repo::rebuild_from_disk(root, max_depth, tracker, app)?;
+                } else {
+                    focus_operations::repo::repair(tracker, keep_missing, app)?;
+                }
                 Ok(ExitCode(0))
             }
 
@@ -956,6 +1806,25 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 focus_operations::repo::register(sparse_repo, tracker, app)?;
                 Ok(ExitCode(0))
             }
+
+            RepoSubcommand::Remove {
+                sparse_repo,
+                delete,
+            } => {
+                let sparse_repo = paths::expand_tilde(sparse_repo)?;
+                focus_operations::repo::remove(sparse_repo, delete, tracker)?;
+                Ok(ExitCode(0))
+            }
+
+            RepoSubcommand::Gc {
+                repo,
+                time_period,
+                timings,
+            } => {
+                let repo = paths::find_repo_root_from(app.clone(), paths::expand_tilde(repo)?)?;
+                focus_operations::repo::gc(repo, time_period, timings, tracker, app)?;
+                Ok(ExitCode(0))
+            }
         },
 
         Subcommand::DetectBuildGraphChanges {
@@ -974,6 +1843,11 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             interactive,
             search_all_targets,
             unroll,
+            dedupe_with_gitignore,
+            allow_full_repo,
+            project_from_label,
+            project,
+            target,
         } => {
             let sparse_repo = paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
             paths::assert_focused_repo(&sparse_repo)?;
@@ -984,13 +1858,20 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                     app,
                     search_all_targets,
                     unroll,
+                    dedupe_with_gitignore,
                 )?;
             } else {
-                focus_operations::selection::add(
+                focus_operations::selection::add_with_options(
                     &sparse_repo,
                     true,
                     projects_and_targets,
-                    unroll,
+                    interpretation(project, target),
+                    AddOptions {
+                        unroll,
+                        project_from_label,
+                        dedupe_with_gitignore,
+                        allow_full_repo,
+                    },
                     app,
                 )?;
             }
@@ -1000,6 +1881,8 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
         Subcommand::Remove {
             projects_and_targets,
             all,
+            project,
+            target,
         } => {
             let sparse_repo = paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
             let _lock_file = hold_lock_file(&sparse_repo)?;
@@ -1007,6 +1890,7 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 &sparse_repo,
                 true,
                 projects_and_targets,
+                interpretation(project, target),
                 all,
                 app,
             )?;
@@ -1016,15 +1900,54 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
         Subcommand::Status {
             targets,
             target_types,
+            format,
+            machine_age,
+            watch_interval,
+            assert_project_selected,
         } => {
             let sparse_repo = paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
-            focus_operations::status::run(&sparse_repo, app, targets, target_types)
+            if !assert_project_selected.is_empty() {
+                focus_operations::status::assert_projects_selected(
+                    &sparse_repo,
+                    &assert_project_selected,
+                    app,
+                )
+            } else if machine_age {
+                let age = focus_operations::status::machine_age(&sparse_repo, app)?;
+                println!("{}", serde_json::to_string_pretty(&age)?);
+                Ok(ExitCode(0))
+            } else if let Some(secs) = watch_interval {
+                focus_operations::status::watch(
+                    &sparse_repo,
+                    app,
+                    targets,
+                    target_types,
+                    format,
+                    Duration::from_secs(secs),
+                )
+            } else {
+                focus_operations::status::run(&sparse_repo, app, targets, target_types, format)
+            }
         }
 
-        Subcommand::Projects {} => {
+        Subcommand::Doctor {} => {
+            let sparse_repo = paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
+            focus_operations::doctor::run(
+                &sparse_repo,
+                app,
+                focus_migrations::production::is_upgrade_required,
+            )
+        }
+
+        Subcommand::Projects { subcommand } => {
             let repo = git_helper::find_top_level(app.clone(), std::env::current_dir()?)
                 .context("Finding the top level of the repo")?;
-            focus_operations::selection::list_projects(&repo, app)?;
+            match subcommand {
+                None => focus_operations::selection::list_projects(&repo, app)?,
+                Some(ProjectsSubcommand::Show { name }) => {
+                    focus_operations::selection::show_project(&repo, &name, app)?
+                }
+            }
             Ok(ExitCode(0))
         }
 
@@ -1037,27 +1960,62 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 tracked,
                 git_config_path,
                 time_period,
+                dry_run,
+                timings,
+                skip_on_battery,
             } => {
                 let git_binary = GitBinary::from_binary_path(git_binary_path)?;
+                let run_options = focus_operations::maintenance::RunOptions {
+                    git_binary: Some(git_binary.clone()),
+                    git_config_key,
+                    git_config_path,
+                    tracked,
+                    timings,
+                    skip_on_battery,
+                    stream_output: false,
+                };
+
+                if dry_run {
+                    let planned = focus_operations::maintenance::plan(
+                        run_options,
+                        time_period,
+                        tracker,
+                        app,
+                    )?;
+                    for planned_run in planned {
+                        println!("{}", planned_run.path.display());
+                        for task in planned_run.tasks {
+                            println!("    {}", task.describe(time_period));
+                        }
+                    }
+                    return Ok(ExitCode(0));
+                }
+
                 if !check_compatible_git_version(&git_binary)? {
                     return Ok(ExitCode(1));
                 }
 
-                focus_operations::maintenance::run(
-                    focus_operations::maintenance::RunOptions {
-                        git_binary: Some(git_binary),
-                        git_config_key,
-                        git_config_path,
-                        tracked,
-                    },
-                    time_period,
-                    tracker,
-                    app,
-                )?;
+                let summary =
+                    focus_operations::maintenance::run(run_options, time_period, tracker, app)?;
+                if summary.skipped_on_battery {
+                    eprintln!("Skipped maintenance because the host is on battery power");
+                    return Ok(ExitCode(0));
+                }
 
                 sandbox::cleanup::run_with_default()?;
 
-                Ok(ExitCode(0))
+                if summary.is_success() {
+                    return Ok(ExitCode(0));
+                }
+                eprintln!(
+                    "Maintenance failed in {} of {} repos:",
+                    summary.failed.len(),
+                    summary.failed.len() + summary.succeeded.len()
+                );
+                for (path, error) in summary.failed.iter() {
+                    eprintln!("  {}: {}", path.display(), error);
+                }
+                Ok(ExitCode(1))
             }
 
             MaintenanceSubcommand::Register {
@@ -1094,6 +2052,7 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                     git_binary_path,
                     force_reload,
                     tracked,
+                    on_battery,
                 } => {
                     maintenance::schedule_enable(maintenance::ScheduleOpts {
                         time_period: if all { None } else { Some(time_period) },
@@ -1105,6 +2064,7 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                         },
                         skip_if_already_scheduled: !force_reload,
                         tracked,
+                        on_battery,
                     })?;
                     Ok(ExitCode(0))
                 }
@@ -1118,12 +2078,16 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             MaintenanceSubcommand::SandboxCleanup {
                 preserve_hours,
                 max_num_sandboxes,
+                keep,
+                dry_run,
             } => {
                 let config = sandbox::cleanup::Config {
                     preserve_hours: preserve_hours
                         .unwrap_or(sandbox::cleanup::Config::DEFAULT_HOURS),
                     max_num_sandboxes: max_num_sandboxes
                         .unwrap_or(sandbox::cleanup::Config::DEFAULT_MAX_NUM_SANDBOXES),
+                    keep,
+                    dry_run,
                     ..sandbox::cleanup::Config::try_from_git_default()?
                 };
 
@@ -1131,19 +2095,79 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
 
                 Ok(ExitCode(0))
             }
+
+            MaintenanceSubcommand::Status {} => {
+                use chrono::TimeZone;
+
+                let status = maintenance::status::status(
+                    &maintenance::status::LastRuns::default_path()?,
+                    &maintenance::status::LastRuns::skipped_path()?,
+                )?;
+                println!("Scheduler: {}", status.backend);
+                let format_time = |timestamp: i64| {
+                    chrono::Local
+                        .timestamp_opt(timestamp, 0)
+                        .single()
+                        .map(|time| time.to_rfc3339())
+                };
+                for job in status.jobs {
+                    let last_run = job
+                        .last_run
+                        .and_then(format_time)
+                        .unwrap_or_else(|| String::from("never"));
+                    // Only mention skips that happened since the last completed run.
+                    let last_skipped = job
+                        .last_skipped
+                        .filter(|skipped| job.last_run < Some(*skipped))
+                        .and_then(format_time)
+                        .map(|time| format!(" (skipped on battery: {})", time))
+                        .unwrap_or_default();
+                    println!(
+                        "{:<8} {:<14} next run: {:<32} last run: {}{}",
+                        job.time_period.name(),
+                        if job.registered {
+                            "scheduled"
+                        } else {
+                            "not scheduled"
+                        },
+                        job.next_run.as_deref().unwrap_or("unknown"),
+                        last_run,
+                        last_skipped,
+                    );
+                }
+                Ok(ExitCode(0))
+            }
         },
 
-        Subcommand::GitTrace { input, output } => {
-            focus_tracing::Trace::git_trace_from(input)?.write_trace_json_to(output)?;
+        Subcommand::GitTrace {
+            input,
+            output,
+            thread,
+            name_filter,
+        } => {
+            let filter = focus_tracing::chrome::EventFilter::default()
+                .with_thread(thread)
+                .with_name_substrings(name_filter);
+            focus_tracing::Trace::stream_git_trace_to(input, output, &filter)?;
             Ok(ExitCode(0))
         }
 
-        Subcommand::Upgrade { repo } => {
-            focus_migrations::production::perform_pending_migrations(
-                paths::find_repo_root_from(app.clone(), repo)?.as_path(),
-                app,
-            )
-            .context("Failed to upgrade repo")?;
+        Subcommand::Upgrade { repo, list } => {
+            let repo = paths::find_repo_root_from(app.clone(), repo)?;
+            if list {
+                let pending = focus_migrations::production::pending_migrations(&repo, app)
+                    .context("Failed to determine pending migrations")?;
+                if pending.is_empty() {
+                    eprintln!("No migrations are pending.");
+                }
+                for (identifier, description) in pending {
+                    println!("{} {}", identifier, description);
+                }
+                return Ok(ExitCode(0));
+            }
+
+            focus_migrations::production::perform_pending_migrations(repo.as_path(), app)
+                .context("Failed to upgrade repo")?;
 
             Ok(ExitCode(0))
         }
@@ -1168,31 +2192,73 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 sparse_repo,
                 force,
                 remote,
+                tag,
+                verify_key,
+                retries,
+                retry_base_ms,
             } => {
                 let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
-                let exit_code = focus_operations::index::fetch(app, sparse_repo, force, remote)?;
+                let exit_code = focus_operations::index::fetch(
+                    app,
+                    sparse_repo,
+                    force,
+                    remote,
+                    tag,
+                    verify_key,
+                    focus_operations::index::RetryPolicy {
+                        retries,
+                        base_delay: Duration::from_millis(retry_base_ms),
+                    },
+                )?;
                 Ok(exit_code)
             }
 
             IndexSubcommand::Generate {
                 sparse_repo,
                 break_on_missing_keys,
+                output_manifest,
+                jobs,
             } => {
                 let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
-                let exit_code =
-                    focus_operations::index::generate(app, sparse_repo, break_on_missing_keys)?;
+                let exit_code = focus_operations::index::generate(
+                    app,
+                    sparse_repo,
+                    break_on_missing_keys,
+                    output_manifest,
+                    jobs,
+                )?;
                 Ok(exit_code)
             }
 
+            IndexSubcommand::Stats { by_project, json } => {
+                let sparse_repo = paths::find_repo_root_from(app.clone(), PathBuf::from("."))?;
+                focus_operations::index::stats(app, &sparse_repo, by_project, json)
+            }
+
+            IndexSubcommand::Verify { sample, fix, json } => {
+                let sparse_repo = paths::find_repo_root_from(app.clone(), PathBuf::from("."))?;
+                focus_operations::index::verify(app, &sparse_repo, sample, fix, json)
+            }
+
             IndexSubcommand::Get { target } => {
                 let sparse_repo = paths::find_repo_root_from(app.clone(), PathBuf::from("."))?;
                 let exit_code = focus_operations::index::get(app, &sparse_repo, &target)?;
                 Ok(exit_code)
             }
 
-            IndexSubcommand::Hash { commit, targets } => {
+            IndexSubcommand::Hash {
+                commit,
+                targets,
+                include_env,
+            } => {
                 let sparse_repo = paths::find_repo_root_from(app.clone(), PathBuf::from("."))?;
-                let exit_code = focus_operations::index::hash(app, &sparse_repo, commit, &targets)?;
+                let exit_code = focus_operations::index::hash(
+                    app,
+                    &sparse_repo,
+                    commit,
+                    &targets,
+                    include_env,
+                )?;
                 Ok(exit_code)
             }
 
@@ -1201,6 +2267,10 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 remote,
                 dry_run,
                 break_on_missing_keys,
+                tag,
+                force,
+                sign_key,
+                read_only_check,
             } => {
                 let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
                 let exit_code = focus_operations::index::push(
@@ -1209,6 +2279,10 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                     remote,
                     dry_run,
                     break_on_missing_keys,
+                    tag,
+                    force,
+                    sign_key,
+                    read_only_check,
                 )?;
                 Ok(exit_code)
             }
@@ -1216,13 +2290,21 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             IndexSubcommand::Resolve {
                 targets,
                 break_on_missing_keys,
+                bazel_startup_opts,
+                bazel_query_opts,
+                bazel_config,
+                assert_subset_of,
             } => {
+                let bazel_options = BazelOptions::new(bazel_startup_opts, bazel_query_opts)?
+                    .with_config(bazel_config)?;
                 let sparse_repo = paths::find_repo_root_from(app.clone(), PathBuf::from("."))?;
                 let exit_code = focus_operations::index::resolve(
                     app,
                     &sparse_repo,
                     targets,
                     break_on_missing_keys,
+                    bazel_options,
+                    &assert_subset_of,
                 )?;
                 Ok(exit_code)
             }
@@ -1255,7 +2337,17 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             }
         },
 
-        Subcommand::Event { args: _ } => Ok(ExitCode(0)),
+        Subcommand::Event { args } => match args.first().map(String::as_str) {
+            Some("post-checkout") => {
+                let auto_sync = args.iter().any(|arg| arg == "--auto-sync");
+                focus_operations::event::post_checkout(app, auto_sync)
+            }
+            Some("post-merge") => {
+                let auto_sync = args.iter().any(|arg| arg == "--auto-sync");
+                focus_operations::event::post_merge(app, auto_sync)
+            }
+            _ => Ok(ExitCode(0)),
+        },
 
         Subcommand::Version => {
             println!("package-name: {}", env!("CARGO_PKG_NAME"));
@@ -1272,6 +2364,37 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
             Ok(ExitCode(0))
         }
 
+        Subcommand::Config { subcommand } => match subcommand {
+            ConfigSubcommand::Get { sparse_repo, key } => {
+                let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
+                focus_operations::config::get(&sparse_repo, &key, app)
+            }
+            ConfigSubcommand::Set {
+                sparse_repo,
+                key,
+                value,
+                force,
+            } => {
+                let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
+                focus_operations::config::set(&sparse_repo, &key, &value, force, app)
+            }
+            ConfigSubcommand::List { sparse_repo } => {
+                let sparse_repo = paths::find_repo_root_from(app, sparse_repo)?;
+                focus_operations::config::list(&sparse_repo)
+            }
+        },
+
+        Subcommand::Hooks { subcommand } => match subcommand {
+            HooksSubcommand::Install { sparse_repo } => {
+                let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
+                focus_operations::event::install_hooks(&sparse_repo, app)
+            }
+            HooksSubcommand::Uninstall { sparse_repo } => {
+                let sparse_repo = paths::find_repo_root_from(app.clone(), sparse_repo)?;
+                focus_operations::event::uninstall_hooks(&sparse_repo, app)
+            }
+        },
+
         Subcommand::Background { subcommand } => match subcommand {
             BackgroundSubcommand::Enable {
                 sparse_repo,
@@ -1310,6 +2433,54 @@ fn run_subcommand(app: Arc<App>, tracker: &Tracker, options: FocusOpts) -> Resul
                 )?;
                 Ok(ExitCode(0))
             }
+            SelectionSubcommand::Validate {} => {
+                let sparse_repo =
+                    paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
+                focus_operations::selection::validate(&sparse_repo, app)
+            }
+            SelectionSubcommand::History { limit, json } => {
+                let sparse_repo =
+                    paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
+                focus_operations::selection::history(&sparse_repo, limit, json, app)
+            }
+            SelectionSubcommand::Profile { subcommand } => {
+                let sparse_repo =
+                    paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
+                match subcommand {
+                    SelectionProfileSubcommand::Save { name } => {
+                        focus_operations::selection::save_profile(&sparse_repo, &name, app)?;
+                    }
+                    SelectionProfileSubcommand::Switch { name } => {
+                        paths::assert_focused_repo(&sparse_repo)?;
+                        let _lock_file = hold_lock_file(&sparse_repo)?;
+                        focus_operations::selection::switch_profile(&sparse_repo, &name, app)?;
+                    }
+                    SelectionProfileSubcommand::List {} => {
+                        focus_operations::selection::list_profiles(&sparse_repo, app)?;
+                    }
+                }
+                Ok(ExitCode(0))
+            }
+        },
+        Subcommand::Sandbox { subcommand } => match subcommand {
+            SandboxSubcommand::List {} => {
+                let config = sandbox::cleanup::Config::try_from_git_default()?;
+                let now = chrono::Utc::now();
+                for summary in sandbox::list::list(&config)? {
+                    // This command's own sandbox is removed when it exits.
+                    if summary.path == app.sandbox().path() {
+                        continue;
+                    }
+                    println!(
+                        "{:>8} {:>10} {:<24} {}",
+                        format_age(now - summary.modified),
+                        format_size(summary.size),
+                        summary.name_prefix.as_deref().unwrap_or("-"),
+                        summary.path.display()
+                    );
+                }
+                Ok(ExitCode(0))
+            }
         },
         Subcommand::On { run_sync } => {
             let sparse_repo = paths::find_repo_root_from(app.clone(), std::env::current_dir()?)?;
@@ -1378,6 +2549,11 @@ fn main_and_drop_locals() -> Result<ExitCode> {
         resolution_threads,
         working_directory,
         no_color,
+        offline,
+        timeout,
+        verbose,
+        quiet,
+        log_format,
         cmd: _,
     } = &options;
 
@@ -1386,19 +2562,32 @@ fn main_and_drop_locals() -> Result<ExitCode> {
     }
 
     let preserve_sandbox = true;
+    let is_tty = termion::is_tty(&std::io::stdout());
+    // The progress bar is drawn on stderr, so it is only shown when stderr is a terminal.
+    let progress_mode = if !progress_requested(&options.cmd) {
+        ProgressMode::Hidden
+    } else if termion::is_tty(&std::io::stderr()) {
+        ProgressMode::Bar
+    } else {
+        ProgressMode::Log
+    };
 
-    let app = Arc::from(App::new(
-        preserve_sandbox,
-        Some(&feature_name_for(&options.cmd)),
-        Some(env!("CARGO_PKG_NAME").to_owned()),
-        Some(env!("CARGO_PKG_VERSION").to_owned()),
-    )?);
+    let feature_name = feature_name_for(&options.cmd);
+    let app = Arc::from(
+        App::new(
+            preserve_sandbox,
+            Some(&feature_name),
+            Some(env!("CARGO_PKG_NAME").to_owned()),
+            Some(env!("CARGO_PKG_VERSION").to_owned()),
+        )?
+        .with_offline(*offline)
+        .with_git_timeout(timeout.map(Duration::from_secs))
+        .with_progress_mode(progress_mode),
+    );
     let ti_context = app.tool_insights_client();
 
     setup_thread_pool(*resolution_threads)?;
 
-    let is_tty = termion::is_tty(&std::io::stdout());
-
     let sandbox_dir = app.sandbox().path().to_owned();
     let tracker = Tracker::from_config_dir()?;
 
@@ -1406,7 +2595,10 @@ fn main_and_drop_locals() -> Result<ExitCode> {
         is_tty,
         no_color: *no_color,
         log_dir: Some(sandbox_dir.to_owned()),
+        console_level: console_level_for(*verbose, *quiet),
+        log_format: *log_format,
     })?;
+    let _feature_span = focus_tracing::feature_span(&feature_name).entered();
 
     info!(path = ?sandbox_dir, "Created sandbox");
 
@@ -1470,4 +2662,51 @@ mod tests {
         assert_eq!(feature_name, "event-this-is-an-event-subcommand-teehee");
         Ok(())
     }
+
+    #[test]
+    fn verbosity_flags_select_the_console_level() -> Result<()> {
+        let console_level = |args: &[&str]| -> Result<Option<LevelFilter>> {
+            let options = FocusOpts::try_parse_from(args)?;
+            Ok(console_level_for(options.verbose, options.quiet))
+        };
+        assert_eq!(console_level(&["focus", "status"])?, None);
+        assert_eq!(
+            console_level(&["focus", "-v", "status"])?,
+            Some(LevelFilter::DEBUG)
+        );
+        assert_eq!(
+            console_level(&["focus", "status", "-vv"])?,
+            Some(LevelFilter::TRACE)
+        );
+        assert_eq!(
+            console_level(&["focus", "-v", "--verbose", "-v", "status"])?,
+            Some(LevelFilter::TRACE)
+        );
+        assert_eq!(
+            console_level(&["focus", "--quiet", "status"])?,
+            Some(LevelFilter::WARN)
+        );
+        assert!(FocusOpts::try_parse_from(["focus", "-q", "-v", "status"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn offline_refuses_network_subcommands() -> Result<()> {
+        let options = FocusOpts::try_parse_from(["focus", "pull", "--offline"])?;
+        assert!(options.offline);
+        assert_eq!(network_operation(&options.cmd), Some("Pulling"));
+
+        let options = FocusOpts::try_parse_from(["focus", "status"])?;
+        assert_eq!(network_operation(&options.cmd), None);
+
+        let options = FocusOpts::try_parse_from(["focus", "index", "push", "--dry-run"])?;
+        assert_eq!(network_operation(&options.cmd), None);
+
+        let app = App::new_for_testing()?.with_offline(true);
+        assert_eq!(
+            app.require_network("Pulling").unwrap_err().to_string(),
+            "Pulling requires network, but --offline was specified"
+        );
+        Ok(())
+    }
 }
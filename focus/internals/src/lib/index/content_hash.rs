@@ -1,15 +1,16 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::cell::RefCell;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Write};
 use std::hash::Hash;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use lazy_static::lazy_static;
+use rayon::prelude::*;
 use regex::Regex;
 use thiserror::Error;
 use tracing::debug;
@@ -18,7 +19,7 @@ use tracing::trace;
 use tracing::warn;
 
 use crate::target::Label;
-use crate::target::TargetName;
+use crate::target::{pants_address_path, TargetName};
 use focus_util::paths::is_relevant_to_build_graph;
 
 use super::DependencyKey;
@@ -84,8 +85,12 @@ pub struct HashContext<'a> {
     /// The tree corresponding to the current working copy.
     head_tree: &'a git2::Tree<'a>,
 
-    /// Associated caches.
-    caches: RefCell<Caches>,
+    /// The Bazel config that targets are resolved under, which changes the
+    /// build graph and so contributes to the hashes of Bazel packages.
+    bazel_config: Option<String>,
+
+    /// Associated caches, shared with the contexts that hash on other threads in [`content_hash_all`].
+    caches: Arc<Mutex<Caches>>,
 }
 
 impl std::fmt::Debug for HashContext<'_> {
@@ -93,11 +98,13 @@ impl std::fmt::Debug for HashContext<'_> {
         let Self {
             repo,
             head_tree,
+            bazel_config,
             caches,
         } = self;
         f.debug_struct("HashContext")
             .field("repo", &repo.path())
             .field("head_tree", &head_tree.id())
+            .field("bazel_config", &bazel_config)
             .field("caches", &caches)
             .finish()
     }
@@ -109,10 +116,21 @@ impl<'repo> HashContext<'repo> {
         Ok(Self {
             repo,
             head_tree,
+            bazel_config: None,
             caches: Default::default(),
         })
     }
 
+    /// Hash Bazel packages as resolved under the named Bazel config. Hashes
+    /// without a config are unchanged.
+    pub fn with_bazel_config(self, bazel_config: Option<&str>) -> Self {
+        Self {
+            bazel_config: bazel_config.map(String::from),
+            caches: Default::default(),
+            ..self
+        }
+    }
+
     /// Get the underlying repository.
     pub fn repo(&self) -> &git2::Repository {
         self.repo
@@ -122,6 +140,13 @@ impl<'repo> HashContext<'repo> {
     pub fn head_tree(&self) -> &git2::Tree {
         self.head_tree
     }
+
+    fn caches(&self) -> MutexGuard<Caches> {
+        // The caches are only ever left partially updated by a panic, which is not recovered from.
+        self.caches
+            .lock()
+            .expect("hash context caches were poisoned")
+    }
 }
 
 #[derive(Debug, Error)]
@@ -132,6 +157,9 @@ pub enum Error {
     #[error("could not hash object: {0}")]
     HashObject(#[source] git2::Error),
 
+    #[error("could not open repository: {0}")]
+    OpenRepository(#[source] git2::Error),
+
     #[error("I/O error: {0}")]
     Fmt(#[from] std::fmt::Error),
 
@@ -148,6 +176,7 @@ impl Clone for Error {
         match self {
             Self::ReadTreeEntry(e) => Self::ReadTreeEntry(clone_git_error(e)),
             Self::HashObject(e) => Self::HashObject(clone_git_error(e)),
+            Self::OpenRepository(e) => Self::OpenRepository(clone_git_error(e)),
             Self::Fmt(e) => Self::Fmt(*e),
             Self::Bug(message) => Self::Bug(message.clone()),
         }
@@ -163,12 +192,56 @@ pub fn content_hash(ctx: &HashContext, key: &DependencyKey) -> Result<ContentHas
     content_hash_dependency_key(ctx, key)
 }
 
+/// Compute the content hashes of `keys` on the threads of the current Rayon pool. Each thread
+/// hashes a share of the keys with its own handle to the repository, and all of them fill the caches
+/// of `ctx`, so the result is the same as hashing the keys one by one with `ctx`.
+pub fn content_hash_all<'key>(
+    ctx: &HashContext,
+    keys: impl IntoIterator<Item = &'key DependencyKey>,
+) -> Result<BTreeMap<DependencyKey, ContentHash>> {
+    let mut keys: Vec<&DependencyKey> = keys.into_iter().collect();
+    let threads = rayon::current_num_threads();
+    if threads <= 1 || keys.len() <= 1 {
+        return keys
+            .into_iter()
+            .map(|key| Ok((key.clone(), content_hash(ctx, key)?)))
+            .collect();
+    }
+
+    // Sorting keeps keys that share dependencies, like packages in the same directory, in the same chunk.
+    keys.sort();
+    let chunk_size = keys.len() / threads + 1;
+    // `git2` handles cannot be shared between threads, so only the caches are.
+    let repo_path = ctx.repo.path();
+    let tree_id = ctx.head_tree.id();
+    let bazel_config = &ctx.bazel_config;
+    let caches = &ctx.caches;
+    let hashed = keys
+        .par_chunks(chunk_size)
+        .map(|chunk| -> Result<Vec<(DependencyKey, ContentHash)>> {
+            let repo = git2::Repository::open(repo_path).map_err(Error::OpenRepository)?;
+            let tree = repo.find_tree(tree_id).map_err(Error::ReadTreeEntry)?;
+            let ctx = HashContext {
+                repo: &repo,
+                head_tree: &tree,
+                bazel_config: bazel_config.clone(),
+                caches: caches.clone(),
+            };
+            chunk
+                .iter()
+                .map(|key| Ok(((*key).clone(), content_hash(&ctx, key)?)))
+                .collect()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(hashed.into_iter().flatten().collect())
+}
+
 fn content_hash_dependency_key(ctx: &HashContext, key: DependencyKey) -> Result<ContentHash> {
     debug!(?key, "Hashing dependency key");
 
     {
-        let cache = &mut ctx.caches.borrow_mut().dependency_key_cache;
-        if let Some(hash) = cache.get(&key) {
+        let caches = ctx.caches();
+        if let Some(hash) = caches.dependency_key_cache.get(&key) {
             return Ok(hash.to_owned());
         }
     }
@@ -180,6 +253,9 @@ fn content_hash_dependency_key(ctx: &HashContext, key: DependencyKey) -> Result<
     if let Some(label) = maybe_label {
         write!(&mut buf, "{label}, ")?;
     }
+    if let (DependencyKey::BazelPackage(_), Some(bazel_config)) = (&key, &ctx.bazel_config) {
+        write!(&mut buf, "config={bazel_config}, ")?;
+    }
     let hashes = values_to_hash
         .into_iter()
         .map(|key_or_hash| match key_or_hash {
@@ -196,8 +272,7 @@ fn content_hash_dependency_key(ctx: &HashContext, key: DependencyKey) -> Result<
     let hash = ContentHash(hash);
 
     if let Some(old_value) = ctx
-        .caches
-        .borrow_mut()
+        .caches()
         .dependency_key_cache
         .insert(key.to_owned(), hash.clone())
     {
@@ -208,6 +283,54 @@ fn content_hash_dependency_key(ctx: &HashContext, key: DependencyKey) -> Result<
     Ok(hash)
 }
 
+/// The version mixed into every content hash. Hashes computed under different
+/// versions never match.
+pub fn content_hash_version() -> usize {
+    VERSION
+}
+
+/// A path whose contents contribute to a content hash.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HashInput {
+    /// The path relative to the root of the tree. The empty path denotes the
+    /// root itself.
+    pub path: PathBuf,
+
+    /// The hash of the tree entry at `path`, or of the zero OID if absent.
+    pub hash: ContentHash,
+}
+
+/// List every path whose contents contribute, directly or transitively, to the
+/// content hash of `key`, sorted by path.
+///
+/// Content hashing reads only the tree in `ctx`. No environment variables,
+/// absolute paths, or machine configuration are consulted; the only other input
+/// is [`content_hash_version`].
+pub fn content_hash_inputs(ctx: &HashContext, key: &DependencyKey) -> Result<Vec<HashInput>> {
+    let mut inputs = BTreeSet::new();
+    let mut seen_keys = HashSet::new();
+    let mut keys = vec![key.clone()];
+    while let Some(key) = keys.pop() {
+        if !seen_keys.insert(key.clone()) {
+            continue;
+        }
+
+        let (_, _, values_to_hash) = get_dependencies(ctx, &key)?;
+        for key_or_path in values_to_hash {
+            match key_or_path {
+                KeyOrPath::Key(dep_key) => keys.push(dep_key),
+                KeyOrPath::Path(path) => {
+                    inputs.insert(HashInput {
+                        path: path.to_owned(),
+                        hash: content_hash_tree_path(ctx, path)?,
+                    });
+                }
+            }
+        }
+    }
+    Ok(inputs.into_iter().collect())
+}
+
 #[derive(Debug)]
 enum KeyOrPath<'a> {
     Key(DependencyKey),
@@ -323,6 +446,14 @@ fn get_dependencies<'a>(
 
         DependencyKey::Path(path) => Ok(("Path", None, vec![KeyOrPath::Path(path)])),
 
+        DependencyKey::PantsTarget(address) => Ok((
+            "PantsTarget",
+            None,
+            vec![KeyOrPath::Key(DependencyKey::Path(pants_address_path(
+                address,
+            )))],
+        )),
+
         DependencyKey::DummyForTesting(inner_dep_key) => Ok((
             "DummyForTesting",
             None,
@@ -352,7 +483,7 @@ pub fn get_workspace_deps(ctx: &HashContext) -> Result<BTreeSet<DependencyKey>>
 /// https://github.com/bazelbuild/bazel/issues/1674 for discussion on what this
 /// file is.
 pub fn get_prelude_deps(ctx: &HashContext) -> Result<BTreeSet<Label>> {
-    if let Some(prelude_deps) = &ctx.caches.borrow().prelude_deps_cache {
+    if let Some(prelude_deps) = &ctx.caches().prelude_deps_cache {
         return Ok(prelude_deps.clone());
     }
 
@@ -375,12 +506,12 @@ pub fn get_prelude_deps(ctx: &HashContext) -> Result<BTreeSet<Label>> {
         Err(err) => return Err(Error::ReadTreeEntry(err)),
     };
 
-    ctx.caches.borrow_mut().prelude_deps_cache = Some(result.clone());
+    ctx.caches().prelude_deps_cache = Some(result.clone());
     Ok(result)
 }
 
 fn content_hash_tree_path(ctx: &HashContext, path: &Path) -> Result<ContentHash> {
-    if let Some(hash) = ctx.caches.borrow().tree_path_cache.get(path) {
+    if let Some(hash) = ctx.caches().tree_path_cache.get(path) {
         return Ok(hash.clone());
     }
 
@@ -395,8 +526,7 @@ fn content_hash_tree_path(ctx: &HashContext, path: &Path) -> Result<ContentHash>
         .map_err(Error::HashObject)?;
     let hash = ContentHash(hash);
     if let Some(old_value) = ctx
-        .caches
-        .borrow_mut()
+        .caches()
         .tree_path_cache
         .insert(path.to_owned(), hash.clone())
     {
@@ -446,7 +576,7 @@ fn get_tree_for_path<'repo>(
 
 fn find_load_dependencies(ctx: &HashContext, tree: &git2::Tree) -> Result<BTreeSet<Label>> {
     trace!(?tree, "Finding load dependencies");
-    if let Some(result) = ctx.caches.borrow().load_dependencies_cache.get(&tree.id()) {
+    if let Some(result) = ctx.caches().load_dependencies_cache.get(&tree.id()) {
         return Ok(result.clone());
     }
 
@@ -458,8 +588,7 @@ fn find_load_dependencies(ctx: &HashContext, tree: &git2::Tree) -> Result<BTreeS
         }
     }
     if let Some(old_value) = ctx
-        .caches
-        .borrow_mut()
+        .caches()
         .load_dependencies_cache
         .insert(tree.id(), result.clone())
     {
@@ -539,7 +668,7 @@ load
     let mut result = BTreeSet::new();
     for cap in RE.captures_iter(content) {
         let value = cap.get(1).or_else(|| cap.get(2)).unwrap().as_str();
-        let label: Label = match value.parse() {
+        let label = match Label::parse_lenient(value) {
             Ok(label) => label,
             Err(e) => {
                 warn!(?e, "Failed to parse label in load statement");
@@ -555,6 +684,130 @@ load
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_content_hash_inputs() -> anyhow::Result<()> {
+        let temp = tempfile::tempdir()?;
+        let fix = focus_testing::ScratchGitRepo::new_static_fixture(temp.path())?;
+        for (file_name, file_contents) in [
+            ("WORKSPACE", ""),
+            (
+                "package1/BUILD",
+                "load(\"//tools:defs.bzl\", \"foo\")\nfoo(name = \"foo\")",
+            ),
+            ("package1/foo.sh", "#!/bin/sh"),
+            ("tools/BUILD", ""),
+            ("tools/defs.bzl", "def foo(name): pass"),
+        ] {
+            fix.write_file(file_name, file_contents)?;
+            fix.add_file(file_name)?;
+        }
+        let head_oid = fix.commit_all("Wrote files")?;
+
+        let repo = fix.repo()?;
+        let head_tree = repo.find_commit(head_oid)?.tree()?;
+        let ctx = HashContext::new(&repo, &head_tree)?;
+        let key = DependencyKey::BazelPackage("//package1:foo".parse()?);
+        let inputs = content_hash_inputs(&ctx, &key)?;
+
+        let paths: Vec<&Path> = inputs.iter().map(|input| input.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("WORKSPACE"),
+                Path::new("package1"),
+                Path::new("tools/defs.bzl"),
+            ]
+        );
+        for input in inputs.iter() {
+            assert_eq!(input.hash, content_hash_tree_path(&ctx, &input.path)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bazel_config_changes_package_hashes() -> anyhow::Result<()> {
+        let temp = tempfile::tempdir()?;
+        let fix = focus_testing::ScratchGitRepo::new_static_fixture(temp.path())?;
+        for (file_name, file_contents) in [("WORKSPACE", ""), ("package1/BUILD", "")] {
+            fix.write_file(file_name, file_contents)?;
+            fix.add_file(file_name)?;
+        }
+        let head_oid = fix.commit_all("Wrote files")?;
+
+        let repo = fix.repo()?;
+        let head_tree = repo.find_commit(head_oid)?.tree()?;
+        let package = DependencyKey::BazelPackage("//package1:foo".parse()?);
+        let path = DependencyKey::Path("package1".into());
+        let hash_under = |config: Option<&str>, key: &DependencyKey| -> anyhow::Result<_> {
+            let ctx = HashContext::new(&repo, &head_tree)?.with_bazel_config(config);
+            Ok(content_hash(&ctx, key)?)
+        };
+
+        let unconfigured = hash_under(None, &package)?;
+        let ci = hash_under(Some("ci"), &package)?;
+        let opt = hash_under(Some("opt"), &package)?;
+        assert_ne!(unconfigured, ci);
+        assert_ne!(ci, opt);
+        assert_eq!(ci, hash_under(Some("ci"), &package)?);
+        assert_eq!(
+            unconfigured,
+            content_hash(&HashContext::new(&repo, &head_tree)?, &package)?
+        );
+
+        // Only the build graph depends on the config.
+        assert_eq!(hash_under(None, &path)?, hash_under(Some("ci"), &path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_hash_all_is_independent_of_thread_count() -> anyhow::Result<()> {
+        let temp = tempfile::tempdir()?;
+        let fix = focus_testing::ScratchGitRepo::new_static_fixture(temp.path())?;
+        fix.write_file("WORKSPACE", "")?;
+        fix.add_file("WORKSPACE")?;
+        fix.write_file("tools/defs.bzl", "def foo(name): pass")?;
+        fix.add_file("tools/defs.bzl")?;
+        let mut keys = HashSet::new();
+        for i in 0..16 {
+            let build_file = format!("package{}/BUILD", i);
+            fix.write_file(
+                &build_file,
+                "load(\"//tools:defs.bzl\", \"foo\")\nfoo(name = \"foo\")",
+            )?;
+            fix.add_file(&build_file)?;
+            keys.insert(DependencyKey::BazelPackage(
+                format!("//package{}:foo", i).parse()?,
+            ));
+            keys.insert(DependencyKey::Path(format!("package{}", i).into()));
+        }
+        let head_oid = fix.commit_all("Wrote files")?;
+
+        let repo = fix.repo()?;
+        let head_tree = repo.find_commit(head_oid)?.tree()?;
+        let ctx = HashContext::new(&repo, &head_tree)?;
+        let sequential = keys
+            .iter()
+            .map(|key| Ok((key.clone(), content_hash(&ctx, key)?)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let ctx = HashContext::new(&repo, &head_tree)?;
+        let parallel = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()?
+            .install(|| content_hash_all(&ctx, &keys))?;
+        assert_eq!(parallel, sequential);
+
+        // The hashes computed on other threads are in the caches of the context they were computed for.
+        let caches = ctx.caches();
+        assert!(keys
+            .iter()
+            .all(|key| caches.dependency_key_cache.contains_key(key)));
+
+        Ok(())
+    }
+
     #[test]
     fn test_extract_load_statements() -> Result<()> {
         let content = r#"
@@ -0,0 +1,532 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sharing the index through a content-addressable store rather than a Git remote.
+//!
+//! Each entry is stored under `objects/<kind>/<key>`, where the key is the content hash of the entry's inputs, so entries shared by many keysets are stored once, and sharing skips entries the store already has. Each keyset is a manifest under `keysets/<keyset id>` listing the composite keys of its entries, one per line. The manifest is written after the entries it lists, so a keyset that can be read is complete.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use content_addressed_cache::{
+    Cache, CacheSynchronizer, CompositeKey, Keyset, KeysetID, PopulateResult, DELIMITER,
+};
+use rayon::prelude::*;
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// The prefix of index remotes that name a content-addressable store, as in `cas+https://artifacts.example.com/focus-index`. Remotes without it are Git remotes.
+pub const CONTENT_STORE_SCHEME_PREFIX: &str = "cas+";
+
+/// The environment variable holding a token to send as a bearer token with requests to a content-addressable store over HTTP.
+pub const CONTENT_STORE_TOKEN_ENV_VAR: &str = "FOCUS_INDEX_STORE_TOKEN";
+
+const KEYSETS_DIR: &str = "keysets";
+const OBJECTS_DIR: &str = "objects";
+const WRITE_CHECK_PATH: &str = "write-check";
+
+/// Whether `remote` names a content-addressable store rather than a Git remote.
+pub fn is_content_store_remote(remote: &str) -> bool {
+    remote.starts_with(CONTENT_STORE_SCHEME_PREFIX)
+}
+
+/// Storage for content-addressed blobs, addressed by paths relative to the root of the store. Requests may be made from several threads at once.
+trait ContentStoreBackend: Send + Sync {
+    /// The blob at `path`, or `None` if there is none.
+    fn get(&self, path: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Whether there is a blob at `path`, without reading it.
+    fn exists(&self, path: &str) -> Result<bool>;
+
+    fn put(&self, path: &str, value: Vec<u8>) -> Result<()>;
+}
+
+/// A store served over HTTP, read with GET and written with PUT.
+struct HttpContentStoreBackend {
+    base: Url,
+    client: Client,
+    token: Option<String>,
+}
+
+impl HttpContentStoreBackend {
+    fn new(mut base: Url) -> Result<Self> {
+        // Paths are joined onto the base, which would otherwise replace its last segment.
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        static APP_USER_AGENT: &str = concat!("focus", "/", env!("CARGO_PKG_VERSION"));
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(APP_USER_AGENT)
+            .gzip(true)
+            .build()
+            .context("Creating HTTP client failed")?;
+        let token = std::env::var(CONTENT_STORE_TOKEN_ENV_VAR)
+            .ok()
+            .filter(|token| !token.is_empty());
+        Ok(Self {
+            base,
+            client,
+            token,
+        })
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        self.base
+            .join(path)
+            .with_context(|| format!("Joining {} onto {}", path, self.base))
+    }
+
+    fn authorize(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+impl ContentStoreBackend for HttpContentStoreBackend {
+    fn get(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let url = self.url(path)?;
+        debug!(url = ?url.as_str(), "GET");
+        let response = self
+            .authorize(self.client.get(url))
+            .send()
+            .context("GET failed")?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let mut response = response.error_for_status()?;
+        let mut buf = Vec::new();
+        response
+            .read_to_end(&mut buf)
+            .context("Reading response failed")?;
+        Ok(Some(buf))
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        let url = self.url(path)?;
+        debug!(url = ?url.as_str(), "HEAD");
+        let response = self
+            .authorize(self.client.head(url))
+            .send()
+            .context("HEAD failed")?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        response.error_for_status()?;
+        Ok(true)
+    }
+
+    fn put(&self, path: &str, value: Vec<u8>) -> Result<()> {
+        let url = self.url(path)?;
+        debug!(url = ?url.as_str(), "PUT");
+        self.authorize(self.client.put(url))
+            .body(value)
+            .send()
+            .context("PUT failed")?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// A store in a local directory, as named by a `file` URL.
+struct FileContentStoreBackend {
+    root: PathBuf,
+}
+
+impl ContentStoreBackend for FileContentStoreBackend {
+    fn get(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.root.join(path);
+        match std::fs::read(&path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Reading {}", path.display())),
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.root.join(path).is_file())
+    }
+
+    fn put(&self, path: &str, value: Vec<u8>) -> Result<()> {
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating {}", parent.display()))?;
+        }
+        std::fs::write(&path, value).with_context(|| format!("Writing {}", path.display()))
+    }
+}
+
+/// Synchronize the index with a content-addressable store over HTTP (or in a local directory, for `cas+file` remotes).
+///
+/// Named snapshots and signing are not supported, since the store holds nothing but content-addressed blobs.
+pub struct ContentStoreSynchronizer {
+    remote: String,
+    backend: Box<dyn ContentStoreBackend>,
+}
+
+impl fmt::Debug for ContentStoreSynchronizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContentStoreSynchronizer")
+            .field("remote", &self.remote)
+            .finish()
+    }
+}
+
+impl ContentStoreSynchronizer {
+    /// Create a synchronizer for `remote`, which must start with [`CONTENT_STORE_SCHEME_PREFIX`].
+    pub fn new(remote: &str) -> Result<Self> {
+        let url = remote
+            .strip_prefix(CONTENT_STORE_SCHEME_PREFIX)
+            .with_context(|| {
+                format!(
+                    "'{}' is not a content-addressable store remote (expected a '{}' prefix)",
+                    remote, CONTENT_STORE_SCHEME_PREFIX
+                )
+            })?;
+        let url = Url::parse(url).with_context(|| format!("Parsing index remote '{}'", remote))?;
+        let backend: Box<dyn ContentStoreBackend> = match url.scheme() {
+            "http" | "https" => Box::new(HttpContentStoreBackend::new(url)?),
+            "file" => {
+                let root = url
+                    .to_file_path()
+                    .map_err(|_| anyhow::anyhow!("'{}' is not a valid file URL", url))?;
+                Box::new(FileContentStoreBackend { root })
+            }
+            scheme => bail!(
+                "Unsupported scheme '{}' for content-addressable store remote '{}'",
+                scheme,
+                remote
+            ),
+        };
+        Ok(Self {
+            remote: remote.to_owned(),
+            backend,
+        })
+    }
+
+    /// Check that the store accepts writes by writing a throwaway blob.
+    pub fn check_remote_is_writable(&self) -> Result<()> {
+        self.backend
+            .put(WRITE_CHECK_PATH, Vec::new())
+            .with_context(|| format!("Index remote {} is not writable", self.remote))
+    }
+
+    fn keyset_path(keyset_id: &KeysetID) -> String {
+        format!("{}/{}", KEYSETS_DIR, keyset_id)
+    }
+
+    fn object_path(key: &CompositeKey) -> String {
+        format!(
+            "{}/{}",
+            OBJECTS_DIR,
+            key.to_string().replacen(DELIMITER, "/", 1)
+        )
+    }
+
+    fn manifest(&self, keyset_id: &KeysetID) -> Result<Option<Vec<CompositeKey>>> {
+        let content = match self.backend.get(&Self::keyset_path(keyset_id))? {
+            Some(content) => content,
+            None => return Ok(None),
+        };
+        let content = String::from_utf8(content)
+            .with_context(|| format!("Manifest for keyset {} is not UTF-8", keyset_id))?;
+        let mut keys = Vec::new();
+        for line in content.lines().filter(|line| !line.is_empty()) {
+            match CompositeKey::from_str(line) {
+                Ok(key) => keys.push(key),
+                Err(_) => warn!(%line, %keyset_id, "Manifest entry is not a composite key"),
+            }
+        }
+        Ok(Some(keys))
+    }
+}
+
+impl CacheSynchronizer for ContentStoreSynchronizer {
+    /// Entries are read from the store as they are populated, so fetching only checks that the keyset exists.
+    fn fetch(&self, keyset_id: KeysetID) -> Result<KeysetID> {
+        if self.find_remote_keyset(&[keyset_id])?.is_none() {
+            bail!("Keyset {} does not exist on {}", keyset_id, self.remote);
+        }
+        Ok(keyset_id)
+    }
+
+    fn populate(&self, keyset_id: &KeysetID, dest_cache: &dyn Cache) -> Result<PopulateResult> {
+        let keys = self
+            .manifest(keyset_id)?
+            .with_context(|| format!("Keyset {} does not exist on {}", keyset_id, self.remote))?;
+
+        let mut result = PopulateResult {
+            entry_count: keys.len(),
+            ..Default::default()
+        };
+        let mut missing_keys = Vec::new();
+        for key in keys {
+            match dest_cache.get(key.kind, key.key) {
+                Ok(Some(_)) => continue,
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(kind = ?key.kind, key = ?key.key, ?e, "Failed to get key from cache")
+                }
+            }
+            missing_keys.push(key);
+        }
+
+        // The entries are independent, so read them concurrently; the cache is only written from this thread.
+        let values: Vec<(CompositeKey, Result<Option<Vec<u8>>>)> = missing_keys
+            .into_par_iter()
+            .map(|key| {
+                let value = self.backend.get(&Self::object_path(&key));
+                (key, value)
+            })
+            .collect();
+        for (key, value) in values {
+            let value = match value {
+                Ok(Some(value)) => value,
+                Ok(None) => {
+                    result.failed_entry_count += 1;
+                    warn!(key = %key.to_string(), "Entry listed in keyset is missing from the store");
+                    continue;
+                }
+                Err(e) => {
+                    result.failed_entry_count += 1;
+                    warn!(key = %key.to_string(), ?e, "Failed to read entry from the store");
+                    continue;
+                }
+            };
+            match dest_cache.put(key.kind, key.key, &value) {
+                Ok(()) => result.new_entry_count += 1,
+                Err(e) => {
+                    result.failed_entry_count += 1;
+                    warn!(key = %key.to_string(), ?e, "Failed to insert key into Cache");
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn fetch_and_populate(
+        &self,
+        keyset_id: KeysetID,
+        dest_cache: &dyn Cache,
+    ) -> Result<(PopulateResult, KeysetID)> {
+        let populate_result = self
+            .populate(&keyset_id, dest_cache)
+            .with_context(|| format!("Populating cache from keyset {}", keyset_id))?;
+        if populate_result != PopulateResult::default() {
+            info!(?populate_result, %keyset_id, "Populated index");
+        }
+        Ok((populate_result, keyset_id))
+    }
+
+    fn share(
+        &self,
+        keyset_id: KeysetID,
+        keyset: &Keyset,
+        cache: &dyn Cache,
+        _previous_keyset_id: Option<KeysetID>,
+    ) -> Result<KeysetID> {
+        let composite_keys: Vec<CompositeKey> = keyset
+            .iter()
+            .map(|(kind, key)| CompositeKey {
+                kind: *kind,
+                key: *key,
+            })
+            .collect();
+
+        // Keys are content hashes, so an entry the store already has does not need to be uploaded again.
+        let missing_keys = composite_keys
+            .par_iter()
+            .map(|composite_key| {
+                let exists = self
+                    .backend
+                    .exists(&Self::object_path(composite_key))
+                    .with_context(|| format!("Checking for entry {}", composite_key.to_string()))?;
+                Ok(if exists { None } else { Some(composite_key) })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut uploads = Vec::new();
+        for composite_key in missing_keys.into_iter().flatten() {
+            let value = cache
+                .get(composite_key.kind, composite_key.key)?
+                .with_context(|| format!("Missing cache entry {}", composite_key.to_string()))?;
+            uploads.push((composite_key, value));
+        }
+        debug!(
+            uploaded = uploads.len(),
+            total = composite_keys.len(),
+            "Uploading entries missing from the store"
+        );
+        uploads
+            .into_par_iter()
+            .map(|(composite_key, value)| {
+                self.backend
+                    .put(&Self::object_path(composite_key), value)
+                    .with_context(|| format!("Storing entry {}", composite_key.to_string()))
+            })
+            .collect::<Result<()>>()?;
+
+        let mut names: Vec<String> = composite_keys.iter().map(|key| key.to_string()).collect();
+        names.sort();
+
+        let mut manifest = names.join("\n");
+        manifest.push('\n');
+        self.backend
+            .put(&Self::keyset_path(&keyset_id), manifest.into_bytes())
+            .with_context(|| format!("Storing manifest for keyset {}", keyset_id))?;
+        Ok(keyset_id)
+    }
+
+    fn available_remote_keysets(&self) -> Result<HashSet<KeysetID>> {
+        bail!(
+            "Content-addressable store {} cannot list its keysets",
+            self.remote
+        )
+    }
+
+    fn find_remote_keyset(&self, candidates: &[KeysetID]) -> Result<Option<KeysetID>> {
+        for keyset_id in candidates {
+            if self.backend.get(&Self::keyset_path(keyset_id))?.is_some() {
+                return Ok(Some(*keyset_id));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use content_addressed_cache::{CacheKey, CacheKeyKind};
+    use git2::Oid;
+    use maplit::hashset;
+
+    use super::*;
+
+    const KIND: [u8; 2] = [0x00, 0x01];
+
+    #[derive(Debug, Default)]
+    struct MemoryCache(RefCell<HashMap<(CacheKeyKind, CacheKey), Vec<u8>>>);
+
+    impl Cache for MemoryCache {
+        fn put(&self, kind: CacheKeyKind, key: CacheKey, value: &[u8]) -> Result<()> {
+            self.0.borrow_mut().insert((kind, key), value.to_vec());
+            Ok(())
+        }
+
+        fn get(&self, kind: CacheKeyKind, key: CacheKey) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.borrow().get(&(kind, key)).cloned())
+        }
+
+        fn clear(&self) -> Result<()> {
+            self.0.borrow_mut().clear();
+            Ok(())
+        }
+    }
+
+    fn remote_for(dir: &std::path::Path) -> String {
+        format!(
+            "{}{}",
+            CONTENT_STORE_SCHEME_PREFIX,
+            Url::from_directory_path(dir).unwrap()
+        )
+    }
+
+    #[test]
+    fn share_and_populate_through_a_content_store() -> Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let source = MemoryCache::default();
+        let dest = MemoryCache::default();
+
+        let key_1 = Oid::hash_object(git2::ObjectType::Blob, b"key 1")?;
+        let key_2 = Oid::hash_object(git2::ObjectType::Blob, b"key 2")?;
+        source.put(KIND, key_1, b"value 1")?;
+        source.put(KIND, key_2, b"value 2")?;
+        let keyset_id = Oid::hash_object(git2::ObjectType::Tree, b"keyset")?;
+        let other_keyset_id = Oid::hash_object(git2::ObjectType::Tree, b"other keyset")?;
+
+        let synchronizer = ContentStoreSynchronizer::new(&remote_for(store_dir.path()))?;
+        synchronizer.check_remote_is_writable()?;
+        assert_eq!(
+            synchronizer.find_remote_keyset(&[other_keyset_id, keyset_id])?,
+            None
+        );
+        synchronizer.share(
+            keyset_id,
+            &hashset! {(KIND, key_1), (KIND, key_2)},
+            &source,
+            None,
+        )?;
+        assert_eq!(
+            synchronizer.find_remote_keyset(&[other_keyset_id, keyset_id])?,
+            Some(keyset_id)
+        );
+        assert!(synchronizer.fetch(other_keyset_id).is_err());
+
+        let (result, fetched) = synchronizer.fetch_and_populate(keyset_id, &dest)?;
+        assert_eq!(fetched, keyset_id);
+        assert_eq!(
+            result,
+            PopulateResult {
+                entry_count: 2,
+                new_entry_count: 2,
+                failed_entry_count: 0,
+            }
+        );
+        assert_eq!(dest.get(KIND, key_1)?, Some(b"value 1".to_vec()));
+        assert_eq!(dest.get(KIND, key_2)?, Some(b"value 2".to_vec()));
+
+        // Entries already present are not fetched again.
+        let (result, _) = synchronizer.fetch_and_populate(keyset_id, &dest)?;
+        assert_eq!(result.new_entry_count, 0);
+
+        // Entries already in the store are not uploaded again.
+        let object_path =
+            store_dir
+                .path()
+                .join(ContentStoreSynchronizer::object_path(&CompositeKey {
+                    kind: KIND,
+                    key: key_1,
+                }));
+        std::fs::write(&object_path, b"stored earlier")?;
+        synchronizer.share(
+            other_keyset_id,
+            &hashset! {(KIND, key_1), (KIND, key_2)},
+            &source,
+            None,
+        )?;
+        assert_eq!(std::fs::read(&object_path)?, b"stored earlier");
+        assert_eq!(
+            synchronizer.find_remote_keyset(&[other_keyset_id])?,
+            Some(other_keyset_id)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_prefixed_remotes_are_content_stores() {
+        assert!(is_content_store_remote(
+            "cas+https://artifacts.example.com/focus-index"
+        ));
+        assert!(!is_content_store_remote(
+            "https://git.example.com/focus-index"
+        ));
+        assert!(ContentStoreSynchronizer::new("https://git.example.com/focus-index").is_err());
+        assert!(ContentStoreSynchronizer::new("cas+ssh://example.com/focus-index").is_err());
+    }
+}
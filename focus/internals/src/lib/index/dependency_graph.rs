@@ -4,7 +4,7 @@
 use std::collections::{BTreeSet, HashSet};
 use std::path::PathBuf;
 
-use crate::index::content_hash::{get_prelude_deps, get_workspace_deps};
+use crate::index::content_hash::{content_hash_all, get_prelude_deps, get_workspace_deps};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
@@ -75,6 +75,14 @@ pub enum DependencyKey {
     /// which isn't a Bazel package.
     Path(PathBuf),
 
+    /// Represents a Pants target by its address, such as `src/python/foo:bar`.
+    ///
+    /// Its content hash only covers the files at the address's own path, so
+    /// it cannot tell when a transitive dependency changes. For that reason
+    /// the resolved dependencies of a Pants target are never stored in the
+    /// [`ObjectDatabase`], and it is resolved again on every sync.
+    PantsTarget(String),
+
     /// This value was generated during testing, and should not appear in a
     /// production object database.
     DummyForTesting(Box<DependencyKey>),
@@ -85,6 +93,7 @@ impl From<Target> for DependencyKey {
         match target {
             Target::Bazel(label) => Self::BazelPackage(label),
             Target::Directory(path) => Self::Path(PathBuf::from(path)),
+            Target::Pants(address) => Self::PantsTarget(address),
         }
     }
 }
@@ -141,6 +150,10 @@ pub fn update_object_database_from_resolution(
                     "Non-Bazel dependency key returned in `ResolutionResult`"
                 )
             }
+            DependencyKey::PantsTarget(_) => {
+                debug!(?dep_key, "Not caching Pants dependency key");
+                continue;
+            }
         }
 
         odb.put(ctx, dep_key, dep_value.clone())?;
@@ -231,6 +244,20 @@ pub fn get_files_to_materialize(
     let mut seen_keys = HashSet::new();
     let mut missing_keys = HashSet::new();
     while !dep_keys.is_empty() {
+        // Hash the keys looked up below on the thread pool first, so that the lookups find their hashes in the caches.
+        content_hash_all(
+            ctx,
+            dep_keys.iter().filter(|dep_key| {
+                matches!(
+                    dep_key,
+                    DependencyKey::BazelPackage(Label {
+                        external_repository: None,
+                        ..
+                    }) | DependencyKey::PantsTarget(_)
+                )
+            }),
+        )?;
+
         let mut next_deps = HashSet::new();
         for dep_key in dep_keys {
             seen_keys.insert(dep_key.clone());
@@ -274,6 +301,10 @@ pub fn get_files_to_materialize(
                     continue;
                 }
 
+                DependencyKey::PantsTarget(_) => {
+                    // Never cached, so this is reported as missing below.
+                }
+
                 DependencyKey::DummyForTesting(inner_dep_key) => {
                     warn!(
                         ?inner_dep_key,
@@ -9,11 +9,19 @@
 
 mod churn;
 mod content_hash;
+mod content_store;
 mod dependency_graph;
 mod object_database;
 
 pub use churn::print_churn_stats;
-pub use content_hash::{content_hash, ContentHash, HashContext};
+pub use content_hash::{
+    content_hash, content_hash_all, content_hash_inputs, content_hash_version, ContentHash,
+    HashContext, HashInput,
+};
+pub use content_store::{
+    is_content_store_remote, ContentStoreSynchronizer, CONTENT_STORE_SCHEME_PREFIX,
+    CONTENT_STORE_TOKEN_ENV_VAR,
+};
 pub use dependency_graph::{
     get_files_to_materialize, update_object_database_from_resolution, DependencyKey,
     DependencyValue, PathsToMaterializeResult,
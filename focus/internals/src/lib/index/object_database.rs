@@ -71,15 +71,16 @@ impl<T: Cache> ObjectDatabase for T {
 
         if let Some(old_payload) = self.get(*FUNCTION_ID, hash.0)? {
             if payload != old_payload {
-                let old_value: DependencyValue = serde_json::from_slice(&old_payload)
-                    .context("deserializing old DependencyValue")?;
-                error!(
-                    ?key,
-                    ?old_value,
-                    new_value = ?value,
-                    ?hash,
-                    "Non-deterministic dependency hashing"
-                );
+                match serde_json::from_slice::<DependencyValue>(&old_payload) {
+                    Ok(old_value) => error!(
+                        ?key,
+                        ?old_value,
+                        new_value = ?value,
+                        ?hash,
+                        "Non-deterministic dependency hashing"
+                    ),
+                    Err(e) => warn!(?key, ?hash, ?e, "Replacing unreadable entry"),
+                }
             }
         }
 
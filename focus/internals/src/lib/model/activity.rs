@@ -0,0 +1,88 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::persistence::{load_model, store_model};
+
+/// Something done to a repo whose most recent occurrence is recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    /// The index was fetched from the remote.
+    IndexFetch,
+
+    /// The working tree was synchronized with the selection.
+    Sync,
+
+    /// Background maintenance ran.
+    Maintenance,
+}
+
+/// When each `Activity` last completed successfully, in seconds since the Unix epoch. Missing values mean the activity has not been recorded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityTimestamps {
+    #[serde(default)]
+    pub index_fetch: Option<i64>,
+
+    #[serde(default)]
+    pub sync: Option<i64>,
+
+    #[serde(default)]
+    pub maintenance: Option<i64>,
+}
+
+impl ActivityTimestamps {
+    pub fn load(path: &Path) -> Result<Self> {
+        load_model(path).with_context(|| format!("Loading activity from {}", path.display()))
+    }
+
+    /// Record that `activity` happened at `timestamp`, updating the file at `path`.
+    pub fn record(path: &Path, activity: Activity, timestamp: i64) -> Result<()> {
+        let mut timestamps = Self::load(path)?;
+        *timestamps.get_mut(activity) = Some(timestamp);
+        store_model(path, &timestamps)
+            .with_context(|| format!("Storing activity to {}", path.display()))
+    }
+
+    pub fn get(&self, activity: Activity) -> Option<i64> {
+        match activity {
+            Activity::IndexFetch => self.index_fetch,
+            Activity::Sync => self.sync,
+            Activity::Maintenance => self.maintenance,
+        }
+    }
+
+    fn get_mut(&mut self, activity: Activity) -> &mut Option<i64> {
+        match activity {
+            Activity::IndexFetch => &mut self.index_fetch,
+            Activity::Sync => &mut self.sync,
+            Activity::Maintenance => &mut self.maintenance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_preserves_other_activities() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("activity.json");
+        assert_eq!(ActivityTimestamps::load(&path)?, Default::default());
+
+        ActivityTimestamps::record(&path, Activity::Sync, 100)?;
+        ActivityTimestamps::record(&path, Activity::Maintenance, 200)?;
+        ActivityTimestamps::record(&path, Activity::Sync, 300)?;
+
+        let timestamps = ActivityTimestamps::load(&path)?;
+        assert_eq!(timestamps.get(Activity::Sync), Some(300));
+        assert_eq!(timestamps.get(Activity::Maintenance), Some(200));
+        assert_eq!(timestamps.get(Activity::IndexFetch), None);
+
+        Ok(())
+    }
+}
@@ -8,6 +8,17 @@ pub struct IndexConfig {
     /// Whether fetching is enabled
     pub enabled: bool,
 
-    /// Remote URL
+    /// Remote URL, or several separated by commas to fall back on in order
     pub remote: String,
 }
+
+impl IndexConfig {
+    /// The remotes listed in `remote`, in order of preference.
+    pub fn remotes(&self) -> Vec<&str> {
+        self.remote
+            .split(',')
+            .map(str::trim)
+            .filter(|remote| !remote.is_empty())
+            .collect()
+    }
+}
@@ -77,6 +77,23 @@ mod testing {
         );
     }
 
+    #[test]
+    fn index_remotes_are_listed_in_order() {
+        let config = IndexConfig {
+            enabled: true,
+            remote: "https://mirror.example.com/focus-index, https://git.example.com/focus-index,"
+                .to_owned(),
+        };
+        assert_eq!(
+            config.remotes(),
+            vec![
+                "https://mirror.example.com/focus-index",
+                "https://git.example.com/focus-index"
+            ]
+        );
+        assert!(IndexConfig::default().remotes().is_empty());
+    }
+
     #[test]
     fn reading_notification_config() {
         let dir = tempdir().unwrap();
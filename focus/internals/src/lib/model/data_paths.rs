@@ -14,6 +14,11 @@ pub struct DataPaths {
     pub data_dir: PathBuf,
     pub project_dir: PathBuf,
     pub selection_file: PathBuf,
+    pub selection_history_file: PathBuf,
+    pub selection_profiles_dir: PathBuf,
+    pub activity_file: PathBuf,
+    pub excluded_projects_file: PathBuf,
+    pub excluded_paths_file: PathBuf,
 }
 
 impl DataPaths {
@@ -23,6 +28,11 @@ impl DataPaths {
         let data_dir = dot_focus_dir.join("focus");
         let project_dir = focus_dir.join("projects");
         let selection_file = dot_focus_dir.join("user.selection.json");
+        let selection_history_file = data_dir.join("selection_history.json");
+        let selection_profiles_dir = dot_focus_dir.join("selections");
+        let activity_file = data_dir.join("activity.json");
+        let excluded_projects_file = data_dir.join("excluded_projects.json");
+        let excluded_paths_file = data_dir.join("excluded_paths.json");
 
         let instance = Self {
             dot_focus_dir,
@@ -30,6 +40,11 @@ impl DataPaths {
             data_dir,
             project_dir,
             selection_file,
+            selection_history_file,
+            selection_profiles_dir,
+            activity_file,
+            excluded_projects_file,
+            excluded_paths_file,
         };
         instance
             .ensure_directories_are_set_up_correctly()
@@ -1,6 +1,7 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod activity;
 pub mod configuration;
 pub mod data_paths;
 pub mod outlining;
@@ -310,6 +310,91 @@ pub fn create_hierarchical_patterns(patterns: &PatternSet) -> PatternSet {
     resulting_patterns
 }
 
+/// Decides which paths a sparse checkout of a `PatternSet` includes, the way Git matches non-cone patterns (of which cone patterns are a subset): the last pattern that matches a path decides, and a path no pattern matches is decided by its nearest ancestor directory that one matches. Paths that nothing decides are excluded.
+pub struct SparseCheckoutMatcher {
+    rules: Vec<SparseCheckoutRule>,
+}
+
+struct SparseCheckoutRule {
+    pattern: glob::Pattern,
+    negated: bool,
+    directory_only: bool,
+    anchored: bool,
+}
+
+impl SparseCheckoutMatcher {
+    pub fn new(patterns: &PatternSet) -> Result<Self> {
+        let mut rules = Vec::new();
+        for pattern in patterns.iter() {
+            let lines: Vec<OsString> = pattern.clone().into();
+            for line in lines {
+                let line = line.to_string_lossy();
+                let mut text = line.trim_end();
+                let negated = text.starts_with('!');
+                if negated {
+                    text = &text[1..];
+                }
+                let directory_only = text.ends_with('/');
+                let text = text.trim_end_matches('/');
+                if text.is_empty() || text.starts_with('#') {
+                    continue;
+                }
+                // As in gitignore, a pattern with a slash before its end is relative to the root; others match names at any depth.
+                let anchored = text.contains('/');
+                let text = text.trim_start_matches('/');
+                let pattern = glob::Pattern::new(text)
+                    .with_context(|| format!("Parsing sparse checkout pattern '{}'", line))?;
+                rules.push(SparseCheckoutRule {
+                    pattern,
+                    negated,
+                    directory_only,
+                    anchored,
+                });
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// Whether the file at `path`, relative to the root of the repository, is included.
+    pub fn includes(&self, path: &Path) -> bool {
+        let mut candidate = Some((path, false));
+        while let Some((path, is_dir)) = candidate {
+            if path.is_empty() {
+                break;
+            }
+            if let Some(included) = self.decide(path, is_dir) {
+                return included;
+            }
+            candidate = path.parent().map(|parent| (parent, true));
+        }
+        false
+    }
+
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                if rule.directory_only && !is_dir {
+                    return false;
+                }
+                if rule.anchored {
+                    rule.pattern.matches_path_with(path, options)
+                } else {
+                    path.file_name().map_or(false, |name| {
+                        rule.pattern.matches_with(&name.to_string_lossy(), options)
+                    })
+                }
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
 lazy_static! {
     pub static ref DEFAULT_OUTLINING_PATTERNS: PatternSet = {
         let mut patterns = PatternSet::new();
@@ -585,4 +670,41 @@ mod testing {
         let hierarchical_patterns = create_hierarchical_patterns(&patterns);
         insta::assert_json_snapshot!(&hierarchical_patterns);
     }
+
+    #[test]
+    fn sparse_checkout_matcher_follows_git_precedence() -> Result<()> {
+        let mut patterns = PatternSet::new();
+        patterns.insert(Pattern::Directory {
+            precedence: 0,
+            path: PathBuf::new(),
+            recursive: true,
+        });
+        patterns.insert(Pattern::Directory {
+            precedence: 1,
+            path: PathBuf::from("a"),
+            recursive: false,
+        });
+        patterns.insert(Pattern::Directory {
+            precedence: 1,
+            path: PathBuf::from("a/b"),
+            recursive: true,
+        });
+        patterns.insert(Pattern::Verbatim {
+            precedence: usize::MAX,
+            fragment: String::from("!/a/b/generated/"),
+        });
+        let matcher = SparseCheckoutMatcher::new(&patterns)?;
+
+        // Top-level files are always included, top-level directories only if selected.
+        assert!(matcher.includes(Path::new("README")));
+        assert!(!matcher.includes(Path::new("c/file")));
+        // The files directly in a non-recursive directory are included, but not its subdirectories unless they are selected.
+        assert!(matcher.includes(Path::new("a/BUILD")));
+        assert!(!matcher.includes(Path::new("a/c/file")));
+        assert!(matcher.includes(Path::new("a/b/c/d/file")));
+        // Later negations take precedence.
+        assert!(!matcher.includes(Path::new("a/b/generated/file")));
+
+        Ok(())
+    }
 }
@@ -7,11 +7,12 @@ use focus_util::{
     git,
     git_helper::{self, get_head_commit, ConfigExt},
     paths::{self, is_build_definition},
+    progress::Progress,
     sandbox_command::SandboxCommandOutput,
 };
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
     fs,
     io::BufWriter,
     io::Write,
@@ -32,33 +33,39 @@ use crate::{
     project_cache::{ProjectCache, Value},
     target::TargetSet,
     target_resolver::{
-        BazelResolutionStrategy, CacheOptions, ResolutionOptions, ResolutionRequest,
-        ResolutionResult, Resolver, RoutingResolver,
+        expand_directory_globs, resolution_cache_key, BazelOptions, BazelResolutionStrategy,
+        CacheOptions, ResolutionCache, ResolutionOptions, ResolutionRequest, ResolutionResult,
+        Resolver, RoutingResolver,
     },
 };
 
 use super::{
+    activity::{Activity, ActivityTimestamps},
     configuration::Configuration,
+    data_paths::DataPaths,
     outlining::{
         pattern_default_precedence, PatternContainer, PatternSet, PatternSetWriter,
-        DEFAULT_OUTLINING_PATTERNS,
+        SparseCheckoutMatcher, DEFAULT_OUTLINING_PATTERNS,
     },
-    selection::{Selection, SelectionManager, Target},
+    persistence::{load_model, store_model},
+    selection::{Selection, SelectionManager, SelectionProblem, Target},
 };
 
 use anyhow::{bail, Context, Result};
+use chrono::Utc;
 use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
 use tracing::{debug, info, info_span, trace, warn};
 use uuid::Uuid;
 
 const SPARSE_SYNC_REF_NAME: &str = "refs/focus/sync";
 const PREEMPTIVE_SYNC_REF_NAME: &str = "refs/focus/presync";
-const UUID_CONFIG_KEY: &str = "focus.uuid";
-const PREEMPTIVE_SYNC_ENABLED_CONFIG_KEY: &str = "focus.preemptive-sync.enabled";
-const PREEMPTIVE_SYNC_USER_IDLE_MILLIS_THRESHOLD_CONFIG_KEY: &str =
+pub const UUID_CONFIG_KEY: &str = "focus.uuid";
+pub const VERSION_CONFIG_KEY: &str = "focus.version";
+pub const PREEMPTIVE_SYNC_ENABLED_CONFIG_KEY: &str = "focus.preemptive-sync.enabled";
+pub const PREEMPTIVE_SYNC_USER_IDLE_MILLIS_THRESHOLD_CONFIG_KEY: &str =
     "focus.preemptive-sync.user-idle-threshold";
 const PREEMPTIVE_SYNC_USER_IDLE_MILLIS_THRESHOLD_DEFAULT: i32 = 15000;
-const FILTER_VIEW: &str = "focus.filter";
+pub const FILTER_VIEW: &str = "focus.filter";
 
 const INDEX_SPARSE_CONFIG_KEY: &str = "index.sparse";
 const CORE_UNTRACKED_CACHE_CONFIG_KEY: &str = "core.untrackedCache";
@@ -70,6 +77,185 @@ pub const PROJECT_CACHE_ENDPOINT_CONFIG_KEY: &str = "focus.project-cache.endpoin
 pub const PROJECT_CACHE_INCLUDE_HEADERS_FILE_CONFIG_KEY: &str =
     "focus.project-cache.include-headers-from";
 pub const BAZEL_ONE_SHOT_RESOLUTION_CONFIG_KEY: &str = "focus.bazel.one-shot";
+pub const POST_CHECKOUT_AUTO_SYNC_CONFIG_KEY: &str = "focus.post-checkout.auto-sync";
+pub const POST_MERGE_AUTO_SYNC_CONFIG_KEY: &str = "focus.post-merge.auto-sync";
+pub const POST_SYNC_HOOK_CONFIG_KEY: &str = "focus.sync.post-sync-hook";
+/// Read when `POST_SYNC_HOOK_CONFIG_KEY` is not set.
+pub const POST_SYNC_HOOK_ALIAS_CONFIG_KEY: &str = "focus.hooks.post-sync";
+pub const MAX_SELECTION_PERCENTAGE_CONFIG_KEY: &str = "focus.selection.max-repo-percentage";
+const MAX_SELECTION_PERCENTAGE_DEFAULT: i32 = 50;
+
+const CHECKOUT_WORKERS_CONFIG_KEY: &str = "checkout.workers";
+const SPARSE_CHECKOUT_CONE_CONFIG_KEY: &str = "core.sparseCheckoutCone";
+pub const ATOMIC_PREVIOUS_CONE_CONFIG_KEY: &str = "focus.sync.atomic-previous-cone";
+/// Git's default for `core.sparseCheckoutCone`, also assumed for a saved profile whose mode was not recorded.
+const SPARSE_CHECKOUT_CONE_DEFAULT: bool = false;
+
+/// Locally changed tracked files that a sync would have removed from the working tree. The sync restores the previous sparse checkout before returning this.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "Local changes to these files would be lost because they are outside the new sparse checkout:\n{}",
+    .paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join("\n")
+)]
+pub struct NarrowingConflict {
+    pub paths: Vec<PathBuf>,
+}
+
+/// Options guiding `Repo::sync`.
+#[derive(Clone, Debug)]
+pub struct SyncOptions {
+    /// Whether to resolve and count the patterns without applying them to the working tree.
+    skip_pattern_application: bool,
+
+    /// Additional options passed to Bazel when resolving targets.
+    bazel_options: BazelOptions,
+
+    /// Whether to leave out selected paths that match ignore rules.
+    dedupe_with_gitignore: bool,
+
+    /// Whether to fail when a directory target does not exist rather than warn.
+    strict_directories: bool,
+
+    /// Whether results cached by earlier resolutions may be used rather than resolving targets afresh.
+    accept_cached_resolution: bool,
+
+    /// The number of parallel workers Git uses to check out the working tree, if not Git's default.
+    checkout_workers: Option<usize>,
+
+    /// Whether a failed checkout should restore the previous sparse checkout rather than leave the working tree partially updated.
+    atomic: bool,
+
+    /// Files that must remain checked out; the sync fails with a `NarrowingConflict` otherwise.
+    preserved_paths: BTreeSet<PathBuf>,
+
+    /// A commit from which, if the build graph has not changed since, the result of the last resolution of the same targets is reused.
+    reuse_since: Option<Oid>,
+
+    /// Directories that every resolved path must be within. Empty means no restriction.
+    allowed_roots: Vec<PathBuf>,
+
+    /// Directories to leave out of the working tree in place of those recorded by `set_excluded_paths`, or `None` to use the recorded ones.
+    excluded_paths: Option<Vec<PathBuf>>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            skip_pattern_application: false,
+            bazel_options: Default::default(),
+            dedupe_with_gitignore: false,
+            strict_directories: false,
+            accept_cached_resolution: true,
+            checkout_workers: None,
+            atomic: false,
+            preserved_paths: BTreeSet::new(),
+            reuse_since: None,
+            allowed_roots: Vec::new(),
+            excluded_paths: None,
+        }
+    }
+}
+
+impl SyncOptions {
+    pub fn with_skip_pattern_application(mut self, skip_pattern_application: bool) -> Self {
+        self.skip_pattern_application = skip_pattern_application;
+        self
+    }
+
+    pub fn with_bazel_options(mut self, bazel_options: BazelOptions) -> Self {
+        self.bazel_options = bazel_options;
+        self
+    }
+
+    pub fn with_dedupe_with_gitignore(mut self, dedupe_with_gitignore: bool) -> Self {
+        self.dedupe_with_gitignore = dedupe_with_gitignore;
+        self
+    }
+
+    pub fn with_strict_directories(mut self, strict_directories: bool) -> Self {
+        self.strict_directories = strict_directories;
+        self
+    }
+
+    pub fn with_accept_cached_resolution(mut self, accept_cached_resolution: bool) -> Self {
+        self.accept_cached_resolution = accept_cached_resolution;
+        self
+    }
+
+    pub fn with_checkout_workers(mut self, checkout_workers: Option<usize>) -> Self {
+        self.checkout_workers = checkout_workers;
+        self
+    }
+
+    pub fn with_atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    pub fn with_preserved_paths(mut self, preserved_paths: BTreeSet<PathBuf>) -> Self {
+        self.preserved_paths = preserved_paths;
+        self
+    }
+
+    pub fn with_reuse_since(mut self, reuse_since: Option<Oid>) -> Self {
+        self.reuse_since = reuse_since;
+        self
+    }
+
+    pub fn with_allowed_roots(mut self, allowed_roots: Vec<PathBuf>) -> Self {
+        self.allowed_roots = allowed_roots;
+        self
+    }
+
+    pub fn with_excluded_paths(mut self, excluded_paths: Option<Vec<PathBuf>>) -> Self {
+        self.excluded_paths = excluded_paths;
+        self
+    }
+}
+
+/// Recursive directory patterns for `paths`.
+fn directory_patterns(paths: impl IntoIterator<Item = PathBuf>) -> PatternSet {
+    paths
+        .into_iter()
+        .map(|path| Pattern::Directory {
+            precedence: LAST,
+            path,
+            recursive: true,
+        })
+        .collect()
+}
+
+/// Remove the directory patterns within any of `excluded_paths` from `patterns`, returning the negated patterns that leave `excluded_paths` out of the directories that remain. Exclusions take precedence over the selection: a directory within an excluded path is left out even if it was selected explicitly.
+fn exclude_paths(patterns: &mut PatternSet, excluded_paths: &[PathBuf]) -> PatternSet {
+    patterns.retain(|pattern| match pattern {
+        Pattern::Directory { path, .. } => !excluded_paths
+            .iter()
+            .any(|excluded_path| path.starts_with(excluded_path)),
+        Pattern::Verbatim { .. } => true,
+    });
+    excluded_paths
+        .iter()
+        .map(|excluded_path| Pattern::Verbatim {
+            precedence: LAST,
+            fragment: format!("!/{}/", excluded_path.display()),
+        })
+        .collect()
+}
+
+/// The largest number of parallel checkout workers accepted by `validate_checkout_workers`.
+pub const MAX_CHECKOUT_WORKERS: usize = 128;
+
+/// Check that `workers` is a sensible number of parallel checkout workers.
+pub fn validate_checkout_workers(workers: usize) -> Result<()> {
+    if workers == 0 || workers > MAX_CHECKOUT_WORKERS {
+        bail!(
+            "The number of checkout workers must be between 1 and {} (got {})",
+            MAX_CHECKOUT_WORKERS,
+            workers
+        );
+    }
+    Ok(())
+}
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum WorkingTreeKind {
@@ -133,11 +319,24 @@ impl WorkingTree {
         self.info_dir().join("sparse-checkout")
     }
 
-    /// Writes the given `patterns` to the working tree.
+    /// Write `patterns` as the sparse profile and check out the working tree to match, first restoring any profile left behind by an interrupted atomic application. `checkout_workers` sets Git's `checkout.workers` for the checkout; when `None`, Git's configured default applies.
     pub fn apply_sparse_patterns(
         &self,
         patterns: PatternSet,
         cone: bool,
+        checkout_workers: Option<usize>,
+        app: Arc<App>,
+    ) -> Result<bool> {
+        self.recover_interrupted_update(app.clone())?;
+        self.write_and_check_out(patterns, cone, checkout_workers, app)
+    }
+
+    /// Write `patterns` as the sparse profile and check out the working tree, without first restoring a profile left behind by an interrupted atomic application.
+    fn write_and_check_out(
+        &self,
+        patterns: PatternSet,
+        cone: bool,
+        checkout_workers: Option<usize>,
         app: Arc<App>,
     ) -> Result<bool> {
         // Make sure the patterns form a hierarchy
@@ -147,7 +346,16 @@ impl WorkingTree {
             patterns
         };
 
-        // Write the patterns
+        if !self.write_sparse_profile(&patterns)? {
+            return Ok(false);
+        }
+        self.update_working_tree(cone, checkout_workers, app)?;
+
+        Ok(true)
+    }
+
+    /// Write `patterns` as the sparse profile, returning whether it changed.
+    fn write_sparse_profile(&self, patterns: &PatternSet) -> Result<bool> {
         let info_dir = self.info_dir();
         std::fs::create_dir_all(&info_dir)
             .with_context(|| format!("In working tree {}", self.work_dir().display()))
@@ -175,9 +383,18 @@ impl WorkingTree {
         }
         std::fs::rename(&candidate_sparse_profile_path, &sparse_profile_path)
             .context("Moving candidate sparse profile into place")?;
-
-        // Update the working tree to match
         info!(profile = ?sparse_profile_path, count = %patterns.len(), "Applying patterns");
+
+        Ok(true)
+    }
+
+    /// Check out the working tree to match the sparse profile.
+    fn update_working_tree(
+        &self,
+        cone: bool,
+        checkout_workers: Option<usize>,
+        app: Arc<App>,
+    ) -> Result<()> {
         {
             let args = vec![
                 "sparse-checkout",
@@ -194,9 +411,9 @@ impl WorkingTree {
         }
 
         // Newer versions of Git don't actually check out files when `sparse-checkout init` runs, so run `git checkout`. It might be worth making this behavior version-dependent.
-        info!("Checking out");
+        info!(?checkout_workers, "Checking out");
         {
-            let args = vec!["checkout"];
+            let args = Self::checkout_args(checkout_workers)?;
             let (mut cmd, scmd) = git_helper::git_command(app)?;
             scmd.ensure_success_or_log(
                 cmd.current_dir(self.work_dir()).args(args),
@@ -206,7 +423,149 @@ impl WorkingTree {
             .context("git checkout failed")?;
         }
 
-        Ok(true)
+        Ok(())
+    }
+
+    /// Like `apply_sparse_patterns`, but all-or-nothing: if applying the patterns fails, the previous sparse profile is restored and checked out again. If the process dies partway through, the previous profile is restored by the next atomic application.
+    pub fn apply_sparse_patterns_atomically(
+        &self,
+        patterns: PatternSet,
+        cone: bool,
+        checkout_workers: Option<usize>,
+        app: Arc<App>,
+    ) -> Result<bool> {
+        self.apply_atomically(app.clone(), || {
+            self.write_and_check_out(patterns, cone, checkout_workers, app.clone())
+        })
+    }
+
+    /// Like `apply_sparse_patterns_atomically`, but also restores the previous sparse checkout and fails with a `NarrowingConflict` if any of `preserved_paths` would no longer be checked out.
+    pub fn apply_sparse_patterns_preserving(
+        &self,
+        patterns: PatternSet,
+        cone: bool,
+        checkout_workers: Option<usize>,
+        preserved_paths: &BTreeSet<PathBuf>,
+        app: Arc<App>,
+    ) -> Result<bool> {
+        self.apply_atomically(app.clone(), || {
+            let checked_out =
+                self.write_and_check_out(patterns, cone, checkout_workers, app.clone())?;
+            if checked_out {
+                let present = self
+                    .checked_out_paths(app.clone())
+                    .context("Listing paths after applying the sparse profile")?;
+                let paths: Vec<PathBuf> = preserved_paths.difference(&present).cloned().collect();
+                if !paths.is_empty() {
+                    return Err(NarrowingConflict { paths }.into());
+                }
+            }
+            Ok(checked_out)
+        })
+    }
+
+    /// Where the sparse profile being replaced by an atomic application is kept until it completes.
+    fn previous_sparse_checkout_path(&self) -> PathBuf {
+        self.sparse_checkout_path()
+            .with_extension(Path::new("previous"))
+    }
+
+    /// Run `apply` after saving the sparse profile it may replace, restoring the saved profile if `apply` fails.
+    fn apply_atomically(
+        &self,
+        app: Arc<App>,
+        apply: impl FnOnce() -> Result<bool>,
+    ) -> Result<bool> {
+        self.recover_interrupted_update(app.clone())?;
+
+        let sparse_profile_path = self.sparse_checkout_path();
+        if !sparse_profile_path.is_file() {
+            // There is no consistent state to preserve yet.
+            return apply();
+        }
+
+        let previous_cone = self
+            .repo
+            .config()?
+            .snapshot()?
+            .get_bool(SPARSE_CHECKOUT_CONE_CONFIG_KEY)
+            .unwrap_or(SPARSE_CHECKOUT_CONE_DEFAULT);
+        self.repo
+            .config()?
+            .set_bool(ATOMIC_PREVIOUS_CONE_CONFIG_KEY, previous_cone)
+            .context("Recording the sparse checkout mode")?;
+        // Copy to a temporary file and rename it so that the saved profile is never partially written.
+        let previous_profile_path = self.previous_sparse_checkout_path();
+        let candidate_previous_profile_path =
+            previous_profile_path.with_extension(Path::new("previous.candidate"));
+        std::fs::copy(&sparse_profile_path, &candidate_previous_profile_path)
+            .context("Saving the current sparse profile")?;
+        std::fs::rename(&candidate_previous_profile_path, &previous_profile_path)
+            .context("Moving the saved sparse profile into place")?;
+
+        match apply() {
+            Ok(checked_out) => {
+                self.discard_previous_sparse_profile()?;
+                Ok(checked_out)
+            }
+            Err(e) => match self.restore_previous_sparse_profile(app) {
+                Ok(()) => Err(e.context("The previous sparse checkout was restored")),
+                Err(restore_error) => Err(e.context(format!(
+                    "Restoring the previous sparse checkout also failed: {:#}",
+                    restore_error
+                ))),
+            },
+        }
+    }
+
+    /// Restore the sparse profile saved by an atomic application that was interrupted, if any.
+    pub fn recover_interrupted_update(&self, app: Arc<App>) -> Result<()> {
+        if self.previous_sparse_checkout_path().is_file() {
+            warn!(
+                "A previous sync was interrupted; restoring the sparse checkout it was replacing"
+            );
+            self.restore_previous_sparse_profile(app)
+                .context("Restoring the sparse checkout from an interrupted sync")?;
+        }
+        Ok(())
+    }
+
+    fn restore_previous_sparse_profile(&self, app: Arc<App>) -> Result<()> {
+        let cone = self
+            .repo
+            .config()?
+            .snapshot()?
+            .get_bool(ATOMIC_PREVIOUS_CONE_CONFIG_KEY)
+            .unwrap_or(SPARSE_CHECKOUT_CONE_DEFAULT);
+        // Keep the saved profile until the checkout succeeds so that restoring can itself be retried.
+        std::fs::copy(
+            self.previous_sparse_checkout_path(),
+            self.sparse_checkout_path(),
+        )
+        .context("Restoring the saved sparse profile")?;
+        self.update_working_tree(cone, None, app)?;
+        self.discard_previous_sparse_profile()
+    }
+
+    fn discard_previous_sparse_profile(&self) -> Result<()> {
+        std::fs::remove_file(self.previous_sparse_checkout_path())
+            .context("Removing the saved sparse profile")?;
+        match self.repo.config()?.remove(ATOMIC_PREVIOUS_CONE_CONFIG_KEY) {
+            Err(e) if e.code() != git2::ErrorCode::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Arguments to the `git checkout` run by `apply_sparse_patterns`.
+    fn checkout_args(checkout_workers: Option<usize>) -> Result<Vec<String>> {
+        let mut args = Vec::new();
+        if let Some(workers) = checkout_workers {
+            validate_checkout_workers(workers)?;
+            args.push(String::from("-c"));
+            args.push(format!("{}={}", CHECKOUT_WORKERS_CONFIG_KEY, workers));
+        }
+        args.push(String::from("checkout"));
+        Ok(args)
     }
 
     /// Switch to the given commit in this working tree.
@@ -272,6 +631,95 @@ impl WorkingTree {
         Ok(patterns)
     }
 
+    /// Returns patterns excluding the files at `commit_id` that are selected by the directory patterns in `patterns` but match the working tree's ignore rules. Ignore rules are read from the files currently present in the working tree.
+    pub fn ignored_path_patterns(
+        &self,
+        commit_id: git2::Oid,
+        patterns: &PatternSet,
+        app: Arc<App>,
+    ) -> Result<PatternSet> {
+        let commit = self
+            .repo
+            .find_commit(commit_id)
+            .with_context(|| format!("Resolving commit {}", commit_id))?;
+        let tree = commit.tree().context("Resolving tree")?;
+
+        let mut candidates = BTreeSet::<PathBuf>::new();
+        for pattern in patterns {
+            let (path, recursive) = match pattern {
+                Pattern::Directory {
+                    path, recursive, ..
+                } if !path.as_os_str().is_empty() => (path, *recursive),
+                _ => continue,
+            };
+            let subtree = match tree.get_path(path) {
+                Ok(entry) if entry.kind() == Some(ObjectType::Tree) => entry
+                    .to_object(&self.repo)
+                    .and_then(|object| object.peel_to_tree())
+                    .with_context(|| format!("Resolving tree for {}", path.display()))?,
+                _ => continue,
+            };
+            subtree
+                .walk(TreeWalkMode::PreOrder, |root, entry| match entry.kind() {
+                    Some(ObjectType::Blob) => {
+                        if let Some(name) = entry.name() {
+                            candidates.insert(path.join(root).join(name));
+                        }
+                        TreeWalkResult::Ok
+                    }
+                    Some(ObjectType::Tree) if !recursive => TreeWalkResult::Skip,
+                    _ => TreeWalkResult::Ok,
+                })
+                .context("Failed to walk tree")?;
+        }
+
+        let candidates: Vec<PathBuf> = candidates.into_iter().collect();
+        let ignored = git_helper::check_ignore(self.work_dir(), &candidates, app)
+            .context("Checking for ignored paths")?;
+        debug!(
+            candidate_count = candidates.len(),
+            ignored_count = ignored.len(),
+            "Checked selected paths against ignore rules"
+        );
+        Ok(ignored
+            .into_iter()
+            .map(|path| Pattern::Verbatim {
+                precedence: LAST,
+                fragment: format!("!/{}", path.display()),
+            })
+            .collect())
+    }
+
+    /// The tracked paths that are present in the working tree, i.e. those not excluded by the sparse checkout.
+    pub fn checked_out_paths(&self, app: Arc<App>) -> Result<BTreeSet<PathBuf>> {
+        let output =
+            git_helper::run_consuming_stdout(self.work_dir(), ["ls-files", "-t", "-z"], app)
+                .context("Listing checked out paths")?;
+        // Each entry is a status tag, a space, and the path. Skip-worktree entries are tagged `S`.
+        Ok(output
+            .split('\0')
+            .filter_map(|entry| entry.split_once(' '))
+            .filter(|(tag, _)| *tag != "S")
+            .map(|(_, path)| PathBuf::from(path))
+            .collect())
+    }
+
+    /// The tracked paths that a sparse checkout of `patterns` would include, whether or not they are currently checked out.
+    pub fn paths_included_by(
+        &self,
+        patterns: &PatternSet,
+        app: Arc<App>,
+    ) -> Result<BTreeSet<PathBuf>> {
+        let matcher = SparseCheckoutMatcher::new(patterns)?;
+        let output = git_helper::run_consuming_stdout(self.work_dir(), ["ls-files", "-z"], app)
+            .context("Listing tracked paths")?;
+        Ok(output
+            .split_terminator('\0')
+            .map(PathBuf::from)
+            .filter(|path| matcher.includes(path))
+            .collect())
+    }
+
     #[allow(dead_code)]
     fn locate_paths_in_tree(&self, prefixes: &HashSet<PathBuf>) -> Result<PatternSet> {
         let mut results = PatternSet::new();
@@ -308,7 +756,7 @@ impl WorkingTree {
 
     fn apply_working_tree_patterns(&self, app: Arc<App>) -> Result<bool> {
         let patterns = self.default_working_tree_patterns()?;
-        self.apply_sparse_patterns(patterns, true, app)
+        self.apply_sparse_patterns(patterns, true, None, app)
             .context("Failed to apply root-only patterns")
     }
 
@@ -456,6 +904,8 @@ impl WorkingTree {
     }
 
     pub fn switch_filter_off(&self, app: Arc<App>) -> Result<()> {
+        self.recover_interrupted_update(app.clone())?;
+
         //save of the current copy of .git/info/sparse-checkout as git/info/sparse-checkout.filtered
         let sparse_profile_path = self.sparse_checkout_path();
         if sparse_profile_path.is_file() {
@@ -485,6 +935,8 @@ impl WorkingTree {
     }
 
     pub fn switch_filter_on(&self, app: Arc<App>) -> Result<()> {
+        self.recover_interrupted_update(app.clone())?;
+
         let sparse_profile_path = self.sparse_checkout_path();
         let filtered_sparse_profile_path = self
             .sparse_checkout_path()
@@ -558,7 +1010,7 @@ impl OutliningTreeOutliner {
     ) -> Result<bool> {
         let patterns = self.configured_outlining_patterns(commit_id)?;
         self.underlying
-            .apply_sparse_patterns(patterns, false, app)
+            .apply_sparse_patterns(patterns, false, None, app)
             .context("Failed to apply build file patterns")
     }
 
@@ -695,11 +1147,15 @@ impl Outliner for DenseRepoOutliner {
 
 impl DenseRepoOutliner {}
 
-fn make_routing_resolver() -> Result<RoutingResolver> {
-    let cache_dir = dirs::cache_dir()
+fn resolver_cache_root() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
         .context("failed to determine cache dir")?
         .join("focus")
-        .join("cache");
+        .join("cache"))
+}
+
+fn make_routing_resolver() -> Result<RoutingResolver> {
+    let cache_dir = resolver_cache_root()?;
     Ok(RoutingResolver::new(cache_dir.as_path()))
 }
 
@@ -878,16 +1334,66 @@ impl Repo {
         &self.path
     }
 
-    /// Run a sync, returning the number of patterns that were applied and whether a checkout occured as a result of the profile changing.
+    /// Run a sync, returning the number of patterns that were applied and whether a checkout occured as a result of the profile changing. Fails before changing the working tree if any resolved directory is outside the allowed roots, unless they are empty. With `atomic`, a failed checkout restores the previous sparse checkout. If any of the preserved paths would no longer be checked out, the previous sparse checkout is restored and a `NarrowingConflict` is returned. With `reuse_since`, resolution is skipped in favor of the result of the last resolution of the same targets if no file relevant to the build graph changed since (see `reusable_resolution`). Unless `accept_cached_resolution` is set, targets are resolved afresh rather than taken from the resolution cache. See `SyncOptions`.
     pub fn sync(
         &self,
         commit_id: git2::Oid,
         targets: &TargetSet,
-        skip_pattern_application: bool,
         app: Arc<App>,
         cache: Option<&RocksDBCache>,
         snapshot: Option<PathBuf>,
+        options: &SyncOptions,
     ) -> Result<(usize, bool)> {
+        let (outline_patterns, cone) =
+            self.sparse_patterns(commit_id, targets, app.clone(), cache, snapshot, options)?;
+        let working_tree = self.working_tree()?;
+
+        let pattern_count = outline_patterns.len();
+        let checked_out = if options.skip_pattern_application {
+            false
+        } else {
+            let checkout_progress = app.progress("Applying sparse checkout", None);
+            let checked_out = if !options.preserved_paths.is_empty() {
+                working_tree.apply_sparse_patterns_preserving(
+                    outline_patterns,
+                    cone,
+                    options.checkout_workers,
+                    &options.preserved_paths,
+                    app,
+                )
+            } else if options.atomic {
+                working_tree.apply_sparse_patterns_atomically(
+                    outline_patterns,
+                    cone,
+                    options.checkout_workers,
+                    app,
+                )
+            } else {
+                working_tree.apply_sparse_patterns(
+                    outline_patterns,
+                    cone,
+                    options.checkout_workers,
+                    app,
+                )
+            }
+            .context("Failed to apply outlined patterns to working tree")?;
+            checkout_progress.finish();
+            checked_out
+        };
+
+        Ok((pattern_count, checked_out))
+    }
+
+    /// Compute the sparse checkout patterns that a sync of `targets` at `commit_id` applies, and whether they can be applied in cone mode, without changing the working tree. See `sync`.
+    pub fn sparse_patterns(
+        &self,
+        commit_id: git2::Oid,
+        targets: &TargetSet,
+        app: Arc<App>,
+        cache: Option<&RocksDBCache>,
+        snapshot: Option<PathBuf>,
+        options: &SyncOptions,
+    ) -> Result<(PatternSet, bool)> {
         let (working_tree, outlining_tree) = match (&self.working_tree, &self.outliner) {
             (Some(working_tree), Some(outlining_tree)) => (working_tree, outlining_tree),
             _ => {
@@ -905,81 +1411,314 @@ impl Repo {
             .configure(app.clone())
             .context("Configuring the outlining tree")?;
 
-        let mut outline_patterns = if let Some(cache) = cache {
+        let targets = &self
+            .expand_directory_globs(commit_id, targets, options.strict_directories)
+            .context("Expanding directory patterns")?;
+        let mut outline_patterns = self.resolve_outline_patterns(
+            commit_id,
+            targets,
+            cache,
+            snapshot,
+            options,
+            app.clone(),
+        )?;
+
+        let violations = paths::paths_outside_roots(
+            outline_patterns.iter().filter_map(|pattern| match pattern {
+                Pattern::Directory {
+                    path,
+                    recursive: true,
+                    ..
+                } => Some(path.as_path()),
+                _ => None,
+            }),
+            &options.allowed_roots,
+        );
+        if !violations.is_empty() {
+            bail!(
+                "The selection materializes paths outside the allowed roots:\n{}",
+                violations
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        let excluded_paths = match &options.excluded_paths {
+            Some(excluded_paths) => excluded_paths.clone(),
+            None => self.excluded_paths()?,
+        };
+        let excluded_path_patterns = exclude_paths(&mut outline_patterns, &excluded_paths);
+        outline_patterns.extend(working_tree.default_working_tree_patterns()?);
+
+        // Negated patterns are not expressible in cone mode, so fall back to non-cone matching when excluding paths.
+        let mut cone = excluded_path_patterns.is_empty();
+        outline_patterns.extend(excluded_path_patterns);
+        if options.dedupe_with_gitignore {
+            let ignored_path_patterns = working_tree
+                .ignored_path_patterns(commit_id, &outline_patterns, app.clone())
+                .context("Excluding ignored paths")?;
+            if !ignored_path_patterns.is_empty() {
+                info!(
+                    count = ignored_path_patterns.len(),
+                    "Excluding ignored paths from the sparse checkout"
+                );
+                cone = false;
+                outline_patterns.extend(ignored_path_patterns);
+            }
+        }
+
+        Ok((outline_patterns, cone))
+    }
+
+    /// Resolve `targets` at `commit_id` to outline patterns with the outlining tree, using the index `cache` if given. Unless `snapshot` holds uncommitted changes to resolve over, the result is stored in the resolution cache (see `resolution_cache`), and taken from it instead of resolving if `accept_cached_resolution` is set in `options`. With `reuse_since`, the result of the last resolution of the same targets and options is also used if no file relevant to the build graph changed since it (see `reusable_resolution`).
+    fn resolve_outline_patterns(
+        &self,
+        commit_id: Oid,
+        targets: &TargetSet,
+        cache: Option<&RocksDBCache>,
+        snapshot: Option<PathBuf>,
+        options: &SyncOptions,
+        app: Arc<App>,
+    ) -> Result<PatternSet> {
+        let outliner = self
+            .outliner
+            .as_deref()
+            .context("Resolution is only possible in a repo with an outlining tree")?;
+        let resolution_options = ResolutionOptions {
+            bazel_resolution_strategy: if cache.is_some() {
+                BazelResolutionStrategy::Incremental
+            } else {
+                BazelResolutionStrategy::OneShot
+            },
+            bazel_options: options.bazel_options.clone(),
+            strict_directories: options.strict_directories,
+        };
+        let commit = self
+            .repo
+            .find_commit(commit_id)
+            .with_context(|| format!("Resolving commit {}", commit_id))?;
+        let tree = commit.tree().context("Resolving tree")?;
+        let hash_context = HashContext::new(&self.repo, &tree)?
+            .with_bazel_config(resolution_options.bazel_options.config());
+        let request = ResolutionRequest {
+            repo: self.path().to_owned(),
+            targets: targets.clone(),
+            options: resolution_options.clone(),
+        };
+
+        // Resolution over uncommitted changes does not correspond to any commit, so it is not cached.
+        let resolution_cache = self.resolution_cache();
+        let key = match snapshot {
+            Some(_) => None,
+            None => Some(resolution_cache_key(&hash_context, &request)?),
+        };
+        if let (Some(_), Some(since)) = (&key, options.reuse_since) {
+            if let Some(paths) = self
+                .reusable_resolution(&request, since, commit_id)
+                .context("Checking whether previously resolved patterns can be reused")?
+            {
+                info!(
+                    "The build graph is unchanged; reusing the patterns resolved by the last sync"
+                );
+                return Ok(directory_patterns(paths));
+            }
+        }
+        if let (Some(key), true) = (&key, options.accept_cached_resolution) {
+            match resolution_cache.get(&hash_context, key) {
+                Ok(Some(paths)) => {
+                    info!(%key, "Using cached resolution result");
+                    return Ok(directory_patterns(paths));
+                }
+                Ok(None) => {}
+                Err(e) => warn!(?e, %key, "Ignoring unreadable cached resolution result"),
+            }
+        }
+
+        let resolution_progress = app.progress("Resolving targets", Some(targets.len() as u64));
+        let (outline_patterns, dependency_keys) = if let Some(cache) = cache {
             self.sync_incremental(
                 commit_id,
                 targets,
-                outlining_tree.as_ref(),
+                &resolution_options,
+                outliner,
                 cache,
+                &hash_context,
                 snapshot,
-                app.clone(),
+                &resolution_progress,
+                app,
             )
         } else {
             self.sync_one_shot(
                 commit_id,
                 targets,
-                outlining_tree.as_ref(),
+                &resolution_options,
+                outliner,
                 snapshot,
-                app.clone(),
+                app,
             )
         }?;
+        resolution_progress.finish();
+
+        if let Some(key) = key {
+            let paths: BTreeSet<PathBuf> = outline_patterns
+                .iter()
+                .filter_map(|pattern| match pattern {
+                    Pattern::Directory { path, .. } => Some(path.clone()),
+                    Pattern::Verbatim { .. } => None,
+                })
+                .collect();
+            // Besides the dependencies resolution reported, the paths depend on the targets themselves and on the directories they name.
+            let dependency_keys: BTreeSet<DependencyKey> = targets
+                .iter()
+                .cloned()
+                .map(DependencyKey::from)
+                .chain(dependency_keys)
+                .chain(paths.iter().cloned().map(DependencyKey::Path))
+                .collect();
+            if let Err(e) =
+                resolution_cache.put(&hash_context, &key, commit_id, dependency_keys, &paths)
+            {
+                warn!(?e, %key, "Failed to cache resolution result");
+            }
+        }
+        Ok(outline_patterns)
+    }
 
-        outline_patterns.extend(working_tree.default_working_tree_patterns()?);
-        let pattern_count = outline_patterns.len();
-        let checked_out = if skip_pattern_application {
-            false
-        } else {
-            working_tree
-                .apply_sparse_patterns(outline_patterns, true, app)
-                .context("Failed to apply outlined patterns to working tree")?
+    /// The cache of resolution results, which is kept in $GIT_DIR/focus/resolution-cache.
+    fn resolution_cache(&self) -> ResolutionCache {
+        ResolutionCache::new(self.git_focus_dir().join("resolution-cache"))
+    }
+
+    /// Seed the resolution cache from that of the existing repo at `source`, copying the results whose dependencies are unchanged at `commit_id`. Returns how many results were copied.
+    pub fn seed_resolution_cache(&self, source: &Path, commit_id: Oid) -> Result<usize> {
+        let source_repo = Repository::open(source)
+            .with_context(|| format!("Opening the repo at {}", source.display()))?;
+        let source_cache = ResolutionCache::new(
+            source_repo
+                .commondir()
+                .join("focus")
+                .join("resolution-cache"),
+        );
+        let tree = self
+            .repo
+            .find_commit(commit_id)
+            .with_context(|| format!("Resolving commit {}", commit_id))?
+            .tree()
+            .context("Resolving tree")?;
+        self.resolution_cache()
+            .seed_from(&source_cache, &self.repo, &tree)
+            .with_context(|| format!("Seeding the resolution cache from {}", source.display()))
+    }
+
+    /// The paths resolved by the last resolution of `request` if they can stand in for resolving it at `commit_id`: no file relevant to the build graph changed between the commit they were resolved at and `since`, or between `since` and `commit_id`. Returns `None` if nothing usable was cached.
+    fn reusable_resolution(
+        &self,
+        request: &ResolutionRequest,
+        since: Oid,
+        commit_id: Oid,
+    ) -> Result<Option<BTreeSet<PathBuf>>> {
+        let (resolved_at, paths) = match self.resolution_cache().latest(request)? {
+            Some(latest) => latest,
+            None => {
+                info!(
+                    "These targets were not resolved with these options before; resolving targets"
+                );
+                return Ok(None);
+            }
         };
+        if self.build_graph_changed_between(resolved_at, since)?
+            || self.build_graph_changed_between(since, commit_id)?
+        {
+            info!("The build graph changed; resolving targets");
+            return Ok(None);
+        }
+        Ok(Some(paths))
+    }
 
-        Ok((pattern_count, checked_out))
+    /// Whether any file relevant to the build graph differs between commits `from` and `to`.
+    fn build_graph_changed_between(&self, from: Oid, to: Oid) -> Result<bool> {
+        if from == to {
+            return Ok(false);
+        }
+        let tree = |commit_id: Oid| -> Result<git2::Tree> {
+            self.repo
+                .find_commit(commit_id)
+                .with_context(|| format!("Resolving commit {}", commit_id))?
+                .tree()
+                .context("Resolving tree")
+        };
+        let (from_tree, to_tree) = (tree(from)?, tree(to)?);
+        let changed_paths = git_helper::get_changed_paths_between_trees(
+            &self.repo,
+            Some(&from_tree),
+            Some(&to_tree),
+        )?;
+        Ok(changed_paths.iter().any(paths::is_relevant_to_build_graph))
     }
 
-    /// Sync in one shot, not using the cache.
+    /// Replace the directory targets in `targets` that contain glob patterns with the directories they match at `commit_id` (see `expand_directory_globs`). Globs are expanded here, before targets are hashed or resolved, so that the index and the resolvers see the same directories.
+    pub fn expand_directory_globs(
+        &self,
+        commit_id: Oid,
+        targets: &TargetSet,
+        strict_directories: bool,
+    ) -> Result<TargetSet> {
+        let tree = self
+            .repo
+            .find_commit(commit_id)
+            .with_context(|| format!("Resolving commit {}", commit_id))?
+            .tree()
+            .context("Resolving tree")?;
+        expand_directory_globs(
+            self.path(),
+            Some((&self.repo, &tree)),
+            targets,
+            strict_directories,
+        )
+    }
+
+    /// Sync in one shot, not using the cache. Returns the patterns and the dependencies resolution reported.
     fn sync_one_shot(
         &self,
         commit_id: Oid,
         targets: &HashSet<Target>,
+        resolution_options: &ResolutionOptions,
         outliner: &dyn Outliner,
         snapshot: Option<PathBuf>,
         app: Arc<App>,
-    ) -> Result<PatternSet> {
+    ) -> Result<(PatternSet, BTreeSet<DependencyKey>)> {
         info!("Running one-shot sync");
-        let resolution_options = ResolutionOptions {
-            bazel_resolution_strategy: BazelResolutionStrategy::OneShot,
-        };
-        let (outline_patterns, _resolution_result) = outliner
-            .outline(commit_id, targets, &resolution_options, snapshot, app)
+        let (outline_patterns, resolution_result) = outliner
+            .outline(commit_id, targets, resolution_options, snapshot, app)
             .context("Failed to outline")?;
-        Ok(outline_patterns)
+        let dependency_keys = resolution_result.package_deps.into_keys().collect();
+        Ok((outline_patterns, dependency_keys))
     }
 
-    /// Sync using the cache, outlining when necessary recursively on dependencies.
+    /// Sync using the cache, outlining when necessary recursively on dependencies. Only the targets whose index entries are missing are resolved. Returns the patterns and the dependencies they were found through. Each target counts as a step of `progress` once its patterns are known.
+    #[allow(clippy::too_many_arguments)]
     fn sync_incremental(
         &self,
         commit_id: Oid,
         targets: &HashSet<Target>,
+        resolution_options: &ResolutionOptions,
         outliner: &dyn Outliner,
         cache: &RocksDBCache,
+        hash_context: &HashContext,
         snapshot: Option<PathBuf>,
+        progress: &Progress,
         app: Arc<App>,
-    ) -> Result<PatternSet> {
+    ) -> Result<(PatternSet, BTreeSet<DependencyKey>)> {
         let index_config = &self.config().index;
-        let commit = self
-            .underlying()
-            .find_commit(commit_id)
-            .with_context(|| format!("Resolving commit {}", commit_id))?;
-        let tree = commit.tree().context("Resolving tree")?;
-        let hash_context = HashContext::new(&self.repo, &tree)?;
         let ti_client = app.tool_insights_client();
         let dependency_keys: HashSet<DependencyKey> =
             targets.iter().cloned().map(DependencyKey::from).collect();
         info!("Checking cache for sparse checkout patterns");
         let mut paths_to_materialize =
-            get_files_to_materialize(&hash_context, cache, dependency_keys.clone())?;
-        if index_config.enabled {
+            get_files_to_materialize(hash_context, cache, dependency_keys.clone())?;
+        if index_config.enabled && !app.is_offline() {
             if let PathsToMaterializeResult::MissingKeys { .. } = paths_to_materialize {
                 info!("Cache miss for sparse checkout patterns; fetching from the remote index");
                 // TODO: Re-enable after the index is moved into its own crate.
@@ -991,7 +1730,7 @@ impl Repo {
                 // );
                 // Query again now that the index is populated.
                 paths_to_materialize =
-                    get_files_to_materialize(&hash_context, cache, dependency_keys)?;
+                    get_files_to_materialize(hash_context, cache, dependency_keys)?;
             }
         }
         Ok(match paths_to_materialize {
@@ -1006,14 +1745,8 @@ impl Repo {
                 ti_client
                     .get_context()
                     .add_to_custom_map("index_hit_count", seen_keys.len().to_string());
-                paths
-                    .into_iter()
-                    .map(|path| Pattern::Directory {
-                        precedence: LAST,
-                        path,
-                        recursive: true,
-                    })
-                    .collect()
+                progress.set_position(targets.len() as u64);
+                (directory_patterns(paths), seen_keys)
             }
 
             PathsToMaterializeResult::MissingKeys {
@@ -1045,37 +1778,104 @@ impl Repo {
                     .add_to_custom_map("index_hit_count", seen_keys.len().to_string());
 
                 debug!(?missing_keys, "These are the missing keys");
-                let resolution_options = ResolutionOptions {
-                    bazel_resolution_strategy: BazelResolutionStrategy::Incremental,
-                };
-                let (outline_patterns, resolution_result) = outliner
+
+                // Only the targets with missing entries are impacted by changes since they were indexed; the others are materialized from the index as they are.
+                let mut outline_patterns = PatternSet::new();
+                let mut dependency_keys = seen_keys;
+                let mut impacted_targets = HashSet::new();
+                for target in targets {
+                    let target_keys = HashSet::from([DependencyKey::from(target.clone())]);
+                    match get_files_to_materialize(hash_context, cache, target_keys)? {
+                        PathsToMaterializeResult::Ok { seen_keys, paths } => {
+                            outline_patterns.extend(directory_patterns(paths));
+                            dependency_keys.extend(seen_keys);
+                            progress.inc(1);
+                        }
+                        PathsToMaterializeResult::MissingKeys { .. } => {
+                            impacted_targets.insert(target.clone());
+                        }
+                    }
+                }
+                info!(
+                    num_impacted_targets = impacted_targets.len(),
+                    num_targets = targets.len(),
+                    "Resolving the targets impacted by changes"
+                );
+
+                let (resolved_patterns, resolution_result) = outliner
                     .outline(
                         commit_id,
-                        targets,
-                        &resolution_options,
+                        &impacted_targets,
+                        resolution_options,
                         snapshot,
                         app.clone(),
                     )
                     .context("Failed to outline")?;
+                progress.inc(impacted_targets.len() as u64);
 
-                debug!(?resolution_result, ?outline_patterns, "Resolved patterns");
-                update_object_database_from_resolution(&hash_context, cache, &resolution_result)?;
-                outline_patterns
+                debug!(?resolution_result, ?resolved_patterns, "Resolved patterns");
+                update_object_database_from_resolution(hash_context, cache, &resolution_result)?;
+                outline_patterns.extend(resolved_patterns);
+                dependency_keys.extend(resolution_result.package_deps.into_keys());
+                (outline_patterns, dependency_keys)
             }
         })
     }
 
-    /// Sync using the project cache returning an optional value of the number of patterns and whether a checkout occured. None is returned if the project cache could not be used.
+    /// Resolve `targets` afresh at `commit_id`, ignoring cached resolutions, and store the results in the index `cache`, replacing the existing entries for them.
+    pub fn reindex(
+        &self,
+        commit_id: Oid,
+        targets: &TargetSet,
+        cache: &RocksDBCache,
+        bazel_options: &BazelOptions,
+        app: Arc<App>,
+    ) -> Result<()> {
+        let outlining_tree = self
+            .outliner
+            .as_ref()
+            .context("Reindexing is only possible in a repo with an outlining tree")?;
+        outlining_tree
+            .underlying()
+            .configure(app.clone())
+            .context("Configuring the outlining tree")?;
+
+        let commit = self
+            .underlying()
+            .find_commit(commit_id)
+            .with_context(|| format!("Resolving commit {}", commit_id))?;
+        let tree = commit.tree().context("Resolving tree")?;
+        let hash_context =
+            HashContext::new(&self.repo, &tree)?.with_bazel_config(bazel_options.config());
+        let resolution_options = ResolutionOptions {
+            bazel_resolution_strategy: BazelResolutionStrategy::Incremental,
+            bazel_options: bazel_options.clone(),
+            strict_directories: false,
+        };
+        let (_, resolution_result) = outlining_tree
+            .outline(commit_id, targets, &resolution_options, None, app)
+            .context("Failed to outline")?;
+        update_object_database_from_resolution(&hash_context, cache, &resolution_result)
+    }
+
+    /// Sync using the project cache returning an optional value of the number of patterns and whether a checkout occured. None is returned if the project cache could not be used. With `atomic`, a failed checkout restores the previous sparse checkout, as does a `NarrowingConflict` over `preserved_paths`. `excluded_paths` replaces the recorded excluded paths when given.
     pub fn sync_using_project_cache(
         &self,
         commit_id: git2::Oid,
         selection: &Selection,
         snapshot: Option<PathBuf>,
+        atomic: bool,
+        preserved_paths: &BTreeSet<PathBuf>,
+        excluded_paths: Option<&[PathBuf]>,
     ) -> Result<Option<(usize, bool)>> {
         if !selection.targets.is_empty() {
             tracing::warn!("Skipping project cache because the selection contains ad-hoc targets");
             return Ok(None);
         }
+        if self.app.is_offline() {
+            info!("Skipping project cache because --offline was specified");
+            return Ok(None);
+        }
 
         let project_names: Vec<&String> = selection
             .projects
@@ -1168,6 +1968,14 @@ impl Repo {
             return Ok(None);
         }
 
+        let excluded_paths = match excluded_paths {
+            Some(excluded_paths) => excluded_paths.to_vec(),
+            None => self.excluded_paths()?,
+        };
+        let excluded_path_patterns = exclude_paths(&mut outline_patterns, &excluded_paths);
+        let cone = excluded_path_patterns.is_empty();
+        outline_patterns.extend(excluded_path_patterns);
+
         // Ensure that the working tree is properly configured
         working_tree
             .configure(self.app.clone())
@@ -1176,9 +1984,27 @@ impl Repo {
 
         // TODO: Implement skipping application if the profile has not changed
         let pattern_count = outline_patterns.len();
-        let checked_out = working_tree
-            .apply_sparse_patterns(outline_patterns, true, self.app.clone())
-            .context("Failed to apply outlined patterns to working tree")?;
+        let checkout_progress = self.app.progress("Applying sparse checkout", None);
+        let checked_out = if !preserved_paths.is_empty() {
+            working_tree.apply_sparse_patterns_preserving(
+                outline_patterns,
+                cone,
+                None,
+                preserved_paths,
+                self.app.clone(),
+            )
+        } else if atomic {
+            working_tree.apply_sparse_patterns_atomically(
+                outline_patterns,
+                cone,
+                None,
+                self.app.clone(),
+            )
+        } else {
+            working_tree.apply_sparse_patterns(outline_patterns, cone, None, self.app.clone())
+        }
+        .context("Failed to apply outlined patterns to working tree")?;
+        checkout_progress.finish();
         info!("Synced from project cache");
         Ok(Some((pattern_count, checked_out)))
     }
@@ -1281,7 +2107,6 @@ impl Repo {
     /// This sets `focus.version` and `twitter.statsenabled`
     pub fn write_git_config_to_support_instrumentation(&self) -> Result<()> {
         if cfg!(feature = "twttr") {
-            const VERSION_CONFIG_KEY: &str = "focus.version";
             const GITSTATS_CONFIG_KEY: &str = "twitter.statsenabled";
             self.repo
                 .config()?
@@ -1301,6 +2126,83 @@ impl Repo {
         self.selection_manager()?.computed_selection()
     }
 
+    /// When activities in this repo last completed.
+    pub fn activity_timestamps(&self) -> Result<ActivityTimestamps> {
+        let paths = DataPaths::from_working_tree(self.working_tree()?)?;
+        ActivityTimestamps::load(&paths.activity_file)
+    }
+
+    /// Record that `activity` just completed in this repo.
+    pub fn record_activity(&self, activity: Activity) -> Result<()> {
+        let paths = DataPaths::from_working_tree(self.working_tree()?)?;
+        ActivityTimestamps::record(&paths.activity_file, activity, Utc::now().timestamp())
+    }
+
+    /// Projects left out of the most recent sync with `focus sync --exclude-project`, sorted. They remain in the selection.
+    pub fn temporarily_excluded_projects(&self) -> Result<Vec<String>> {
+        let paths = DataPaths::from_working_tree(self.working_tree()?)?;
+        load_model(&paths.excluded_projects_file).with_context(|| {
+            format!(
+                "Loading excluded projects from {}",
+                paths.excluded_projects_file.display()
+            )
+        })
+    }
+
+    /// Record the projects that the most recent sync left out. An empty list clears the record.
+    pub fn set_temporarily_excluded_projects(&self, projects: &[String]) -> Result<()> {
+        let paths = DataPaths::from_working_tree(self.working_tree()?)?;
+        let path = &paths.excluded_projects_file;
+        if projects.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("Removing {}", path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+        let mut projects = projects.to_vec();
+        projects.sort();
+        projects.dedup();
+        store_model(path, &projects)
+            .with_context(|| format!("Storing excluded projects to {}", path.display()))
+    }
+
+    /// Directories left out of every sync with `focus sync --exclude`, sorted.
+    pub fn excluded_paths(&self) -> Result<Vec<PathBuf>> {
+        let paths = DataPaths::from_working_tree(self.working_tree()?)?;
+        load_model(&paths.excluded_paths_file).with_context(|| {
+            format!(
+                "Loading excluded paths from {}",
+                paths.excluded_paths_file.display()
+            )
+        })
+    }
+
+    /// Record the directories to leave out of every sync. An empty list clears the record.
+    pub fn set_excluded_paths(&self, excluded_paths: &[PathBuf]) -> Result<()> {
+        let paths = DataPaths::from_working_tree(self.working_tree()?)?;
+        let path = &paths.excluded_paths_file;
+        if excluded_paths.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("Removing {}", path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+        let mut excluded_paths = excluded_paths.to_vec();
+        excluded_paths.sort();
+        excluded_paths.dedup();
+        store_model(path, &excluded_paths)
+            .with_context(|| format!("Storing excluded paths to {}", path.display()))
+    }
+
+    /// Check the stored selection for problems without loading or modifying it.
+    pub fn validate_selection(&self) -> Result<Vec<SelectionProblem>> {
+        SelectionManager::validate(self)
+    }
+
     pub fn get_prefetch_head_commit(
         &self,
         remote_name: &str,
@@ -1461,4 +2363,352 @@ impl Repo {
             self.app.clone(),
         )
     }
+
+    /// Whether the post-checkout hook should sync automatically when the build graph changed, rather than only warning.
+    pub fn get_post_checkout_auto_sync(&self) -> Result<bool> {
+        let mut config_snapshot = self.repo.config()?.snapshot()?;
+        config_snapshot.get_bool_with_default(POST_CHECKOUT_AUTO_SYNC_CONFIG_KEY, false)
+    }
+
+    /// Whether the post-merge hook should sync automatically when a merge changes the build graph.
+    pub fn get_post_merge_auto_sync(&self) -> Result<bool> {
+        let mut config_snapshot = self.repo.config()?.snapshot()?;
+        config_snapshot.get_bool_with_default(POST_MERGE_AUTO_SYNC_CONFIG_KEY, false)
+    }
+
+    /// A shell command to run after each successful sync, if configured.
+    pub fn get_post_sync_hook(&self) -> Result<Option<String>> {
+        let config_snapshot = self.repo.config()?.snapshot()?;
+        Ok([POST_SYNC_HOOK_CONFIG_KEY, POST_SYNC_HOOK_ALIAS_CONFIG_KEY]
+            .iter()
+            .filter_map(|key| config_snapshot.get_str(key).ok())
+            .find(|s| !s.trim().is_empty())
+            .map(|s| s.to_owned()))
+    }
+
+    /// The largest percentage of the repository's files that a single addition to the selection may materialize without explicit confirmation.
+    pub fn get_max_selection_percentage(&self) -> Result<u32> {
+        let snapshot = self.repo.config()?.snapshot()?;
+        let percentage = snapshot
+            .get_i32(MAX_SELECTION_PERCENTAGE_CONFIG_KEY)
+            .unwrap_or(MAX_SELECTION_PERCENTAGE_DEFAULT);
+        if !(1..=100).contains(&percentage) {
+            warn!(
+                "Configuration value of '{}' must be between 1 and 100; using default ({})",
+                MAX_SELECTION_PERCENTAGE_CONFIG_KEY, MAX_SELECTION_PERCENTAGE_DEFAULT
+            );
+            return Ok(MAX_SELECTION_PERCENTAGE_DEFAULT as u32);
+        }
+        Ok(percentage as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excluded_paths_take_precedence_over_selected_directories() {
+        let directory = |path: &str| Pattern::Directory {
+            precedence: 0,
+            path: PathBuf::from(path),
+            recursive: true,
+        };
+        let mut patterns: PatternSet = [
+            directory("library_a"),
+            directory("library_a/generated/protos"),
+            directory("library_b"),
+            directory("library_b/testdata"),
+        ]
+        .into_iter()
+        .collect();
+
+        let excluded_path_patterns = exclude_paths(
+            &mut patterns,
+            &[
+                PathBuf::from("library_a/generated"),
+                PathBuf::from("library_b"),
+            ],
+        );
+        assert_eq!(
+            patterns.into_iter().collect::<Vec<_>>(),
+            vec![directory("library_a")]
+        );
+        let fragments: Vec<String> = excluded_path_patterns
+            .into_iter()
+            .map(|pattern| match pattern {
+                Pattern::Verbatim { fragment, .. } => fragment,
+                pattern => panic!("Unexpected pattern {:?}", pattern),
+            })
+            .collect();
+        assert_eq!(fragments, vec!["!/library_a/generated/", "!/library_b/"]);
+    }
+
+    #[test]
+    fn checkout_workers_are_passed_to_checkout() -> Result<()> {
+        assert_eq!(WorkingTree::checkout_args(None)?, vec!["checkout"]);
+        assert_eq!(
+            WorkingTree::checkout_args(Some(8))?,
+            vec!["-c", "checkout.workers=8", "checkout"]
+        );
+        assert!(WorkingTree::checkout_args(Some(0)).is_err());
+        assert!(WorkingTree::checkout_args(Some(MAX_CHECKOUT_WORKERS + 1)).is_err());
+        Ok(())
+    }
+
+    fn working_tree_with_directories(dir: &Path) -> Result<WorkingTree> {
+        let repo = git2::Repository::init(dir)?;
+        for directory in ["a", "b"] {
+            std::fs::create_dir(dir.join(directory))?;
+            std::fs::write(dir.join(directory).join("file"), directory)?;
+        }
+        let mut index = repo.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("Focus", "focus@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+        drop(tree);
+        WorkingTree::new(repo)
+    }
+
+    fn patterns_for(working_tree: &WorkingTree, directory: &str) -> Result<PatternSet> {
+        let mut patterns = working_tree.default_working_tree_patterns()?;
+        patterns.insert(Pattern::Directory {
+            precedence: LAST,
+            path: PathBuf::from(directory),
+            recursive: true,
+        });
+        Ok(patterns)
+    }
+
+    #[test]
+    fn atomic_application_restores_previous_checkout_when_interrupted() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let working_tree = working_tree_with_directories(dir.path())?;
+        let only_a = patterns_for(&working_tree, "a")?;
+        let only_b = patterns_for(&working_tree, "b")?;
+        working_tree.apply_sparse_patterns_atomically(only_a, true, None, app.clone())?;
+        let original_profile = std::fs::read_to_string(working_tree.sparse_checkout_path())?;
+        let assert_only_a_checked_out = || -> Result<()> {
+            assert!(dir.path().join("a/file").is_file());
+            assert!(!dir.path().join("b/file").exists());
+            assert_eq!(
+                std::fs::read_to_string(working_tree.sparse_checkout_path())?,
+                original_profile
+            );
+            assert!(!working_tree.previous_sparse_checkout_path().exists());
+            Ok(())
+        };
+        assert_only_a_checked_out()?;
+
+        // The checkout fails after the new profile was written and the working tree partially updated.
+        let result = working_tree.apply_atomically(app.clone(), || {
+            working_tree.write_sparse_profile(&only_b)?;
+            std::fs::create_dir_all(dir.path().join("b"))?;
+            std::fs::write(dir.path().join("b/file"), "b")?;
+            bail!("checkout interrupted")
+        });
+        assert!(result.is_err());
+        assert_only_a_checked_out()?;
+
+        // The process dies mid-checkout, so nothing is restored until the next atomic application.
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            working_tree.apply_atomically(app.clone(), || {
+                working_tree.write_sparse_profile(&only_b)?;
+                panic!("killed mid-checkout")
+            })
+        }));
+        assert!(panicked.is_err());
+        assert!(working_tree.previous_sparse_checkout_path().is_file());
+        working_tree.recover_interrupted_update(app.clone())?;
+        assert_only_a_checked_out()?;
+
+        // A non-atomic application also restores the interrupted one's profile before writing its own.
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            working_tree.apply_atomically(app.clone(), || {
+                working_tree.write_sparse_profile(&only_b)?;
+                panic!("killed mid-checkout")
+            })
+        }));
+        assert!(panicked.is_err());
+        assert!(!working_tree.apply_sparse_patterns(
+            patterns_for(&working_tree, "a")?,
+            true,
+            None,
+            app.clone()
+        )?);
+        assert_only_a_checked_out()?;
+
+        // A successful application replaces the profile and discards the saved one.
+        assert!(working_tree.apply_sparse_patterns_atomically(
+            patterns_for(&working_tree, "b")?,
+            true,
+            None,
+            app
+        )?);
+        assert!(dir.path().join("b/file").is_file());
+        assert!(!dir.path().join("a/file").exists());
+        assert!(!working_tree.previous_sparse_checkout_path().exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolved_patterns_are_reused_until_the_build_graph_changes() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let scratch_repo = focus_testing::ScratchGitRepo::new_copied_fixture(
+            app.git_binary().clone(),
+            Path::new("bazel_java_example"),
+            &dir.path().join("dense"),
+            "main",
+        )?;
+        let repo = Repo::open(scratch_repo.path(), app)?;
+        let request_for = |target: &str| -> Result<ResolutionRequest> {
+            Ok(ResolutionRequest {
+                repo: repo.path().to_owned(),
+                targets: [Target::try_from(target)?].into_iter().collect(),
+                options: ResolutionOptions::default(),
+            })
+        };
+        let request = request_for("bazel://library_a/...")?;
+        let paths: BTreeSet<PathBuf> = [PathBuf::from("library_a")].into_iter().collect();
+        let base = repo.get_head_commit()?.id();
+
+        // Nothing has been resolved yet.
+        assert_eq!(repo.reusable_resolution(&request, base, base)?, None);
+
+        let tree = repo.underlying().find_commit(base)?.tree()?;
+        let hash_context = HashContext::new(repo.underlying(), &tree)?;
+        let key = resolution_cache_key(&hash_context, &request)?;
+        repo.resolution_cache()
+            .put(&hash_context, &key, base, [], &paths)?;
+        assert_eq!(
+            repo.reusable_resolution(&request, base, base)?,
+            Some(paths.clone())
+        );
+        assert_eq!(
+            repo.reusable_resolution(&request_for("bazel://library_b/...")?, base, base)?,
+            None
+        );
+
+        // Changes to files that are not part of the build graph do not matter.
+        let unrelated = scratch_repo.write_and_commit_file("README.md", "Updated", "Docs")?;
+        assert_eq!(
+            repo.reusable_resolution(&request, base, unrelated)?,
+            Some(paths)
+        );
+
+        let build_change = scratch_repo.write_and_commit_file(
+            "library_a/BUILD",
+            "# Changed\n",
+            "Change a build file",
+        )?;
+        assert_eq!(
+            repo.reusable_resolution(&request, unrelated, build_change)?,
+            None
+        );
+        // Changes between the commit the targets were resolved at and `since` are not overlooked either.
+        assert_eq!(
+            repo.reusable_resolution(&request, build_change, build_change)?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolution_results_are_seeded_from_another_repo() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let open_fixture = |name: &str| -> Result<Repo> {
+            let scratch_repo = focus_testing::ScratchGitRepo::new_copied_fixture(
+                app.git_binary().clone(),
+                Path::new("bazel_java_example"),
+                &dir.path().join(name),
+                "main",
+            )?;
+            Repo::open(scratch_repo.path(), app.clone())
+        };
+        let source = open_fixture("source")?;
+        let destination = open_fixture("destination")?;
+        let request = ResolutionRequest {
+            repo: source.path().to_owned(),
+            targets: [Target::try_from("bazel://library_a/...")?]
+                .into_iter()
+                .collect(),
+            options: ResolutionOptions::default(),
+        };
+        let paths: BTreeSet<PathBuf> = [PathBuf::from("library_a")].into_iter().collect();
+
+        let head = source.get_head_commit()?.id();
+        let tree = source.underlying().find_commit(head)?.tree()?;
+        let hash_context = HashContext::new(source.underlying(), &tree)?;
+        let key = resolution_cache_key(&hash_context, &request)?;
+        source
+            .resolution_cache()
+            .put(&hash_context, &key, head, [], &paths)?;
+
+        // A repo without a resolution cache has nothing to seed from.
+        assert!(source
+            .seed_resolution_cache(destination.path(), head)
+            .is_err());
+
+        let head = destination.get_head_commit()?.id();
+        assert_eq!(destination.seed_resolution_cache(source.path(), head)?, 1);
+        let tree = destination.underlying().find_commit(head)?.tree()?;
+        let hash_context = HashContext::new(destination.underlying(), &tree)?;
+        assert_eq!(
+            destination.resolution_cache().get(&hash_context, &key)?,
+            Some(paths)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn preserving_application_restores_checkout_when_preserved_paths_are_removed() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let working_tree = working_tree_with_directories(dir.path())?;
+        working_tree.apply_sparse_patterns_atomically(
+            patterns_for(&working_tree, "a")?,
+            true,
+            None,
+            app.clone(),
+        )?;
+        let preserved_paths: BTreeSet<PathBuf> = [PathBuf::from("a/file")].into_iter().collect();
+
+        let error = working_tree
+            .apply_sparse_patterns_preserving(
+                patterns_for(&working_tree, "b")?,
+                true,
+                None,
+                &preserved_paths,
+                app.clone(),
+            )
+            .unwrap_err();
+        let conflict = error.downcast_ref::<NarrowingConflict>().unwrap();
+        assert_eq!(conflict.paths, vec![PathBuf::from("a/file")]);
+        assert!(dir.path().join("a/file").is_file());
+        assert!(!dir.path().join("b/file").exists());
+        assert!(!working_tree.previous_sparse_checkout_path().exists());
+
+        // Widening keeps the preserved path checked out.
+        let mut both = patterns_for(&working_tree, "a")?;
+        both.extend(patterns_for(&working_tree, "b")?);
+        assert!(working_tree.apply_sparse_patterns_preserving(
+            both,
+            true,
+            None,
+            &preserved_paths,
+            app
+        )?);
+        assert!(dir.path().join("a/file").is_file());
+        assert!(dir.path().join("b/file").is_file());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,134 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashSet, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{load_model, store_model};
+
+/// The number of changes kept in the selection history. Older changes are discarded as new ones are recorded.
+const MAX_ENTRIES: usize = 100;
+
+/// A change to the selection: the projects and targets it added and removed, each sorted by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectionChange {
+    /// When the change was saved, in seconds since the Unix epoch.
+    pub timestamp: i64,
+
+    #[serde(default)]
+    pub added_projects: Vec<String>,
+
+    #[serde(default)]
+    pub removed_projects: Vec<String>,
+
+    #[serde(default)]
+    pub added_targets: Vec<String>,
+
+    #[serde(default)]
+    pub removed_targets: Vec<String>,
+}
+
+impl SelectionChange {
+    /// Describe the change from the `previous` to the `current` names of selected projects and targets.
+    pub(crate) fn between(
+        previous: (&HashSet<String>, &HashSet<String>),
+        current: (&HashSet<String>, &HashSet<String>),
+        timestamp: i64,
+    ) -> Self {
+        let difference = |a: &HashSet<String>, b: &HashSet<String>| {
+            let mut names: Vec<String> = a.difference(b).cloned().collect();
+            names.sort();
+            names
+        };
+        let (previous_projects, previous_targets) = previous;
+        let (current_projects, current_targets) = current;
+        Self {
+            timestamp,
+            added_projects: difference(current_projects, previous_projects),
+            removed_projects: difference(previous_projects, current_projects),
+            added_targets: difference(current_targets, previous_targets),
+            removed_targets: difference(previous_targets, current_targets),
+        }
+    }
+
+    /// Whether the change neither added nor removed anything.
+    pub fn is_empty(&self) -> bool {
+        self.added_projects.is_empty()
+            && self.removed_projects.is_empty()
+            && self.added_targets.is_empty()
+            && self.removed_targets.is_empty()
+    }
+}
+
+/// Recent changes to the selection, oldest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectionHistory {
+    #[serde(default)]
+    pub entries: Vec<SelectionChange>,
+}
+
+impl SelectionHistory {
+    pub fn load(path: &Path) -> Result<Self> {
+        load_model(path)
+            .with_context(|| format!("Loading selection history from {}", path.display()))
+    }
+
+    /// Append `change` to the history stored at `path`, discarding the oldest changes beyond the limit.
+    pub fn record(path: &Path, change: SelectionChange) -> Result<()> {
+        let mut history = Self::load(path)?;
+        history.entries.push(change);
+        if history.entries.len() > MAX_ENTRIES {
+            let excess = history.entries.len() - MAX_ENTRIES;
+            history.entries.drain(..excess);
+        }
+        store_model(path, &history)
+            .with_context(|| format!("Storing selection history to {}", path.display()))
+    }
+
+    /// The most recent changes, newest first, up to `limit` if given.
+    pub fn recent(&self, limit: Option<usize>) -> Vec<&SelectionChange> {
+        self.entries
+            .iter()
+            .rev()
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashset;
+
+    use super::*;
+
+    #[test]
+    fn history_is_trimmed_to_the_most_recent_changes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("selection_history.json");
+        for timestamp in 0..(MAX_ENTRIES as i64 + 5) {
+            let change = SelectionChange::between(
+                (&hashset! {}, &hashset! {}),
+                (&hashset! {format!("project_{}", timestamp)}, &hashset! {}),
+                timestamp,
+            );
+            SelectionHistory::record(&path, change)?;
+        }
+
+        let history = SelectionHistory::load(&path)?;
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        let timestamps: Vec<i64> = history
+            .recent(Some(2))
+            .iter()
+            .map(|change| change.timestamp)
+            .collect();
+        assert_eq!(
+            timestamps,
+            vec![MAX_ENTRIES as i64 + 4, MAX_ENTRIES as i64 + 3]
+        );
+        assert_eq!(history.entries[0].timestamp, 5);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod history;
+pub use history::{SelectionChange, SelectionHistory};
+
 mod project;
 pub use project::resolve_targets_for_project;
 pub use project::Project;
@@ -11,11 +14,13 @@ use project::ProjectIndex;
 mod selection;
 pub use selection::Selection;
 pub(crate) use selection::SelectionManager;
+pub use selection::SelectionProblem;
 
 use super::data_paths::DataPaths;
 
 mod operations;
 pub use operations::AddOptions;
+pub use operations::Interpretation;
 pub use operations::Operation;
 pub use operations::OperationAction;
 pub use operations::OperationResult;
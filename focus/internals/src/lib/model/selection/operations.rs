@@ -3,11 +3,22 @@
 
 use std::collections::HashSet;
 
+use anyhow::{Context, Result};
+
 use super::*;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
 pub struct AddOptions {
     pub unroll: bool,
+
+    /// Replace each target with the smallest optional project that contains it.
+    pub project_from_label: bool,
+
+    /// Leave out selected paths that match ignore rules when synchronizing afterwards.
+    pub dedupe_with_gitignore: bool,
+
+    /// Allow additions that would materialize most of the repository.
+    pub allow_full_repo: bool,
 }
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct RemoveOptions {
@@ -23,7 +34,7 @@ pub enum OperationAction {
 impl OperationAction {
     /// Set default options for OperationAction::Add
     pub fn default_add() -> OperationAction {
-        OperationAction::Add(AddOptions { unroll: false })
+        OperationAction::Add(AddOptions::default())
     }
 
     /// Set default options for OperationAction::Remove
@@ -32,6 +43,33 @@ impl OperationAction {
     }
 }
 
+/// Whether the names of projects and targets given by the user are projects or targets.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Interpretation {
+    /// Names that parse as targets are targets, and anything else is a project.
+    #[default]
+    Inferred,
+
+    /// Every name is a project, even if it parses as a target.
+    Project,
+
+    /// Every name is a target. Names that do not parse as targets are an error.
+    Target,
+}
+
+impl Interpretation {
+    /// Returns the target that `name` is interpreted as, or `None` if it is interpreted as a project.
+    pub fn target(self, name: &str) -> Result<Option<Target>> {
+        match self {
+            Interpretation::Inferred => Ok(Target::try_from(name).ok()),
+            Interpretation::Project => Ok(None),
+            Interpretation::Target => Target::try_from(name)
+                .map(Some)
+                .with_context(|| format!("Interpreting {:?} as a target", name)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Underlying {
     Target(Target),
@@ -54,6 +92,20 @@ impl Operation {
 
         Self { action, underlying }
     }
+
+    /// Like `new`, but interpreting `string_repr` as a project or target according to `interpretation`.
+    pub fn with_interpretation(
+        action: OperationAction,
+        string_repr: impl AsRef<str>,
+        interpretation: Interpretation,
+    ) -> Result<Self> {
+        let string_repr = string_repr.as_ref();
+        let underlying = match interpretation.target(string_repr)? {
+            Some(target) => Underlying::Target(target),
+            None => Underlying::Project(string_repr.to_owned()),
+        };
+        Ok(Self { action, underlying })
+    }
 }
 
 #[derive(Debug, Default)]
@@ -103,4 +155,26 @@ mod testing {
             }
         );
     }
+
+    #[test]
+    fn operation_with_interpretation() -> Result<()> {
+        let action = OperationAction::default_add();
+        assert_eq!(
+            Operation::with_interpretation(action, "bazel://a/b:*", Interpretation::Project)?,
+            Operation {
+                action,
+                underlying: Underlying::Project(String::from("bazel://a/b:*"))
+            }
+        );
+        assert_eq!(
+            Operation::with_interpretation(action, "bazel://a/b:*", Interpretation::Target)?,
+            Operation::new(action, "bazel://a/b:*")
+        );
+        assert_eq!(
+            Operation::with_interpretation(action, "foo", Interpretation::Inferred)?,
+            Operation::new(action, "foo")
+        );
+        assert!(Operation::with_interpretation(action, "foo", Interpretation::Target).is_err());
+        Ok(())
+    }
 }
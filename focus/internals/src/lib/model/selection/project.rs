@@ -301,6 +301,22 @@ impl ProjectIndex {
 
         Ok(())
     }
+
+    /// Find the project with the fewest targets, counting those of its subprojects, that contains `target`. Ties are broken by project name. Projects whose targets can't be resolved are not considered.
+    pub fn smallest_project_containing(&self, target: &Target) -> Option<&Project> {
+        self.underlying
+            .values()
+            .filter_map(|project| {
+                let targets =
+                    resolve_targets_for_project(vec![project.clone()], &self.underlying).ok()?;
+                targets
+                    .iter()
+                    .any(|candidate| candidate.contains(target))
+                    .then_some((targets.len(), project))
+            })
+            .min_by(|(a_len, a), (b_len, b)| a_len.cmp(b_len).then_with(|| a.name.cmp(&b.name)))
+            .map(|(_, project)| project)
+    }
 }
 
 impl TryFrom<&ProjectIndex> for TargetSet {
@@ -348,6 +364,14 @@ impl ProjectCatalog {
         self.optional_project_set_store.0.save()
     }
 
+    /// Find the optional or mandatory project called `name`.
+    pub fn find_project(&self, name: &str) -> Option<&Project> {
+        self.optional_projects
+            .underlying
+            .get(name)
+            .or_else(|| self.mandatory_projects.underlying.get(name))
+    }
+
     pub fn set_project(
         &mut self,
         project_name: String,
@@ -3,10 +3,11 @@
 
 use crate::model::selection::operations::OperationAction;
 use anyhow::{bail, Context, Result};
+use chrono::Utc;
 use focus_util::backed_up_file::BackedUpFile;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeSet, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Display,
     path::{Path, PathBuf},
 };
@@ -194,6 +195,10 @@ impl<'processor> SelectionOperationProcessor<'processor> {
 pub struct SelectionManager {
     /// The path where the selection is stored.
     selection_path: PathBuf,
+    /// The path where changes to the selection are recorded.
+    history_path: PathBuf,
+    /// The directory where named selection profiles are stored.
+    profiles_dir: PathBuf,
     /// The currently selected projects.
     selection: Selection,
     /// A catalog of defined projects.
@@ -205,12 +210,24 @@ impl SelectionManager {
         let working_tree = repo.working_tree()?;
         let paths = DataPaths::from_working_tree(working_tree)?;
         let project_catalog = ProjectCatalog::new(&paths)?;
-        Self::new(&paths.selection_file, project_catalog)
+        Self::new(
+            &paths.selection_file,
+            &paths.selection_history_file,
+            &paths.selection_profiles_dir,
+            project_catalog,
+        )
     }
 
-    fn new(selection_path: impl AsRef<Path>, project_catalog: ProjectCatalog) -> Result<Self> {
+    fn new(
+        selection_path: impl AsRef<Path>,
+        history_path: impl AsRef<Path>,
+        profiles_dir: impl AsRef<Path>,
+        project_catalog: ProjectCatalog,
+    ) -> Result<Self> {
         let mut instance = Self {
             selection_path: selection_path.as_ref().to_owned(),
+            history_path: history_path.as_ref().to_owned(),
+            profiles_dir: profiles_dir.as_ref().to_owned(),
             selection: Default::default(),
             project_catalog,
         };
@@ -235,15 +252,81 @@ impl SelectionManager {
         Ok(())
     }
 
-    /// Save the current selection to the configured `selection_path`.
+    /// Save the current selection to the configured `selection_path`, recording what changed in the selection history.
     pub fn save(&self) -> Result<()> {
         let selection = self.selection.clone();
         let persisted_selection = PersistedSelection::from(&selection);
+        let previous_selection: PersistedSelection =
+            load_model(&self.selection_path).unwrap_or_default();
         store_model(&self.selection_path, &persisted_selection)?;
         debug!(?persisted_selection, path = ?self.selection_path, "Saved selection");
+
+        let change = SelectionChange::between(
+            (&previous_selection.projects, &previous_selection.targets),
+            (&persisted_selection.projects, &persisted_selection.targets),
+            Utc::now().timestamp(),
+        );
+        if !change.is_empty() {
+            if let Err(e) = SelectionHistory::record(&self.history_path, change) {
+                warn!(?e, "Failed to record the change in the selection history");
+            }
+        }
         Ok(())
     }
 
+    /// Returns recent changes to the selection.
+    pub fn history(&self) -> Result<SelectionHistory> {
+        SelectionHistory::load(&self.history_path)
+    }
+
+    /// The path where the selection profile `name` is stored.
+    fn profile_path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            bail!("Invalid selection profile name {:?}", name);
+        }
+        Ok(self.profiles_dir.join(format!("{}.json", name)))
+    }
+
+    /// Store the current selection as the profile `name`, replacing any profile of that name.
+    pub fn save_profile(&self, name: &str) -> Result<()> {
+        let path = self.profile_path(name)?;
+        std::fs::create_dir_all(&self.profiles_dir)
+            .context("Creating the selection profile directory")?;
+        store_model(&path, &PersistedSelection::from(&self.selection))
+            .with_context(|| format!("Storing selection profile {:?}", name))
+    }
+
+    /// Replace the current selection with the profile `name`. The selection is not saved.
+    pub fn switch_to_profile(&mut self, name: &str) -> Result<()> {
+        let path = self.profile_path(name)?;
+        if !path.is_file() {
+            bail!("There is no selection profile named {:?}", name);
+        }
+        self.selection = Self::load(&path, &self.project_catalog.optional_projects)
+            .with_context(|| format!("Loading selection profile {:?}", name))?;
+        Ok(())
+    }
+
+    /// Returns the names of the stored selection profiles, sorted.
+    pub fn profiles(&self) -> Result<Vec<String>> {
+        if !self.profiles_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.profiles_dir)
+            .context("Reading the selection profile directory")?
+        {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) == Some("json") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
     /// Returns a back up of the selection file.
     pub fn create_backup(&self) -> Result<BackedUpFile> {
         BackedUpFile::new(&self.selection_path)
@@ -274,7 +357,11 @@ impl SelectionManager {
     pub fn compute_complete_target_set(&self) -> Result<HashSet<Target>> {
         let selection = self.computed_selection()?;
         debug!(selected = ?selection, "Computed selection");
+        self.compute_target_set(selection)
+    }
 
+    /// Returns the targets of `selection`, including those of the projects it contains.
+    pub fn compute_target_set(&self, selection: Selection) -> Result<HashSet<Target>> {
         let optional_projects = &self.project_catalog().optional_projects.underlying;
         let mut target_set = selection.targets.clone();
         let projects = selection.projects;
@@ -295,12 +382,13 @@ impl SelectionManager {
     pub fn mutate(
         &mut self,
         action: OperationAction,
+        interpretation: Interpretation,
         projects_and_targets: &[String],
     ) -> Result<bool> {
         let operations = projects_and_targets
             .iter()
-            .map(|value| Operation::new(action, value.clone()))
-            .collect::<Vec<Operation>>();
+            .map(|value| Operation::with_interpretation(action, value, interpretation))
+            .collect::<Result<Vec<Operation>>>()?;
         let result = self
             .process(&operations)
             .context("Processing updates to the selection")?;
@@ -327,6 +415,32 @@ impl SelectionManager {
     pub fn mut_project_catalog(&mut self) -> &mut ProjectCatalog {
         &mut self.project_catalog
     }
+
+    /// Check the selection stored in `repo` against its project catalog without modifying anything. Unlike loading the selection, which fails on uninterpretable targets and skips undefined projects, this reports every problem found.
+    pub fn validate(repo: &Repo) -> Result<Vec<SelectionProblem>> {
+        let working_tree = repo.working_tree()?;
+        let paths = DataPaths::from_working_tree(working_tree)?;
+        let project_catalog = ProjectCatalog::new(&paths)?;
+        let persisted_selection: PersistedSelection =
+            load_model(&paths.selection_file).context("Loading persisted selection")?;
+        Ok(persisted_selection.problems(&project_catalog))
+    }
+}
+
+/// A problem with the stored selection found by `SelectionManager::validate`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, thiserror::Error)]
+pub enum SelectionProblem {
+    #[error("Target {target:?} could not be interpreted: {reason}")]
+    InvalidTarget { target: String, reason: String },
+
+    #[error("Project {0:?} is not defined")]
+    UndefinedProject(String),
+
+    #[error("Project {project:?} includes undefined project {included:?}")]
+    UndefinedIncludedProject { project: String, included: String },
+
+    #[error("Projects include each other in a cycle: {}", .0.join(" -> "))]
+    ProjectCycle(Vec<String>),
 }
 
 /// A structure to store the names of selected projects and targets. Converted from the fully-featured in-memory representation Selection.
@@ -353,6 +467,83 @@ impl From<&Selection> for PersistedSelection {
     }
 }
 
+impl PersistedSelection {
+    /// Returns the problems with this selection given the projects defined in `catalog`, sorted.
+    fn problems(&self, catalog: &ProjectCatalog) -> Vec<SelectionProblem> {
+        let mut problems = Vec::new();
+
+        for target in self.targets.iter() {
+            if let Err(error) = Target::try_from(target.as_str()) {
+                problems.push(SelectionProblem::InvalidTarget {
+                    target: target.clone(),
+                    reason: error.to_string(),
+                });
+            }
+        }
+
+        let optional_projects = &catalog.optional_projects.underlying;
+        let mut roots: Vec<&Project> = Vec::new();
+        for name in self.projects.iter() {
+            match optional_projects.get(name) {
+                Some(project) => roots.push(project),
+                None => problems.push(SelectionProblem::UndefinedProject(name.clone())),
+            }
+        }
+        roots.extend(catalog.mandatory_projects.underlying.values());
+        roots.sort();
+
+        // Walk project inclusions depth-first, reporting undefined inclusions and any back edge as a cycle.
+        let mut finished: HashSet<&str> = HashSet::new();
+        for root in roots {
+            let mut stack: Vec<&str> = Vec::new();
+            Self::visit(
+                root,
+                optional_projects,
+                &mut stack,
+                &mut finished,
+                &mut problems,
+            );
+        }
+
+        problems.sort();
+        problems.dedup();
+        problems
+    }
+
+    fn visit<'a>(
+        project: &'a Project,
+        projects: &'a HashMap<String, Project>,
+        stack: &mut Vec<&'a str>,
+        finished: &mut HashSet<&'a str>,
+        problems: &mut Vec<SelectionProblem>,
+    ) {
+        if finished.contains(project.name.as_str()) {
+            return;
+        }
+        stack.push(project.name.as_str());
+        for included in project.projects.iter() {
+            if let Some(position) = stack.iter().position(|&name| name == included) {
+                let mut cycle: Vec<String> = stack[position..]
+                    .iter()
+                    .map(|&name| name.to_owned())
+                    .collect();
+                cycle.push(included.clone());
+                problems.push(SelectionProblem::ProjectCycle(cycle));
+                continue;
+            }
+            match projects.get(included) {
+                Some(subproject) => Self::visit(subproject, projects, stack, finished, problems),
+                None => problems.push(SelectionProblem::UndefinedIncludedProject {
+                    project: project.name.clone(),
+                    included: included.clone(),
+                }),
+            }
+        }
+        stack.pop();
+        finished.insert(project.name.as_str());
+    }
+}
+
 impl TryFrom<PersistedSelection> for Vec<Operation> {
     type Error = anyhow::Error;
 
@@ -128,6 +128,100 @@ fn modifying_and_saving_the_selection() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn history_lists_saved_changes_newest_first() -> Result<()> {
+    init_logging();
+
+    let fixture = Fixture::new()?;
+    let repo = fixture.repo()?;
+
+    let project_name = String::from("team_banzai/project_a");
+    let target = Target::try_from("bazel://library_b/...")?;
+
+    let mut selection_manager = repo.selection_manager()?;
+    selection_manager.process(&[Operation {
+        action: OperationAction::default_add(),
+        underlying: Underlying::Project(project_name.clone()),
+    }])?;
+    selection_manager.save()?;
+    selection_manager.process(&[Operation {
+        action: OperationAction::default_add(),
+        underlying: Underlying::Target(target.clone()),
+    }])?;
+    selection_manager.save()?;
+    // Saving an unchanged selection records nothing.
+    selection_manager.save()?;
+    selection_manager.process(&[Operation {
+        action: OperationAction::default_remove(),
+        underlying: Underlying::Project(project_name.clone()),
+    }])?;
+    selection_manager.save()?;
+
+    let history = repo.selection_manager()?.history()?;
+    let changes = history.recent(None);
+    assert_eq!(changes.len(), 3);
+    assert_eq!(changes[0].removed_projects, vec![project_name.clone()]);
+    assert!(changes[0].added_projects.is_empty());
+    assert_eq!(changes[1].added_targets, vec![target.to_string()]);
+    assert_eq!(changes[2].added_projects, vec![project_name]);
+    assert!(changes
+        .windows(2)
+        .all(|pair| pair[0].timestamp >= pair[1].timestamp));
+
+    assert_eq!(history.recent(Some(1)), changes[..1].to_vec());
+
+    Ok(())
+}
+
+#[test]
+fn switching_between_selection_profiles() -> Result<()> {
+    init_logging();
+
+    let fixture = Fixture::new()?;
+    let repo = fixture.repo()?;
+
+    let project_name = String::from("team_banzai/project_a");
+    let target = Target::try_from("bazel://library_b/...")?;
+
+    let mut selection_manager = repo.selection_manager()?;
+    assert!(selection_manager.profiles()?.is_empty());
+    selection_manager.process(&[Operation {
+        action: OperationAction::default_add(),
+        underlying: Underlying::Project(project_name.clone()),
+    }])?;
+    selection_manager.save_profile("frontend")?;
+    selection_manager.process(&[
+        Operation {
+            action: OperationAction::default_remove(),
+            underlying: Underlying::Project(project_name.clone()),
+        },
+        Operation {
+            action: OperationAction::default_add(),
+            underlying: Underlying::Target(target.clone()),
+        },
+    ])?;
+    selection_manager.save_profile("pipeline")?;
+    assert_eq!(
+        selection_manager.profiles()?,
+        vec![String::from("frontend"), String::from("pipeline")]
+    );
+
+    selection_manager.switch_to_profile("frontend")?;
+    let selection = selection_manager.selection()?;
+    assert_eq!(project_names(&selection), hashset! {project_name});
+    assert!(selection.targets.is_empty());
+
+    selection_manager.switch_to_profile("pipeline")?;
+    let selection = selection_manager.selection()?;
+    assert!(selection.projects.is_empty());
+    assert_eq!(selection.targets, hashset! {target});
+
+    assert!(selection_manager.switch_to_profile("missing").is_err());
+    assert!(selection_manager.save_profile("../escape").is_err());
+
+    Ok(())
+}
+
 #[test]
 fn adding_an_unknown_project() -> Result<()> {
     init_logging();
@@ -191,3 +285,117 @@ fn duplicate_projects_are_ignored() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn validating_a_selection_referencing_a_deleted_project() -> Result<()> {
+    init_logging();
+
+    let fixture = Fixture::new()?;
+    let repo = fixture.repo()?;
+
+    let project_b = String::from("team_zissou/project_b");
+    {
+        let mut selection_manager = repo.selection_manager()?;
+        selection_manager.process(&[Operation {
+            action: OperationAction::default_add(),
+            underlying: Underlying::Project(project_b.clone()),
+        }])?;
+        selection_manager.save()?;
+    }
+    assert!(repo.validate_selection()?.is_empty());
+
+    std::fs::remove_file(
+        fixture
+            .underlying
+            .path()
+            .join("focus/projects/project_b.projects.json"),
+    )?;
+    assert_eq!(
+        repo.validate_selection()?,
+        vec![SelectionProblem::UndefinedProject(project_b)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn validating_a_selection_with_a_project_cycle() -> Result<()> {
+    init_logging();
+
+    let fixture = Fixture::new()?;
+    std::fs::write(
+        fixture
+            .underlying
+            .path()
+            .join("focus/projects/cycle.projects.json"),
+        r#"{
+    "projects": [
+        {
+            "name": "cycle/x",
+            "description": "Includes y",
+            "projects": ["cycle/y"]
+        },
+        {
+            "name": "cycle/y",
+            "description": "Includes x",
+            "projects": ["cycle/x", "cycle/z"]
+        }
+    ]
+}"#,
+    )?;
+    let repo = fixture.repo()?;
+    {
+        let mut selection_manager = repo.selection_manager()?;
+        selection_manager.process(&[Operation {
+            action: OperationAction::default_add(),
+            underlying: Underlying::Project(String::from("cycle/x")),
+        }])?;
+        selection_manager.save()?;
+    }
+
+    assert_eq!(
+        repo.validate_selection()?,
+        vec![
+            SelectionProblem::UndefinedIncludedProject {
+                project: String::from("cycle/y"),
+                included: String::from("cycle/z"),
+            },
+            SelectionProblem::ProjectCycle(vec![
+                String::from("cycle/x"),
+                String::from("cycle/y"),
+                String::from("cycle/x"),
+            ]),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn finding_the_smallest_project_containing_a_label() -> Result<()> {
+    init_logging();
+
+    let fixture = Fixture::new()?;
+    let repo = fixture.repo()?;
+    let selection_manager = repo.selection_manager()?;
+    let optional_projects = &selection_manager.project_catalog().optional_projects;
+    let owner = |target: &str| -> Result<Option<String>> {
+        Ok(optional_projects
+            .smallest_project_containing(&Target::try_from(target)?)
+            .map(|project| project.name.clone()))
+    };
+
+    // Both `numbered/lib/3` and `numbered/libs_1_to_5` contain library_3.
+    assert_eq!(
+        owner("bazel://library_3/src/main/java/com/example:lib")?.as_deref(),
+        Some("numbered/lib/3")
+    );
+    // `team_zissou/project_c` contains project_a through its subproject, but has more targets.
+    assert_eq!(
+        owner("bazel://project_a/src/main/java/com/example/cmdline:runner")?.as_deref(),
+        Some("team_banzai/project_a")
+    );
+    assert_eq!(owner("bazel://library_11/...")?, None);
+
+    Ok(())
+}
@@ -212,6 +212,10 @@ impl<'cache> ProjectCache<'cache> {
             .outliner()
             .ok_or_else(|| anyhow::anyhow!("No outliner available"))?;
         tracing::info!(outliner = outliner.identity());
+        let targets = &self
+            .repo
+            .expand_directory_globs(commit_id, targets, resolution_options.strict_directories)
+            .context("Expanding directory patterns")?;
         let (patterns, _resolution_result) = outliner
             .outline(
                 commit_id,
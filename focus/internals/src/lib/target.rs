@@ -4,6 +4,7 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::{collections::HashSet, convert::TryFrom, fmt::Display};
 
@@ -11,6 +12,34 @@ use thiserror::Error;
 
 pub type TargetSet = HashSet<Target>;
 
+/// Operations on a [`TargetSet`].
+pub trait TargetSetExt {
+    /// Split the set by scheme into Bazel labels, directories, and Pants addresses, so that each subset can be given to the resolver for its scheme.
+    fn partition_by_type(&self) -> (HashSet<Label>, HashSet<String>, HashSet<String>);
+}
+
+impl TargetSetExt for TargetSet {
+    fn partition_by_type(&self) -> (HashSet<Label>, HashSet<String>, HashSet<String>) {
+        let mut labels = HashSet::new();
+        let mut directories = HashSet::new();
+        let mut pants_addresses = HashSet::new();
+        for target in self.iter() {
+            match target {
+                Target::Bazel(label) => {
+                    labels.insert(label.clone());
+                }
+                Target::Directory(directory) => {
+                    directories.insert(directory.clone());
+                }
+                Target::Pants(address) => {
+                    pants_addresses.insert(address.clone());
+                }
+            }
+        }
+        (labels, directories, pants_addresses)
+    }
+}
+
 #[derive(clap::ArgEnum, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum TargetTypes {
     Bazel,
@@ -25,27 +54,171 @@ pub enum Target {
 
     /// A specific directory within the repository.
     Directory(String),
+
+    /// A Pants address like `src/python/foo:bar` or `src/python/foo::`.
+    Pants(String),
 }
 
 impl Display for Target {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Target::Bazel(c) => write!(f, "bazel:{}", c),
-            Target::Directory(c) => write!(f, "directory:{}", c),
+            Target::Bazel(c) => write!(f, "{}:{}", self.scheme(), c),
+            Target::Directory(c) => write!(f, "{}:{}", self.scheme(), c),
+            Target::Pants(c) => write!(f, "{}:{}", self.scheme(), c),
         }
     }
 }
 
+impl Target {
+    /// The scheme of this target.
+    pub fn target_type(&self) -> TargetTypes {
+        match self {
+            Target::Bazel(_) => TargetTypes::Bazel,
+            Target::Directory(_) => TargetTypes::Directory,
+            Target::Pants(_) => TargetTypes::Pants,
+        }
+    }
+
+    /// The scheme prefix of this target as written, such as `bazel` in `bazel://foo:bar`.
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Target::Bazel(_) => "bazel",
+            Target::Directory(_) => "directory",
+            Target::Pants(_) => "pants",
+        }
+    }
+
+    /// A Bazel target for `label`.
+    pub fn bazel(label: Label) -> Self {
+        Target::Bazel(label)
+    }
+
+    /// The label of a Bazel target, or `None` for other schemes.
+    pub fn as_label(&self) -> Option<&Label> {
+        match self {
+            Target::Bazel(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// The path of a directory target, or `None` for other schemes.
+    pub fn as_directory(&self) -> Option<&str> {
+        match self {
+            Target::Directory(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The address of a Pants target, or `None` for other schemes.
+    pub fn as_pants(&self) -> Option<&str> {
+        match self {
+            Target::Pants(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    /// Rewrite a directory target written relative to `cwd`, which starts with `./` or `../`, as relative to `repo_root`. Other targets are returned unchanged. Fails if the directory is not within the repository.
+    pub fn relative_to_repo_root(
+        self,
+        repo_root: &Path,
+        cwd: &Path,
+    ) -> Result<Target, TargetError> {
+        let directory = match &self {
+            Target::Directory(directory) if is_cwd_relative(directory) => directory,
+            _ => return Ok(self),
+        };
+        let outside = || TargetError::OutsideRepository(directory.clone());
+
+        let cwd_in_repo = match cwd.strip_prefix(repo_root) {
+            Ok(relative) => relative.to_owned(),
+            // The paths may differ only in symbolic links.
+            Err(_) => {
+                let repo_root = repo_root.canonicalize().map_err(|_| outside())?;
+                let cwd = cwd.canonicalize().map_err(|_| outside())?;
+                cwd.strip_prefix(repo_root)
+                    .map_err(|_| outside())?
+                    .to_owned()
+            }
+        };
+
+        let mut components: Vec<String> = Vec::new();
+        for component in cwd_in_repo.join(directory).components() {
+            match component {
+                Component::Normal(name) => {
+                    components.push(name.to_str().ok_or_else(outside)?.to_owned())
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    components.pop().ok_or_else(outside)?;
+                }
+                Component::RootDir | Component::Prefix(_) => return Err(outside()),
+            }
+        }
+        if components.is_empty() {
+            return Err(outside());
+        }
+        Ok(Target::Directory(components.join("/")))
+    }
+
+    /// Whether selecting this target also selects `other`, either because they
+    /// are the same target or because this target covers the directory `other`
+    /// is defined in.
+    pub fn contains(&self, other: &Target) -> bool {
+        if self == other {
+            return true;
+        }
+
+        let covered_path = match self {
+            Target::Bazel(label) => label.ellipsis_path(),
+            Target::Directory(path) => Some(PathBuf::from(path)),
+            Target::Pants(_) => None,
+        };
+        let other_path = match other {
+            Target::Bazel(label) if label.external_repository.is_none() => {
+                Some(label.path_components.iter().collect::<PathBuf>())
+            }
+            Target::Bazel(_) => None,
+            Target::Directory(path) => Some(PathBuf::from(path)),
+            Target::Pants(address) => Some(pants_address_path(address)),
+        };
+        match (covered_path, other_path) {
+            (Some(covered_path), Some(other_path)) => other_path.starts_with(covered_path),
+            _ => false,
+        }
+    }
+}
+
+/// Whether a directory target is written relative to the current directory rather than the repository root.
+fn is_cwd_relative(directory: &str) -> bool {
+    directory == "."
+        || directory == ".."
+        || directory.starts_with("./")
+        || directory.starts_with("../")
+}
+
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum TargetError {
-    #[error("Scheme not supported")]
-    UnsupportedScheme(String),
+    /// The `scheme` is lowercased so that errors for the same scheme compare equal however it was written.
+    #[error("Scheme {scheme:?} not supported{}", .suggestion.map(|s| format!("; did you mean {:?}?", s)).unwrap_or_default())]
+    UnsupportedScheme {
+        scheme: String,
+        suggestion: Option<&'static str>,
+    },
 
     #[error("No target scheme provided")]
     NoSchemeProvidedError,
 
     #[error("Failed to parse label")]
     LabelError(#[from] LabelParseError),
+
+    #[error("Expected a {expected:?} target, but got {target}")]
+    UnexpectedType {
+        expected: TargetTypes,
+        target: String,
+    },
+
+    #[error("Directory {0:?} is not within the repository")]
+    OutsideRepository(String),
 }
 
 impl TryFrom<&str> for Target {
@@ -57,11 +230,15 @@ impl TryFrom<&str> for Target {
                 let rest = rest.to_owned();
                 if prefix.eq_ignore_ascii_case("bazel") {
                     let label: Label = rest.parse()?;
-                    Ok(Target::Bazel(label))
+                    Ok(Target::bazel(label))
                 } else if prefix.eq_ignore_ascii_case("directory") {
                     Ok(Target::Directory(rest))
+                } else if prefix.eq_ignore_ascii_case("pants") {
+                    Ok(Target::Pants(rest))
                 } else {
-                    Err(TargetError::UnsupportedScheme(prefix.to_owned()))
+                    let scheme = prefix.to_ascii_lowercase();
+                    let suggestion = nearest_scheme(&scheme);
+                    Err(TargetError::UnsupportedScheme { scheme, suggestion })
                 }
             }
             None => Err(TargetError::NoSchemeProvidedError),
@@ -69,11 +246,49 @@ impl TryFrom<&str> for Target {
     }
 }
 
+/// The schemes that targets can be written with.
+const SCHEMES: &[&str] = &["bazel", "directory", "pants"];
+
+/// The supported scheme closest to the lowercase `scheme`, if it is close enough to be a likely typo.
+fn nearest_scheme(scheme: &str) -> Option<&'static str> {
+    SCHEMES
+        .iter()
+        .map(|candidate| (edit_distance(scheme, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= 2 && *distance < candidate.len() / 2 + 1)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The path named by a Pants address, without its target name or generated
+/// target suffix. For `src/python/foo:bar` this is `src/python/foo`; for
+/// `src/python/foo/bar.py:lib` it is the file `src/python/foo/bar.py`.
+pub fn pants_address_path(address: &str) -> PathBuf {
+    let path = address.trim_start_matches("//");
+    let path = path.split([':', '#']).next().unwrap_or_default();
+    PathBuf::from(path.trim_end_matches('/'))
+}
+
 impl From<&Target> for String {
     fn from(val: &Target) -> Self {
         match val {
             Target::Bazel(spec) => format!("bazel:{}", spec),
             Target::Directory(spec) => format!("directory:{}", spec),
+            Target::Pants(spec) => format!("pants:{}", spec),
         }
     }
 }
@@ -149,12 +364,66 @@ pub enum LabelParseError {
 
     #[error("Empty label")]
     EmptyLabel,
+
+    #[error("Invalid target name: {0:?}")]
+    InvalidTargetName(String),
+
+    #[error("Invalid package path: {0:?}")]
+    InvalidPackage(String),
 }
 
 impl FromStr for Label {
     type Err = LabelParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, true)
+    }
+}
+
+impl Label {
+    /// For a label like `//foo/bar/...` in the main repository, the directory
+    /// `foo/bar` that it covers. The root ellipsis `//...` covers the empty
+    /// path.
+    pub fn ellipsis_path(&self) -> Option<PathBuf> {
+        match (&self.external_repository, &self.target_name) {
+            (None, TargetName::Ellipsis) => Some(self.path_components.iter().collect()),
+            _ => None,
+        }
+    }
+
+    /// Parse a label written in the BUILD file of `package`, a directory relative to the repository root. A package-relative label like `:foo` names a target in `package`; other labels are parsed as by `from_str`, which resolves `:foo` against the root package.
+    pub fn parse_in_package(s: &str, package: &Path) -> Result<Self, LabelParseError> {
+        let mut label: Label = s.parse()?;
+        if !s.starts_with(':') {
+            return Ok(label);
+        }
+
+        let invalid_package = || LabelParseError::InvalidPackage(package.display().to_string());
+        let mut path_components = package
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .map(|component| match component {
+                Component::Normal(name) => name.to_str().map(ToOwned::to_owned),
+                _ => None,
+            })
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(invalid_package)?;
+        if path_components.is_empty() && label.target_name != TargetName::Ellipsis {
+            // Match how `//:foo` is parsed.
+            path_components.push(String::new());
+        }
+        label.path_components = path_components;
+        Ok(label)
+    }
+
+    /// Parse a label emitted by Bazel itself (such as query output or `load`
+    /// statements). Unlike `from_str`, this accepts file targets whose names
+    /// contain `/`, like `//foo:bar/baz.py`.
+    pub(crate) fn parse_lenient(s: &str) -> Result<Self, LabelParseError> {
+        Self::parse(s, false)
+    }
+
+    fn parse(s: &str, validate_target_name: bool) -> Result<Self, LabelParseError> {
         let (external_package, label) = match s.split_once("//") {
             None => (None, s),
             Some(("", label)) => (None, label),
@@ -166,6 +435,14 @@ impl FromStr for Label {
             None => (label, None),
         };
 
+        if let Some(target) = target {
+            // An empty target name (as in `//foo:`) is never valid, and we do
+            // not accept `/` in target names given after the colon from users.
+            if target.is_empty() || (validate_target_name && target.contains('/')) {
+                return Err(LabelParseError::InvalidTargetName(target.to_string()));
+            }
+        }
+
         let path_components: Vec<String> = package.split('/').map(|s| s.to_string()).collect();
         let target = match (path_components.last(), target) {
             (Some(_last_component), Some(target)) => target.to_string(),
@@ -239,11 +516,9 @@ mod tests {
         );
         assert_eq!(
             Target::try_from("bazel://foo/bar:baz/qux.py"),
-            Ok(Target::Bazel(Label {
-                external_repository: None,
-                path_components: vec!["foo".to_string(), "bar".to_string()],
-                target_name: TargetName::Name("baz/qux.py".to_string()),
-            }))
+            Err(TargetError::LabelError(LabelParseError::InvalidTargetName(
+                "baz/qux.py".to_string()
+            )))
         );
         assert_eq!(
             Target::try_from("bazel://..."),
@@ -258,9 +533,45 @@ mod tests {
             "bazel://...",
         );
 
+        assert_eq!(
+            Target::try_from("pants:src/python/foo:bar"),
+            Ok(Target::Pants("src/python/foo:bar".to_string()))
+        );
+        assert_eq!(
+            Target::try_from("pants:src/python/foo::")
+                .unwrap()
+                .to_string(),
+            "pants:src/python/foo::",
+        );
+
         assert_eq!(
             Target::try_from("bogus:whatever").unwrap_err(),
-            TargetError::UnsupportedScheme("bogus".to_owned())
+            TargetError::UnsupportedScheme {
+                scheme: "bogus".to_owned(),
+                suggestion: None
+            }
+        );
+        assert_eq!(
+            Target::try_from("Bzel:foo").unwrap_err(),
+            TargetError::UnsupportedScheme {
+                scheme: "bzel".to_owned(),
+                suggestion: Some("bazel")
+            }
+        );
+        assert_eq!(
+            Target::try_from("DIRECTROY:foo").unwrap_err().to_string(),
+            "Scheme \"directroy\" not supported; did you mean \"directory\"?"
+        );
+        assert_eq!(
+            Target::try_from("pant:foo").unwrap_err(),
+            TargetError::UnsupportedScheme {
+                scheme: "pant".to_owned(),
+                suggestion: Some("pants")
+            }
+        );
+        assert_eq!(
+            Target::try_from("bogus:whatever").unwrap_err().to_string(),
+            "Scheme \"bogus\" not supported"
         );
         assert_eq!(
             Target::try_from("okay").unwrap_err(),
@@ -273,4 +584,212 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    pub fn label_target_name_validation() -> Result<()> {
+        assert_eq!(
+            "//foo:bar/baz".parse::<Label>(),
+            Err(LabelParseError::InvalidTargetName("bar/baz".to_string()))
+        );
+        assert_eq!(
+            "//foo:".parse::<Label>(),
+            Err(LabelParseError::InvalidTargetName("".to_string()))
+        );
+        assert_eq!(
+            "//foo/...".parse::<Label>(),
+            Ok(Label {
+                external_repository: None,
+                path_components: vec!["foo".to_string()],
+                target_name: TargetName::Ellipsis,
+            })
+        );
+        assert_eq!(
+            "//foo:bar".parse::<Label>(),
+            Ok(Label {
+                external_repository: None,
+                path_components: vec!["foo".to_string()],
+                target_name: TargetName::Name("bar".to_string()),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn package_relative_label_parsing() -> Result<()> {
+        let root_target: Label = "//:foo".parse()?;
+        assert_eq!(":foo".parse::<Label>()?, root_target);
+        assert_eq!(":foo".parse::<Label>()?.to_string(), "//:foo");
+        assert_eq!(
+            Target::try_from("bazel::foo")?,
+            Target::Bazel(root_target.clone())
+        );
+        assert_eq!(
+            ":".parse::<Label>(),
+            Err(LabelParseError::InvalidTargetName(String::new()))
+        );
+
+        assert_eq!(
+            Label::parse_in_package(":foo", Path::new("pkg/sub"))?,
+            "//pkg/sub:foo".parse::<Label>()?
+        );
+        assert_eq!(
+            Label::parse_in_package(":foo", Path::new("./pkg"))?.to_string(),
+            "//pkg:foo"
+        );
+        assert_eq!(Label::parse_in_package(":foo", Path::new(""))?, root_target);
+        assert_eq!(
+            Label::parse_in_package("//pkg:foo", Path::new("other"))?,
+            "//pkg:foo".parse::<Label>()?
+        );
+        assert_eq!(
+            Label::parse_in_package("@repo//pkg:foo", Path::new("other"))?.to_string(),
+            "@repo//pkg:foo"
+        );
+        assert_eq!(
+            Label::parse_in_package(":foo", Path::new("../outside")),
+            Err(LabelParseError::InvalidPackage(String::from("../outside")))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn label_round_trip() -> Result<()> {
+        for s in [
+            "//foo/bar:baz",
+            "@foo//bar:qux",
+            "@foo//bar/...",
+            "@foo//:qux",
+            "//foo/...",
+            "//...",
+            "@foo//...",
+            "//:baz",
+        ] {
+            let label: Label = s.parse()?;
+            assert_eq!(label.to_string(), s);
+        }
+
+        let label: Label = "@foo//bar:qux".parse()?;
+        assert_eq!(label.external_repository.as_deref(), Some("@foo"));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn typed_accessors() -> Result<()> {
+        let bazel = Target::try_from("bazel://foo:bar")?;
+        let directory = Target::try_from("directory:foo/bar")?;
+        let pants = Target::try_from("pants:src/python/foo:bar")?;
+
+        let label: Label = "//foo:bar".parse()?;
+        assert_eq!(Target::bazel(label.clone()), bazel);
+        assert_eq!(bazel.as_label(), Some(&label));
+        assert_eq!(directory.as_label(), None);
+        assert_eq!(pants.as_label(), None);
+
+        assert_eq!(directory.as_directory(), Some("foo/bar"));
+        assert_eq!(bazel.as_directory(), None);
+        assert_eq!(pants.as_directory(), None);
+
+        assert_eq!(pants.as_pants(), Some("src/python/foo:bar"));
+        assert_eq!(bazel.as_pants(), None);
+        assert_eq!(directory.as_pants(), None);
+
+        assert_eq!(bazel.target_type(), TargetTypes::Bazel);
+        assert_eq!(directory.target_type(), TargetTypes::Directory);
+        assert_eq!(pants.target_type(), TargetTypes::Pants);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn scheme_matches_display() -> Result<()> {
+        for (input, scheme) in [
+            ("bazel://foo:bar", "bazel"),
+            ("directory:foo/bar", "directory"),
+            ("pants:src/python/foo:bar", "pants"),
+        ] {
+            let target = Target::try_from(input)?;
+            assert_eq!(target.scheme(), scheme);
+            assert!(target.to_string().starts_with(&format!("{}:", scheme)));
+            assert_eq!(Target::try_from(target.to_string().as_str())?, target);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn directories_relative_to_the_current_directory() -> Result<()> {
+        let repo_root = Path::new("/home/user/repo");
+        let cwd = repo_root.join("services/api");
+        let normalize = |target: &str| {
+            Target::try_from(target)
+                .unwrap()
+                .relative_to_repo_root(repo_root, &cwd)
+        };
+
+        assert_eq!(
+            normalize("directory:./config"),
+            Ok(Target::Directory(String::from("services/api/config")))
+        );
+        assert_eq!(
+            normalize("directory:../web/./static/"),
+            Ok(Target::Directory(String::from("services/web/static")))
+        );
+        assert_eq!(
+            normalize("directory:."),
+            Ok(Target::Directory(String::from("services/api")))
+        );
+        assert_eq!(
+            normalize("directory:./*/config"),
+            Ok(Target::Directory(String::from("services/api/*/config")))
+        );
+
+        // Other targets are relative to the repository root already.
+        assert_eq!(
+            normalize("directory:config"),
+            Ok(Target::Directory(String::from("config")))
+        );
+        assert_eq!(
+            normalize("bazel://foo:bar"),
+            Ok(Target::try_from("bazel://foo:bar")?)
+        );
+
+        assert_eq!(
+            normalize("directory:../../.."),
+            Err(TargetError::OutsideRepository(String::from("../../..")))
+        );
+        assert_eq!(
+            normalize("directory:../.."),
+            Err(TargetError::OutsideRepository(String::from("../..")))
+        );
+        assert_eq!(
+            Target::Directory(String::from("./config"))
+                .relative_to_repo_root(repo_root, Path::new("/home/user/elsewhere")),
+            Err(TargetError::OutsideRepository(String::from("./config")))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn target_sets_partition_by_type() -> Result<()> {
+        let targets: TargetSet = ["bazel://a:b", "directory:c/d", "pants:e/f::", "bazel://g:h"]
+            .iter()
+            .map(|s| Target::try_from(*s))
+            .collect::<Result<_, _>>()?;
+        let (labels, directories, pants_addresses) = targets.partition_by_type();
+        assert_eq!(
+            labels,
+            HashSet::from(["//a:b".parse::<Label>()?, "//g:h".parse::<Label>()?])
+        );
+        assert_eq!(directories, HashSet::from([String::from("c/d")]));
+        assert_eq!(pants_addresses, HashSet::from([String::from("e/f::")]));
+
+        let (labels, directories, pants_addresses) = TargetSet::new().partition_by_type();
+        assert!(labels.is_empty() && directories.is_empty() && pants_addresses.is_empty());
+
+        Ok(())
+    }
 }
@@ -3,7 +3,17 @@
 
 use std::borrow::Borrow;
 
-use crate::target::Label;
+use crate::target::{Label, Target, TargetError, TargetTypes};
+
+/// The label of `target`, which the Bazel resolvers must only be given Bazel targets for.
+pub fn label_of(target: &Target) -> Result<&Label, TargetError> {
+    target
+        .as_label()
+        .ok_or_else(|| TargetError::UnexpectedType {
+            expected: TargetTypes::Bazel,
+            target: target.to_string(),
+        })
+}
 
 pub fn make_set(labels: impl IntoIterator<Item = impl Borrow<Label>>) -> String {
     format!(
@@ -1,19 +1,199 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{
-    iter::FromIterator,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use tracing::{debug, warn};
+
+use crate::target::{TargetError, TargetTypes};
 
 use super::*;
 
+/// Whether a directory target contains glob metacharacters.
+fn is_glob(directory: &str) -> bool {
+    directory.contains(['*', '?', '['])
+}
+
+/// The names of the directories immediately within `directory`, which is relative to the repository root. Both the tree and the working tree at `repo` are consulted, since a sparse working tree may not have them checked out. Symbolic links and submodules are not directories.
+fn child_directories(
+    repo: &Path,
+    tree: Option<(&git2::Repository, &git2::Tree)>,
+    directory: &Path,
+) -> BTreeSet<String> {
+    let mut children = BTreeSet::new();
+
+    if let Some((git_repo, tree)) = tree {
+        let subtree = if directory.as_os_str().is_empty() {
+            Some(tree.clone())
+        } else {
+            tree.get_path(directory)
+                .ok()
+                .filter(|entry| entry.kind() == Some(git2::ObjectType::Tree))
+                .and_then(|entry| git_repo.find_tree(entry.id()).ok())
+        };
+        if let Some(subtree) = subtree {
+            children.extend(
+                subtree
+                    .iter()
+                    .filter(|entry| entry.kind() == Some(git2::ObjectType::Tree))
+                    .filter_map(|entry| entry.name().map(String::from)),
+            );
+        }
+    }
+
+    if let Ok(entries) = std::fs::read_dir(repo.join(directory)) {
+        children.extend(entries.filter_map(|entry| {
+            let entry = entry.ok()?;
+            // `DirEntry::file_type` does not follow symbolic links.
+            if !entry.file_type().ok()?.is_dir() {
+                return None;
+            }
+            entry.file_name().into_string().ok()
+        }));
+    }
+
+    children.remove(".git");
+    children
+}
+
+/// Expand a glob like `services/*/config` into the directories it matches. Each component of the pattern is matched against the directories at that depth, starting at the repository root.
+fn expand_directory_glob(
+    repo: &Path,
+    tree: Option<(&git2::Repository, &git2::Tree)>,
+    pattern: &str,
+) -> Result<BTreeSet<String>> {
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern.split('/').filter(|component| !component.is_empty()) {
+        if component == "." || component == ".." {
+            bail!(
+                "Directory pattern {:?} must be relative to the repository root",
+                pattern
+            );
+        }
+
+        let component_pattern = glob::Pattern::new(component)
+            .with_context(|| format!("Invalid directory pattern {:?}", pattern))?;
+        let match_options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: true,
+        };
+        matches = matches
+            .into_iter()
+            .flat_map(|directory| {
+                child_directories(repo, tree, &directory)
+                    .into_iter()
+                    .filter(|child| component_pattern.matches_with(child, match_options))
+                    .map(move |child| directory.join(child))
+            })
+            .collect();
+    }
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|directory| directory.to_str().map(String::from))
+        .collect())
+}
+
+/// Replace directory targets containing glob patterns like `services/*/config` with a target for each directory in the repository they match, as found in `tree` and on disk under `repo`. Other targets are returned unchanged. Patterns that match nothing are dropped with a warning, or fail if `strict_directories` is set.
+pub(crate) fn expand_directory_globs(
+    repo: &Path,
+    tree: Option<(&git2::Repository, &git2::Tree)>,
+    targets: &TargetSet,
+    strict_directories: bool,
+) -> Result<TargetSet> {
+    let mut expanded = TargetSet::new();
+    for target in targets {
+        let pattern = match target {
+            Target::Directory(directory) if is_glob(directory) => directory,
+            _ => {
+                expanded.insert(target.clone());
+                continue;
+            }
+        };
+
+        let directories = expand_directory_glob(repo, tree, pattern)?;
+        debug!(?pattern, ?directories, "Expanded directory pattern");
+        if directories.is_empty() {
+            if strict_directories {
+                bail!("Directory pattern {:?} matches no directories", pattern);
+            }
+            warn!(?pattern, "Directory pattern matches no directories");
+        }
+        expanded.extend(directories.into_iter().map(Target::Directory));
+    }
+    Ok(expanded)
+}
+
 /// Resolves directories verbatim
 pub struct DirectoryResolver {
     #[allow(dead_code)]
     cache_root: PathBuf,
 }
 
+impl DirectoryResolver {
+    /// Check that every directory target names a directory in the repository, warning about any that do not (or failing if `strict_directories` is set). Directories are looked up relative to the repository root, both on disk and in the tree at `HEAD`, since a sparse working tree may not have them checked out.
+    fn check_directories_exist(request: &ResolutionRequest) -> Result<()> {
+        let repo = git2::Repository::open(&request.repo).ok();
+        let head_tree = repo
+            .as_ref()
+            .and_then(|repo| repo.head().ok())
+            .and_then(|head| head.peel_to_tree().ok());
+
+        let missing: BTreeSet<&str> = request
+            .targets
+            .iter()
+            .filter_map(Target::as_directory)
+            .filter(|directory| {
+                if request.repo.join(directory).is_dir() {
+                    return false;
+                }
+                let in_head_tree = head_tree
+                    .as_ref()
+                    .and_then(|tree| tree.get_path(Path::new(directory)).ok())
+                    .map(|entry| entry.kind() == Some(git2::ObjectType::Tree))
+                    .unwrap_or(false);
+                !in_head_tree
+            })
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let missing = missing.into_iter().collect::<Vec<_>>().join(", ");
+        if request.options.strict_directories {
+            bail!(
+                "Directories do not exist in {}: {}",
+                request.repo.display(),
+                missing
+            );
+        }
+        warn!(repo = ?request.repo, %missing, "Directory targets do not exist");
+        Ok(())
+    }
+
+    fn directory_of(target: &Target) -> Result<&str, TargetError> {
+        target
+            .as_directory()
+            .ok_or_else(|| TargetError::UnexpectedType {
+                expected: TargetTypes::Directory,
+                target: target.to_string(),
+            })
+    }
+
+    fn package_info(target: &Target) -> Result<(DependencyKey, DependencyValue), TargetError> {
+        let directory = Self::directory_of(target)?;
+        Ok((
+            DependencyKey::Path(directory.into()),
+            DependencyValue::Path {
+                path: directory.into(),
+            },
+        ))
+    }
+}
+
 impl Resolver for DirectoryResolver {
     fn new(cache_root: &Path) -> Self {
         Self {
@@ -27,26 +207,38 @@ impl Resolver for DirectoryResolver {
         _cache_options: &CacheOptions,
         _app: Arc<App>,
     ) -> Result<ResolutionResult> {
-        let paths =
-            BTreeSet::<PathBuf>::from_iter(request.targets.iter().filter_map(
-                |target| match target {
-                    Target::Directory(inner) => Some(PathBuf::from(inner)),
-                    _ => unreachable!(),
-                },
-            ));
-        let package_infos: BTreeMap<_, _> = request
+        for directory in request.targets.iter().filter_map(Target::as_directory) {
+            if is_glob(directory) {
+                bail!(
+                    "Directory pattern {:?} must be expanded before it is resolved",
+                    directory
+                );
+            }
+            if Path::new(directory)
+                .components()
+                .any(|component| !matches!(component, std::path::Component::Normal(_)))
+            {
+                bail!(
+                    "Directory target {:?} must be relative to the repository root",
+                    directory
+                );
+            }
+        }
+        Self::check_directories_exist(request)?;
+
+        let paths = request
             .targets
             .iter()
-            .map(|target| match &target {
-                Target::Directory(directory) => (
-                    DependencyKey::Path(directory.into()),
-                    DependencyValue::Path {
-                        path: directory.into(),
-                    },
-                ),
-                _ => unreachable!("Bad target type (expected directory): {:?}", &target),
-            })
-            .collect();
+            .map(|target| Self::directory_of(target).map(PathBuf::from))
+            .collect::<Result<BTreeSet<PathBuf>, _>>()?;
+        // Runs on the global thread pool, which is sized by
+        // `--resolution-threads`. Collecting into a `BTreeMap` keeps the
+        // output ordering independent of scheduling.
+        let package_infos = request
+            .targets
+            .par_iter()
+            .map(Self::package_info)
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
 
         Ok(ResolutionResult {
             paths,
@@ -54,3 +246,187 @@ impl Resolver for DirectoryResolver {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_resolution_matches_sequential() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let cache_dir = tempfile::tempdir()?;
+        let targets: TargetSet = (0..5000)
+            .map(|i| Target::Directory(format!("dir{}/sub{}", i % 97, i)))
+            .collect();
+        let request = ResolutionRequest {
+            repo: cache_dir.path().to_path_buf(),
+            targets: targets.clone(),
+            options: Default::default(),
+        };
+
+        let resolver = DirectoryResolver::new(cache_dir.path());
+        let result = resolver.resolve(&request, &CacheOptions::default(), app)?;
+
+        let sequential: BTreeMap<_, _> = targets
+            .iter()
+            .map(DirectoryResolver::package_info)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(result.package_deps, sequential);
+        assert_eq!(result.paths.len(), targets.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_directories_are_reported_relative_to_repo_root() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let repo_dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(repo_dir.path().join("present"))?;
+
+        // Only committed to the tree at `HEAD`, as in a sparse working tree.
+        let repo = git2::Repository::init(repo_dir.path())?;
+        {
+            let blob = repo.blob(b"content")?;
+            let mut inner = repo.treebuilder(None)?;
+            inner.insert("file.txt", blob, 0o100644)?;
+            let inner = inner.write()?;
+            let mut root = repo.treebuilder(None)?;
+            root.insert("committed", inner, 0o040000)?;
+            let tree = repo.find_tree(root.write()?)?;
+            let signature = git2::Signature::now("Test", "test@example.com")?;
+            repo.commit(Some("HEAD"), &signature, &signature, "Add", &tree, &[])?;
+        }
+
+        let mut request = ResolutionRequest {
+            repo: repo_dir.path().to_path_buf(),
+            targets: ["present", "committed", "missing"]
+                .iter()
+                .map(|d| Target::Directory(d.to_string()))
+                .collect(),
+            options: Default::default(),
+        };
+        let resolver = DirectoryResolver::new(repo_dir.path());
+
+        // Without strict checking, missing directories only produce a warning.
+        resolver.resolve(&request, &CacheOptions::default(), app.clone())?;
+
+        request.options.strict_directories = true;
+        let error = resolver
+            .resolve(&request, &CacheOptions::default(), app.clone())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("missing"), "{}", error);
+        assert!(!error.contains("present"), "{}", error);
+        assert!(!error.contains("committed"), "{}", error);
+
+        request
+            .targets
+            .remove(&Target::Directory(String::from("missing")));
+        resolver.resolve(&request, &CacheOptions::default(), app)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn directory_globs_expand_within_the_repo() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let temp = tempfile::tempdir()?;
+        let repo_dir = temp.path().join("repo");
+        std::fs::create_dir_all(repo_dir.join("services/on_disk/config"))?;
+        std::fs::create_dir_all(repo_dir.join("services/unconfigured"))?;
+        std::fs::create_dir_all(temp.path().join("outside/config"))?;
+        std::os::unix::fs::symlink(
+            temp.path().join("outside"),
+            repo_dir.join("services/symlinked"),
+        )?;
+
+        // Only committed to the tree at `HEAD`, as in a sparse working tree.
+        let repo = git2::Repository::init(&repo_dir)?;
+        {
+            let blob = repo.blob(b"content")?;
+            let mut config = repo.treebuilder(None)?;
+            config.insert("file.txt", blob, 0o100644)?;
+            let config = config.write()?;
+            let mut service = repo.treebuilder(None)?;
+            service.insert("config", config, 0o040000)?;
+            let service = service.write()?;
+            let mut services = repo.treebuilder(None)?;
+            services.insert("committed", service, 0o040000)?;
+            let services = services.write()?;
+            let mut root = repo.treebuilder(None)?;
+            root.insert("services", services, 0o040000)?;
+            let tree = repo.find_tree(root.write()?)?;
+            let signature = git2::Signature::now("Test", "test@example.com")?;
+            repo.commit(Some("HEAD"), &signature, &signature, "Add", &tree, &[])?;
+        }
+
+        let head_tree = repo.head()?.peel_to_tree()?;
+        let expand = |patterns: &[&str], strict_directories: bool| {
+            let targets: TargetSet = patterns
+                .iter()
+                .map(|d| Target::Directory(d.to_string()))
+                .collect();
+            expand_directory_globs(
+                &repo_dir,
+                Some((&repo, &head_tree)),
+                &targets,
+                strict_directories,
+            )
+        };
+        assert_eq!(
+            expand(&["services/*/config", "services/unconfigured"], false)?,
+            [
+                "services/committed/config",
+                "services/on_disk/config",
+                "services/unconfigured"
+            ]
+            .iter()
+            .map(|d| Target::Directory(d.to_string()))
+            .collect::<TargetSet>()
+        );
+        assert!(expand(&["../*"], false).is_err());
+        assert!(expand(&["nothing/*"], false)?.is_empty());
+        assert!(expand(&["nothing/*"], true).is_err());
+
+        // The resolver only accepts expanded patterns.
+        let request = ResolutionRequest {
+            repo: repo_dir.clone(),
+            targets: TargetSet::from([Target::Directory(String::from("services/*/config"))]),
+            options: Default::default(),
+        };
+        assert!(DirectoryResolver::new(&repo_dir)
+            .resolve(&request, &CacheOptions::default(), app)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn misrouted_targets_are_errors() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let repo_dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(repo_dir.path().join("present"))?;
+        let request = ResolutionRequest {
+            repo: repo_dir.path().to_path_buf(),
+            targets: TargetSet::from([
+                Target::Directory(String::from("present")),
+                Target::try_from("bazel://foo:bar")?,
+            ]),
+            options: Default::default(),
+        };
+
+        let resolver = DirectoryResolver::new(repo_dir.path());
+        let error = resolver
+            .resolve(&request, &CacheOptions::default(), app)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TargetError>(),
+            Some(&TargetError::UnexpectedType {
+                expected: TargetTypes::Directory,
+                target: String::from("bazel://foo:bar"),
+            })
+        );
+
+        Ok(())
+    }
+}
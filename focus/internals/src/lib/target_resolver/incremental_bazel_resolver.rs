@@ -4,7 +4,6 @@
 use std::{
     io::Write,
     path::{Path, PathBuf},
-    str::FromStr,
     sync::Mutex,
 };
 
@@ -59,17 +58,11 @@ impl Resolver for IncrementalBazelResolver {
 
         let mut directories = BTreeSet::<PathBuf>::new();
         let mut package_deps = BTreeMap::new();
-        let labels: HashSet<&Label> = request
+        let labels = request
             .targets
             .iter()
-            .filter_map(|target| {
-                // TODO: Consider parameterizing depth
-                match target {
-                    Target::Bazel(label) => Some(label),
-                    _ => unreachable!(),
-                }
-            })
-            .collect();
+            .map(bazel_common::label_of)
+            .collect::<Result<HashSet<&Label>, _>>()?;
 
         #[allow(clippy::redundant_clone)]
         let app = app.clone();
@@ -119,7 +112,7 @@ impl IncrementalBazelResolver {
             let mut dep_labels: BTreeSet<Label> = labels.iter().copied().cloned().collect();
 
             for line in result.lines() {
-                dep_labels.insert(Label::from_str(line)?);
+                dep_labels.insert(Label::parse_lenient(line)?);
             }
 
             info!("'{}' requires {} packages", &query, dep_labels.len());
@@ -178,8 +171,8 @@ impl IncrementalBazelResolver {
 
                 DependencyKey::Path(path) => Some(path.clone()),
 
-                key @ DependencyKey::DummyForTesting(_) => {
-                    panic!("Got dummy dependency key: {:?}", key)
+                key @ (DependencyKey::PantsTarget(_) | DependencyKey::DummyForTesting(_)) => {
+                    panic!("Got non-Bazel dependency key: {:?}", key)
                 }
             })
             .collect();
@@ -209,12 +202,16 @@ impl IncrementalBazelResolver {
             initial_bazel_args.push(format!("--bazelrc={}", OUTLINING_BAZELRC_PATH));
         }
         let (mut cmd, scmd) = SandboxCommand::new(Self::locate_bazel_binary(request), app)?;
+        let bazel_options = &request.options.bazel_options;
         scmd.ensure_success_or_log(
             cmd.args(initial_bazel_args)
+                .args(bazel_options.startup_options())
                 .arg("query")
                 .arg("--query_file")
                 .arg(query_file_path)
                 .args(bazel_args)
+                .args(bazel_options.config_option())
+                .args(bazel_options.query_options())
                 .current_dir(&request.repo),
             SandboxCommandOutput::Stderr,
         )?;
@@ -266,13 +263,13 @@ impl IncrementalBazelResolver {
                     location: _,
                     elements,
                 }) => {
-                    let target_label: Label = name.parse()?;
+                    let target_label = Label::parse_lenient(&name)?;
 
                     let mut rule_inputs = BTreeSet::new();
                     for rule_element in elements {
                         match rule_element {
                             RuleElement::RuleInput { name } => {
-                                let label: Label = name.parse()?;
+                                let label = Label::parse_lenient(&name)?;
                                 rule_inputs.insert(DependencyKey::BazelPackage(label));
                             }
 
@@ -298,7 +295,7 @@ impl IncrementalBazelResolver {
 
                 QueryElement::SourceFile { name, body: () }
                 | QueryElement::GeneratedFile { name, body: () } => {
-                    let target_label: Label = name.parse()?;
+                    let target_label = Label::parse_lenient(&name)?;
                     let key = DependencyKey::BazelPackage(target_label);
 
                     // Create an entry for this package if it doesn't exist. We
@@ -376,7 +373,8 @@ impl IncrementalBazelResolver {
                         path_components: _,
                         target_name: _,
                     })
-                    | DependencyKey::Path(_) => {
+                    | DependencyKey::Path(_)
+                    | DependencyKey::PantsTarget(_) => {
                         // None of these could have been associated with a
                         // `//...` pattern inside the repository itself.
                     }
@@ -398,3 +396,88 @@ impl IncrementalBazelResolver {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::target::TargetError;
+
+    use super::*;
+
+    #[test]
+    fn bazel_options_reach_the_command_line() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let args_path = dir.path().join("args");
+
+        // A stand-in for the in-repo Bazel wrapper that records its arguments.
+        let wrapper_path = dir.path().join("bazel");
+        std::fs::write(
+            &wrapper_path,
+            format!(
+                "#!/bin/sh\nfor arg in \"$@\"; do echo \"$arg\"; done > {}\n",
+                args_path.display()
+            ),
+        )?;
+        std::fs::set_permissions(&wrapper_path, std::fs::Permissions::from_mode(0o755))?;
+
+        let request = ResolutionRequest {
+            repo: dir.path().to_owned(),
+            targets: Default::default(),
+            options: ResolutionOptions {
+                bazel_options: BazelOptions::new(
+                    vec![String::from("--output_base=/tmp/focus_output_base")],
+                    vec![String::from("--noshow_progress")],
+                )?
+                .with_config(Some(String::from("ci")))?,
+                ..Default::default()
+            },
+        };
+        IncrementalBazelResolver::run_bazel_query(
+            app.clone(),
+            &request,
+            &["--output=xml"],
+            "//...",
+        )?;
+
+        let args = std::fs::read_to_string(&args_path)?;
+        let args: Vec<&str> = args.lines().collect();
+        assert_eq!(args[0], "--output_base=/tmp/focus_output_base");
+        assert_eq!(args[1], "query");
+        assert_eq!(args[2], "--query_file");
+        assert_eq!(
+            &args[4..],
+            &["--output=xml", "--config=ci", "--noshow_progress"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn misrouted_targets_are_errors() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let request = ResolutionRequest {
+            repo: dir.path().to_owned(),
+            targets: TargetSet::from([
+                Target::try_from("bazel://foo:bar")?,
+                Target::try_from("directory:foo")?,
+            ]),
+            options: Default::default(),
+        };
+
+        let error = IncrementalBazelResolver::new(dir.path())
+            .resolve(&request, &CacheOptions::default(), app)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TargetError>(),
+            Some(&TargetError::UnexpectedType {
+                expected: TargetTypes::Bazel,
+                target: String::from("directory:foo"),
+            })
+        );
+
+        Ok(())
+    }
+}
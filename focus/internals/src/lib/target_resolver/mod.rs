@@ -6,14 +6,16 @@ mod bazel_de;
 mod directory_resolver;
 mod incremental_bazel_resolver;
 mod oneshot_bazel_resolver;
+mod pants_resolver;
+mod resolution_cache;
 
 use focus_util::app::App;
 
 use crate::{
     index::{DependencyKey, DependencyValue},
-    target::{Target, TargetSet},
+    target::{Target, TargetSet, TargetSetExt, TargetTypes},
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::{
     collections::{BTreeMap, BTreeSet, HashSet},
     path::{Path, PathBuf},
@@ -21,8 +23,11 @@ use std::{
 };
 
 pub(crate) use self::{
-    directory_resolver::DirectoryResolver, incremental_bazel_resolver::IncrementalBazelResolver,
+    directory_resolver::{expand_directory_globs, DirectoryResolver},
+    incremental_bazel_resolver::IncrementalBazelResolver,
     oneshot_bazel_resolver::OneShotBazelResolver,
+    pants_resolver::PantsResolver,
+    resolution_cache::{resolution_cache_key, ResolutionCache},
 };
 
 /// Directs the strategy to resolve Bazel targets.
@@ -35,16 +40,92 @@ pub enum BazelResolutionStrategy {
     OneShot,
 }
 
+/// Additional options passed to Bazel when resolving targets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BazelOptions {
+    startup_options: Vec<String>,
+    query_options: Vec<String>,
+    config: Option<String>,
+}
+
+impl BazelOptions {
+    /// Create options from startup options (placed before the `query` command) and query options (placed after our own query options, so they take precedence). Each must be a long flag such as `--output_base=/tmp/x` or `--noshow_progress`.
+    pub fn new(startup_options: Vec<String>, query_options: Vec<String>) -> Result<Self> {
+        for option in startup_options.iter().chain(query_options.iter()) {
+            Self::validate(option)?;
+        }
+        Ok(Self {
+            startup_options,
+            query_options,
+            config: None,
+        })
+    }
+
+    /// Resolve under the named Bazel config (as in `--config=<NAME>`). Configs can change the build graph, so the config also contributes to index hashes.
+    pub fn with_config(self, config: Option<String>) -> Result<Self> {
+        if let Some(config) = &config {
+            if config.is_empty()
+                || !config
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            {
+                bail!("Bazel config {:?} is not a well-formed config name", config);
+            }
+        }
+        Ok(Self { config, ..self })
+    }
+
+    fn validate(option: &str) -> Result<()> {
+        let name = match option.strip_prefix("--") {
+            Some(rest) => rest.split_once('=').map_or(rest, |(name, _)| name),
+            None => bail!("Bazel option {:?} must start with `--`", option),
+        };
+        if name.is_empty()
+            || !name.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            bail!("Bazel option {:?} is not a well-formed flag", option);
+        }
+        Ok(())
+    }
+
+    /// Options passed to Bazel before the command.
+    pub fn startup_options(&self) -> &[String] {
+        &self.startup_options
+    }
+
+    /// Options passed to `bazel query`.
+    pub fn query_options(&self) -> &[String] {
+        &self.query_options
+    }
+
+    /// The Bazel config to resolve under, if any.
+    pub fn config(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    /// The `--config` option selecting [`Self::config`], if any.
+    pub fn config_option(&self) -> Option<String> {
+        self.config().map(|config| format!("--config={}", config))
+    }
+}
+
 /// A set of options guiding resolution.
 #[derive(Clone, Debug)]
 pub struct ResolutionOptions {
     pub(crate) bazel_resolution_strategy: BazelResolutionStrategy,
+    pub(crate) bazel_options: BazelOptions,
+
+    /// Fail instead of warning when a directory target does not exist.
+    pub(crate) strict_directories: bool,
 }
 
 impl Default for ResolutionOptions {
     fn default() -> Self {
         Self {
             bazel_resolution_strategy: BazelResolutionStrategy::Incremental,
+            bazel_options: Default::default(),
+            strict_directories: false,
         }
     }
 }
@@ -133,6 +214,7 @@ pub struct RoutingResolver {
     incremental_bazel_resolver: IncrementalBazelResolver,
     oneshot_bazel_resolver: OneShotBazelResolver,
     directory_resolver: DirectoryResolver,
+    pants_resolver: PantsResolver,
 }
 
 impl Resolver for RoutingResolver {
@@ -141,6 +223,7 @@ impl Resolver for RoutingResolver {
             incremental_bazel_resolver: IncrementalBazelResolver::new(cache_root),
             oneshot_bazel_resolver: OneShotBazelResolver::new(cache_root),
             directory_resolver: DirectoryResolver::new(cache_root),
+            pants_resolver: PantsResolver::new(cache_root),
         }
     }
 
@@ -153,37 +236,35 @@ impl Resolver for RoutingResolver {
         use rayon::prelude::*;
 
         let subrequests = {
-            let mut bazel_targets = HashSet::new();
-            let mut directory_targets = HashSet::new();
-            for target in request.targets.iter().cloned() {
-                match target {
-                    target @ Target::Bazel(_) => {
-                        bazel_targets.insert(target);
-                    }
-                    target @ Target::Directory(_) => {
-                        directory_targets.insert(target);
-                    }
-                }
-            }
-
-            let bazel_subrequest = ResolutionRequest {
-                targets: bazel_targets,
-                ..request.clone()
-            };
-            let directory_subrequest = ResolutionRequest {
-                targets: directory_targets,
+            let (labels, directories, pants_addresses) = request.targets.partition_by_type();
+            let subrequest = |targets: TargetSet| ResolutionRequest {
+                targets,
                 ..request.clone()
             };
-            vec![bazel_subrequest, directory_subrequest]
+            vec![
+                (
+                    TargetTypes::Bazel,
+                    subrequest(labels.into_iter().map(Target::Bazel).collect()),
+                ),
+                (
+                    TargetTypes::Directory,
+                    subrequest(directories.into_iter().map(Target::Directory).collect()),
+                ),
+                (
+                    TargetTypes::Pants,
+                    subrequest(pants_addresses.into_iter().map(Target::Pants).collect()),
+                ),
+            ]
         };
 
         subrequests
             .par_iter()
-            .map(|subrequest| {
+            .filter(|(_, subrequest)| !subrequest.targets.is_empty())
+            .map(|(target_type, subrequest)| {
                 let app_clone = app.clone();
 
-                match subrequest.targets.iter().next() {
-                    Some(Target::Bazel(_)) => {
+                match target_type {
+                    TargetTypes::Bazel => {
                         match subrequest.options.bazel_resolution_strategy {
                             BazelResolutionStrategy::Incremental => self
                                 .incremental_bazel_resolver
@@ -193,11 +274,14 @@ impl Resolver for RoutingResolver {
                                 .resolve(subrequest, cache_options, app_clone),
                         }
                     }
-                    Some(Target::Directory(_)) => {
+                    TargetTypes::Directory => {
                         self.directory_resolver
                             .resolve(subrequest, cache_options, app_clone)
                     }
-                    None => Ok(Default::default()),
+                    TargetTypes::Pants => {
+                        self.pants_resolver
+                            .resolve(subrequest, cache_options, app_clone)
+                    }
                 }
             })
             .try_reduce(ResolutionResult::new, |mut acc, result| {
@@ -207,3 +291,31 @@ impl Resolver for RoutingResolver {
             .context("Resolving targets failed")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bazel_options_must_be_well_formed_flags() {
+        assert!(BazelOptions::new(
+            vec![String::from("--output_base=/tmp/a b")],
+            vec![String::from("--noshow_progress")],
+        )
+        .is_ok());
+        assert!(BazelOptions::new(vec![String::from("output_base=/tmp")], vec![]).is_err());
+        assert!(BazelOptions::new(vec![], vec![String::from("-k")]).is_err());
+        assert!(BazelOptions::new(vec![], vec![String::from("--")]).is_err());
+        assert!(BazelOptions::new(vec![], vec![String::from("--=x")]).is_err());
+        assert!(BazelOptions::new(vec![], vec![String::from("--keep going")]).is_err());
+        assert!(BazelOptions::default()
+            .with_config(Some(String::from("ci")))
+            .is_ok());
+        assert!(BazelOptions::default()
+            .with_config(Some(String::from("ci --keep_going")))
+            .is_err());
+        assert!(BazelOptions::default()
+            .with_config(Some(String::new()))
+            .is_err());
+    }
+}
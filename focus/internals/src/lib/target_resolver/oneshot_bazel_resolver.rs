@@ -62,17 +62,11 @@ impl Resolver for OneShotBazelResolver {
         #[allow(clippy::redundant_clone)]
         let app = app.clone();
 
-        let labels: HashSet<&Label> = request
+        let labels = request
             .targets
             .iter()
-            .filter_map(|target| {
-                // TODO: Consider parameterizing depth
-                match target {
-                    Target::Bazel(label) => Some(label),
-                    _ => unreachable!(),
-                }
-            })
-            .collect();
+            .map(bazel_common::label_of)
+            .collect::<Result<HashSet<&Label>, _>>()?;
 
         let query = format!(
             "deps({0}) union kind(rule, filter('^//', buildfiles(deps({0}))))",
@@ -115,14 +109,18 @@ impl OneShotBazelResolver {
             initial_bazel_args.push(format!("--bazelrc={}", OUTLINING_BAZELRC_PATH));
         }
         let (mut cmd, scmd) = SandboxCommand::new(Self::locate_bazel_binary(request), app)?;
+        let bazel_options = &request.options.bazel_options;
         scmd.ensure_exit_with_status_or_log(
             cmd.args(initial_bazel_args)
+                .args(bazel_options.startup_options())
                 .arg("query")
                 .arg("--output=package")
                 .arg("--order_output=no")
                 .arg("--noimplicit_deps")
                 .arg("--query_file")
                 .arg(query_file_path)
+                .args(bazel_options.config_option())
+                .args(bazel_options.query_options())
                 .current_dir(&request.repo),
             SandboxCommandOutput::Stderr,
             &[0, 3],
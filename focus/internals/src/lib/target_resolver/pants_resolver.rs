@@ -0,0 +1,193 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{bail, Result};
+use focus_util::sandbox_command::{SandboxCommand, SandboxCommandOutput};
+use tracing::{debug, info};
+
+use crate::target::{pants_address_path, TargetError};
+
+use super::*;
+
+/// Resolves Pants targets to the directories of their transitive dependencies
+pub struct PantsResolver {
+    mutex: Mutex<()>,
+}
+
+impl PantsResolver {
+    fn locate_pants_binary(request: &ResolutionRequest) -> PathBuf {
+        if request.repo.join("pants").is_file() {
+            PathBuf::from("./pants")
+        } else {
+            PathBuf::from("pants")
+        }
+    }
+
+    /// The address of `target`, which this resolver must only be given Pants targets for.
+    fn address_of(target: &Target) -> Result<&str, TargetError> {
+        target
+            .as_pants()
+            .ok_or_else(|| TargetError::UnexpectedType {
+                expected: TargetTypes::Pants,
+                target: target.to_string(),
+            })
+    }
+
+    /// The directory to materialize for the target at `address`. Addresses of files, like `src/python/foo/bar.py:lib`, resolve to the directory containing the file. Targets at the root of the repository resolve to nothing since the top level is always materialized.
+    fn directory_of(repo: &Path, address: &str) -> Option<PathBuf> {
+        let path = pants_address_path(address);
+        let directory = if repo.join(&path).is_dir() || path.extension().is_none() {
+            path
+        } else {
+            path.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        Some(directory).filter(|directory| !directory.as_os_str().is_empty())
+    }
+
+    fn run_pants_dependencies(
+        app: Arc<App>,
+        request: &ResolutionRequest,
+        address: &str,
+    ) -> Result<Vec<String>> {
+        let (mut cmd, scmd) = SandboxCommand::new(Self::locate_pants_binary(request), app)?;
+        scmd.ensure_success_or_log(
+            cmd.arg("dependencies")
+                .arg("--transitive")
+                .arg(address)
+                .current_dir(&request.repo),
+            SandboxCommandOutput::Stderr,
+        )?;
+
+        let mut raw_result = String::new();
+        scmd.read_to_string(SandboxCommandOutput::Stdout, &mut raw_result)?;
+        debug!(?address, ?raw_result, "Pants returned dependencies");
+        Ok(raw_result
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+    }
+}
+
+impl Resolver for PantsResolver {
+    /// Pants keeps its own caches, so nothing is stored under `_cache_root`.
+    fn new(_cache_root: &Path) -> Self {
+        Self {
+            mutex: Mutex::new(()),
+        }
+    }
+
+    fn resolve(
+        &self,
+        request: &ResolutionRequest,
+        _cache_options: &CacheOptions,
+        app: Arc<App>,
+    ) -> Result<ResolutionResult> {
+        // Pants does not allow concurrent runs in the same build root.
+        let lock = self.mutex.lock();
+        if let Err(e) = lock {
+            bail!("Failed to lock mutex: {}", e);
+        }
+
+        let addresses = request
+            .targets
+            .iter()
+            .map(Self::address_of)
+            .collect::<Result<Vec<&str>, _>>()?;
+        let mut result = ResolutionResult::new();
+        for address in addresses {
+            let dependencies = Self::run_pants_dependencies(app.clone(), request, address)
+                .with_context(|| format!("Resolving Pants target {}", address))?;
+            let directories: BTreeSet<PathBuf> = std::iter::once(address)
+                .chain(dependencies.iter().map(String::as_str))
+                .filter_map(|address| Self::directory_of(&request.repo, address))
+                .collect();
+            info!("'{}' requires {} directories", address, directories.len());
+
+            result.package_deps.insert(
+                DependencyKey::PantsTarget(address.to_owned()),
+                DependencyValue::PackageInfo {
+                    deps: directories
+                        .iter()
+                        .cloned()
+                        .map(DependencyKey::Path)
+                        .collect(),
+                },
+            );
+            result.paths.extend(directories);
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_resolve_to_directories() -> Result<()> {
+        let repo = tempfile::tempdir()?;
+        std::fs::create_dir_all(repo.path().join("src/python/foo.d"))?;
+
+        let directory_of = |address| PantsResolver::directory_of(repo.path(), address);
+        assert_eq!(
+            directory_of("src/python/foo:bar"),
+            Some(PathBuf::from("src/python/foo"))
+        );
+        assert_eq!(
+            directory_of("src/python/foo::"),
+            Some(PathBuf::from("src/python/foo"))
+        );
+        assert_eq!(
+            directory_of("src/python/foo/bar.py:lib"),
+            Some(PathBuf::from("src/python/foo"))
+        );
+        assert_eq!(
+            directory_of("src/python/foo/bar.py"),
+            Some(PathBuf::from("src/python/foo"))
+        );
+        assert_eq!(
+            directory_of("3rdparty/python:reqs#requests"),
+            Some(PathBuf::from("3rdparty/python"))
+        );
+        assert_eq!(
+            directory_of("src/python/foo.d:lib"),
+            Some(PathBuf::from("src/python/foo.d"))
+        );
+        assert_eq!(directory_of("//:reqs#requests"), None);
+        assert_eq!(directory_of("setup.py"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn misrouted_targets_are_errors() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let repo = tempfile::tempdir()?;
+        let request = ResolutionRequest {
+            repo: repo.path().to_owned(),
+            targets: TargetSet::from([Target::try_from("directory:src/python/foo")?]),
+            options: Default::default(),
+        };
+
+        let error = PantsResolver::new(repo.path())
+            .resolve(&request, &CacheOptions::default(), app)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TargetError>(),
+            Some(&TargetError::UnexpectedType {
+                expected: TargetTypes::Pants,
+                target: String::from("directory:src/python/foo"),
+            })
+        );
+
+        Ok(())
+    }
+}
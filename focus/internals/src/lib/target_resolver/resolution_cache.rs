@@ -0,0 +1,530 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt::Write,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::index::{content_hash, content_hash_version, HashContext};
+
+use super::*;
+
+/// Mixed into every cache key. Update this value when resolution changes in a way that invalidates stored results.
+const VERSION: usize = 2;
+
+/// The number of results kept in the cache. The least recently used results are evicted beyond this.
+const MAX_ENTRIES: usize = 64;
+
+/// A digest of the inputs to resolving `request` other than the contents of the repository: the options and the targets. The keys of results for the same request begin with it.
+fn request_digest(request: &ResolutionRequest) -> Result<String> {
+    let mut buf = String::new();
+    let ResolutionOptions {
+        bazel_resolution_strategy,
+        bazel_options,
+        strict_directories,
+    } = &request.options;
+    writeln!(
+        &mut buf,
+        "ResolutionV{VERSION}(content_hash_version={}, {bazel_resolution_strategy:?}, {bazel_options:?}, strict_directories={strict_directories})",
+        content_hash_version()
+    )?;
+
+    let mut targets: Vec<String> = request.targets.iter().map(Target::to_string).collect();
+    targets.sort();
+    for target in targets {
+        writeln!(&mut buf, "target {target}")?;
+    }
+
+    Ok(git2::Oid::hash_object(git2::ObjectType::Blob, buf.as_bytes())?.to_string())
+}
+
+/// Compute the key under which the result of resolving `request` in the tree of `ctx` is cached: the [`request_digest`] of the request followed by a digest of the content hashes of the requested targets, as computed for the index. Any change to the options, the targets, or the build files and directories the targets name yields a different key. Changes to the dependencies of the targets are caught when the result is read instead (see [`ResolutionCache::get`]).
+pub(crate) fn resolution_cache_key(
+    ctx: &HashContext,
+    request: &ResolutionRequest,
+) -> Result<String> {
+    let mut keys: Vec<DependencyKey> = request
+        .targets
+        .iter()
+        .cloned()
+        .map(DependencyKey::from)
+        .collect();
+    keys.sort();
+
+    let mut buf = String::new();
+    for key in keys {
+        writeln!(&mut buf, "{:?} {}", key, content_hash(ctx, &key)?)?;
+    }
+    let content_digest = git2::Oid::hash_object(git2::ObjectType::Blob, buf.as_bytes())?;
+    Ok(format!("{}-{}", request_digest(request)?, content_digest))
+}
+
+/// The paths a request resolved to, stored with the content hashes of the dependency keys they were derived from.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResolution {
+    /// The commit the targets were resolved at.
+    commit_id: String,
+
+    /// The Bazel config the content hashes were computed under.
+    bazel_config: Option<String>,
+
+    /// The content hash of every dependency key the paths were derived from, at the time they were resolved.
+    content_hashes: Vec<(DependencyKey, String)>,
+
+    paths: BTreeSet<PathBuf>,
+}
+
+impl CachedResolution {
+    /// Whether none of the dependency keys the paths were derived from changed in `ctx`.
+    fn is_current(&self, ctx: &HashContext) -> Result<bool> {
+        for (key, hash) in self.content_hashes.iter() {
+            if content_hash(ctx, key)?.to_string() != *hash {
+                debug!(?key, "Cached resolution result is stale");
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// The paths that requests resolved to, stored on disk one file per key, evicting the least recently used beyond a fixed number of entries. A stored result is only used while the dependency keys it was derived from have the same content hashes.
+pub(crate) struct ResolutionCache {
+    dir: PathBuf,
+    max_entries: usize,
+}
+
+impl ResolutionCache {
+    /// The cache stored in `dir`.
+    pub(crate) fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+            max_entries: MAX_ENTRIES,
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Returns the paths stored under `key` if none of the dependency keys they were derived from changed in `ctx`, marking the entry as recently used.
+    pub(crate) fn get(&self, ctx: &HashContext, key: &str) -> Result<Option<BTreeSet<PathBuf>>> {
+        let entry = match self.read(key)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if !entry.is_current(ctx)? {
+            return Ok(None);
+        }
+        self.mark_used(key);
+        Ok(Some(entry.paths))
+    }
+
+    /// Returns the paths most recently stored for `request` along with the commit they were resolved at, whatever content they were resolved against. The entry is marked as recently used.
+    pub(crate) fn latest(
+        &self,
+        request: &ResolutionRequest,
+    ) -> Result<Option<(git2::Oid, BTreeSet<PathBuf>)>> {
+        let prefix = format!("{}-", request_digest(request)?);
+        let latest_key = self
+            .keys()?
+            .into_iter()
+            .filter(|(_, key)| key.starts_with(&prefix))
+            .max()
+            .map(|(_, key)| key);
+        let key = match latest_key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let entry = match self.read(&key)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let commit_id = git2::Oid::from_str(&entry.commit_id)
+            .context("Parsing the commit the cached result was resolved at")?;
+        self.mark_used(&key);
+        Ok(Some((commit_id, entry.paths)))
+    }
+
+    fn mark_used(&self, key: &str) {
+        if let Err(e) = filetime::set_file_mtime(self.entry_path(key), FileTime::now()) {
+            debug!(?e, %key, "Failed to mark cached resolution result as used");
+        }
+    }
+
+    fn read(&self, key: &str) -> Result<Option<CachedResolution>> {
+        let path = self.entry_path(key);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Opening {}", path.display()));
+            }
+        };
+        let entry: CachedResolution = serde_json::from_reader(BufReader::new(&file))
+            .with_context(|| format!("Reading cached resolution result {}", path.display()))?;
+        Ok(Some(entry))
+    }
+
+    /// Store the `paths` resolved at `commit_id` under `key`, along with the content hashes in `ctx` of `dependency_keys`, the keys they were derived from. Then evict the least recently used results beyond the limit.
+    pub(crate) fn put(
+        &self,
+        ctx: &HashContext,
+        key: &str,
+        commit_id: git2::Oid,
+        dependency_keys: impl IntoIterator<Item = DependencyKey>,
+        paths: &BTreeSet<PathBuf>,
+    ) -> Result<()> {
+        let content_hashes = dependency_keys
+            .into_iter()
+            .map(|key| {
+                let hash = content_hash(ctx, &key)?.to_string();
+                Ok((key, hash))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.write(
+            key,
+            &CachedResolution {
+                commit_id: commit_id.to_string(),
+                bazel_config: ctx.bazel_config().map(String::from),
+                content_hashes,
+                paths: paths.clone(),
+            },
+        )?;
+        self.evict()
+    }
+
+    fn write(&self, key: &str, entry: &CachedResolution) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Creating {}", self.dir.display()))?;
+        // Write to a temporary file and rename it so that readers never see a partial entry.
+        let path = self.entry_path(key);
+        let mut temp = tempfile::NamedTempFile::new_in(&self.dir)
+            .context("Creating a temporary file for the resolution result")?;
+        serde_json::to_writer(BufWriter::new(temp.as_file_mut()), entry)
+            .context("Writing the resolution result")?;
+        temp.persist(&path)
+            .with_context(|| format!("Moving the resolution result to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Copy the results in `source` that are not cached already and are still current in `tree`, returning how many were copied. Unreadable results are skipped.
+    pub(crate) fn seed_from(
+        &self,
+        source: &ResolutionCache,
+        repo: &git2::Repository,
+        tree: &git2::Tree,
+    ) -> Result<usize> {
+        if !source.dir.is_dir() {
+            bail!(
+                "{} does not contain a resolution cache",
+                source.dir.display()
+            );
+        }
+
+        // Results are checked under the Bazel config they were resolved under.
+        let mut contexts: BTreeMap<Option<String>, HashContext> = BTreeMap::new();
+        let mut copied = 0;
+        for (_, key) in source.keys()? {
+            if self.entry_path(&key).exists() {
+                continue;
+            }
+            let entry = match source.read(&key) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(?e, %key, "Skipping unreadable cached resolution result");
+                    continue;
+                }
+            };
+            let ctx = match contexts.entry(entry.bazel_config.clone()) {
+                Entry::Occupied(ctx) => ctx.into_mut(),
+                Entry::Vacant(vacant) => vacant.insert(
+                    HashContext::new(repo, tree)?.with_bazel_config(entry.bazel_config.as_deref()),
+                ),
+            };
+            if !entry.is_current(ctx)? {
+                continue;
+            }
+            self.write(&key, &entry)?;
+            copied += 1;
+        }
+        if copied > 0 {
+            self.evict()?;
+        }
+        Ok(copied)
+    }
+
+    /// The keys of the stored results with the time each was last used.
+    fn keys(&self) -> Result<Vec<(SystemTime, String)>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Listing {}", self.dir.display())),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let key = match file_name
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(".json"))
+            {
+                Some(key) => key.to_owned(),
+                None => continue,
+            };
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            keys.push((modified, key));
+        }
+        Ok(keys)
+    }
+
+    fn evict(&self) -> Result<()> {
+        let mut keys = self.keys()?;
+        if keys.len() <= self.max_entries {
+            return Ok(());
+        }
+
+        keys.sort();
+        let excess = keys.len() - self.max_entries;
+        for (_, key) in keys.into_iter().take(excess) {
+            let path = self.entry_path(&key);
+            debug!(?path, "Evicting cached resolution result");
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!(?e, ?path, "Failed to evict cached resolution result");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use focus_testing::ScratchGitRepo;
+
+    use super::*;
+
+    fn paths(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    fn fixture(dir: &Path) -> Result<(ScratchGitRepo, git2::Repository)> {
+        let app = Arc::new(App::new_for_testing()?);
+        let scratch_repo = ScratchGitRepo::new_copied_fixture(
+            app.git_binary().clone(),
+            Path::new("bazel_java_example"),
+            &dir.join("dense"),
+            "main",
+        )?;
+        let repo = git2::Repository::open(scratch_repo.path())?;
+        Ok((scratch_repo, repo))
+    }
+
+    fn tree_at(repo: &git2::Repository, commit_id: git2::Oid) -> Result<git2::Tree> {
+        Ok(repo.find_commit(commit_id)?.tree()?)
+    }
+
+    #[test]
+    fn least_recently_used_results_are_evicted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (_scratch_repo, repo) = fixture(dir.path())?;
+        let head = repo.head()?.peel_to_commit()?.id();
+        let tree = tree_at(&repo, head)?;
+        let ctx = HashContext::new(&repo, &tree)?;
+        let cache = ResolutionCache {
+            dir: dir.path().join("resolution"),
+            max_entries: 2,
+        };
+        assert!(cache.get(&ctx, "a")?.is_none());
+
+        let set_age = |key: &str, seconds_ago: u64| -> Result<()> {
+            let mtime = SystemTime::now() - Duration::from_secs(seconds_ago);
+            filetime::set_file_mtime(cache.entry_path(key), FileTime::from_system_time(mtime))?;
+            Ok(())
+        };
+        cache.put(&ctx, "a", head, [], &paths(&["library_a"]))?;
+        set_age("a", 30)?;
+        cache.put(&ctx, "b", head, [], &paths(&["library_b"]))?;
+        set_age("b", 20)?;
+
+        // Using `a` makes `b` the least recently used.
+        assert_eq!(cache.get(&ctx, "a")?, Some(paths(&["library_a"])));
+        cache.put(&ctx, "c", head, [], &paths(&["library_c"]))?;
+
+        assert!(cache.get(&ctx, "a")?.is_some());
+        assert!(cache.get(&ctx, "b")?.is_none());
+        assert!(cache.get(&ctx, "c")?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn keys_cover_the_options_and_the_content_of_the_targets() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (scratch_repo, repo) = fixture(dir.path())?;
+        let request = ResolutionRequest {
+            repo: scratch_repo.path().to_owned(),
+            targets: [
+                Target::try_from("bazel://library_a/...")?,
+                Target::try_from("directory:library_b")?,
+            ]
+            .into_iter()
+            .collect(),
+            options: Default::default(),
+        };
+        let key_at = |commit_id: git2::Oid, request: &ResolutionRequest| -> Result<String> {
+            let tree = tree_at(&repo, commit_id)?;
+            let ctx = HashContext::new(&repo, &tree)?
+                .with_bazel_config(request.options.bazel_options.config());
+            resolution_cache_key(&ctx, request)
+        };
+        let base = repo.head()?.peel_to_commit()?.id();
+        let base_key = key_at(base, &request)?;
+        assert_eq!(key_at(base, &request)?, base_key);
+
+        let unrelated = scratch_repo.write_and_commit_file("README.md", "Updated", "Docs")?;
+        assert_eq!(key_at(unrelated, &request)?, base_key);
+
+        for options in [
+            ResolutionOptions {
+                strict_directories: true,
+                ..Default::default()
+            },
+            ResolutionOptions {
+                bazel_resolution_strategy: BazelResolutionStrategy::OneShot,
+                ..Default::default()
+            },
+            ResolutionOptions {
+                bazel_options: BazelOptions::default().with_config(Some(String::from("ci")))?,
+                ..Default::default()
+            },
+            ResolutionOptions {
+                bazel_options: BazelOptions::new(vec![], vec![String::from("--keep_going")])?,
+                ..Default::default()
+            },
+        ] {
+            let other_request = ResolutionRequest {
+                options,
+                ..request.clone()
+            };
+            assert_ne!(key_at(unrelated, &other_request)?, base_key);
+        }
+
+        let directory_change =
+            scratch_repo.write_and_commit_file("library_b/notes.txt", "Notes", "Add notes")?;
+        let directory_key = key_at(directory_change, &request)?;
+        assert_ne!(directory_key, base_key);
+
+        let build_change = scratch_repo.write_and_commit_file(
+            "library_a/BUILD",
+            "# Changed\n",
+            "Change a build file",
+        )?;
+        assert_ne!(key_at(build_change, &request)?, directory_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn results_are_used_while_their_dependencies_are_unchanged() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (scratch_repo, repo) = fixture(dir.path())?;
+        let cache = ResolutionCache::new(dir.path().join("resolution"));
+        let request = ResolutionRequest {
+            repo: scratch_repo.path().to_owned(),
+            targets: [Target::try_from("bazel://library_a/...")?]
+                .into_iter()
+                .collect(),
+            options: Default::default(),
+        };
+        let dependency_keys = [DependencyKey::Path(PathBuf::from("library_b"))];
+
+        let base = repo.head()?.peel_to_commit()?.id();
+        let base_tree = tree_at(&repo, base)?;
+        let ctx = HashContext::new(&repo, &base_tree)?;
+        let key = resolution_cache_key(&ctx, &request)?;
+        cache.put(
+            &ctx,
+            &key,
+            base,
+            dependency_keys.clone(),
+            &paths(&["library_a", "library_b"]),
+        )?;
+        assert_eq!(
+            cache.get(&ctx, &key)?,
+            Some(paths(&["library_a", "library_b"]))
+        );
+        assert_eq!(
+            cache.latest(&request)?,
+            Some((base, paths(&["library_a", "library_b"])))
+        );
+
+        // The key is unchanged, but one of the dependencies the result was derived from is not.
+        let dependency_change =
+            scratch_repo.write_and_commit_file("library_b/notes.txt", "Notes", "Add notes")?;
+        let tree = tree_at(&repo, dependency_change)?;
+        let ctx = HashContext::new(&repo, &tree)?;
+        assert_eq!(resolution_cache_key(&ctx, &request)?, key);
+        assert_eq!(cache.get(&ctx, &key)?, None);
+
+        // Results for other requests are not mistaken for the latest.
+        let other_request = ResolutionRequest {
+            targets: [Target::try_from("bazel://library_b/...")?]
+                .into_iter()
+                .collect(),
+            ..request.clone()
+        };
+        assert_eq!(cache.latest(&other_request)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_current_results_are_seeded() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (scratch_repo, repo) = fixture(dir.path())?;
+        let base = repo.head()?.peel_to_commit()?.id();
+        let base_tree = tree_at(&repo, base)?;
+        let ctx = HashContext::new(&repo, &base_tree)?;
+        let source = ResolutionCache::new(dir.path().join("source"));
+        let library_a = [DependencyKey::Path(PathBuf::from("library_a"))];
+        let library_b = [DependencyKey::Path(PathBuf::from("library_b"))];
+        source.put(&ctx, "a", base, library_a, &paths(&["library_a"]))?;
+        source.put(&ctx, "b", base, library_b, &paths(&["library_b"]))?;
+
+        let change =
+            scratch_repo.write_and_commit_file("library_b/notes.txt", "Notes", "Add notes")?;
+        let tree = tree_at(&repo, change)?;
+        let cache = ResolutionCache::new(dir.path().join("cache"));
+        assert_eq!(cache.seed_from(&source, &repo, &tree)?, 1);
+        let ctx = HashContext::new(&repo, &tree)?;
+        assert_eq!(cache.get(&ctx, "a")?, Some(paths(&["library_a"])));
+        assert_eq!(cache.get(&ctx, "b")?, None);
+        // Results that are already cached are not copied again.
+        assert_eq!(cache.seed_from(&source, &repo, &tree)?, 0);
+
+        assert!(cache
+            .seed_from(
+                &ResolutionCache::new(dir.path().join("missing")),
+                &repo,
+                &tree
+            )
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -100,6 +100,19 @@ impl Snapshot {
     }
 }
 
+/// What `Tracker::repair` found besides the entries it always removes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    /// The paths of registered repos that no longer exist on disk.
+    pub missing: Vec<PathBuf>,
+
+    /// Whether the entries for `missing` repos were removed.
+    pub pruned_missing: bool,
+
+    /// Registered repos that exist but have no `.focus` directory.
+    pub without_focus_dir: Vec<PathBuf>,
+}
+
 #[derive(Debug)]
 pub struct Tracker {
     _tempdir: Option<tempfile::TempDir>,
@@ -181,8 +194,8 @@ impl Tracker {
         self.repos_by_uuid_dir().join("regisry.lock")
     }
 
-    // Repair the registry of tracked repositories by checking that symlinks point to canonicalizable destinations and that the configured UUIDs match the inbound link.
-    pub fn repair(&self, app: Arc<App>) -> Result<()> {
+    // Repair the registry of tracked repositories by checking that symlinks point to canonicalizable destinations and that the configured UUIDs match the inbound link. Entries for repos that no longer exist are removed unless `keep_missing` is set.
+    pub fn repair(&self, keep_missing: bool, app: Arc<App>) -> Result<RepairReport> {
         // Hold a repo repair lock.
         let _lock = LockFile::new(&self.repo_registry_lock_path());
 
@@ -191,6 +204,10 @@ impl Tracker {
             .read_dir()
             .with_context(|| format!("Failed reading directory {}", self.directory.display()))?;
 
+        let mut report = RepairReport {
+            pruned_missing: !keep_missing,
+            ..Default::default()
+        };
         for entry in reader {
             match entry {
                 Ok(entry) => {
@@ -213,6 +230,15 @@ impl Tracker {
                                         Uuid::parse_str(utf_file_name.unwrap())
                                             .context("parsing file name as a uuid")?;
 
+                                    if !canonical_path.join(".focus").is_dir() {
+                                        warn!(
+                                            ?entry_path,
+                                            ?canonical_path,
+                                            "Registered repository has no .focus directory"
+                                        );
+                                        report.without_focus_dir.push(canonical_path.clone());
+                                    }
+
                                     let repo = Repo::open(&canonical_path, app.clone())?;
                                     let uuid_from_config =
                                         if let Ok(working_tree) = repo.working_tree() {
@@ -237,8 +263,22 @@ impl Tracker {
                                 }
 
                                 Err(e) => {
-                                    debug!(?entry_path, ?e, "Removing entry: invalid destination",);
-                                    std::fs::remove_file(entry.path())?;
+                                    let target = std::fs::read_link(entry.path())?;
+                                    if !keep_missing {
+                                        debug!(
+                                            ?entry_path,
+                                            ?e,
+                                            "Removing entry: invalid destination",
+                                        );
+                                        std::fs::remove_file(entry.path())?;
+                                    } else {
+                                        debug!(
+                                            ?entry_path,
+                                            ?e,
+                                            "Keeping entry with invalid destination"
+                                        );
+                                    }
+                                    report.missing.push(target);
                                 }
                             }
                         } else {
@@ -256,7 +296,74 @@ impl Tracker {
             }
         }
 
-        Ok(())
+        report.missing.sort();
+        report.without_focus_dir.sort();
+        Ok(report)
+    }
+
+    /// Reconstruct the registry from scratch by searching `root` for focused repos, descending at most `max_depth` levels. Existing entries are discarded first, so this recovers from a registry that is lost or too damaged for `repair`. Returns the paths of the repos that were registered.
+    pub fn rebuild_from_disk(
+        &self,
+        root: &Path,
+        max_depth: usize,
+        app: Arc<App>,
+    ) -> Result<Vec<PathBuf>> {
+        self.ensure_directories_exist()?;
+        let _lock = LockFile::new(&self.repo_registry_lock_path())
+            .context("Locking the repository registry")?;
+
+        let reader = self
+            .repos_by_uuid_dir()
+            .read_dir()
+            .with_context(|| format!("Failed reading directory {}", self.directory.display()))?;
+        for entry in reader {
+            let entry = entry.context("Failed reading directory entry")?;
+            if entry.file_type()?.is_symlink() {
+                debug!(entry_path = ?entry.path(), "Removing entry");
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        let repos = find_focused_repos(root, max_depth)?;
+        for repo in repos.iter() {
+            info!(?repo, "Registering repository");
+            self.ensure_registered(repo, app.clone())
+                .with_context(|| format!("Registering {}", repo.display()))?;
+        }
+
+        Ok(repos)
+    }
+
+    /// Remove the registration of the repo at `repo_directory`, which should be absolute, returning whether it was registered. The repo itself is left in place. Entries are matched by their literal link target as well as their canonical one, so repos that have already been deleted can be deregistered.
+    pub fn deregister(&self, repo_directory: &Path) -> Result<bool> {
+        let _lock = LockFile::new(&self.repo_registry_lock_path())
+            .context("Locking the repository registry")?;
+
+        let canonical_repo_dir = repo_directory.canonicalize().ok();
+        let reader = self
+            .repos_by_uuid_dir()
+            .read_dir()
+            .with_context(|| format!("Failed reading directory {}", self.directory.display()))?;
+        let mut removed = false;
+        for entry in reader {
+            let entry = entry.context("Failed reading directory entry")?;
+            if !entry.file_type()?.is_symlink() {
+                continue;
+            }
+            let target = std::fs::read_link(entry.path())
+                .with_context(|| format!("Reading symlink {}", entry.path().display()))?;
+            let matches = target == repo_directory
+                || (canonical_repo_dir.is_some()
+                    && canonicalize(entry.path()).ok() == canonical_repo_dir);
+            if matches {
+                debug!(entry_path = ?entry.path(), "Removing entry");
+                std::fs::remove_file(entry.path())
+                    .with_context(|| format!("Removing {}", entry.path().display()))?;
+                removed = true;
+            }
+        }
+
+        Ok(removed)
     }
 
     // Scan the directory containing repos labeled by UUID.
@@ -327,3 +434,150 @@ impl Tracker {
         self.repos_dir().join("by-uuid")
     }
 }
+
+/// Find focused repos (directories containing both `.git` and `.focus`) at most `max_depth` levels below `root`. Hidden directories and the contents of repos that were found are not searched, and symlinks are not followed.
+fn find_focused_repos(root: &Path, max_depth: usize) -> Result<Vec<PathBuf>> {
+    let root = canonicalize(root).with_context(|| format!("Resolving {}", root.display()))?;
+    let mut repos = Vec::new();
+    let mut walker = walkdir::WalkDir::new(&root)
+        .max_depth(max_depth)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!(?e, "Skipping unreadable entry");
+                continue;
+            }
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let is_hidden = entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.');
+        if is_hidden {
+            walker.skip_current_dir();
+            continue;
+        }
+
+        let path = entry.path();
+        if path.join(".focus").is_dir() && path.join(".git").exists() {
+            repos.push(path.to_owned());
+            walker.skip_current_dir();
+        }
+    }
+
+    Ok(repos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_focused_repos_respects_depth_and_skips_hidden_directories() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let make_repo = |relative: &str| -> Result<PathBuf> {
+            let path = root.path().join(relative);
+            std::fs::create_dir_all(path.join(".git"))?;
+            std::fs::create_dir_all(path.join(".focus"))?;
+            Ok(canonicalize(path)?)
+        };
+        let shallow = make_repo("a")?;
+        let deep = make_repo("b/c/d")?;
+        make_repo(".hidden/e")?;
+        // Nested within a repo that was found.
+        make_repo("a/f")?;
+        // Not focused.
+        std::fs::create_dir_all(root.path().join("g").join(".git"))?;
+
+        assert_eq!(
+            find_focused_repos(root.path(), 3)?,
+            vec![shallow.clone(), deep.clone()]
+        );
+        assert_eq!(find_focused_repos(root.path(), 2)?, vec![shallow]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deregister_removes_only_the_matching_entry() -> Result<()> {
+        let tracker = Tracker::for_testing()?;
+        tracker.ensure_directories_exist()?;
+        let root = tempfile::tempdir()?;
+        let kept = root.path().join("kept");
+        let removed = root.path().join("removed");
+        let deleted = root.path().join("deleted");
+        for (path, uuid) in [
+            (&kept, Uuid::new_v4()),
+            (&removed, Uuid::new_v4()),
+            (&deleted, Uuid::new_v4()),
+        ] {
+            std::fs::create_dir(path)?;
+            std::os::unix::fs::symlink(path, tracker.repos_by_uuid_dir().join(uuid.to_string()))?;
+        }
+        std::fs::remove_dir(&deleted)?;
+
+        assert!(!tracker.deregister(&root.path().join("unknown"))?);
+        assert!(tracker.deregister(&removed)?);
+        assert!(!tracker.deregister(&removed)?);
+        // Repos deleted before being deregistered can still be removed.
+        assert!(tracker.deregister(&deleted)?);
+
+        let remaining: Vec<PathBuf> = tracker
+            .scan()?
+            .repos()
+            .iter()
+            .map(|repo| repo.location().to_owned())
+            .collect();
+        assert_eq!(remaining, vec![canonicalize(&kept)?]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn repair_reports_deleted_repos_and_prunes_them_unless_kept() -> Result<()> {
+        let tracker = Tracker::for_testing()?;
+        tracker.ensure_directories_exist()?;
+        let root = tempfile::tempdir()?;
+        let deleted = root.path().join("deleted");
+        let unfocused = root.path().join("unfocused");
+        let mismatched = root.path().join("mismatched");
+        for path in [&deleted, &unfocused, &mismatched] {
+            let uuid = Uuid::new_v4();
+            let repo = git2::Repository::init(path)?;
+            if path != &mismatched {
+                repo.config()?
+                    .set_str(crate::model::repo::UUID_CONFIG_KEY, &uuid.to_string())?;
+            }
+            std::os::unix::fs::symlink(path, tracker.repos_by_uuid_dir().join(uuid.to_string()))?;
+        }
+        std::fs::remove_dir_all(&deleted)?;
+        let entry_count =
+            || -> Result<usize> { Ok(tracker.repos_by_uuid_dir().read_dir()?.count()) };
+        let app = Arc::new(App::new_for_testing()?);
+
+        // Repos without a .focus directory are reported, but their UUIDs are still checked.
+        let report = tracker.repair(true, app.clone())?;
+        assert_eq!(
+            report,
+            RepairReport {
+                missing: vec![deleted.clone()],
+                pruned_missing: false,
+                without_focus_dir: vec![canonicalize(&mismatched)?, canonicalize(&unfocused)?],
+            }
+        );
+        assert_eq!(entry_count()?, 2);
+
+        let report = tracker.repair(false, app.clone())?;
+        assert_eq!(report.missing, vec![deleted]);
+        assert!(report.pruned_missing);
+        assert_eq!(entry_count()?, 1);
+
+        // Nothing is left to prune.
+        assert!(tracker.repair(false, app)?.missing.is_empty());
+
+        Ok(())
+    }
+}
@@ -14,13 +14,23 @@ use std::{
     path::{Path, PathBuf},
     sync::Arc,
 };
-use tracing::{debug, info};
+use tracing::{debug, error, info};
+
+/// State captured before a migration runs, used to undo a failed migration.
+pub trait Checkpoint {
+    fn restore(&self, path: &Path, app: Arc<App>) -> Result<()>;
+}
 
 /// Migration instance should implement this trait.
 pub trait Migration {
     fn id(&self) -> Identifier;
     fn description(&self) -> &str;
     fn upgrade(&self, path: &Path, app: Arc<App>) -> Result<()>;
+
+    /// Capture the state that `upgrade` modifies so that it can be restored if the upgrade fails partway through. Migrations that cannot leave partial changes behind need not override this.
+    fn checkpoint(&self, _path: &Path, _app: Arc<App>) -> Result<Option<Box<dyn Checkpoint>>> {
+        Ok(None)
+    }
 }
 
 pub type Migrations = Vec<Box<dyn Migration>>;
@@ -106,6 +116,11 @@ impl Runner {
         self.migrations.last().map(|m| m.as_ref().id())
     }
 
+    /// The identifier of the most recent migration applied to the repo.
+    pub fn version(&self) -> Identifier {
+        self.manifest.borrow().version.get()
+    }
+
     pub fn is_upgrade_required(&self) -> Result<bool> {
         if let Some(ultimate_version) = self.ultimate_migration() {
             return Ok(self.manifest.borrow().version.get() < ultimate_version);
@@ -114,26 +129,36 @@ impl Runner {
         Ok(false)
     }
 
-    pub fn perform_pending_migrations(&self) -> Result<bool> {
-        // Iterate through migrations. Keep track of the highest one that succeeded. Make sure to mark those that have been performed as we go. If one fails, stop.
-        let previous_version = &self.manifest.borrow().version;
-        for migration in self
-            .migrations
+    /// The migrations that have not been performed yet, in the order they would run.
+    pub fn pending_migrations(&self) -> Vec<&dyn Migration> {
+        let previous_version = self.manifest.borrow().version.get();
+        self.migrations
             .iter()
-            .skip_while(|&m| m.as_ref().id() <= previous_version.get())
-        {
-            let migration = migration.as_ref();
+            .map(|m| m.as_ref())
+            .skip_while(|m| m.id() <= previous_version)
+            .collect()
+    }
+
+    pub fn perform_pending_migrations(&self) -> Result<bool> {
+        // Iterate through migrations, recording each one in the manifest as it succeeds. If one fails, restore the state it captured beforehand and stop, leaving the manifest at the last migration that succeeded.
+        for migration in self.pending_migrations() {
             let identifier = migration.id();
             let description = migration.description();
             info!(%identifier, %description, "Running migration");
-            match migration.upgrade(&self.repo_path, self.app.clone()) {
-                Ok(()) => {
-                    self.manifest.borrow().version.replace(identifier);
-                }
-                Err(e) => {
-                    return Err(e);
-                }
+            let checkpoint = migration
+                .checkpoint(&self.repo_path, self.app.clone())
+                .with_context(|| {
+                    format!(
+                        "Migration {} ({}) could not capture the state of the repo; nothing was changed",
+                        identifier, description
+                    )
+                })?;
+            if let Err(e) = migration.upgrade(&self.repo_path, self.app.clone()) {
+                return Err(self.fail_migration(migration, checkpoint, e));
             }
+            self.manifest.borrow().version.replace(identifier);
+            self.store_manifest()
+                .context("Failed to store the manifest")?;
         }
 
         self.store_manifest()
@@ -141,12 +166,46 @@ impl Runner {
 
         Ok(true)
     }
+
+    /// Undo the partial changes of `migration`, which failed with `error`, and describe how to recover.
+    fn fail_migration(
+        &self,
+        migration: &dyn Migration,
+        checkpoint: Option<Box<dyn Checkpoint>>,
+        error: anyhow::Error,
+    ) -> anyhow::Error {
+        let version = self.manifest.borrow().version.get();
+        let restored = match checkpoint {
+            Some(checkpoint) => checkpoint.restore(&self.repo_path, self.app.clone()),
+            None => Ok(()),
+        };
+        let recovery = match restored {
+            Ok(()) => format!(
+                "The repo was left at version {}. Address the error and run `focus upgrade` again.",
+                version
+            ),
+            Err(restore_error) => {
+                error!(%restore_error, "Failed to restore the repo after a failed migration");
+                format!(
+                    "Restoring the repo also failed ({:#}), so it may be partially migrated past version {}. Address the errors and run `focus upgrade` again, or clone the repo again if that does not succeed.",
+                    restore_error, version
+                )
+            }
+        };
+        error.context(format!(
+            "Migration {} ({}) failed. {}",
+            migration.id(),
+            migration.description(),
+            recovery
+        ))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::{bail, Result};
     use std::path::PathBuf;
+    use std::rc::Rc;
     use tempfile::TempDir;
 
     use super::*;
@@ -255,7 +314,10 @@ mod tests {
         let runner = fixture.new_runner_with_migrations(migrations, app)?;
         assert!(runner.is_upgrade_required()?);
         let error = runner.perform_pending_migrations().unwrap_err();
-        assert_eq!(error.to_string(), "boom");
+        assert_eq!(error.root_cause().to_string(), "boom");
+        assert!(error
+            .to_string()
+            .starts_with("Migration #2 (A migration that fails for use in tests) failed."));
 
         assert!(runner.is_upgrade_required()?);
         Ok(())
@@ -275,6 +337,82 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn pending_migrations_are_listed_in_order_until_performed() -> Result<()> {
+        let app = Arc::from(App::new_for_testing()?);
+        let migrations: Vec<Box<dyn Migration>> = vec![
+            Box::new(FailureMigrationWithOldID {}),
+            Box::new(SuccessfulMigration {}),
+            Box::new(FailingMigration {}),
+        ];
+        let fixture = Fixture::new()?;
+        let runner = fixture.new_runner_with_migrations(migrations, app)?;
+        let pending: Vec<Identifier> = runner.pending_migrations().iter().map(|m| m.id()).collect();
+        assert_eq!(pending, vec![Identifier::Serial(1), Identifier::Serial(2)]);
+        assert!(!fixture.manifest_path.exists());
+
+        assert!(runner.perform_pending_migrations().is_err());
+        let pending: Vec<Identifier> = runner.pending_migrations().iter().map(|m| m.id()).collect();
+        assert_eq!(pending, vec![Identifier::Serial(2)]);
+
+        Ok(())
+    }
+
+    struct RecordingCheckpoint(Rc<Cell<bool>>);
+    impl Checkpoint for RecordingCheckpoint {
+        fn restore(&self, _path: &Path, _app: Arc<App>) -> Result<()> {
+            self.0.set(true);
+            Ok(())
+        }
+    }
+
+    struct FailingMigrationWithCheckpoint(Rc<Cell<bool>>);
+    impl Migration for FailingMigrationWithCheckpoint {
+        fn id(&self) -> Identifier {
+            Identifier::Serial(2)
+        }
+
+        fn description(&self) -> &str {
+            "A migration that fails after capturing a checkpoint"
+        }
+
+        fn upgrade(&self, _path: &Path, _app: Arc<App>) -> Result<()> {
+            bail!("boom")
+        }
+
+        fn checkpoint(&self, _path: &Path, _app: Arc<App>) -> Result<Option<Box<dyn Checkpoint>>> {
+            Ok(Some(Box::new(RecordingCheckpoint(self.0.clone()))))
+        }
+    }
+
+    #[test]
+    fn failed_migration_is_restored_and_earlier_migrations_are_kept() -> Result<()> {
+        let app = Arc::from(App::new_for_testing()?);
+        let restored = Rc::new(Cell::new(false));
+        let fixture = Fixture::new()?;
+        let migrations: Vec<Box<dyn Migration>> = vec![
+            Box::new(SuccessfulMigration {}),
+            Box::new(FailingMigrationWithCheckpoint(restored.clone())),
+        ];
+        let runner = fixture.new_runner_with_migrations(migrations, app.clone())?;
+        let error = runner.perform_pending_migrations().unwrap_err();
+        assert!(restored.get());
+        assert!(error.to_string().contains("left at version #1"));
+
+        // The successful migration was recorded, so only the failed one is pending.
+        let runner = fixture.new_runner_with_migrations(
+            vec![
+                Box::new(SuccessfulMigration {}),
+                Box::new(FailingMigrationWithCheckpoint(restored)),
+            ],
+            app,
+        )?;
+        let pending: Vec<Identifier> = runner.pending_migrations().iter().map(|m| m.id()).collect();
+        assert_eq!(pending, vec![Identifier::Serial(2)]);
+
+        Ok(())
+    }
+
     #[test]
     fn manifest_persists_after_upgrade() -> Result<()> {
         let app = Arc::from(App::new_for_testing()?);
@@ -3,7 +3,7 @@
 
 use super::*;
 use anyhow::Result;
-use focus_internals::model::repo::Repo;
+use focus_internals::model::repo::{Repo, BAZEL_ONE_SHOT_RESOLUTION_CONFIG_KEY};
 
 fn migrations() -> Migrations {
     vec![
@@ -21,10 +21,104 @@ pub fn is_upgrade_required(repo_path: &Path, app: Arc<App>) -> Result<bool> {
     runner_for_repo(repo_path, app).and_then(|runner| runner.is_upgrade_required())
 }
 
+/// The identifier of the most recent migration applied to the repo at `repo_path`.
+pub fn current_version(repo_path: &Path, app: Arc<App>) -> Result<Identifier> {
+    runner_for_repo(repo_path, app).map(|runner| runner.version())
+}
+
+/// The identifiers and descriptions of the migrations that `perform_pending_migrations` would run, in order. Nothing is modified.
+pub fn pending_migrations(repo_path: &Path, app: Arc<App>) -> Result<Vec<(Identifier, String)>> {
+    let runner = runner_for_repo(repo_path, app)?;
+    Ok(runner
+        .pending_migrations()
+        .into_iter()
+        .map(|migration| (migration.id(), migration.description().to_owned()))
+        .collect())
+}
+
 pub fn perform_pending_migrations(repo_path: &Path, app: Arc<App>) -> Result<bool> {
     runner_for_repo(repo_path, app).and_then(|runner| runner.perform_pending_migrations())
 }
 
+/// The contents of a set of files before a migration, where `None` means the file did not exist.
+struct FilesCheckpoint {
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl FilesCheckpoint {
+    fn capture(paths: impl IntoIterator<Item = PathBuf>) -> Result<Self> {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let contents = if path.exists() {
+                    Some(
+                        std::fs::read(&path)
+                            .with_context(|| format!("Reading {}", path.display()))?,
+                    )
+                } else {
+                    None
+                };
+                Ok((path, contents))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { files })
+    }
+}
+
+impl Checkpoint for FilesCheckpoint {
+    fn restore(&self, _path: &Path, _app: Arc<App>) -> Result<()> {
+        for (path, contents) in self.files.iter() {
+            match contents {
+                Some(contents) => std::fs::write(path, contents)
+                    .with_context(|| format!("Restoring {}", path.display()))?,
+                None if path.exists() => std::fs::remove_file(path)
+                    .with_context(|| format!("Removing {}", path.display()))?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value of a key in a repo's local Git config before a migration, where `None` means the key was unset.
+struct GitConfigCheckpoint {
+    key: &'static str,
+    value: Option<String>,
+}
+
+impl GitConfigCheckpoint {
+    fn local_config(path: &Path) -> Result<git2::Config> {
+        git2::Repository::open(path)
+            .with_context(|| format!("Opening repo {}", path.display()))?
+            .config()?
+            .open_level(git2::ConfigLevel::Local)
+            .context("Opening the local Git config")
+    }
+
+    fn capture(path: &Path, key: &'static str) -> Result<Self> {
+        let value = match Self::local_config(path)?.get_string(key) {
+            Ok(value) => Some(value),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Reading {}", key)),
+        };
+        Ok(Self { key, value })
+    }
+}
+
+impl Checkpoint for GitConfigCheckpoint {
+    fn restore(&self, path: &Path, _app: Arc<App>) -> Result<()> {
+        let mut config = Self::local_config(path)?;
+        let result = match &self.value {
+            Some(value) => config.set_str(self.key, value),
+            None => match config.remove(self.key) {
+                Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(()),
+                result => result,
+            },
+        };
+        result.with_context(|| format!("Restoring {}", self.key))
+    }
+}
+
 struct HooksMigration;
 impl Migration for HooksMigration {
     fn id(&self) -> Identifier {
@@ -35,8 +129,18 @@ impl Migration for HooksMigration {
         "Initialize the repo with required hooks"
     }
 
-    fn upgrade(&self, path: &Path, _app: Arc<App>) -> Result<()> {
-        focus_operations::event::init(path)
+    fn upgrade(&self, path: &Path, app: Arc<App>) -> Result<()> {
+        focus_operations::event::init(path, app)
+    }
+
+    fn checkpoint(&self, path: &Path, app: Arc<App>) -> Result<Option<Box<dyn Checkpoint>>> {
+        let hooks_dir = focus_operations::event::hooks_dir(path, app)?;
+        let checkpoint = FilesCheckpoint::capture(
+            focus_operations::event::HOOK_NAMES
+                .iter()
+                .map(|hook| hooks_dir.join(hook)),
+        )?;
+        Ok(Some(Box::new(checkpoint)))
     }
 }
 
@@ -55,4 +159,46 @@ impl Migration for UseOneshotSyncByDefaultMigration {
         repo.set_bazel_oneshot_resolution(true)?;
         Ok(())
     }
+
+    fn checkpoint(&self, path: &Path, _app: Arc<App>) -> Result<Option<Box<dyn Checkpoint>>> {
+        let checkpoint = GitConfigCheckpoint::capture(path, BAZEL_ONE_SHOT_RESOLUTION_CONFIG_KEY)?;
+        Ok(Some(Box::new(checkpoint)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoints_restore_files_and_config() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let repo = git2::Repository::init(dir.path())?;
+        let existing = dir.path().join("existing");
+        let created = dir.path().join("created");
+        std::fs::write(&existing, "before")?;
+
+        let files = FilesCheckpoint::capture(vec![existing.clone(), created.clone()])?;
+        let unset = GitConfigCheckpoint::capture(dir.path(), "focus.test.unset")?;
+        repo.config()?.set_str("focus.test.set", "before")?;
+        let set = GitConfigCheckpoint::capture(dir.path(), "focus.test.set")?;
+
+        std::fs::write(&existing, "after")?;
+        std::fs::write(&created, "after")?;
+        repo.config()?.set_str("focus.test.unset", "after")?;
+        repo.config()?.set_str("focus.test.set", "after")?;
+
+        files.restore(dir.path(), app.clone())?;
+        unset.restore(dir.path(), app.clone())?;
+        set.restore(dir.path(), app)?;
+
+        assert_eq!(std::fs::read_to_string(&existing)?, "before");
+        assert!(!created.exists());
+        let config = repo.config()?.snapshot()?;
+        assert!(config.get_str("focus.test.unset").is_err());
+        assert_eq!(config.get_str("focus.test.set")?, "before");
+
+        Ok(())
+    }
 }
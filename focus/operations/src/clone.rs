@@ -7,17 +7,23 @@ use focus_internals::index::RocksDBMemoizationCacheExt;
 use focus_internals::model::selection::{Operation, OperationAction};
 
 use anyhow::{bail, Context, Result};
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono::{NaiveDate, NaiveDateTime};
 use content_addressed_cache::RocksDBCache;
-use focus_internals::{model::repo::Repo, target::TargetSet, tracker::Tracker};
+use focus_internals::{
+    model::repo::{Repo, SyncOptions},
+    target::TargetSet,
+    tracker::Tracker,
+};
 
 use focus_util::sandbox_command::SandboxCommand;
 use focus_util::{self, app::App, git_helper, sandbox_command::SandboxCommandOutput};
 use git2::Repository;
+use serde::{Deserialize, Serialize};
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fs::OpenOptions;
 use std::process::Command;
+use std::time::{Duration, Instant};
 use std::{
     ffi::OsString,
     fs::File,
@@ -43,6 +49,12 @@ pub enum InitOpt {
     Bare,
     Sparse,
     Progress,
+    /// Borrow objects from the repository at this path if it can be used, passed as `--reference-if-able`.
+    Reference(PathBuf),
+    /// Copy borrowed objects into the clone so that it does not depend on the reference repository.
+    Dissociate,
+    /// Keep this many commits from the tip of each branch, passed as `--depth`.
+    Depth(u32),
 }
 
 #[derive(Debug)]
@@ -73,6 +85,59 @@ pub fn parse_shallow_since_date(s: &str) -> Result<NaiveDate> {
     Ok(NaiveDate::parse_from_str(s, "%Y-%m-%d")?)
 }
 
+/// How much history a clone keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum History {
+    /// Commits from this many days back; zero keeps the entire history.
+    Days(u64),
+
+    /// Commits since this date.
+    Since(NaiveDate),
+
+    /// This many commits from the tip of each branch.
+    Depth(u32),
+}
+
+impl History {
+    /// The history to keep given a clone's options. A `depth` or a `shallow_since` date, which cannot be combined, takes precedence over `days_of_history`.
+    pub fn new(
+        days_of_history: u64,
+        shallow_since: Option<NaiveDate>,
+        depth: Option<u32>,
+    ) -> Result<Self> {
+        match (depth, shallow_since) {
+            (Some(_), Some(_)) => {
+                bail!("A clone can be limited by --depth or by --shallow-since, but not both")
+            }
+            (Some(0), None) => bail!("The clone depth must be at least 1"),
+            (Some(depth), None) => Ok(History::Depth(depth)),
+            (None, Some(date)) => Ok(History::Since(date)),
+            (None, None) => Ok(History::Days(days_of_history)),
+        }
+    }
+
+    /// The date before which commits are left out, if the history is limited by date.
+    fn since_date(&self) -> Result<Option<NaiveDate>> {
+        match self {
+            History::Days(0) | History::Depth(_) => Ok(None),
+            History::Days(days) => Ok(Some(
+                focus_util::time::date_at_day_in_past((*days).try_into()?)?.naive_utc(),
+            )),
+            History::Since(date) => Ok(Some(*date)),
+        }
+    }
+
+    /// The argument limiting the history fetched by `git fetch`, if any.
+    fn fetch_arg(&self) -> Result<Option<String>> {
+        Ok(match self {
+            History::Depth(depth) => Some(format!("--depth={}", depth)),
+            _ => self
+                .since_date()?
+                .map(|date| format!("--shallow-since={}", date.format("%Y-%m-%d"))),
+        })
+    }
+}
+
 impl Default for CloneBuilder {
     fn default() -> Self {
         Self {
@@ -101,8 +166,17 @@ impl CloneBuilder {
     pub fn build(self, app: Arc<App>) -> Result<(Command, SandboxCommand)> {
         let mut opt_args: Vec<String> = Vec::new();
 
-        if let Some(ss) = self.shallow_since {
-            opt_args.push(format!["--shallow-since={}", ss.format("%Y-%m-%d")]);
+        let depth = self.init_opts.iter().find_map(|opt| match opt {
+            InitOpt::Depth(depth) => Some(*depth),
+            _ => None,
+        });
+        match (depth, self.shallow_since) {
+            (Some(_), Some(_)) => {
+                bail!("A clone can be limited by --depth or by --shallow-since, but not both")
+            }
+            (Some(depth), None) => opt_args.push(format!["--depth={}", depth]),
+            (None, Some(ss)) => opt_args.push(format!["--shallow-since={}", ss.format("%Y-%m-%d")]),
+            (None, None) => {}
         }
 
         opt_args.push("-b".to_string());
@@ -132,6 +206,16 @@ impl CloneBuilder {
             opt_args.push(String::from("--progress"));
         }
 
+        for opt in self.init_opts.iter() {
+            if let InitOpt::Reference(path) = opt {
+                opt_args.push(format!["--reference-if-able={}", path.display()]);
+            }
+        }
+
+        if self.opt_set(InitOpt::Dissociate) {
+            opt_args.push(String::from("--dissociate"));
+        }
+
         if let Some(push_url) = self.push_url.to_owned() {
             opt_args.push(String::from("-c"));
             opt_args.push(format!["remote.origin.pushUrl={}", push_url]);
@@ -166,6 +250,14 @@ impl CloneBuilder {
         self
     }
 
+    /// Keep only the most recent `depth` commits of history. This cannot be combined with `shallow_since`.
+    pub fn depth(&mut self, depth: u32) -> &mut Self {
+        self.init_opts
+            .retain(|opt| !matches!(opt, InitOpt::Depth(_)));
+        self.init_opts.insert(InitOpt::Depth(depth));
+        self
+    }
+
     pub fn branch(&mut self, name: String) -> &mut Self {
         self.branch_name = name;
         self
@@ -205,6 +297,13 @@ impl CloneBuilder {
         self
     }
 
+    /// Share objects with the repository at `path` when it is usable. With `dissociate`, the borrowed objects are copied so that the clone outlives the reference.
+    pub fn reference(&mut self, path: PathBuf, dissociate: bool) -> &mut Self {
+        self.init_opts.insert(InitOpt::Reference(path));
+        self.add_or_remove_init_opt(InitOpt::Dissociate, dissociate);
+        self
+    }
+
     pub fn fetch_url(&mut self, ourl: String) -> &mut Self {
         self.fetch_url = Some(ourl);
         self
@@ -273,21 +372,57 @@ impl CloneBuilder {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum Origin {
     /// Clone from a local path
     Local(PathBuf),
 
     /// Clone from a remote URL
     Remote(Url),
+
+    /// Clone over SSH from an scp-style address like `git@github.com:org/repo.git`
+    Scp(ScpAddress),
+}
+
+impl Origin {
+    /// Whether the origin can be cloned without network access, i.e. it is a local path or a `file://` URL.
+    pub fn is_local(&self) -> bool {
+        match self {
+            Origin::Local(_) => true,
+            Origin::Remote(url) => url.scheme() == "file",
+            Origin::Scp(_) => false,
+        }
+    }
+
+    /// What to pass to `git clone` for a remote origin.
+    fn fetch_url(&self) -> Option<String> {
+        match self {
+            Origin::Local(_) => None,
+            Origin::Remote(url) => Some(url.as_str().to_owned()),
+            Origin::Scp(address) => Some(address.to_string()),
+        }
+    }
+
+    /// The origin as a URL, with scp-style addresses given as the equivalent `ssh://` URL.
+    fn url(&self) -> Option<Url> {
+        match self {
+            Origin::Local(_) => None,
+            Origin::Remote(url) => Some(url.clone()),
+            Origin::Scp(address) => address.to_ssh_url(),
+        }
+    }
 }
 
 impl TryFrom<&str> for Origin {
     type Error = anyhow::Error;
 
+    /// Classify `value` the way Git does: anything with `://` is a URL; otherwise a colon with no slash before it makes an scp-style SSH address, and anything else is a local path. Local paths containing a colon can be written with a leading `./`.
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if let Ok(url) = Url::parse(value) {
+        if value.contains("://") {
+            let url = Url::parse(value).with_context(|| format!("Invalid URL '{}'", value))?;
             Ok(Origin::Remote(url))
+        } else if let Some(address) = ScpAddress::parse(value) {
+            Ok(Origin::Scp(address))
         } else {
             let dense_repo_path = PathBuf::from(value);
             let dense_repo_path = focus_util::paths::expand_tilde(dense_repo_path.as_path())?;
@@ -296,6 +431,55 @@ impl TryFrom<&str> for Origin {
     }
 }
 
+/// An SSH address in the scp-like syntax `[user@]host:path` that Git accepts. Unlike an `ssh://` URL, a relative path is relative to the user's home directory on the host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScpAddress {
+    pub user: Option<String>,
+    pub host: String,
+    pub path: String,
+}
+
+impl ScpAddress {
+    /// Parse `value` if it is an scp-style address: the part before the first colon contains no slash, so that `./dir:name` and `/tmp/a:b` are paths. Single letters before the colon are taken to be Windows drive letters.
+    fn parse(value: &str) -> Option<Self> {
+        let (authority, path) = value.split_once(':')?;
+        if authority.contains('/') || path.is_empty() {
+            return None;
+        }
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_owned()), host),
+            None => (None, authority),
+        };
+        if host.len() < 2 || user.as_deref() == Some("") {
+            return None;
+        }
+        Some(Self {
+            user,
+            host: host.to_owned(),
+            path: path.to_owned(),
+        })
+    }
+
+    /// The equivalent `ssh://` URL, used to recognize well-known repositories.
+    fn to_ssh_url(&self) -> Option<Url> {
+        let user = match &self.user {
+            Some(user) => format!("{}@", user),
+            None => String::new(),
+        };
+        let path = self.path.trim_start_matches('/');
+        Url::parse(&format!("ssh://{}{}/{}", user, self.host, path)).ok()
+    }
+}
+
+impl std::fmt::Display for ScpAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(user) = &self.user {
+            write!(f, "{}@", user)?;
+        }
+        write!(f, "{}:{}", self.host, self.path)
+    }
+}
+
 #[derive(Debug)]
 pub struct CloneArgs {
     pub origin: Option<Origin>,
@@ -303,8 +487,32 @@ pub struct CloneArgs {
     pub projects_and_targets: Vec<String>,
     pub copy_branches: bool,
     pub days_of_history: u64,
+    /// Keep the history since this date instead of `days_of_history`.
+    pub shallow_since: Option<NaiveDate>,
+    /// Keep this many commits of history instead of `days_of_history`. Cannot be combined with `shallow_since`.
+    pub depth: Option<u32>,
     pub do_post_clone_fetch: bool,
     pub sync_mode: SyncMode,
+    /// Whether the index may be consulted or populated while materializing the initial selection. When false, targets are resolved live and nothing is fetched from or written to the index.
+    pub use_index: bool,
+    /// Forbid network access. The origin must be local, and the post-clone fetch is skipped.
+    pub offline: bool,
+    /// Make the clone resumable: a failed clone is kept next to the destination, and a clone kept by an earlier attempt with the same arguments is continued, skipping the phases it completed.
+    pub resume: bool,
+    /// An existing repo whose resolution cache seeds the new repo's before the initial sync, so that targets it resolved before for the same build graph need not be resolved again.
+    pub resolution_cache_from: Option<PathBuf>,
+    /// Directories to leave out of the sparse checkout, recorded so that later syncs leave them out too.
+    pub excluded_paths: Vec<PathBuf>,
+    /// An existing local checkout of the same repository to borrow objects from when cloning from a remote origin. Only objects it lacks are downloaded, and the borrowed objects are copied so the new clone does not depend on it. A checkout with shallow history cannot be borrowed from and is skipped.
+    pub mirror_from: Option<PathBuf>,
+    /// A local repository, such as an object cache on a shared volume, to borrow objects from when cloning from a remote origin. Unlike `mirror_from`, the new clone keeps borrowing from it unless `dissociate` is set.
+    pub reference: Option<PathBuf>,
+    /// Copy the objects borrowed from `reference` so that the clone does not depend on it.
+    pub dissociate: bool,
+    /// Branches to fetch from a remote origin in addition to `branch`. The origin remote is configured to keep fetching them.
+    pub additional_branches: Vec<String>,
+    /// If the origin has no commits yet, initialize the clone with an empty commit on `branch` instead of cloning, so that it has a HEAD. Origins with history are cloned as usual.
+    pub initial_commit: bool,
 }
 
 impl Default for CloneArgs {
@@ -315,8 +523,20 @@ impl Default for CloneArgs {
             projects_and_targets: Vec::default(),
             copy_branches: true,
             days_of_history: 90,
+            shallow_since: None,
+            depth: None,
             do_post_clone_fetch: true,
             sync_mode: SyncMode::Incremental,
+            use_index: true,
+            offline: false,
+            resume: false,
+            resolution_cache_from: None,
+            excluded_paths: Vec::new(),
+            mirror_from: None,
+            reference: None,
+            dissociate: false,
+            additional_branches: Vec::new(),
+            initial_commit: false,
         }
     }
 }
@@ -329,15 +549,27 @@ pub fn run(
     template: Option<ClonedRepoTemplate>,
     tracker: &Tracker,
     app: Arc<App>,
-) -> Result<()> {
+) -> Result<PhaseTimings> {
     let CloneArgs {
         origin,
         branch,
         projects_and_targets,
         copy_branches,
         days_of_history,
+        shallow_since,
+        depth,
         do_post_clone_fetch,
         sync_mode,
+        use_index,
+        offline,
+        resume,
+        resolution_cache_from,
+        excluded_paths,
+        mirror_from,
+        reference,
+        dissociate,
+        additional_branches,
+        initial_commit,
     } = clone_args;
 
     let origin = match origin {
@@ -345,17 +577,120 @@ pub fn run(
         None => bail!("Clone does not have a valid origin"),
     };
 
-    if sparse_repo_path.is_dir() {
+    if offline && !origin.is_local() {
+        bail!(
+            "Offline clones require a local path or file:// URL as the origin, but {:?} would need network access",
+            origin
+        );
+    }
+
+    let history = History::new(days_of_history, shallow_since, depth)?;
+
+    let reference = match (mirror_from, reference) {
+        (Some(_), Some(_)) => {
+            bail!("A clone can borrow objects from a mirror or a reference, but not both")
+        }
+        (Some(path), None) => Some((path, true)),
+        (None, Some(path)) => Some((path, dissociate)),
+        (None, None) => None,
+    };
+    let reference = match (&origin, reference) {
+        (Origin::Remote(_) | Origin::Scp(_), Some((path, dissociate))) => {
+            reference_objects_path(&path)?.map(|path| ObjectReference { path, dissociate })
+        }
+        (Origin::Local(_), Some((path, _))) => {
+            info!(
+                reference = %path.display(),
+                "Ignoring the reference repository because the origin is already a local repository"
+            );
+            None
+        }
+        (_, None) => None,
+    };
+
+    if matches!(origin, Origin::Local(_)) && !additional_branches.is_empty() {
+        info!(
+            ?additional_branches,
+            "Ignoring the additional branches because the origin is a local repository; use --copy-branches to copy its branches"
+        );
+    }
+
+    let recorded_args = RecordedCloneArgs {
+        origin: format!("{:?}", origin),
+        branch: branch.clone(),
+        projects_and_targets: projects_and_targets.clone(),
+        copy_branches,
+        history: format!("{:?}", history),
+        use_index,
+        excluded_paths: excluded_paths.clone(),
+        additional_branches: additional_branches.clone(),
+    };
+
+    // Resumable clones are assembled next to the destination so that a failed clone can be found again; others are assembled in the sandbox as usual.
+    let resumable_path = staging_path(&sparse_repo_path)?;
+    let tmp_sparse_repo_path = if resume {
+        resumable_path.clone()
+    } else {
+        app.sandbox().path().join("tmp_sparse_repo")
+    };
+    let mut state = if resume && tmp_sparse_repo_path.is_dir() {
+        let state = CloneState::load(&tmp_sparse_repo_path)?;
+        if state.args.as_ref() != Some(&recorded_args) {
+            bail!(
+                "The interrupted clone at {} was started with different arguments; rerun it with the same arguments to continue it, or delete it to start over",
+                tmp_sparse_repo_path.display()
+            );
+        }
+        if state.is_complete(ClonePhase::Fetch) {
+            info!(completed_phases = ?state.completed_phases, "Resuming interrupted clone");
+            state
+        } else {
+            info!("The interrupted clone did not finish fetching; starting over");
+            std::fs::remove_dir_all(&tmp_sparse_repo_path)
+                .context("Failed to remove the interrupted clone")?;
+            CloneState::new(recorded_args)
+        }
+    } else {
+        if resume {
+            info!("There is no interrupted clone to resume; starting a new clone");
+        } else if resumable_path.is_dir() {
+            info!(
+                path = %resumable_path.display(),
+                "Ignoring an interrupted clone; it is removed if this clone succeeds"
+            );
+        }
+        CloneState::new(recorded_args)
+    };
+
+    let resuming_into_reserved_directory = resume
+        && sparse_repo_path.is_dir()
+        && sparse_repo_path
+            .read_dir()
+            .context("Failed to read repo directory")?
+            .next()
+            .is_none();
+    if sparse_repo_path.is_dir() && !resuming_into_reserved_directory {
         bail!("{} already exists", sparse_repo_path.display());
     }
 
     //create the sparse repo dir, so other clones don't use the same name
     std::fs::create_dir_all(&sparse_repo_path).context("Failed to create repo directory")?;
 
-    let mut tmp_sparse_repo_path = PathBuf::from(app.sandbox().path());
-    tmp_sparse_repo_path.push("tmp_sparse_repo");
-
+    let mut timings = PhaseTimings::default();
     let configure_repo_then_move_in_place = || -> Result<()> {
+        if initial_commit && !state.is_complete(ClonePhase::Fetch) {
+            if origin_is_empty(&origin, app.clone())? {
+                timings.time(ClonePhase::Fetch.label(), || {
+                    init_with_initial_commit(&origin, &tmp_sparse_repo_path, &branch, app.clone())
+                })?;
+                state.complete(&tmp_sparse_repo_path, ClonePhase::Fetch)?;
+                // There are no branches to copy from an empty origin.
+                state.complete(&tmp_sparse_repo_path, ClonePhase::CopyBranches)?;
+            } else {
+                info!("Not creating an initial commit because the origin has history");
+            }
+        }
+
         let template = match origin {
             Origin::Local(dense_repo_path) => {
                 tracing::info!(path = ?dense_repo_path, "Cloning from local path");
@@ -364,25 +699,41 @@ pub fn run(
                     &tmp_sparse_repo_path,
                     &branch,
                     copy_branches,
-                    days_of_history,
+                    history,
+                    &mut state,
+                    &mut timings,
                     app.clone(),
                 )?;
 
                 template
             }
-            Origin::Remote(url) => {
-                tracing::info!(?url, "Cloning from remote");
-                clone_remote(
-                    url.clone(),
-                    &tmp_sparse_repo_path,
-                    &branch,
-                    days_of_history,
-                    app.clone(),
-                )?;
+            remote @ (Origin::Remote(_) | Origin::Scp(_)) => {
+                let fetch_url = remote.fetch_url().expect("Remote origins have a fetch URL");
+                tracing::info!(url = %fetch_url, "Cloning from remote");
+                if !state.is_complete(ClonePhase::Fetch) {
+                    timings.time(ClonePhase::Fetch.label(), || -> Result<()> {
+                        clone_remote(
+                            &fetch_url,
+                            &tmp_sparse_repo_path,
+                            &branch,
+                            history,
+                            reference.as_ref(),
+                            app.clone(),
+                        )?;
+                        fetch_additional_branches(
+                            &tmp_sparse_repo_path,
+                            &additional_branches,
+                            history,
+                            app.clone(),
+                        )
+                    })?;
+                    state.complete(&tmp_sparse_repo_path, ClonePhase::Fetch)?;
+                }
 
-                let template = template.or_else(|| ClonedRepoTemplate::from_url(url.clone()));
+                let template =
+                    template.or_else(|| remote.url().and_then(ClonedRepoTemplate::from_url));
                 if let Some(template) = template {
-                    info!(?template, %url, "Using repo template for url");
+                    info!(?template, url = %fetch_url, "Using repo template for url");
                 }
                 template
             }
@@ -393,16 +744,30 @@ pub fn run(
             projects_and_targets,
             template,
             sync_mode,
+            use_index,
+            resolution_cache_from.as_deref(),
+            &excluded_paths,
+            &mut state,
+            &mut timings,
             app.clone(),
         )?;
 
-        if do_post_clone_fetch {
-            fetch_default_remote(&tmp_sparse_repo_path, app.clone())
-                .context("Could not complete post clone fetch")?;
+        if do_post_clone_fetch && !state.is_complete(ClonePhase::PostCloneFetch) {
+            if offline {
+                info!("Skipping the post clone fetch because the clone is offline");
+            } else {
+                timings
+                    .time(ClonePhase::PostCloneFetch.label(), || {
+                        fetch_default_remote(&tmp_sparse_repo_path, app.clone())
+                    })
+                    .context("Could not complete post clone fetch")?;
+                state.complete(&tmp_sparse_repo_path, ClonePhase::PostCloneFetch)?;
+            }
         }
 
-        set_up_hooks(&tmp_sparse_repo_path)?;
+        set_up_hooks(&tmp_sparse_repo_path, app.clone())?;
 
+        CloneState::remove(&tmp_sparse_repo_path)?;
         move_repo(
             &tmp_sparse_repo_path,
             &sparse_repo_path,
@@ -414,16 +779,204 @@ pub fn run(
         Ok(())
     };
 
-    if let err @ Err(_) = configure_repo_then_move_in_place() {
-        //cleanup, will rely on maintanence sandbox cleanup for tmp_sparse_repo
-        if std::fs::remove_dir_all(sparse_repo_path).is_err() {
-            return err.context("Failed to cleanup repo");
+    if let Err(err) = configure_repo_then_move_in_place() {
+        if std::fs::remove_dir_all(&sparse_repo_path).is_err() {
+            return Err(err.context("Failed to cleanup repo"));
         };
 
-        return err;
+        if !tmp_sparse_repo_path.is_dir() {
+            return Err(err);
+        }
+        if resume {
+            // Leave the partial clone in place so that it can be resumed.
+            return Err(err.context(format!(
+                "The partial clone at {} can be continued by running the same command again with --resume",
+                tmp_sparse_repo_path.display()
+            )));
+        }
+        if let Err(e) = std::fs::remove_dir_all(&tmp_sparse_repo_path) {
+            warn!(?e, path = %tmp_sparse_repo_path.display(), "Failed to remove the partial clone");
+        }
+        return Err(err);
     }
 
-    Ok(())
+    if !resume && resumable_path.is_dir() {
+        // The interrupted clone has been superseded.
+        std::fs::remove_dir_all(&resumable_path).with_context(|| {
+            format!(
+                "Failed to remove the interrupted clone at {}",
+                resumable_path.display()
+            )
+        })?;
+    }
+
+    Ok(timings)
+}
+
+/// Where a resumable clone into `sparse_repo_path` is assembled before it is moved into place. This is next to the destination, rather than in the sandbox, so that an interrupted clone can be found and resumed.
+fn staging_path(sparse_repo_path: &Path) -> Result<PathBuf> {
+    let file_name = sparse_repo_path
+        .file_name()
+        .with_context(|| format!("{} has no file name", sparse_repo_path.display()))?;
+    let mut staging_name = OsString::from(".");
+    staging_name.push(file_name);
+    staging_name.push(".focus-clone");
+    Ok(sparse_repo_path.with_file_name(staging_name))
+}
+
+/// The phases of a clone that are recorded as they complete, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ClonePhase {
+    /// Fetching the repository from the origin.
+    Fetch,
+
+    /// Copying branches, remotes, and configuration from a local dense repo.
+    CopyBranches,
+
+    /// Creating the outlining and working trees.
+    CreateTrees,
+
+    /// Storing the initial selection and synchronizing it, which consults the index.
+    InitialSync,
+
+    /// Fetching from the default remote after the clone.
+    PostCloneFetch,
+}
+
+impl ClonePhase {
+    /// The name of the phase in timing breakdowns.
+    fn label(self) -> &'static str {
+        match self {
+            ClonePhase::Fetch => "git_clone",
+            ClonePhase::CopyBranches => "branch_copy",
+            ClonePhase::CreateTrees => "create_trees",
+            ClonePhase::InitialSync => "initial_sync",
+            ClonePhase::PostCloneFetch => "post_clone_fetch",
+        }
+    }
+}
+
+/// The wall-clock time taken by each phase of a clone, in the order the phases ran. Phases skipped when resuming a clone are absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    phases: Vec<(String, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn record(&mut self, phase: impl Into<String>, elapsed: Duration) {
+        self.phases.push((phase.into(), elapsed));
+    }
+
+    /// Run `f`, recording how long it took as `phase`.
+    pub fn time<T>(&mut self, phase: impl Into<String>, f: impl FnOnce() -> T) -> T {
+        let started_at = Instant::now();
+        let result = f();
+        self.record(phase, started_at.elapsed());
+        result
+    }
+
+    pub fn phases(&self) -> &[(String, Duration)] {
+        &self.phases
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+
+    /// A table with a line for each phase and its duration in seconds, followed by the total.
+    pub fn breakdown(&self) -> String {
+        let width = self
+            .phases
+            .iter()
+            .map(|(phase, _)| phase.len())
+            .chain(std::iter::once("total".len()))
+            .max()
+            .unwrap_or_default();
+        self.phases
+            .iter()
+            .map(|(phase, elapsed)| (phase.as_str(), *elapsed))
+            .chain(std::iter::once(("total", self.total())))
+            .map(|(phase, elapsed)| {
+                format!(
+                    "{:<width$}  {:>8.3}s\n",
+                    phase,
+                    elapsed.as_secs_f64(),
+                    width = width
+                )
+            })
+            .collect()
+    }
+}
+
+/// The arguments that determine what a clone produces, recorded so that a clone is only resumed by the same command.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct RecordedCloneArgs {
+    origin: String,
+    branch: String,
+    projects_and_targets: Vec<String>,
+    copy_branches: bool,
+    history: String,
+    use_index: bool,
+    excluded_paths: Vec<PathBuf>,
+    additional_branches: Vec<String>,
+}
+
+/// The progress of a clone, stored in `.focus/clone-state` within the partial clone until it is moved into place.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct CloneState {
+    completed_phases: BTreeSet<ClonePhase>,
+
+    /// The arguments the clone was started with. Absent in the state of clones that recorded none, which are not resumed.
+    #[serde(default)]
+    args: Option<RecordedCloneArgs>,
+}
+
+impl CloneState {
+    fn new(args: RecordedCloneArgs) -> Self {
+        Self {
+            completed_phases: Default::default(),
+            args: Some(args),
+        }
+    }
+
+    fn path(repo: &Path) -> PathBuf {
+        repo.join(".focus").join("clone-state")
+    }
+
+    /// Read the state of the partial clone at `repo`. A clone with no recorded state has completed nothing.
+    fn load(repo: &Path) -> Result<Self> {
+        let path = Self::path(repo);
+        if !path.is_file() {
+            return Ok(Default::default());
+        }
+        let file = File::open(&path).with_context(|| format!("Opening {}", path.display()))?;
+        serde_json::from_reader(file).with_context(|| format!("Reading {}", path.display()))
+    }
+
+    fn is_complete(&self, phase: ClonePhase) -> bool {
+        self.completed_phases.contains(&phase)
+    }
+
+    /// Record that `phase` of the partial clone at `repo` completed.
+    fn complete(&mut self, repo: &Path, phase: ClonePhase) -> Result<()> {
+        self.completed_phases.insert(phase);
+        let path = Self::path(repo);
+        std::fs::create_dir_all(path.parent().unwrap())
+            .with_context(|| format!("Creating the directory for {}", path.display()))?;
+        let file = File::create(&path).with_context(|| format!("Creating {}", path.display()))?;
+        serde_json::to_writer(BufWriter::new(file), self)
+            .with_context(|| format!("Writing {}", path.display()))
+    }
+
+    /// Remove the record of progress once the clone is complete.
+    fn remove(repo: &Path) -> Result<()> {
+        let path = Self::path(repo);
+        if path.is_file() {
+            std::fs::remove_file(&path).with_context(|| format!("Removing {}", path.display()))?;
+        }
+        Ok(())
+    }
 }
 
 fn move_repo(from_path: &Path, to_path: &Path, tracker: &Tracker, app: Arc<App>) -> Result<()> {
@@ -448,7 +1001,9 @@ fn clone_local(
     sparse_repo_path: &Path,
     branch: &str,
     copy_branches: bool,
-    days_of_history: u64,
+    history: History,
+    state: &mut CloneState,
+    timings: &mut PhaseTimings,
     app: Arc<App>,
 ) -> Result<()> {
     info!("Dense repo path: {}", dense_repo_path.display());
@@ -464,53 +1019,123 @@ fn clone_local(
         bail!("Dense repo path must be absolute");
     }
 
-    if sparse_repo_path.is_dir() {
-        bail!("Sparse repo directory already exists");
+    if !state.is_complete(ClonePhase::Fetch) {
+        if sparse_repo_path.is_dir() {
+            bail!("Sparse repo directory already exists");
+        }
+
+        timings.time(ClonePhase::Fetch.label(), || -> Result<()> {
+            enable_filtering(&dense_repo_path)
+                .context("setting configuration options in the dense repo")?;
+
+            let url = Url::from_file_path(&dense_repo_path)
+                .expect("Failed to convert dense repo path to a file URL");
+
+            let span = info_span!("Cloning", dense_repo_path = ?dense_repo_path, sparse_repo_path = ?sparse_repo_path);
+            let _guard = span.enter();
+            clone_shallow(
+                url.as_str(),
+                sparse_repo_path,
+                branch,
+                copy_branches,
+                history,
+                None,
+                app.clone(),
+            )
+            .context("Failed to clone the repository")
+        })?;
+        state.complete(sparse_repo_path, ClonePhase::Fetch)?;
     }
 
-    enable_filtering(&dense_repo_path)
-        .context("setting configuration options in the dense repo")?;
+    if state.is_complete(ClonePhase::CopyBranches) {
+        return Ok(());
+    }
 
-    let url = Url::from_file_path(&dense_repo_path)
-        .expect("Failed to convert dense repo path to a file URL");
+    timings.time(ClonePhase::CopyBranches.label(), || -> Result<()> {
+        let dense_repo = Repository::open(&dense_repo_path).context("Opening dense repo")?;
+        let sparse_repo = Repository::open(sparse_repo_path).context("Opening sparse repo")?;
 
-    {
-        let span = info_span!("Cloning", dense_repo_path = ?dense_repo_path, sparse_repo_path = ?sparse_repo_path);
-        let _guard = span.enter();
-        clone_shallow(
-            &url,
-            sparse_repo_path,
-            branch,
-            copy_branches,
-            days_of_history,
-            app.clone(),
-        )
-        .context("Failed to clone the repository")?;
-    }
+        if copy_branches {
+            let span = info_span!("Copying branches");
+            let _guard = span.enter();
+            copy_local_branches(&dense_repo, &sparse_repo, branch, app.clone(), history)
+                .context("Failed to copy references")?;
+        }
 
-    let dense_repo = Repository::open(&dense_repo_path).context("Opening dense repo")?;
-    let sparse_repo = Repository::open(sparse_repo_path).context("Opening sparse repo")?;
+        set_up_remotes(&dense_repo, &sparse_repo, branch, app)
+            .context("Failed to set up the remotes")?;
 
-    if copy_branches {
-        let span = info_span!("Copying branches");
-        let _guard = span.enter();
-        copy_local_branches(
-            &dense_repo,
-            &sparse_repo,
-            branch,
-            app.clone(),
-            days_of_history,
-        )
-        .context("Failed to copy references")?;
-    }
+        copy_dense_config(&dense_repo, &sparse_repo)
+            .context("failed to copy config from dense repo")
+    })?;
 
-    set_up_remotes(&dense_repo, &sparse_repo, branch, app)
-        .context("Failed to set up the remotes")?;
+    state.complete(sparse_repo_path, ClonePhase::CopyBranches)
+}
 
-    copy_dense_config(&dense_repo, &sparse_repo)
-        .context("failed to copy config from dense repo")?;
+/// Whether `origin` has no branches, as a newly created repository does.
+fn origin_is_empty(origin: &Origin, app: Arc<App>) -> Result<bool> {
+    match origin {
+        Origin::Local(path) => {
+            let repo = Repository::open(path)
+                .with_context(|| format!("Opening the origin at {}", path.display()))?;
+            repo.is_empty()
+                .context("Checking whether the origin is empty")
+        }
+        remote @ (Origin::Remote(_) | Origin::Scp(_)) => {
+            let fetch_url = remote.fetch_url().expect("Remote origins have a fetch URL");
+            if !remote.is_local() {
+                app.require_network(&format!("Listing branches in {}", fetch_url))?;
+            }
+            let (mut cmd, scmd) = git_helper::git_command(app)?;
+            scmd.ensure_success_or_log(
+                cmd.args(["ls-remote", "--heads", &fetch_url]),
+                SandboxCommandOutput::Stderr,
+            )
+            .context("Listing the branches of the origin")?;
+            let mut heads = String::new();
+            scmd.read_to_string(SandboxCommandOutput::Stdout, &mut heads)?;
+            Ok(heads.trim().is_empty())
+        }
+    }
+}
 
-    Ok(())
+/// Initialize a repo at `sparse_repo_path` for the empty `origin`, with an empty commit on `branch` so that the repo has a HEAD.
+fn init_with_initial_commit(
+    origin: &Origin,
+    sparse_repo_path: &Path,
+    branch: &str,
+    app: Arc<App>,
+) -> Result<()> {
+    if sparse_repo_path.is_dir() {
+        bail!("Sparse repo directory already exists");
+    }
+    let url = match origin {
+        Origin::Local(path) => {
+            let path = std::fs::canonicalize(path)
+                .with_context(|| format!("Resolving the origin path {}", path.display()))?;
+            Url::from_file_path(&path)
+                .map_err(|_| anyhow::anyhow!("Failed to convert {} to a file URL", path.display()))?
+                .to_string()
+        }
+        remote => remote.fetch_url().expect("Remote origins have a fetch URL"),
+    };
+
+    info!(%url, %branch, "The origin is empty; starting the repo with an initial commit");
+    let run_git = |args: &[&str]| -> Result<()> {
+        let (mut cmd, scmd) = git_helper::git_command(app.clone())?;
+        scmd.ensure_success_or_log(
+            cmd.current_dir(sparse_repo_path).args(args),
+            SandboxCommandOutput::Stderr,
+        )
+        .map(|_| ())
+    };
+    std::fs::create_dir_all(sparse_repo_path).context("Failed to create the repo directory")?;
+    run_git(&["init"]).context("Failed to initialize the repo")?;
+    run_git(&["symbolic-ref", "HEAD", &format!("refs/heads/{}", branch)])
+        .context("Failed to switch to the branch")?;
+    run_git(&["remote", "add", "origin", &url]).context("Failed to add the origin remote")?;
+    run_git(&["commit", "--allow-empty", "--message", "Initial commit"])
+        .context("Failed to create the initial commit")
 }
 
 /// Enable filtering in the dense repo
@@ -533,11 +1158,40 @@ fn enable_filtering(dense_repo_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// A local repository to borrow objects from while cloning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ObjectReference {
+    /// The repository directory (such as `.git`) to borrow from.
+    path: PathBuf,
+
+    /// Whether to copy the borrowed objects so that the clone does not depend on the reference.
+    dissociate: bool,
+}
+
+/// The repository directory of the checkout at `path`, to borrow objects from with `git clone --reference`. Git cannot borrow from a shallow repository, which focus clones usually are, so there is nothing to borrow from one.
+fn reference_objects_path(path: &Path) -> Result<Option<PathBuf>> {
+    let repo = Repository::open(path).with_context(|| {
+        format!(
+            "{} cannot be borrowed from because it is not a Git repository",
+            path.display()
+        )
+    })?;
+    if repo.is_shallow() {
+        warn!(
+            reference = %path.display(),
+            "The repository has shallow history, which Git cannot borrow objects from; cloning without it"
+        );
+        return Ok(None);
+    }
+    Ok(Some(repo.path().to_owned()))
+}
+
 fn clone_remote(
-    dense_repo_url: Url,
+    dense_repo_url: &str,
     sparse_repo_path: &Path,
     branch: &str,
-    days_of_history: u64,
+    history: History,
+    reference: Option<&ObjectReference>,
     app: Arc<App>,
 ) -> Result<()> {
     if sparse_repo_path.is_dir() {
@@ -546,75 +1200,166 @@ fn clone_remote(
 
     info!(
         "Cloning {} to {}",
-        &dense_repo_url,
+        dense_repo_url,
         &sparse_repo_path.display()
     );
+    if let Some(reference) = reference {
+        info!(reference = %reference.path.display(), dissociate = reference.dissociate, "Borrowing objects from a local repository");
+    }
 
     // Clone the repository.
     clone_shallow(
-        &dense_repo_url,
+        dense_repo_url,
         sparse_repo_path,
         branch,
         false,
-        days_of_history,
+        history,
+        reference,
         app,
     )
     .context("Failed to clone the repository")
 }
 
+/// Configure the origin remote of the single-branch clone at `sparse_repo_path` to also fetch `branches`, then fetch them with the same history limit as the clone.
+fn fetch_additional_branches(
+    sparse_repo_path: &Path,
+    branches: &[String],
+    history: History,
+    app: Arc<App>,
+) -> Result<()> {
+    if branches.is_empty() {
+        return Ok(());
+    }
+
+    let refspecs: Vec<String> = branches
+        .iter()
+        .map(|branch| format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch))
+        .collect();
+    for refspec in refspecs.iter() {
+        let (mut cmd, scmd) = git_helper::git_command(app.clone())?;
+        scmd.ensure_success_or_log(
+            cmd.current_dir(sparse_repo_path).args([
+                "config",
+                "--add",
+                "remote.origin.fetch",
+                refspec,
+            ]),
+            SandboxCommandOutput::Stderr,
+        )
+        .with_context(|| format!("Failed to add fetch refspec {}", refspec))?;
+    }
+
+    info!(?branches, "Fetching additional branches");
+    let (mut cmd, scmd) = git_helper::git_command(app)?;
+    cmd.current_dir(sparse_repo_path)
+        .args(["fetch", "--no-tags"]);
+    if let Some(history_arg) = history.fetch_arg()? {
+        cmd.arg(history_arg);
+    }
+    cmd.arg("origin").args(&refspecs);
+    scmd.ensure_success_or_log(&mut cmd, SandboxCommandOutput::Stderr)
+        .context("Failed to fetch the additional branches")?;
+
+    Ok(())
+}
+
 fn set_up_sparse_repo(
     sparse_repo_path: &Path,
     projects_and_targets: Vec<String>,
     template: Option<ClonedRepoTemplate>,
     sync_mode: SyncMode,
+    use_index: bool,
+    resolution_cache_from: Option<&Path>,
+    excluded_paths: &[PathBuf],
+    state: &mut CloneState,
+    timings: &mut PhaseTimings,
     app: Arc<App>,
 ) -> Result<()> {
-    {
+    if state.is_complete(ClonePhase::InitialSync) {
+        return Ok(());
+    }
+
+    if !state.is_complete(ClonePhase::CreateTrees) {
+        timings.time(ClonePhase::CreateTrees.label(), || -> Result<()> {
+            let repo = Repo::open(sparse_repo_path, app.clone()).context("Failed to open repo")?;
+            let outlining_tree_path = Repo::outlining_tree_path(repo.git_dir());
+            if outlining_tree_path.is_dir() {
+                info!("Removing the outlining tree left by the interrupted clone");
+                std::fs::remove_dir_all(&outlining_tree_path)
+                    .context("Failed to remove the partial outlining tree")?;
+                let (mut cmd, scmd) = git_helper::git_command(app.clone())?;
+                scmd.ensure_success_or_log(
+                    cmd.current_dir(sparse_repo_path)
+                        .arg("worktree")
+                        .arg("prune"),
+                    SandboxCommandOutput::Stderr,
+                )
+                .context("Failed to prune the partial outlining tree")?;
+            }
+
+            // TODO: Parallelize these tree set up processes.
+            info!("Setting up the outlining tree");
+            repo.create_outlining_tree()
+                .context("Failed to create the outlining tree")?;
+
+            info!("Setting up the working tree");
+            repo.create_working_tree()
+                .context("Failed to create the working tree")
+        })?;
+        state.complete(sparse_repo_path, ClonePhase::CreateTrees)?;
+    }
+
+    timings.time(ClonePhase::InitialSync.label(), || -> Result<()> {
+        // N.B. we must re-open the repo because otherwise it has no trees...
         let repo = Repo::open(sparse_repo_path, app.clone()).context("Failed to open repo")?;
-        // TODO: Parallelize these tree set up processes.
-        info!("Setting up the outlining tree");
-        repo.create_outlining_tree()
-            .context("Failed to create the outlining tree")?;
-
-        info!("Setting up the working tree");
-        repo.create_working_tree()
-            .context("Failed to create the working tree")?;
-    }
-
-    // N.B. we must re-open the repo because otherwise it has no trees...
-    let repo = Repo::open(sparse_repo_path, app.clone()).context("Failed to open repo")?;
-    // before running rest of setup config worktree view to be filtered
-    let working_tree = repo.working_tree()?;
-    working_tree.set_filter_config(true)?;
-    let head_commit = repo.get_head_commit().context("Resolving head commit")?;
-    let target_set = compute_and_store_initial_selection(&repo, projects_and_targets, template)?;
-    debug!(target_set = ?target_set, "Complete target set");
-    repo.set_bazel_oneshot_resolution(sync_mode == SyncMode::OneShot)?;
-
-    let odb = if repo.get_bazel_oneshot_resolution()? {
-        None
-    } else {
-        Some(RocksDBCache::new(repo.underlying()))
-    };
-    repo.sync(
-        head_commit.id(),
-        &target_set,
-        false,
-        app,
-        odb.as_ref(),
-        None,
-    )
-    .context("Sync failed")?;
+        // before running rest of setup config worktree view to be filtered
+        let working_tree = repo.working_tree()?;
+        working_tree.set_filter_config(true)?;
+        let head_commit = repo.get_head_commit().context("Resolving head commit")?;
+        let target_set =
+            compute_and_store_initial_selection(&repo, projects_and_targets, template)?;
+        debug!(target_set = ?target_set, "Complete target set");
+        repo.set_bazel_oneshot_resolution(sync_mode == SyncMode::OneShot)?;
+        repo.set_excluded_paths(excluded_paths)
+            .context("Recording excluded paths")?;
+
+        if let Some(resolution_cache_from) = resolution_cache_from {
+            let seeded = repo.seed_resolution_cache(resolution_cache_from, head_commit.id())?;
+            info!(
+                seeded,
+                "Seeded the resolution cache from {}",
+                resolution_cache_from.display()
+            );
+        }
+
+        let odb = if !use_index {
+            info!("Index disabled for this clone; resolving the initial selection directly");
+            None
+        } else if repo.get_bazel_oneshot_resolution()? {
+            None
+        } else {
+            Some(RocksDBCache::new(repo.underlying()))
+        };
+        repo.sync(
+            head_commit.id(),
+            &target_set,
+            app,
+            odb.as_ref(),
+            None,
+            &SyncOptions::default(),
+        )
+        .context("Sync failed")?;
 
-    repo.working_tree()?.write_sync_point_ref()?;
+        repo.working_tree()?.write_sync_point_ref()?;
 
-    info!("Writing git config to support instrumentation");
-    repo.write_git_config_to_support_instrumentation()
-        .context("Could not write git config to support instrumentation")?;
+        info!("Writing git config to support instrumentation");
+        repo.write_git_config_to_support_instrumentation()
+            .context("Could not write git config to support instrumentation")?;
 
-    set_up_bazel_preflight_script(sparse_repo_path)?;
+        set_up_bazel_preflight_script(sparse_repo_path)
+    })?;
 
-    Ok(())
+    state.complete(sparse_repo_path, ClonePhase::InitialSync)
 }
 
 fn compute_and_store_initial_selection(
@@ -662,32 +1407,35 @@ fn compute_and_store_initial_selection(
     let info_dir = repo.git_dir().join("info");
     std::fs::create_dir_all(&info_dir).context("Creating .git/info")?;
     let exclude_path = info_dir.join("exclude");
-    let mut exclude_file = OpenOptions::new()
-        .append(true)
-        .open(exclude_path)
-        .context("Opening .git/info/exclude")?;
-    writeln!(exclude_file, "/.focus/").context("Writing initial .git/info/exclude")?;
+    let already_excluded = std::fs::read_to_string(&exclude_path)
+        .map(|contents| contents.lines().any(|line| line == "/.focus/"))
+        .unwrap_or(false);
+    if !already_excluded {
+        let mut exclude_file = OpenOptions::new()
+            .append(true)
+            .open(exclude_path)
+            .context("Opening .git/info/exclude")?;
+        writeln!(exclude_file, "/.focus/").context("Writing initial .git/info/exclude")?;
+    }
 
     Ok(target_set)
 }
 
 fn clone_shallow(
-    source_url: &Url,
+    source_url: &str,
     destination_path: &Path,
     branch: &str,
     copy_branches: bool,
-    days_of_history: u64,
+    history: History,
+    reference: Option<&ObjectReference>,
     app: Arc<App>,
 ) -> Result<()> {
-    // Unfortunately time::duration is signed
-    let days_of_history: i64 = days_of_history.try_into()?;
-
-    let shallow_since_datestamp =
-        focus_util::time::formatted_datestamp_at_day_in_past(days_of_history)?;
-
     // Check if local dense repo has a main branch that's out of date
-    if source_url.scheme().eq_ignore_ascii_case("file") {
-        let source_path = Path::new(source_url.path());
+    let file_url = Url::parse(source_url)
+        .ok()
+        .filter(|url| url.scheme().eq_ignore_ascii_case("file"));
+    if let (Some(file_url), Some(shallow_since_date)) = (file_url, history.since_date()?) {
+        let source_path = Path::new(file_url.path());
         let repo = Repo::open(source_path, app.clone()).with_context(|| {
             format!("Could not open the dense repo to check date of {}", branch)
         })?;
@@ -699,32 +1447,40 @@ fn clone_shallow(
             .get()
             .peel_to_commit()
             .context("Failed to peel to commit of main branch")?;
-        let main_branch_tip_date = DateTime::from_utc(
-            NaiveDateTime::from_timestamp(main_branch_commit_id.time().seconds(), 0),
-            Utc,
-        )
-        .date();
+        let main_branch_tip_date =
+            NaiveDateTime::from_timestamp(main_branch_commit_id.time().seconds(), 0).date();
 
-        let shallow_since_date = focus_util::time::date_at_day_in_past(days_of_history)?;
-        if days_of_history > 0 && main_branch_tip_date < shallow_since_date {
-            bail!("Your main branch {} is older than the specified shallow date: {}. Run a `git pull` in your dense repo to update it!", branch, shallow_since_datestamp)
+        if main_branch_tip_date < shallow_since_date {
+            bail!("Your main branch {} is older than the specified shallow date: {}. Run a `git pull` in your dense repo to update it!", branch, shallow_since_date.format("%Y-%m-%d"))
         }
     }
 
     let mut builder = CloneBuilder::new(destination_path.into());
     builder
-        .fetch_url(source_url.as_str().into())
+        .fetch_url(source_url.into())
         .no_checkout(true)
         .follow_tags(false)
         .branch(branch.into());
 
-    if days_of_history > 0 {
-        builder.add_clone_arg(format!("--shallow-since={}", shallow_since_datestamp));
+    match history {
+        History::Depth(depth) => {
+            builder.depth(depth);
+        }
+        _ => {
+            if let Some(date) = history.since_date()? {
+                builder.shallow_since(date);
+            }
+        }
     }
 
     if !copy_branches {
         builder.add_clone_arg("--single-branch");
     }
+
+    // Objects missing from the reference are fetched from the origin. Dissociating copies the borrowed objects so that the clone keeps working if the reference is moved or pruned.
+    if let Some(reference) = reference {
+        builder.reference(reference.path.clone(), reference.dissociate);
+    }
     run_clone(builder, app)?;
     Ok(())
 }
@@ -871,8 +1627,8 @@ fn copy_dense_config(dense_repo: &Repository, sparse_repo: &Repository) -> Resul
     Ok(())
 }
 
-fn set_up_hooks(sparse_repo: &Path) -> Result<()> {
-    event::init(sparse_repo)?;
+fn set_up_hooks(sparse_repo: &Path, app: Arc<App>) -> Result<()> {
+    event::init(sparse_repo, app)?;
     Ok(())
 }
 
@@ -894,8 +1650,9 @@ fn copy_local_branches(
     sparse_repo: &Repository,
     branch: &str,
     app: Arc<App>,
-    days_of_history: u64,
+    history: History,
 ) -> Result<()> {
+    let shallow_since_date = history.since_date()?;
     let branches = dense_repo
         .branches(Some(git2::BranchType::Local))
         .context("Failed to enumerate local branches in the dense repo")?;
@@ -925,24 +1682,23 @@ fn copy_local_branches(
             .peel_to_commit()
             .context("Failed to peel branch ref to commit")?;
 
-        let dense_commit_date = DateTime::from_utc(
-            NaiveDateTime::from_timestamp(dense_commit.time().seconds(), 0),
-            Utc,
-        )
-        .date();
-
-        let days_of_history: i64 = days_of_history.try_into()?;
-        let shallow_since_datestamp = focus_util::time::date_at_day_in_past(days_of_history)?;
+        let dense_commit_date =
+            NaiveDateTime::from_timestamp(dense_commit.time().seconds(), 0).date();
 
-        if days_of_history > 0 {
-            if dense_commit_date > shallow_since_datestamp {
+        match shallow_since_date {
+            Some(shallow_since_date) if dense_commit_date > shallow_since_date => {
                 valid_local_branches.push((name.to_owned(), dense_commit.to_owned()));
-            } else {
+            }
+            Some(shallow_since_date) => {
                 warn!(
                     "Branch {} is older than the configured limit ({}). Rebase it if you would like it to be included in the new repo.",
-                    name, shallow_since_datestamp
+                    name, shallow_since_date
                 );
             }
+            None if matches!(history, History::Depth(_)) => {
+                valid_local_branches.push((name.to_owned(), dense_commit.to_owned()));
+            }
+            None => {}
         }
     }
 
@@ -1078,11 +1834,28 @@ impl ClonedRepoTemplate {
 
 #[cfg(test)]
 mod test {
-    use crate::{clone::ClonedRepoTemplate, testing::integration::RepoPairFixture};
-    use focus_internals::target::Target;
+    use crate::{
+        clone::{
+            clone_remote, fetch_additional_branches, parse_shallow_since_date,
+            reference_objects_path, staging_path, CloneArgs, CloneBuilder, ClonePhase, CloneState,
+            ClonedRepoTemplate, History, ObjectReference, Origin, PhaseTimings, RecordedCloneArgs,
+            ScpAddress,
+        },
+        testing::integration::RepoPairFixture,
+    };
+    use focus_internals::index::{
+        get_files_to_materialize, DependencyKey, HashContext, PathsToMaterializeResult,
+        RocksDBCache, RocksDBMemoizationCacheExt,
+    };
+    use focus_internals::target::{Label, Target};
     use focus_testing::init_logging;
+    use focus_util::app::App;
+    use maplit::hashset;
 
     use anyhow::Result;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
     use url::Url;
 
     #[test]
@@ -1116,6 +1889,609 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn clone_without_index_performs_no_index_fetch_or_generate() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let label: Label = "//project_a/src/main/java/com/example/cmdline:runner".parse()?;
+        let clone_args = CloneArgs {
+            origin: Some(Origin::Local(fixture.dense_repo_path.clone())),
+            branch: fixture.branch.clone(),
+            projects_and_targets: vec![format!("bazel:{}", label)],
+            do_post_clone_fetch: false,
+            use_index: false,
+            ..Default::default()
+        };
+        crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args,
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+
+        // Nothing was fetched from a remote index.
+        assert!(!fixture
+            .sparse_repo_path
+            .join(".git")
+            .join("focus")
+            .join("index")
+            .exists());
+
+        // Nothing was generated into the local index either.
+        let repo = fixture.sparse_repo()?;
+        let repo = repo.underlying();
+        let head_tree = repo.head()?.peel_to_commit()?.tree()?;
+        let ctx = HashContext::new(repo, &head_tree)?;
+        let odb = RocksDBCache::new(repo);
+        let materialize_result =
+            get_files_to_materialize(&ctx, &odb, hashset! {DependencyKey::BazelPackage(label)})?;
+        assert!(matches!(
+            materialize_result,
+            PathsToMaterializeResult::MissingKeys { .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn initial_commit_is_created_only_for_an_empty_origin() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let empty_origin_path = fixture.dir.path().join("empty");
+        git2::Repository::init_bare(&empty_origin_path)?;
+        let clone_args = CloneArgs {
+            origin: Some(Origin::Local(empty_origin_path)),
+            branch: fixture.branch.clone(),
+            do_post_clone_fetch: false,
+            initial_commit: true,
+            ..Default::default()
+        };
+        crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args,
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+
+        let repo = fixture.sparse_repo()?;
+        let head = repo.underlying().head()?;
+        assert_eq!(
+            head.name(),
+            Some(format!("refs/heads/{}", fixture.branch).as_str())
+        );
+        let head_commit = head.peel_to_commit()?;
+        assert_eq!(head_commit.parent_count(), 0);
+        assert!(head_commit.tree()?.is_empty());
+
+        // An origin with history is cloned as usual.
+        let sparse_repo_path = fixture.dir.path().join("sparse-with-history");
+        let clone_args = CloneArgs {
+            origin: Some(Origin::Local(fixture.dense_repo_path.clone())),
+            branch: fixture.branch.clone(),
+            do_post_clone_fetch: false,
+            initial_commit: true,
+            ..Default::default()
+        };
+        crate::clone::run(
+            sparse_repo_path.clone(),
+            clone_args,
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+        let dense_head = git2::Repository::open(&fixture.dense_repo_path)?
+            .head()?
+            .peel_to_commit()?
+            .id();
+        let sparse_head = git2::Repository::open(&sparse_repo_path)?
+            .head()?
+            .peel_to_commit()?
+            .id();
+        assert_eq!(sparse_head, dense_head);
+
+        Ok(())
+    }
+
+    #[test]
+    fn offline_clone_requires_a_local_origin() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let clone_args = CloneArgs {
+            origin: Some(Origin::Remote(Url::parse(
+                "https://git.example.com/focus-test-repo",
+            )?)),
+            branch: fixture.branch.clone(),
+            offline: true,
+            ..Default::default()
+        };
+        let result = crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args,
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Offline clones require a local path"));
+        assert!(!fixture.sparse_repo_path.exists());
+
+        // The post clone fetch would contact the dense repo's remotes, so it is skipped.
+        let file_url = Url::from_file_path(&fixture.dense_repo_path).unwrap();
+        let clone_args = CloneArgs {
+            origin: Some(Origin::Remote(file_url)),
+            branch: fixture.branch.clone(),
+            do_post_clone_fetch: true,
+            offline: true,
+            ..Default::default()
+        };
+        crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args,
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+        assert!(fixture.sparse_repo()?.working_tree().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_clone_borrows_objects_from_a_mirror() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let not_a_repo = tempfile::tempdir()?;
+        assert!(reference_objects_path(not_a_repo.path()).is_err());
+
+        let mirror = reference_objects_path(&fixture.dense_repo_path)?
+            .expect("The dense repo has its entire history");
+        let destination = fixture.dir.path().join("mirrored");
+        clone_remote(
+            Url::from_file_path(&fixture.dense_repo_path)
+                .unwrap()
+                .as_str(),
+            &destination,
+            &fixture.branch,
+            History::Days(90),
+            Some(&ObjectReference {
+                path: mirror.clone(),
+                dissociate: true,
+            }),
+            fixture.app.clone(),
+        )?;
+
+        let repo = git2::Repository::open(&destination)?;
+        assert_eq!(
+            repo.find_reference(&format!("refs/remotes/origin/{}", fixture.branch))?
+                .peel_to_commit()?
+                .id(),
+            git2::Repository::open(&fixture.dense_repo_path)?
+                .head()?
+                .peel_to_commit()?
+                .id()
+        );
+        // The borrowed objects were copied, so the clone does not depend on the mirror.
+        assert!(!destination
+            .join(".git")
+            .join("objects")
+            .join("info")
+            .join("alternates")
+            .exists());
+
+        // Without dissociating, the clone keeps borrowing from the reference.
+        let destination = fixture.dir.path().join("referencing");
+        clone_remote(
+            Url::from_file_path(&fixture.dense_repo_path)
+                .unwrap()
+                .as_str(),
+            &destination,
+            &fixture.branch,
+            History::Days(90),
+            Some(&ObjectReference {
+                path: mirror.clone(),
+                dissociate: false,
+            }),
+            fixture.app.clone(),
+        )?;
+        let alternates = std::fs::read_to_string(
+            destination
+                .join(".git")
+                .join("objects")
+                .join("info")
+                .join("alternates"),
+        )?;
+        assert_eq!(
+            std::fs::canonicalize(alternates.trim())?,
+            std::fs::canonicalize(mirror.join("objects"))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_clone_falls_back_when_the_mirror_is_shallow() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            CloneArgs {
+                origin: Some(Origin::Local(fixture.dense_repo_path.clone())),
+                branch: fixture.branch.clone(),
+                depth: Some(1),
+                do_post_clone_fetch: false,
+                ..Default::default()
+            },
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+        let sparse_repo = git2::Repository::open(&fixture.sparse_repo_path)?;
+        assert!(sparse_repo.is_shallow());
+        assert_eq!(reference_objects_path(&fixture.sparse_repo_path)?, None);
+
+        // Git skips a shallow reference rather than failing the clone.
+        let destination = fixture.dir.path().join("mirrored");
+        clone_remote(
+            Url::from_file_path(&fixture.dense_repo_path)
+                .unwrap()
+                .as_str(),
+            &destination,
+            &fixture.branch,
+            History::Days(90),
+            Some(&ObjectReference {
+                path: sparse_repo.path().to_owned(),
+                dissociate: true,
+            }),
+            fixture.app.clone(),
+        )?;
+        assert!(!destination
+            .join(".git")
+            .join("objects")
+            .join("info")
+            .join("alternates")
+            .exists());
+        assert!(git2::Repository::open(&destination)?
+            .find_reference(&format!("refs/remotes/origin/{}", fixture.branch))
+            .is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_clone_fetches_additional_branches() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let dense_repo = git2::Repository::open(&fixture.dense_repo_path)?;
+        let head_commit = dense_repo.head()?.peel_to_commit()?;
+        dense_repo.branch("release-1", &head_commit, false)?;
+        dense_repo.branch("release-2", &head_commit, false)?;
+
+        let destination = fixture.dir.path().join("with-releases");
+        clone_remote(
+            Url::from_file_path(&fixture.dense_repo_path)
+                .unwrap()
+                .as_str(),
+            &destination,
+            &fixture.branch,
+            History::Depth(1),
+            None,
+            fixture.app.clone(),
+        )?;
+        let branches = vec![String::from("release-1"), String::from("release-2")];
+        fetch_additional_branches(
+            &destination,
+            &branches,
+            History::Depth(1),
+            fixture.app.clone(),
+        )?;
+
+        let repo = git2::Repository::open(&destination)?;
+        for branch in branches.iter() {
+            assert_eq!(
+                repo.find_reference(&format!("refs/remotes/origin/{}", branch))?
+                    .peel_to_commit()?
+                    .id(),
+                head_commit.id()
+            );
+        }
+        assert_eq!(
+            focus_util::git_helper::read_config_all(
+                &destination,
+                "remote.origin.fetch",
+                fixture.app.clone()
+            )?,
+            vec![
+                format!("+refs/heads/{0}:refs/remotes/origin/{0}", fixture.branch),
+                String::from("+refs/heads/release-1:refs/remotes/origin/release-1"),
+                String::from("+refs/heads/release-2:refs/remotes/origin/release-2"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    fn clone_builder_args(builder: CloneBuilder) -> Result<Vec<String>> {
+        let app = Arc::new(App::new_for_testing()?);
+        let (cmd, _scmd) = builder.build(app)?;
+        Ok(cmd
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    #[test]
+    fn clone_builder_passes_reference_args() -> Result<()> {
+        let mut builder = CloneBuilder::new(PathBuf::from("/tmp/sparse"));
+        builder.fetch_url(String::from("https://git.example.com/repo"));
+        let args = clone_builder_args(builder)?;
+        assert!(!args.iter().any(|arg| arg.starts_with("--reference")));
+        assert!(!args.contains(&String::from("--dissociate")));
+
+        let mut builder = CloneBuilder::new(PathBuf::from("/tmp/sparse"));
+        builder
+            .fetch_url(String::from("https://git.example.com/repo"))
+            .reference(PathBuf::from("/mnt/cache/repo.git"), false);
+        let args = clone_builder_args(builder)?;
+        assert!(args.contains(&String::from("--reference-if-able=/mnt/cache/repo.git")));
+        assert!(!args.contains(&String::from("--dissociate")));
+
+        let mut builder = CloneBuilder::new(PathBuf::from("/tmp/sparse"));
+        builder
+            .fetch_url(String::from("https://git.example.com/repo"))
+            .reference(PathBuf::from("/mnt/cache/repo.git"), true);
+        let args = clone_builder_args(builder)?;
+        assert!(args.contains(&String::from("--reference-if-able=/mnt/cache/repo.git")));
+        assert!(args.contains(&String::from("--dissociate")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn clone_builder_passes_depth_or_shallow_since() -> Result<()> {
+        let date = parse_shallow_since_date("2022-01-01")?;
+
+        let mut builder = CloneBuilder::new(PathBuf::from("/tmp/sparse"));
+        builder
+            .fetch_url(String::from("https://git.example.com/repo"))
+            .depth(10)
+            .depth(5);
+        let args = clone_builder_args(builder)?;
+        assert!(args.contains(&String::from("--depth=5")));
+        assert!(!args.iter().any(|arg| arg.starts_with("--depth=10")));
+        assert!(!args.iter().any(|arg| arg.starts_with("--shallow-since")));
+
+        let mut builder = CloneBuilder::new(PathBuf::from("/tmp/sparse"));
+        builder
+            .fetch_url(String::from("https://git.example.com/repo"))
+            .shallow_since(date);
+        let args = clone_builder_args(builder)?;
+        assert!(args.contains(&String::from("--shallow-since=2022-01-01")));
+        assert!(!args.iter().any(|arg| arg.starts_with("--depth")));
+
+        let mut builder = CloneBuilder::new(PathBuf::from("/tmp/sparse"));
+        builder
+            .fetch_url(String::from("https://git.example.com/repo"))
+            .shallow_since(date)
+            .depth(5);
+        assert!(clone_builder_args(builder).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn history_options_take_precedence_over_days() -> Result<()> {
+        let date = parse_shallow_since_date("2022-03-01")?;
+        assert!(History::new(90, Some(date), Some(5)).is_err());
+        assert_eq!(History::new(90, None, Some(5))?, History::Depth(5));
+        assert_eq!(History::new(90, Some(date), None)?, History::Since(date));
+        assert_eq!(History::new(90, None, None)?, History::Days(90));
+        assert!(History::new(90, None, Some(0)).is_err());
+
+        assert_eq!(History::Depth(5).fetch_arg()?.as_deref(), Some("--depth=5"));
+        assert_eq!(
+            History::Since(date).fetch_arg()?.as_deref(),
+            Some("--shallow-since=2022-03-01")
+        );
+        assert_eq!(History::Days(0).fetch_arg()?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_clone_can_be_limited_by_depth() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let destination = fixture.dir.path().join("shallow");
+        clone_remote(
+            Url::from_file_path(&fixture.dense_repo_path)
+                .unwrap()
+                .as_str(),
+            &destination,
+            &fixture.branch,
+            History::Depth(1),
+            None,
+            fixture.app.clone(),
+        )?;
+
+        let shallow = std::fs::read_to_string(destination.join(".git").join("shallow"))?;
+        assert_eq!(shallow.lines().count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_clone_can_be_resumed() -> Result<()> {
+        init_logging();
+
+        let fixture = RepoPairFixture::new()?;
+        let clone_args = |projects_and_targets: Vec<String>, resume: bool| CloneArgs {
+            origin: Some(Origin::Local(fixture.dense_repo_path.clone())),
+            branch: fixture.branch.clone(),
+            projects_and_targets,
+            do_post_clone_fetch: false,
+            resume,
+            ..Default::default()
+        };
+        let undefined_project = || vec![String::from("team_zissou/undefined")];
+        let project_b = || vec![String::from("team_zissou/project_b")];
+        let staging = staging_path(&fixture.sparse_repo_path)?;
+
+        // Selecting an undefined project fails after the repository is fetched. Clones that are not resumable clean up after themselves.
+        let result = crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args(undefined_project(), false),
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        );
+        assert!(!result.unwrap_err().to_string().contains("--resume"));
+        assert!(!fixture.sparse_repo_path.exists());
+        assert!(!staging.exists());
+
+        // Resumable clones are kept along with the arguments they were started with.
+        let result = crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args(undefined_project(), true),
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        );
+        assert!(result.unwrap_err().to_string().contains("--resume"));
+        assert!(!fixture.sparse_repo_path.exists());
+        let mut state = CloneState::load(&staging)?;
+        assert!(state.is_complete(ClonePhase::Fetch));
+        assert!(state.is_complete(ClonePhase::CreateTrees));
+        assert!(!state.is_complete(ClonePhase::InitialSync));
+        assert_eq!(
+            state
+                .args
+                .as_ref()
+                .map(|args| args.projects_and_targets.clone()),
+            Some(undefined_project())
+        );
+
+        // A clone with different arguments does not continue it.
+        let result = crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args(project_b(), true),
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("different arguments"));
+        assert!(staging.is_dir());
+
+        // Pretend that the clone was started with `project_b` and interrupted at the same point.
+        if let Some(args) = state.args.as_mut() {
+            args.projects_and_targets = project_b();
+        }
+        state.complete(&staging, ClonePhase::CreateTrees)?;
+        crate::clone::run(
+            fixture.sparse_repo_path.clone(),
+            clone_args(project_b(), true),
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+        assert!(!staging.exists());
+        assert!(!CloneState::path(&fixture.sparse_repo_path).exists());
+        assert!(fixture.sparse_repo()?.working_tree().is_ok());
+
+        // A leftover partial clone does not stand in the way of a new clone, which replaces it.
+        let other_path = fixture.sparse_repo_path.with_file_name("other_sparse_repo");
+        let other_staging = staging_path(&other_path)?;
+        assert!(crate::clone::run(
+            other_path.clone(),
+            clone_args(undefined_project(), true),
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )
+        .is_err());
+        assert!(other_staging.is_dir());
+        crate::clone::run(
+            other_path.clone(),
+            clone_args(project_b(), false),
+            None,
+            &fixture.tracker,
+            fixture.app.clone(),
+        )?;
+        assert!(other_path.is_dir());
+        assert!(!other_staging.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn clone_state_round_trips() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(CloneState::load(dir.path())?, CloneState::default());
+
+        let mut state = CloneState::new(RecordedCloneArgs {
+            branch: String::from("main"),
+            ..Default::default()
+        });
+        state.complete(dir.path(), ClonePhase::Fetch)?;
+        state.complete(dir.path(), ClonePhase::CopyBranches)?;
+        let loaded = CloneState::load(dir.path())?;
+        assert_eq!(loaded, state);
+        assert!(loaded.is_complete(ClonePhase::CopyBranches));
+        assert!(!loaded.is_complete(ClonePhase::InitialSync));
+
+        CloneState::remove(dir.path())?;
+        assert_eq!(CloneState::load(dir.path())?, CloneState::default());
+
+        assert_eq!(
+            staging_path(&dir.path().join("source"))?,
+            dir.path().join(".source.focus-clone")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn timing_breakdown_has_an_entry_per_phase() -> Result<()> {
+        let phases = [
+            ClonePhase::Fetch,
+            ClonePhase::CopyBranches,
+            ClonePhase::CreateTrees,
+            ClonePhase::InitialSync,
+            ClonePhase::PostCloneFetch,
+        ];
+        let mut timings = PhaseTimings::default();
+        for phase in phases {
+            timings.time(phase.label(), || Ok::<(), anyhow::Error>(()))?;
+        }
+        timings.record("migrations", Duration::from_millis(1500));
+
+        let breakdown = timings.breakdown();
+        let lines: Vec<&str> = breakdown.lines().collect();
+        assert_eq!(lines.len(), phases.len() + 2);
+        for (line, phase) in lines.iter().zip(phases) {
+            assert!(line.starts_with(phase.label()), "{}", line);
+        }
+        assert!(lines[phases.len()].starts_with("migrations"));
+        assert!(lines[phases.len()].ends_with("1.500s"));
+        assert!(lines[phases.len() + 1].starts_with("total"));
+        assert!(timings.total() >= Duration::from_millis(1500));
+
+        Ok(())
+    }
+
     #[test]
     fn test_template_from_url() -> Result<()> {
         assert_eq!(
@@ -1137,6 +2513,74 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn origin_classifies_urls_scp_addresses_and_paths() -> Result<()> {
+        assert_eq!(
+            Origin::try_from("git@github.com:org/repo.git")?,
+            Origin::Scp(ScpAddress {
+                user: Some("git".to_owned()),
+                host: "github.com".to_owned(),
+                path: "org/repo.git".to_owned(),
+            })
+        );
+        assert_eq!(
+            Origin::try_from("example.com:/srv/git/repo.git")?,
+            Origin::Scp(ScpAddress {
+                user: None,
+                host: "example.com".to_owned(),
+                path: "/srv/git/repo.git".to_owned(),
+            })
+        );
+        assert_eq!(
+            Origin::try_from("ssh://git@example.com/org/repo.git")?,
+            Origin::Remote(Url::parse("ssh://git@example.com/org/repo.git")?)
+        );
+        assert_eq!(
+            Origin::try_from("https://github.com/org/repo")?,
+            Origin::Remote(Url::parse("https://github.com/org/repo")?)
+        );
+        assert_eq!(
+            Origin::try_from("/abs/path")?,
+            Origin::Local(PathBuf::from("/abs/path"))
+        );
+        assert_eq!(
+            Origin::try_from("./rel/path")?,
+            Origin::Local(PathBuf::from("./rel/path"))
+        );
+        // Paths with a colon after a slash are not scp-style addresses.
+        assert_eq!(
+            Origin::try_from("./rel:path")?,
+            Origin::Local(PathBuf::from("./rel:path"))
+        );
+        assert_eq!(
+            Origin::try_from("/tmp/a:b")?,
+            Origin::Local(PathBuf::from("/tmp/a:b"))
+        );
+        assert!(Origin::try_from("https://[bad").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scp_addresses_round_trip_and_map_to_ssh_urls() -> Result<()> {
+        let origin = Origin::try_from("git@github.com:envoyproxy/envoy")?;
+        assert!(!origin.is_local());
+        assert_eq!(
+            origin.fetch_url().as_deref(),
+            Some("git@github.com:envoyproxy/envoy")
+        );
+        assert_eq!(
+            origin.url(),
+            Some(Url::parse("ssh://git@github.com/envoyproxy/envoy")?)
+        );
+        assert_eq!(
+            origin.url().and_then(ClonedRepoTemplate::from_url),
+            Some(ClonedRepoTemplate::Envoy)
+        );
+
+        Ok(())
+    }
 }
 
 #[cfg(feature = "twttr")]
@@ -0,0 +1,297 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{path::Path, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+use focus_internals::model::repo;
+use focus_util::{
+    app::{App, ExitCode},
+    git_helper::{self, ConfigExt},
+    sandbox::cleanup,
+};
+use tracing::warn;
+
+/// The namespace of the Git configuration keys that focus reads.
+const NAMESPACE: &str = "focus";
+
+/// A focus configuration key that is read somewhere.
+#[derive(Clone, Copy, Debug)]
+pub struct KnownKey {
+    /// The full name of the key, like `focus.uuid`.
+    pub name: &'static str,
+
+    /// What the key controls.
+    pub description: &'static str,
+
+    /// Whether focus maintains the key itself, so that `set` refuses to change it without `force`.
+    pub managed: bool,
+}
+
+impl KnownKey {
+    const fn new(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            managed: false,
+        }
+    }
+
+    const fn managed(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            managed: true,
+        }
+    }
+}
+
+/// The focus configuration keys that are read somewhere, in the order `list` prints them.
+pub const KNOWN_KEYS: &[KnownKey] = &[
+    KnownKey::new(
+        repo::BAZEL_ONE_SHOT_RESOLUTION_CONFIG_KEY,
+        "Resolve Bazel targets in a single query instead of incrementally.",
+    ),
+    KnownKey::managed(
+        repo::FILTER_VIEW,
+        "Whether the sparse checkout is filtered to the selection. Managed by `focus on` and `focus off`.",
+    ),
+    KnownKey::new(
+        repo::POST_SYNC_HOOK_ALIAS_CONFIG_KEY,
+        "A command to run after each sync, used when focus.sync.post-sync-hook is not set.",
+    ),
+    KnownKey::new(
+        repo::POST_CHECKOUT_AUTO_SYNC_CONFIG_KEY,
+        "Sync after a checkout changes the build graph, rather than only warning.",
+    ),
+    KnownKey::new(
+        repo::POST_MERGE_AUTO_SYNC_CONFIG_KEY,
+        "Sync after a merge changes the build graph, rather than only warning.",
+    ),
+    KnownKey::new(
+        repo::PREEMPTIVE_SYNC_ENABLED_CONFIG_KEY,
+        "Sync in the background before the user needs it.",
+    ),
+    KnownKey::new(
+        repo::PREEMPTIVE_SYNC_USER_IDLE_MILLIS_THRESHOLD_CONFIG_KEY,
+        "How long, in milliseconds, the user must be idle before a preemptive sync starts.",
+    ),
+    KnownKey::new(
+        repo::PROJECT_CACHE_ENDPOINT_CONFIG_KEY,
+        "The URL of the project cache.",
+    ),
+    KnownKey::new(
+        repo::PROJECT_CACHE_INCLUDE_HEADERS_FILE_CONFIG_KEY,
+        "A file of HTTP headers to send with project cache requests.",
+    ),
+    KnownKey::new(
+        cleanup::Config::CLEANUP_KEY,
+        "Whether old sandboxes are removed. Read from the global configuration.",
+    ),
+    KnownKey::new(
+        cleanup::Config::MAX_NUM_SANDBOXES_KEY,
+        "The number of sandboxes to keep. Read from the global configuration.",
+    ),
+    KnownKey::new(
+        cleanup::Config::PRESERVE_HOURS_KEY,
+        "How many hours sandboxes are kept. Read from the global configuration.",
+    ),
+    KnownKey::new(
+        repo::MAX_SELECTION_PERCENTAGE_CONFIG_KEY,
+        "The largest share of the repository, as a percentage, that a selection may check out without a warning.",
+    ),
+    KnownKey::managed(
+        repo::ATOMIC_PREVIOUS_CONE_CONFIG_KEY,
+        "Set while a sync is changing the sparse checkout so that an interrupted sync can be rolled back. Managed by focus.",
+    ),
+    KnownKey::new(
+        repo::POST_SYNC_HOOK_CONFIG_KEY,
+        "A command to run after each sync, given the changed paths on standard input.",
+    ),
+    KnownKey::managed(
+        repo::UUID_CONFIG_KEY,
+        "An identifier for the repository used in telemetry. Managed by focus.",
+    ),
+    KnownKey::managed(
+        repo::VERSION_CONFIG_KEY,
+        "The version of the repository's focus data. Managed by focus.",
+    ),
+];
+
+/// The entry in `KNOWN_KEYS` for `key`, if it is one that focus reads.
+pub fn known_key(key: &str) -> Option<&'static KnownKey> {
+    KNOWN_KEYS
+        .iter()
+        .find(|known| known.name.eq_ignore_ascii_case(key))
+}
+
+/// The description of `key` if it is one that focus reads.
+pub fn describe(key: &str) -> Option<&'static str> {
+    known_key(key).map(|known| known.description)
+}
+
+/// The full name of `key` in the focus namespace. The `focus.` prefix may be left out.
+fn qualified_key(key: &str) -> Result<String> {
+    let key = key.trim();
+    let qualified = match key.split_once('.') {
+        Some((section, _)) if section.eq_ignore_ascii_case(NAMESPACE) => key.to_owned(),
+        _ => format!("{}.{}", NAMESPACE, key),
+    };
+    if qualified.len() == NAMESPACE.len() + 1 || qualified.ends_with('.') {
+        bail!("'{}' is not a valid configuration key", key);
+    }
+    Ok(qualified)
+}
+
+/// Print the value of `key` in the repo's configuration. Like `git config`, exits with status 1 if it is not set.
+pub fn get(sparse_repo: &Path, key: &str, app: Arc<App>) -> Result<ExitCode> {
+    let key = qualified_key(key)?;
+    match git_helper::read_config(sparse_repo, &key, app)? {
+        Some(value) => {
+            println!("{}", value);
+            Ok(ExitCode(0))
+        }
+        None => Ok(ExitCode(1)),
+    }
+}
+
+/// Set `key` to `value` in the repo's local configuration. Keys that focus manages itself are only changed when `force` is set.
+pub fn set(
+    sparse_repo: &Path,
+    key: &str,
+    value: &str,
+    force: bool,
+    app: Arc<App>,
+) -> Result<ExitCode> {
+    let key = qualified_key(key)?;
+    match known_key(&key) {
+        Some(known) if known.managed && !force => {
+            bail!(
+                "{} is managed by focus; pass --force to change it anyway",
+                known.name
+            );
+        }
+        Some(_) => {}
+        None => warn!(%key, "This key is not read by focus"),
+    }
+    git_helper::write_config(sparse_repo, &key, value, app)
+        .with_context(|| format!("Setting {}", key))?;
+    Ok(ExitCode(0))
+}
+
+/// The focus configuration of the repo, as key and value pairs in the order Git reports them.
+pub fn entries(sparse_repo: &Path) -> Result<Vec<(String, String)>> {
+    let repo = git2::Repository::open(sparse_repo).context("Opening the repository")?;
+    let config = repo.config().context("Reading the configuration")?;
+    config.dump_config(Some(&format!("^{}\\.", NAMESPACE)))
+}
+
+/// Print every focus key set in the repo's configuration with its description, followed by the known keys that are not set.
+pub fn list(sparse_repo: &Path) -> Result<ExitCode> {
+    let entries = entries(sparse_repo)?;
+    for (key, value) in entries.iter() {
+        println!("{}={}", key, value);
+        if let Some(description) = describe(key) {
+            println!("    {}", description);
+        }
+    }
+
+    let unset: Vec<&KnownKey> = KNOWN_KEYS
+        .iter()
+        .filter(|known| {
+            !entries
+                .iter()
+                .any(|(key, _)| key.eq_ignore_ascii_case(known.name))
+        })
+        .collect();
+    if !unset.is_empty() {
+        println!();
+        println!("Not set:");
+        for known in unset {
+            println!("{}", known.name);
+            println!("    {}", known.description);
+        }
+    }
+
+    Ok(ExitCode(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_qualified_with_the_namespace() -> Result<()> {
+        assert_eq!(
+            qualified_key("sync.post-sync-hook")?,
+            "focus.sync.post-sync-hook"
+        );
+        assert_eq!(
+            qualified_key("focus.sync.post-sync-hook")?,
+            "focus.sync.post-sync-hook"
+        );
+        assert_eq!(qualified_key("Focus.uuid")?, "Focus.uuid");
+        assert_eq!(qualified_key("core.bare")?, "focus.core.bare");
+        assert!(qualified_key("").is_err());
+        assert!(qualified_key("focus.").is_err());
+        assert!(describe("focus.post-merge.auto-sync").is_some());
+        assert!(describe("focus.no-such-key").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn set_and_get_round_trip() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        git2::Repository::init(dir.path())?;
+
+        assert_eq!(
+            get(dir.path(), "post-merge.auto-sync", app.clone())?,
+            ExitCode(1)
+        );
+        set(
+            dir.path(),
+            "post-merge.auto-sync",
+            "true",
+            false,
+            app.clone(),
+        )?;
+        assert_eq!(
+            git_helper::read_config(dir.path(), "focus.post-merge.auto-sync", app.clone())?,
+            Some(String::from("true"))
+        );
+        assert_eq!(
+            get(dir.path(), "focus.post-merge.auto-sync", app)?,
+            ExitCode(0)
+        );
+        assert!(entries(dir.path())?.contains(&(
+            String::from("focus.post-merge.auto-sync"),
+            String::from("true")
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn managed_keys_are_only_set_with_force() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        git2::Repository::init(dir.path())?;
+
+        for key in ["uuid", "focus.version", "Focus.Sync.Atomic-Previous-Cone"] {
+            assert!(set(dir.path(), key, "1", false, app.clone()).is_err());
+        }
+        assert_eq!(
+            git_helper::read_config(dir.path(), repo::UUID_CONFIG_KEY, app.clone())?,
+            None
+        );
+
+        set(dir.path(), "uuid", "1", true, app.clone())?;
+        assert_eq!(
+            git_helper::read_config(dir.path(), repo::UUID_CONFIG_KEY, app)?,
+            Some(String::from("1"))
+        );
+
+        Ok(())
+    }
+}
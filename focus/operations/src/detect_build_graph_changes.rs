@@ -14,7 +14,7 @@ use focus_util::{
     git_helper, paths,
 };
 
-fn find_committed_changes(app: Arc<App>, repo_path: &Path) -> Result<Vec<PathBuf>> {
+pub(crate) fn find_committed_changes(app: Arc<App>, repo_path: &Path) -> Result<Vec<PathBuf>> {
     let repo = Repo::open(repo_path, app.clone())?;
     let working_tree = repo.working_tree()?;
     let sync_state_oid = {
@@ -0,0 +1,336 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt::Display, path::Path, sync::Arc};
+
+use anyhow::{Context, Result};
+use focus_internals::index::is_content_store_remote;
+use focus_internals::model::repo::{Repo, WorkingTree};
+use focus_util::{
+    app::{App, ExitCode},
+    git_helper,
+};
+
+/// How serious a failed check is. Any critical problem makes `focus doctor` exit with an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+/// Why a check failed and what the user can do about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub detail: String,
+    pub suggestion: String,
+}
+
+impl Problem {
+    fn new(severity: Severity, detail: impl Display, suggestion: impl Into<String>) -> Self {
+        Self {
+            severity,
+            detail: detail.to_string(),
+            suggestion: suggestion.into(),
+        }
+    }
+
+    fn critical(detail: impl Display, suggestion: impl Into<String>) -> Self {
+        Self::new(Severity::Critical, detail, suggestion)
+    }
+
+    fn warning(detail: impl Display, suggestion: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, detail, suggestion)
+    }
+}
+
+/// The outcome of a single check. `problem` is `None` if the check passed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub category: &'static str,
+    pub description: String,
+    pub problem: Option<Problem>,
+}
+
+/// The findings of every check run against a sparse repo, in the order they ran.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DoctorReport {
+    pub findings: Vec<Finding>,
+}
+
+impl DoctorReport {
+    fn record(
+        &mut self,
+        category: &'static str,
+        description: impl Into<String>,
+        problem: Option<Problem>,
+    ) {
+        self.findings.push(Finding {
+            category,
+            description: description.into(),
+            problem,
+        });
+    }
+
+    /// Whether any check failed with a critical problem.
+    pub fn has_critical_problems(&self) -> bool {
+        self.findings.iter().any(|finding| {
+            matches!(
+                finding.problem,
+                Some(Problem {
+                    severity: Severity::Critical,
+                    ..
+                })
+            )
+        })
+    }
+
+    /// Print the findings grouped by category, with a suggested fix for each problem.
+    pub fn print(&self) {
+        let mut category = None;
+        for finding in self.findings.iter() {
+            if category != Some(finding.category) {
+                category = Some(finding.category);
+                println!("{}:", finding.category);
+            }
+            match &finding.problem {
+                None => println!("  [ok]       {}", finding.description),
+                Some(problem) => {
+                    let label = match problem.severity {
+                        Severity::Warning => "[warning]",
+                        Severity::Critical => "[critical]",
+                    };
+                    println!("  {:10} {}: {}", label, finding.description, problem.detail);
+                    println!("             Suggestion: {}", problem.suggestion);
+                }
+            }
+        }
+    }
+}
+
+/// Check the sparse checkout configuration and profile of `working_tree`.
+fn check_sparse_checkout(working_tree: &WorkingTree) -> Option<Problem> {
+    let config = match working_tree.git_repo().config() {
+        Ok(config) => config,
+        Err(e) => return Some(Problem::critical(e, "Check that .git/config is readable")),
+    };
+    if !config.get_bool("core.sparseCheckout").unwrap_or(false) {
+        return Some(Problem::critical(
+            "core.sparseCheckout is not enabled",
+            "Run `git config core.sparseCheckout true`, then `focus sync`",
+        ));
+    }
+
+    let sparse_checkout_path = working_tree.sparse_checkout_path();
+    let contents = match std::fs::read_to_string(&sparse_checkout_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return Some(Problem::critical(
+                format!("Reading {}: {}", sparse_checkout_path.display(), e),
+                "Run `focus sync` to write the sparse checkout profile",
+            ))
+        }
+    };
+    let has_patterns = contents
+        .lines()
+        .map(str::trim)
+        .any(|line| !line.is_empty() && !line.starts_with('#'));
+    if !has_patterns {
+        return Some(Problem::critical(
+            "The sparse checkout profile has no patterns",
+            "Run `focus sync` to write the sparse checkout profile",
+        ));
+    }
+
+    None
+}
+
+/// Run every check against the sparse repo at `sparse_repo`. Whether migrations are pending is determined by `is_upgrade_required`, which is supplied by the caller since the migrations live outside this crate.
+pub fn diagnose(
+    sparse_repo: &Path,
+    app: Arc<App>,
+    is_upgrade_required: impl FnOnce(&Path, Arc<App>) -> Result<bool>,
+) -> Result<DoctorReport> {
+    let mut report = DoctorReport::default();
+
+    let focus_dir = sparse_repo.join(".focus");
+    if !focus_dir.is_dir() {
+        report.record(
+            "Repository",
+            "Focus configuration directory is present",
+            Some(Problem::critical(
+                format!("{} does not exist", focus_dir.display()),
+                "Make sure you are inside a repo created by `focus new`",
+            )),
+        );
+        return Ok(report);
+    }
+    report.record(
+        "Repository",
+        "Focus configuration directory is present",
+        None,
+    );
+
+    let repo = match Repo::open(sparse_repo, app.clone()) {
+        Ok(repo) => repo,
+        Err(e) => {
+            report.record(
+                "Repository",
+                "Repository can be opened",
+                Some(Problem::critical(
+                    format!("{:#}", e),
+                    "If the repo is damaged, clone it again with `focus new`",
+                )),
+            );
+            return Ok(report);
+        }
+    };
+    report.record("Repository", "Repository can be opened", None);
+
+    let problem = match is_upgrade_required(sparse_repo, app.clone()) {
+        Ok(false) => None,
+        Ok(true) => Some(Problem::critical(
+            "Migrations are pending",
+            "Run `focus upgrade`",
+        )),
+        Err(e) => Some(Problem::critical(
+            format!("{:#}", e),
+            "Check that .focus/manifest.json is readable, then run `focus upgrade`",
+        )),
+    };
+    report.record("Repository", "Repository is up to date", problem);
+
+    let problem = super::ensure_clean::run(sparse_repo, app.clone())
+        .err()
+        .map(|e| Problem::warning(e, "Commit or stash your changes before syncing"));
+    report.record("Working tree", "Working tree is clean", problem);
+
+    let problem = match repo.working_tree() {
+        Ok(working_tree) => check_sparse_checkout(&working_tree),
+        Err(e) => Some(Problem::critical(
+            format!("{:#}", e),
+            "If the repo is damaged, clone it again with `focus new`",
+        )),
+    };
+    report.record("Working tree", "Sparse checkout is configured", problem);
+
+    let index_config = &repo.config().index;
+    let description = "Index remote is reachable";
+    if !index_config.enabled {
+        report.record("Index", "Index fetching is disabled", None);
+    } else if index_config.remotes().is_empty() {
+        report.record(
+            "Index",
+            description,
+            Some(Problem::warning(
+                "No index remote is configured",
+                "Set `remote` in the index configuration or disable index fetching",
+            )),
+        );
+    } else if app.is_offline() {
+        report.record(
+            "Index",
+            format!("{} (not checked with --offline)", description),
+            None,
+        );
+    } else {
+        let remotes = index_config.remotes();
+        for remote in remotes.iter() {
+            let description = match remotes.len() {
+                1 => description.to_owned(),
+                _ => format!("Index remote {} is reachable", remote),
+            };
+            if is_content_store_remote(remote) {
+                report.record(
+                    "Index",
+                    format!(
+                        "{} (not checked for content-addressable stores)",
+                        description
+                    ),
+                    None,
+                );
+                continue;
+            }
+            let problem = git_helper::ls_remote(remote, app.clone())
+                .with_context(|| format!("Contacting {}", remote))
+                .err()
+                .map(|e| {
+                    Problem::warning(
+                        format!("{:#}", e),
+                        "Check your network connection and access to the index remote",
+                    )
+                });
+            report.record("Index", description, problem);
+        }
+    }
+
+    Ok(report)
+}
+
+pub fn run(
+    sparse_repo: &Path,
+    app: Arc<App>,
+    is_upgrade_required: impl FnOnce(&Path, Arc<App>) -> Result<bool>,
+) -> Result<ExitCode> {
+    let report = diagnose(sparse_repo, app, is_upgrade_required)?;
+    report.print();
+    if report.has_critical_problems() {
+        eprintln!("Critical problems were found. Address them and run `focus doctor` again.");
+        Ok(ExitCode(1))
+    } else {
+        Ok(ExitCode(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparse_checkout_problems_are_critical() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let working_tree = WorkingTree::new(git2::Repository::init(dir.path())?)?;
+        let severity = |working_tree: &WorkingTree| {
+            check_sparse_checkout(working_tree).map(|problem| problem.severity)
+        };
+
+        assert_eq!(severity(&working_tree), Some(Severity::Critical));
+
+        working_tree
+            .git_repo()
+            .config()?
+            .set_bool("core.sparseCheckout", true)?;
+        assert_eq!(severity(&working_tree), Some(Severity::Critical));
+
+        let sparse_checkout_path = working_tree.sparse_checkout_path();
+        std::fs::create_dir_all(sparse_checkout_path.parent().unwrap())?;
+        std::fs::write(&sparse_checkout_path, "# empty\n")?;
+        assert_eq!(severity(&working_tree), Some(Severity::Critical));
+
+        std::fs::write(&sparse_checkout_path, "/*\n!/*/\n")?;
+        assert_eq!(severity(&working_tree), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_critical_problems_fail_the_report() {
+        let mut report = DoctorReport::default();
+        report.record("Index", "Index remote is reachable", None);
+        assert!(!report.has_critical_problems());
+
+        report.record(
+            "Working tree",
+            "Working tree is clean",
+            Some(Problem::warning("dirty", "commit")),
+        );
+        assert!(!report.has_critical_problems());
+
+        report.record(
+            "Repository",
+            "Repository is up to date",
+            Some(Problem::critical("pending", "upgrade")),
+        );
+        assert!(report.has_critical_problems());
+    }
+}
@@ -2,59 +2,254 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::{Context, Result};
+use focus_internals::model::repo::Repo;
 use focus_util::app::{App, ExitCode};
+use focus_util::git_helper;
+use focus_util::lock_file::LockFile;
 use std::io::Write;
-use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::PathBuf;
 use std::{fs::File, path::Path, sync::Arc};
-use tracing::debug;
+use tracing::{debug, info, warn};
 
+use crate::detect_build_graph_changes::find_committed_changes;
 use crate::sync::{SyncMode, SyncRequest};
 
+/// The hooks installed by `init` and `install_hooks` and removed by `uninstall_hooks`.
+pub const HOOK_NAMES: &[&str] = &["post-checkout", "post-merge"];
+
+/// Hooks that older versions of `init` installed but that are no longer handled. `uninstall_hooks` still removes them.
+const LEGACY_HOOK_NAMES: &[&str] = &["post-commit"];
+
+/// Ends each line that `install_hooks` adds to a hook script, so that `uninstall_hooks` can remove exactly those lines.
+const HOOK_MARKER: &str = "# Added by `focus hooks install`";
+
+/// The directory Git runs the hooks of the repo at `repo_path` from.
+pub fn hooks_dir(repo_path: &Path, app: Arc<App>) -> Result<PathBuf> {
+    git_helper::git_path(repo_path, "hooks", app)
+        .with_context(|| format!("Finding the hooks directory of {}", repo_path.display()))
+}
+
 /// Initializes hooks in passed in repo
-pub fn init(repo_path: &Path) -> Result<()> {
+pub fn init(repo_path: &Path, app: Arc<App>) -> Result<()> {
     debug!("Writing hooks to {}", repo_path.display());
 
-    let hooks = vec!["post-merge", "post-commit"];
-    let hooks_dir = repo_path.join(".git").join("hooks");
-    write_hooks_to_dir(&hooks, &hooks_dir)?;
+    let hooks_dir = hooks_dir(repo_path, app)?;
+    std::fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Creating {}", hooks_dir.display()))?;
+    for hook in HOOK_NAMES {
+        install_hook(&hooks_dir, hook)?;
+    }
 
     Ok(())
 }
 
-fn write_hooks_to_dir(hooks: &[&str], dir: &Path) -> Result<()> {
-    for hook in hooks {
-        let focus_exe = &std::env::current_exe().unwrap_or_else(|_| PathBuf::from("focus"));
-        let focus_exe_path = focus_exe.file_name().unwrap().to_string_lossy();
-        let contents = format!("{} event {}", focus_exe_path, hook);
+fn focus_exe_name() -> String {
+    let focus_exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("focus"));
+    focus_exe
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned()
+}
 
-        let file_path = dir.join(hook);
-        let mut file = File::options()
-            .write(true)
-            .create(true)
-            .mode(0o755)
-            .open(&file_path)
-            .context(format!("opening/creating {}", hook))?;
-        writeln!(file, "{}", contents)
-            .context(format!("writing contents to {}", file_path.display()))?;
+fn read_hook(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Reading {}", path.display())),
     }
+}
 
-    Ok(())
+fn write_hook(path: &Path, lines: &[&str]) -> Result<()> {
+    let mut file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o755)
+        .open(path)
+        .with_context(|| format!("Opening {}", path.display()))?;
+    writeln!(file, "{}", lines.join("\n"))
+        .with_context(|| format!("Writing {}", path.display()))?;
+    let mut permissions = file.metadata()?.permissions();
+    permissions.set_mode(0o755);
+    file.set_permissions(permissions)
+        .with_context(|| format!("Making {} executable", path.display()))
 }
 
-pub fn post_merge(app: Arc<App>) -> Result<ExitCode> {
-    let current_dir = std::env::current_dir().context("Failed to obtain current directory")?;
-    debug!(sparse_repo = ?current_dir.display(), "Running post-merge hook");
-    let _response = crate::sync::run(&SyncRequest::new(&current_dir, SyncMode::Incremental), app)?;
+/// Add a line invoking `focus event <hook>` to the script for `hook` in `hooks_dir`, keeping its other content. An unmarked line written by older versions of `init` is replaced. Returns whether the script changed.
+fn install_hook(hooks_dir: &Path, hook: &str) -> Result<bool> {
+    let path = hooks_dir.join(hook);
+    let content = read_hook(&path)?.unwrap_or_default();
+    let invocation = format!("{} event {}", focus_exe_name(), hook);
+    let line = format!("{} {}", invocation, HOOK_MARKER);
+    if content.lines().any(|l| l == line) {
+        return Ok(false);
+    }
+
+    let mut lines: Vec<&str> = content
+        .lines()
+        .filter(|l| l.trim() != invocation && !l.ends_with(HOOK_MARKER))
+        .collect();
+    if lines.is_empty() {
+        lines.push("#!/bin/sh");
+    }
+    lines.push(&line);
+    write_hook(&path, &lines)?;
+    Ok(true)
+}
+
+/// Remove the lines added by `install_hook`, or by older versions of `init`, from the script for `hook` in `hooks_dir`, deleting the script if nothing else is left in it. Returns whether the script changed.
+fn uninstall_hook(hooks_dir: &Path, hook: &str) -> Result<bool> {
+    let path = hooks_dir.join(hook);
+    let content = match read_hook(&path)? {
+        Some(content) => content,
+        None => return Ok(false),
+    };
+    let invocation = format!("{} event {}", focus_exe_name(), hook);
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|l| l.trim() != invocation && !l.ends_with(HOOK_MARKER))
+        .collect();
+    if lines.len() == content.lines().count() {
+        return Ok(false);
+    }
+
+    if lines
+        .iter()
+        .all(|l| l.trim().is_empty() || l.starts_with("#!"))
+    {
+        std::fs::remove_file(&path).with_context(|| format!("Removing {}", path.display()))?;
+    } else {
+        write_hook(&path, &lines)?;
+    }
+    Ok(true)
+}
+
+/// Install the `HOOK_NAMES` hooks in `sparse_repo`, appending to any existing hook scripts. Installing again changes nothing.
+pub fn install_hooks(sparse_repo: &Path, app: Arc<App>) -> Result<ExitCode> {
+    let hooks_dir = hooks_dir(sparse_repo, app)?;
+    std::fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Creating {}", hooks_dir.display()))?;
+    for hook in HOOK_NAMES {
+        if install_hook(&hooks_dir, hook)? {
+            info!("Installed the {} hook", hook);
+        } else {
+            info!("The {} hook is already installed", hook);
+        }
+    }
     Ok(ExitCode(0))
 }
 
-pub fn post_checkout(_app: Arc<App>) -> Result<ExitCode> {
+/// Remove the lines added by `install_hooks` from the hook scripts in `sparse_repo`, along with the hooks in `LEGACY_HOOK_NAMES`, leaving any other content in place.
+pub fn uninstall_hooks(sparse_repo: &Path, app: Arc<App>) -> Result<ExitCode> {
+    let hooks_dir = hooks_dir(sparse_repo, app)?;
+    for hook in HOOK_NAMES.iter().chain(LEGACY_HOOK_NAMES) {
+        if uninstall_hook(&hooks_dir, hook)? {
+            info!("Uninstalled the {} hook", hook);
+        }
+    }
+    Ok(ExitCode(0))
+}
+
+/// What a hook did after a checkout or merge.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EventOutcome {
+    /// The build graph is unchanged since the last sync.
+    InSync,
+
+    /// The build graph changed and the user was told to sync.
+    Warned,
+
+    /// The build graph changed, but another Focus process holds the repo lock.
+    Locked,
+
+    /// The build graph changed and a sync was run.
+    Synced,
+}
+
+/// Determine whether the build graph at HEAD differs from the last sync of `sparse_repo`, and if so either warn or, when `auto_sync` is set or `auto_sync_configured` reads as enabled from the repo's Git config, call `sync` while holding the repo lock.
+fn handle_build_graph_change(
+    sparse_repo: &Path,
+    auto_sync: bool,
+    auto_sync_configured: fn(&Repo) -> Result<bool>,
+    app: Arc<App>,
+    sync: impl FnOnce() -> Result<()>,
+) -> Result<EventOutcome> {
+    let repo = Repo::open(sparse_repo, app.clone())?;
+    let working_tree = repo.working_tree()?;
+    let sync_point = working_tree.read_sparse_sync_point_ref()?;
+    if sync_point == Some(working_tree.get_head_commit()?.id()) {
+        return Ok(EventOutcome::InSync);
+    }
+
+    let changes = find_committed_changes(app, sparse_repo)?;
+    if changes.is_empty() {
+        return Ok(EventOutcome::InSync);
+    }
+
+    let auto_sync = auto_sync || auto_sync_configured(&repo)?;
+    if !auto_sync {
+        eprintln!("The build graph differs from the last sync; run `focus sync` to update the sparse checkout.");
+        return Ok(EventOutcome::Warned);
+    }
+
+    let _lock_file = match LockFile::new(&sparse_repo.join(".focus").join("focus.lock")) {
+        Ok(lock_file) => lock_file,
+        Err(e) => {
+            debug!(?e, "Skipping automatic sync since the repo is locked");
+            return Ok(EventOutcome::Locked);
+        }
+    };
+    eprintln!("The build graph differs from the last sync; syncing automatically.");
+    sync()?;
+    Ok(EventOutcome::Synced)
+}
+
+/// Run `handle_build_graph_change` for the sparse repo in the current directory, syncing it incrementally if needed. Failures are logged rather than returned so that the Git operation is never reported as failing.
+fn handle_event_in_current_dir(
+    hook: &str,
+    auto_sync: bool,
+    auto_sync_configured: fn(&Repo) -> Result<bool>,
+    app: Arc<App>,
+) -> Result<ExitCode> {
     let current_dir = std::env::current_dir().context("Failed to obtain current directory")?;
-    debug!(sparse_repo = ?current_dir.display(), "Running post-checkout hook");
+    debug!(sparse_repo = ?current_dir.display(), "Running {} hook", hook);
+    let outcome = handle_build_graph_change(
+        &current_dir,
+        auto_sync,
+        auto_sync_configured,
+        app.clone(),
+        || {
+            crate::sync::run(
+                &SyncRequest::new(&current_dir, SyncMode::Incremental),
+                app.clone(),
+            )
+            .map(|_| ())
+        },
+    );
+    match outcome {
+        Ok(outcome) => debug!(?outcome, "The {} hook finished", hook),
+        Err(e) => warn!("The {} hook failed: {:#}", hook, e),
+    }
     Ok(ExitCode(0))
 }
 
+/// Handle a checkout in the sparse repo in the current directory. A sync is run automatically when the build graph changed and `auto_sync` is set or `focus.post-checkout.auto-sync` is enabled in the Git config.
+pub fn post_checkout(app: Arc<App>, auto_sync: bool) -> Result<ExitCode> {
+    handle_event_in_current_dir(
+        "post-checkout",
+        auto_sync,
+        Repo::get_post_checkout_auto_sync,
+        app,
+    )
+}
+
+/// Handle a merge (including `git pull`) in the sparse repo in the current directory. A sync is run automatically when the merge changed the build graph and `auto_sync` is set or `focus.post-merge.auto-sync` is enabled in the Git config.
+pub fn post_merge(app: Arc<App>, auto_sync: bool) -> Result<ExitCode> {
+    handle_event_in_current_dir("post-merge", auto_sync, Repo::get_post_merge_auto_sync, app)
+}
+
 pub fn post_commit(_app: Arc<App>) -> Result<ExitCode> {
     let current_dir = std::env::current_dir().context("Failed to obtain current directory")?;
     debug!(sparse_repo = ?current_dir.display(), "Running post-commit hook");
@@ -69,21 +264,249 @@ pub(crate) mod testing {
     use super::*;
 
     #[test]
-    fn write_hooks_to_dir_produces_correct_scripts() -> Result<()> {
-        let focus_exe = &std::env::current_exe().unwrap_or_else(|_| PathBuf::from("focus"));
-        let focus_exe_path = focus_exe.file_name().unwrap().to_string_lossy();
-        let hook_names = vec!["fancy-hook", "boring-hook"];
-        let temp_dir = tempfile::tempdir()?;
-        let temp_dir_path = temp_dir.path();
-
-        write_hooks_to_dir(&hook_names, temp_dir_path)?;
-
-        for hook in hook_names {
-            let expected_content = format!("{} event {}\n", focus_exe_path, hook);
-            let content = fs::read_to_string(temp_dir_path.join(hook))
-                .context(format!("Could not read hook {}", hook))?;
-            assert_eq!(content, expected_content);
+    fn hooks_are_installed_where_git_runs_them() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = git2::Repository::init(dir.path())?;
+        let app = Arc::new(App::new_for_testing()?);
+        let focus_exe_path = focus_exe_name();
+
+        init(dir.path(), app.clone())?;
+        for hook in HOOK_NAMES {
+            assert_eq!(
+                fs::read_to_string(dir.path().join(".git/hooks").join(hook))?,
+                format!(
+                    "#!/bin/sh\n{} event {} {}\n",
+                    focus_exe_path, hook, HOOK_MARKER
+                )
+            );
+        }
+
+        // `core.hooksPath` moves the hooks out of the Git directory.
+        repo.config()?.set_str("core.hooksPath", "shared-hooks")?;
+        install_hooks(dir.path(), app.clone())?;
+        for hook in HOOK_NAMES {
+            assert!(dir.path().join("shared-hooks").join(hook).is_file());
+        }
+        assert!(!dir.path().join("shared-hooks/post-commit").exists());
+
+        // The `post-commit` hook written by older versions of `init` is removed as well.
+        fs::write(
+            dir.path().join("shared-hooks/post-commit"),
+            format!("#!/bin/sh\n{} event post-commit\n", focus_exe_path),
+        )?;
+        uninstall_hooks(dir.path(), app)?;
+        for hook in HOOK_NAMES.iter().chain(LEGACY_HOOK_NAMES) {
+            assert!(!dir.path().join("shared-hooks").join(hook).exists());
         }
+
+        Ok(())
+    }
+
+    #[test]
+    fn hooks_install_idempotently_and_uninstall_only_their_own_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let hooks_dir = dir.path();
+        let focus_exe_path = focus_exe_name();
+        fs::write(hooks_dir.join("post-merge"), "#!/bin/bash\necho merged\n")?;
+        fs::write(
+            hooks_dir.join("post-checkout"),
+            format!("{} event post-checkout\n", focus_exe_path),
+        )?;
+
+        for _ in 0..2 {
+            for hook in HOOK_NAMES {
+                install_hook(hooks_dir, hook)?;
+            }
+        }
+        assert!(!install_hook(hooks_dir, "post-merge")?);
+        assert_eq!(
+            fs::read_to_string(hooks_dir.join("post-merge"))?,
+            format!(
+                "#!/bin/bash\necho merged\n{} event post-merge {}\n",
+                focus_exe_path, HOOK_MARKER
+            )
+        );
+        // The unmarked line written by older versions of `init` is replaced rather than duplicated.
+        assert_eq!(
+            fs::read_to_string(hooks_dir.join("post-checkout"))?,
+            format!(
+                "#!/bin/sh\n{} event post-checkout {}\n",
+                focus_exe_path, HOOK_MARKER
+            )
+        );
+        assert_eq!(
+            fs::metadata(hooks_dir.join("post-checkout"))?
+                .permissions()
+                .mode()
+                & 0o777,
+            0o755
+        );
+
+        for hook in HOOK_NAMES {
+            assert!(uninstall_hook(hooks_dir, hook)?);
+            assert!(!uninstall_hook(hooks_dir, hook)?);
+        }
+        assert_eq!(
+            fs::read_to_string(hooks_dir.join("post-merge"))?,
+            "#!/bin/bash\necho merged\n"
+        );
+        assert!(!hooks_dir.join("post-checkout").exists());
+
+        Ok(())
+    }
+
+    fn commit_file(repo: &git2::Repository, path: &str, contents: &str) -> Result<git2::Oid> {
+        let work_dir = repo.workdir().unwrap();
+        fs::write(work_dir.join(path), contents)?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new(path))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("Focus", "focus@example.com")?;
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit()?],
+            Err(_) => Vec::new(),
+        };
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        Ok(repo.commit(Some("HEAD"), &signature, &signature, path, &tree, &parents)?)
+    }
+
+    fn switch_to_branch(repo: &git2::Repository, branch: &str) -> Result<()> {
+        if repo.find_branch(branch, git2::BranchType::Local).is_err() {
+            repo.branch(branch, &repo.head()?.peel_to_commit()?, false)?;
+        }
+        repo.set_head(&format!("refs/heads/{}", branch))?;
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+        Ok(())
+    }
+
+    fn handle_post_checkout(
+        sparse_repo: &Path,
+        auto_sync: bool,
+        app: Arc<App>,
+        sync: impl FnOnce() -> Result<()>,
+    ) -> Result<EventOutcome> {
+        handle_build_graph_change(
+            sparse_repo,
+            auto_sync,
+            Repo::get_post_checkout_auto_sync,
+            app,
+            sync,
+        )
+    }
+
+    #[test]
+    fn post_checkout_auto_syncs_only_when_build_graph_differs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sparse_repo = dir.path();
+        let repo = git2::Repository::init(sparse_repo)?;
+        fs::create_dir(sparse_repo.join(".focus"))?;
+        fs::write(sparse_repo.join(".git/info/exclude"), ".focus\n")?;
+        let synced_commit = commit_file(&repo, "BUILD", "# original")?;
+        repo.reference("refs/focus/sync", synced_commit, true, "test")?;
+        let main_branch = repo.head()?.shorthand().unwrap().to_owned();
+        switch_to_branch(&repo, "docs")?;
+        commit_file(&repo, "README", "docs")?;
+        switch_to_branch(&repo, &main_branch)?;
+        switch_to_branch(&repo, "graph")?;
+        commit_file(&repo, "BUILD", "# changed")?;
+        let app = Arc::new(App::new_for_testing()?);
+
+        let auto_sync = |synced: &mut bool| {
+            handle_post_checkout(sparse_repo, true, app.clone(), || {
+                *synced = true;
+                Ok(())
+            })
+        };
+        let mut synced = false;
+
+        // Switching back to the synced commit does nothing.
+        switch_to_branch(&repo, &main_branch)?;
+        assert_eq!(auto_sync(&mut synced)?, EventOutcome::InSync);
+        assert!(!synced);
+
+        // Switching to a branch that only changes sources does nothing.
+        switch_to_branch(&repo, "docs")?;
+        assert_eq!(auto_sync(&mut synced)?, EventOutcome::InSync);
+        assert!(!synced);
+
+        // Without auto-sync, switching to a branch that changes the build graph only warns.
+        switch_to_branch(&repo, "graph")?;
+        let outcome = handle_post_checkout(sparse_repo, false, app.clone(), || {
+            panic!("sync should not run")
+        })?;
+        assert_eq!(outcome, EventOutcome::Warned);
+
+        // With auto-sync, it syncs.
+        assert_eq!(auto_sync(&mut synced)?, EventOutcome::Synced);
+        assert!(synced);
+
+        // The Git config key enables auto-sync as well.
+        repo.config()?.set_bool(
+            focus_internals::model::repo::POST_CHECKOUT_AUTO_SYNC_CONFIG_KEY,
+            true,
+        )?;
+        let mut synced = false;
+        let outcome = handle_post_checkout(sparse_repo, false, app.clone(), || {
+            synced = true;
+            Ok(())
+        })?;
+        assert_eq!(outcome, EventOutcome::Synced);
+        assert!(synced);
+
+        // Syncs are skipped while another process holds the lock.
+        let _lock_file = LockFile::new(&sparse_repo.join(".focus").join("focus.lock"))?;
+        assert_eq!(auto_sync(&mut synced)?, EventOutcome::Locked);
+
+        Ok(())
+    }
+
+    #[test]
+    fn post_merge_auto_syncs_only_when_enabled() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sparse_repo = dir.path();
+        let repo = git2::Repository::init(sparse_repo)?;
+        fs::create_dir(sparse_repo.join(".focus"))?;
+        fs::write(sparse_repo.join(".git/info/exclude"), ".focus\n")?;
+        let synced_commit = commit_file(&repo, "BUILD", "# original")?;
+        repo.reference("refs/focus/sync", synced_commit, true, "test")?;
+        let app = Arc::new(App::new_for_testing()?);
+
+        let handle_post_merge = |synced: &mut bool| {
+            handle_build_graph_change(
+                sparse_repo,
+                false,
+                Repo::get_post_merge_auto_sync,
+                app.clone(),
+                || {
+                    *synced = true;
+                    Ok(())
+                },
+            )
+        };
+        let mut synced = false;
+
+        // A merge that only brings in sources does nothing.
+        commit_file(&repo, "README", "docs")?;
+        assert_eq!(handle_post_merge(&mut synced)?, EventOutcome::InSync);
+
+        // A merge that changes the build graph only warns by default, even when post-checkout syncs are enabled.
+        commit_file(&repo, "BUILD", "# changed")?;
+        repo.config()?.set_bool(
+            focus_internals::model::repo::POST_CHECKOUT_AUTO_SYNC_CONFIG_KEY,
+            true,
+        )?;
+        assert_eq!(handle_post_merge(&mut synced)?, EventOutcome::Warned);
+        assert!(!synced);
+
+        // Enabling it in the Git config syncs.
+        repo.config()?.set_bool(
+            focus_internals::model::repo::POST_MERGE_AUTO_SYNC_CONFIG_KEY,
+            true,
+        )?;
+        assert_eq!(handle_post_merge(&mut synced)?, EventOutcome::Synced);
+        assert!(synced);
+
         Ok(())
     }
 }
@@ -2,28 +2,37 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::borrow::Borrow;
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context;
-use content_addressed_cache::{Cache, CacheSynchronizer, GitBackedCacheSynchronizer, KeysetID};
+use content_addressed_cache::{
+    load_signing_key, load_verifying_key, Cache, CacheSynchronizer, GitBackedCacheSynchronizer,
+    IndexChecksumMismatch, IndexSignatureInvalid, KeysetID, SigningKey, VerifyingKey,
+};
 use focus_util::app::{App, ExitCode};
 use focus_util::git_helper;
-use focus_util::paths::assert_focused_repo;
-use tracing::{debug, debug_span, info};
+use focus_util::paths::{assert_focused_repo, paths_outside_roots};
+use focus_util::sandbox_command::{CommandFailed, CommandTimedOut};
+use rand::seq::IteratorRandom;
+use serde::Serialize;
+use tracing::{debug, debug_span, info, warn};
 
 use focus_internals::index::{
-    self, content_hash, get_files_to_materialize, ContentHash, DependencyKey, HashContext,
-    ObjectDatabase, PathsToMaterializeResult, RocksDBCache, RocksDBMemoizationCacheExt,
-    FUNCTION_ID,
+    self, content_hash, content_hash_all, content_hash_inputs, content_hash_version,
+    get_files_to_materialize, is_content_store_remote, ContentHash, ContentStoreSynchronizer,
+    DependencyKey, HashContext, HashInput, ObjectDatabase, PathsToMaterializeResult, RocksDBCache,
+    RocksDBMemoizationCacheExt, FUNCTION_ID,
 };
+use focus_internals::model::activity::Activity;
 use focus_internals::model::configuration::IndexConfig;
-use focus_internals::model::repo::Repo;
-use focus_internals::model::selection::OperationAction;
+use focus_internals::model::repo::{Repo, SyncOptions};
+use focus_internals::model::selection::{Interpretation, OperationAction};
 use focus_internals::target::{Target, TargetSet};
+use focus_internals::target_resolver::BazelOptions;
 
 const PARENTS_TO_TRY_IN_FETCH: u32 = 100;
 const TAG_NAMESPACE: &str = "focus";
@@ -46,6 +55,8 @@ fn dep_key_to_target(dep_key: &DependencyKey) -> String {
 
         DependencyKey::Path(path) => format!("directory:{}", path.display()),
 
+        DependencyKey::PantsTarget(address) => format!("pants:{}", address),
+
         DependencyKey::DummyForTesting(inner_dep_key) => {
             panic!(
                 "Cannot convert dummy testing key into target: {:?}",
@@ -66,6 +77,7 @@ fn resolve_targets(
     sparse_repo_path: &Path,
     targets: HashSet<Target>,
     break_on_missing_keys: bool,
+    bazel_options: &BazelOptions,
 ) -> anyhow::Result<Result<ResolveTargetResult, ExitCode>> {
     let dep_keys: HashSet<DependencyKey> = targets
         .iter()
@@ -75,7 +87,7 @@ fn resolve_targets(
     let repo = git2::Repository::open(sparse_repo_path).context("opening sparse repo")?;
     let head_commit = git_helper::get_head_commit(&repo).context("Resolving head commit")?;
     let tree = head_commit.tree().context("Resolving tree")?;
-    let ctx = HashContext::new(&repo, &tree)?;
+    let ctx = HashContext::new(&repo, &tree)?.with_bazel_config(bazel_options.config());
     let odb = RocksDBCache::new(&repo);
 
     let borrowed_odb = odb.borrow();
@@ -95,17 +107,20 @@ fn resolve_targets(
             }
 
             let repo = Repo::open(repo.path(), app.clone())?;
+            let options = SyncOptions::default()
+                .with_skip_pattern_application(true)
+                .with_bazel_options(bazel_options.clone());
             let (pattern_count, _checked_out) = repo.sync(
                 head_commit.id(),
                 &targets,
-                true,
                 app.clone(),
                 Some(borrowed_odb),
                 None,
+                &options,
             )?;
             println!("Pattern count: {}", pattern_count);
 
-            match get_files_to_materialize(&ctx, borrowed_odb, dep_keys)? {
+            match get_files_to_materialize(&ctx, borrowed_odb, dep_keys.clone())? {
                 PathsToMaterializeResult::Ok { seen_keys, paths } => Ok(Ok(ResolveTargetResult {
                     seen_keys,
                     paths: paths.into_iter().collect(),
@@ -141,20 +156,29 @@ pub fn resolve(
     sparse_repo_path: &Path,
     projects_and_targets: Vec<String>,
     break_on_missing_keys: bool,
+    bazel_options: BazelOptions,
+    allowed_roots: &[PathBuf],
 ) -> anyhow::Result<ExitCode> {
     assert_focused_repo(sparse_repo_path)?;
     let repo = Repo::open(sparse_repo_path, app.clone())?;
     let targets = {
         let mut selections = repo.selection_manager()?;
-        selections.mutate(OperationAction::default_add(), &projects_and_targets)?;
+        selections.mutate(
+            OperationAction::default_add(),
+            Interpretation::Inferred,
+            &projects_and_targets,
+        )?;
         selections.compute_complete_target_set()
     }?;
 
-    let paths = match resolve_targets(app, sparse_repo_path, targets, break_on_missing_keys)? {
-        Ok(ResolveTargetResult {
-            seen_keys: _,
-            paths,
-        }) => paths,
+    let paths = match resolve_targets(
+        app,
+        sparse_repo_path,
+        targets,
+        break_on_missing_keys,
+        &bazel_options,
+    )? {
+        Ok(ResolveTargetResult { paths, .. }) => paths,
         Err(exit_code) => return Ok(exit_code),
     };
 
@@ -162,6 +186,15 @@ pub fn resolve(
     for path in paths.iter() {
         println!("{}", path.display());
     }
+
+    let violations = paths_outside_roots(paths.iter().map(PathBuf::as_path), allowed_roots);
+    if !violations.is_empty() {
+        eprintln!("Paths outside the allowed roots:");
+        for path in violations {
+            eprintln!("{}", path.display());
+        }
+        return Ok(ExitCode(1));
+    }
     Ok(ExitCode(0))
 }
 
@@ -170,6 +203,7 @@ pub fn hash(
     sparse_repo_path: &Path,
     commit: String,
     targets: &[String],
+    include_env: bool,
 ) -> anyhow::Result<ExitCode> {
     let repo = git2::Repository::open(sparse_repo_path)?;
     let object = repo
@@ -185,6 +219,12 @@ pub fn hash(
         let dep_key = DependencyKey::from(target);
         let hash = content_hash(&hash_context, &dep_key)?;
         println!("{hash} {dep_key:?}");
+        if include_env {
+            println!("    version {}", content_hash_version());
+            for HashInput { path, hash } in content_hash_inputs(&hash_context, &dep_key)? {
+                println!("    path {hash} {}", path.display());
+            }
+        }
     }
 
     debug!(?hash_context, "Finished with this hash context");
@@ -209,25 +249,133 @@ pub fn get(_app: Arc<App>, sparse_repo_path: &Path, hash: &str) -> anyhow::Resul
     }
 }
 
+/// An entry of the manifest written by `generate`, describing the index entry for one target of a project.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ManifestEntry {
+    pub project: String,
+    pub target: String,
+
+    /// The content hash that the entry is stored under.
+    pub hash: String,
+
+    /// The size in bytes of the stored entry. Targets whose paths are known statically, such as directories, have no entry.
+    pub size: Option<usize>,
+}
+
+/// The entries produced by `generate`, sorted by project and target.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GenerateManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Generate the index for all projects. If `output_manifest` is given, a `GenerateManifest` describing the generated entries is written there as JSON. Resolution and hashing run on a pool of `jobs` threads if given, and on the global pool otherwise; the generated index is the same either way.
 pub fn generate(
     app: Arc<App>,
 
     sparse_repo_path: PathBuf,
     break_on_missing_keys: bool,
+    output_manifest: Option<PathBuf>,
+    jobs: Option<usize>,
 ) -> anyhow::Result<ExitCode> {
-    let repo = Repo::open(&sparse_repo_path, app.clone())?;
+    match jobs {
+        Some(jobs) => rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .context("Failed to create the index generation thread pool")?
+            .install(|| {
+                generate_in_current_pool(
+                    app,
+                    sparse_repo_path,
+                    break_on_missing_keys,
+                    output_manifest,
+                )
+            }),
+        None => generate_in_current_pool(
+            app,
+            sparse_repo_path,
+            break_on_missing_keys,
+            output_manifest,
+        ),
+    }
+}
+
+/// The targets of every mandatory and optional project, with the name of the project each belongs to.
+fn project_targets(repo: &Repo) -> anyhow::Result<Vec<(String, Target)>> {
     let selections = repo.selection_manager()?;
-    let all_targets = {
-        let mut targets = TargetSet::try_from(&selections.project_catalog().mandatory_projects)?;
-        targets.extend(TargetSet::try_from(
-            &selections.project_catalog().optional_projects,
-        )?);
-        targets
-    };
-    match resolve_targets(app, &sparse_repo_path, all_targets, break_on_missing_keys)? {
-        Ok(_result) => Ok(ExitCode(0)),
-        Err(exit_code) => Ok(exit_code),
+    let catalog = selections.project_catalog();
+    let mut project_targets = Vec::new();
+    for project in catalog
+        .mandatory_projects
+        .underlying
+        .values()
+        .chain(catalog.optional_projects.underlying.values())
+    {
+        for target in TargetSet::try_from(project)? {
+            project_targets.push((project.name.clone(), target));
+        }
     }
+    Ok(project_targets)
+}
+
+fn generate_in_current_pool(
+    app: Arc<App>,
+    sparse_repo_path: PathBuf,
+    break_on_missing_keys: bool,
+    output_manifest: Option<PathBuf>,
+) -> anyhow::Result<ExitCode> {
+    let repo = Repo::open(&sparse_repo_path, app.clone())?;
+    let project_targets = project_targets(&repo)?;
+    let all_targets: TargetSet = project_targets
+        .iter()
+        .map(|(_, target)| target.clone())
+        .collect();
+
+    if let Err(exit_code) = resolve_targets(
+        app,
+        &sparse_repo_path,
+        all_targets,
+        break_on_missing_keys,
+        &Default::default(),
+    )? {
+        return Ok(exit_code);
+    }
+
+    if let Some(output_manifest) = output_manifest {
+        let head_tree = repo
+            .get_head_commit()?
+            .tree()
+            .context("finding HEAD tree")?;
+        let ctx = HashContext::new(repo.underlying(), &head_tree)?;
+        let dep_keys: Vec<DependencyKey> = project_targets
+            .iter()
+            .map(|(_, target)| DependencyKey::from(target.clone()))
+            .collect();
+        let hashes = content_hash_all(&ctx, &dep_keys)?;
+        let odb = RocksDBCache::new(repo.underlying());
+        let mut entries = Vec::new();
+        for (project, target) in project_targets {
+            let hash = hashes
+                .get(&DependencyKey::from(target.clone()))
+                .cloned()
+                .with_context(|| format!("No hash was computed for {}", target))?;
+            let size = Cache::get(&odb, *FUNCTION_ID, git2::Oid::from(hash.clone()))?
+                .map(|payload| payload.len());
+            entries.push(ManifestEntry {
+                project,
+                target: target.to_string(),
+                hash: hash.to_string(),
+                size,
+            });
+        }
+        entries.sort_by(|a, b| (&a.project, &a.target).cmp(&(&b.project, &b.target)));
+
+        let file = std::fs::File::create(&output_manifest)
+            .with_context(|| format!("Creating {}", output_manifest.display()))?;
+        serde_json::to_writer_pretty(file, &GenerateManifest { entries })
+            .with_context(|| format!("Writing manifest to {}", output_manifest.display()))?;
+    }
+
+    Ok(ExitCode(0))
 }
 
 fn index_repo_dir(sparse_repo_path: &Path) -> PathBuf {
@@ -236,11 +384,196 @@ fn index_repo_dir(sparse_repo_path: &Path) -> PathBuf {
 
 pub const INDEX_DEFAULT_REMOTE: &str = "https://git.example.com/focus-index";
 
+/// Where the index is shared: a Git remote, or a content-addressable store for remotes starting with `cas+`.
+enum IndexRemote {
+    Git(Box<GitBackedCacheSynchronizer>),
+    ContentStore(ContentStoreSynchronizer),
+}
+
+impl IndexRemote {
+    fn open(remote: String, sparse_repo_path: &Path, app: Arc<App>) -> anyhow::Result<Self> {
+        if is_content_store_remote(&remote) {
+            return Ok(IndexRemote::ContentStore(ContentStoreSynchronizer::new(
+                &remote,
+            )?));
+        }
+        Ok(IndexRemote::Git(Box::new(
+            GitBackedCacheSynchronizer::create(
+                index_repo_dir(sparse_repo_path),
+                remote,
+                app,
+                TAG_NAMESPACE.to_string(),
+                COMMIT_USER_EMAIL.to_string(),
+                COMMIT_USER_NAME.to_string(),
+            )?,
+        )))
+    }
+
+    fn synchronizer(&self) -> &dyn CacheSynchronizer {
+        match self {
+            IndexRemote::Git(synchronizer) => synchronizer.as_ref(),
+            IndexRemote::ContentStore(synchronizer) => synchronizer,
+        }
+    }
+
+    /// The Git synchronizer, for `feature`s that content-addressable stores do not support.
+    fn git(&self, feature: &str) -> anyhow::Result<&GitBackedCacheSynchronizer> {
+        match self {
+            IndexRemote::Git(synchronizer) => Ok(synchronizer.as_ref()),
+            IndexRemote::ContentStore(_) => Err(Self::unsupported(feature)),
+        }
+    }
+
+    fn with_signing_key(self, key: SigningKey) -> anyhow::Result<Self> {
+        match self {
+            IndexRemote::Git(synchronizer) => Ok(IndexRemote::Git(Box::new(
+                synchronizer.with_signing_key(key),
+            ))),
+            IndexRemote::ContentStore(_) => Err(Self::unsupported("Signing the index")),
+        }
+    }
+
+    fn with_verifying_key(self, key: VerifyingKey) -> anyhow::Result<Self> {
+        match self {
+            IndexRemote::Git(synchronizer) => Ok(IndexRemote::Git(Box::new(
+                synchronizer.with_verifying_key(key),
+            ))),
+            IndexRemote::ContentStore(_) => Err(Self::unsupported("Verifying index signatures")),
+        }
+    }
+
+    fn unsupported(feature: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "{} is not supported with content-addressable store remotes",
+            feature
+        )
+    }
+
+    fn check_remote_is_writable(&self) -> anyhow::Result<()> {
+        match self {
+            IndexRemote::Git(synchronizer) => synchronizer.check_remote_is_writable(),
+            IndexRemote::ContentStore(synchronizer) => synchronizer.check_remote_is_writable(),
+        }
+    }
+}
+
+/// How the network operations of an index fetch are retried when they fail with a transient error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt.
+    pub retries: u32,
+
+    /// The delay before the first retry, which doubles with each further retry.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Fragments of error messages from Git and HTTP clients that indicate a failure worth retrying.
+const TRANSIENT_ERROR_MARKERS: &[&str] = &[
+    "timed out",
+    "timeout",
+    "could not resolve host",
+    "temporary failure in name resolution",
+    "connection reset",
+    "connection refused",
+    "connection was reset",
+    "error trying to connect",
+    "the remote end hung up unexpectedly",
+    "early eof",
+    "rpc failed",
+    "returned error: 5",
+    "server error (",
+];
+
+/// Fragments of error messages that indicate a failure that retrying will not fix, even if the message also looks transient.
+const PERMANENT_ERROR_MARKERS: &[&str] = &[
+    "authentication failed",
+    "permission denied",
+    "could not read username",
+    "returned error: 401",
+    "returned error: 403",
+    "returned error: 404",
+    "client error (",
+    "not found",
+    "does not exist",
+    "requires network, but --offline was specified",
+];
+
+/// The messages of `e` and the standard error of any failed command in it, lowercased for matching against markers.
+fn error_text(e: &anyhow::Error) -> String {
+    let mut text = String::new();
+    for cause in e.chain() {
+        text.push_str(&cause.to_string());
+        text.push('\n');
+        if let Some(failed) = cause.downcast_ref::<CommandFailed>() {
+            text.push_str(&failed.stderr);
+            text.push('\n');
+        }
+    }
+    text.to_lowercase()
+}
+
+/// Whether `e` looks like a transient network or transport failure, judging by its messages and the standard error of any failed command. Commands that timed out are always considered transient.
+fn is_transient_network_error(e: &anyhow::Error) -> bool {
+    if e.chain().any(|cause| cause.is::<CommandTimedOut>()) {
+        return true;
+    }
+
+    let text = error_text(e);
+    !PERMANENT_ERROR_MARKERS
+        .iter()
+        .any(|marker| text.contains(marker))
+        && TRANSIENT_ERROR_MARKERS
+            .iter()
+            .any(|marker| text.contains(marker))
+}
+
+/// Run `operation`, retrying it with exponential backoff according to `policy` while it fails with transient errors.
+fn with_retries<T>(
+    policy: &RetryPolicy,
+    description: &str,
+    mut operation: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        debug!(%description, attempt, "Attempting");
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt <= policy.retries && is_transient_network_error(&e) => {
+                let delay = policy.base_delay * 2u32.saturating_pow(attempt - 1);
+                warn!(
+                    %description,
+                    attempt,
+                    retries = policy.retries,
+                    ?delay,
+                    error = %format!("{:#}", e),
+                    "Failed with a transient error; retrying"
+                );
+                std::thread::sleep(delay);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Fetch the index for the current commit, or the named snapshot `tag` if given. With `verify_key`, the path of an ed25519 public key, indexes that are not signed by the matching private key are refused. Network operations that fail with transient errors are retried according to `retry`. The configured index remotes are tried in order, moving on to the next only when a remote is missing, unreachable, or has no matching index; an index that fails checksum or signature verification is never replaced by another remote's.
 pub fn fetch(
     app: Arc<App>,
     sparse_repo_path: PathBuf,
     force: bool,
     remote: Option<String>,
+    tag: Option<String>,
+    verify_key: Option<PathBuf>,
+    retry: RetryPolicy,
 ) -> anyhow::Result<ExitCode> {
     let repo = Repo::open(&sparse_repo_path, app.clone())
         .with_context(|| format!("Opening repository at {}", &sparse_repo_path.display()))?;
@@ -265,7 +598,29 @@ pub fn fetch(
 
     debug!(?index_config, "Using index config");
     if index_config.enabled {
-        fetch_internal(app, &cache, sparse_repo_path, &index_config)
+        let verifying_key = verify_key
+            .map(|path| load_verifying_key(&path))
+            .transpose()
+            .context("Loading the index verification key")?;
+        match tag {
+            Some(tag) => fetch_snapshot(
+                app,
+                &cache,
+                sparse_repo_path,
+                &index_config,
+                &tag,
+                verifying_key,
+                &retry,
+            ),
+            None => fetch_internal(
+                app,
+                &cache,
+                sparse_repo_path,
+                &index_config,
+                verifying_key,
+                &retry,
+            ),
+        }
     } else {
         debug!("Skipping fetch: was not enabled in repository config and --force was not passed");
         Ok(ExitCode(0))
@@ -277,53 +632,211 @@ fn fetch_internal(
     cache: &RocksDBCache,
     sparse_repo_path: PathBuf,
     index_config: &IndexConfig,
+    verifying_key: Option<VerifyingKey>,
+    retry: &RetryPolicy,
 ) -> anyhow::Result<ExitCode> {
-    let index_dir = index_repo_dir(&sparse_repo_path);
-    let synchronizer = GitBackedCacheSynchronizer::create(
-        index_dir,
-        index_config.remote.clone(),
-        app.clone(),
-        TAG_NAMESPACE.to_string(),
-        COMMIT_USER_EMAIL.to_string(),
-        COMMIT_USER_NAME.to_string(),
-    )?;
-    let repo = Repo::open(sparse_repo_path.as_path(), app).context("Failed to open repo")?;
+    let repo =
+        Repo::open(sparse_repo_path.as_path(), app.clone()).context("Failed to open repo")?;
     let mut commit = repo.get_head_commit()?;
 
-    let available_keysets = synchronizer.available_remote_keysets()?;
-
-    let mut found_keyset: Option<KeysetID> = None;
+    let mut candidates: Vec<KeysetID> = Vec::new();
     for _ in 0..PARENTS_TO_TRY_IN_FETCH {
-        let keyset_id = commit.tree()?.id();
-
-        if available_keysets.contains(&keyset_id) {
-            found_keyset = Some(keyset_id);
+        candidates.push(commit.tree()?.id());
+        if commit.parent_count() == 0 {
             break;
         }
         commit = commit.parent(0)?;
     }
-    if let Some(keyset_id) = found_keyset {
-        let keyset_id_str = keyset_id.to_string();
-        let span = debug_span!("Fetching index");
-        info!(tag = %keyset_id_str, "Fetching index");
-        let _guard = span.enter();
-        synchronizer
-            .fetch_and_populate(keyset_id, cache)
-            .context("Fetching index data")?;
-    } else {
-        info!("No index matches the current commit");
+
+    let mut last_error = None;
+    let mut any_remote_responded = false;
+    for remote in index_remotes(index_config)? {
+        match fetch_from_remote(
+            app.clone(),
+            cache,
+            &sparse_repo_path,
+            remote,
+            &candidates,
+            verifying_key,
+            retry,
+        ) {
+            Ok(Some(keyset_id)) => {
+                info!(%remote, %keyset_id, "Fetched index from {}", remote);
+                if let Err(e) = repo.record_activity(Activity::IndexFetch) {
+                    warn!(?e, "Failed to record index fetch time");
+                }
+                return Ok(ExitCode(0));
+            }
+            Ok(None) => {
+                info!(%remote, "No index on this remote matches the current commit");
+                any_remote_responded = true;
+            }
+            Err(e) if is_remote_unavailable(&e) => {
+                warn!(%remote, error = %format!("{:#}", e), "Fetching the index from this remote failed");
+                last_error = Some(e);
+            }
+            Err(e) => return discard_corrupted_index(e),
+        }
     }
 
-    Ok(ExitCode(0))
+    match last_error {
+        Some(e) if !any_remote_responded => Err(e),
+        _ => {
+            info!("No index matches the current commit");
+            Ok(ExitCode(0))
+        }
+    }
+}
+
+/// Fragments of error messages that indicate that a remote does not exist or could not be reached, besides the transient errors in `TRANSIENT_ERROR_MARKERS`.
+const REMOTE_UNAVAILABLE_MARKERS: &[&str] = &[
+    "not found",
+    "does not exist",
+    "does not appear to be a git repository",
+    "could not read from remote repository",
+    "unable to access",
+    "returned error: 404",
+];
+
+/// Whether `e` means that the index could not be fetched from a remote because the remote is missing or unreachable, so that the next remote may be tried. Integrity failures never qualify: an index that fails checksum or signature verification is not replaced by another remote's.
+fn is_remote_unavailable(e: &anyhow::Error) -> bool {
+    if e.chain()
+        .any(|cause| cause.is::<IndexChecksumMismatch>() || cause.is::<IndexSignatureInvalid>())
+    {
+        return false;
+    }
+    if is_transient_network_error(e) {
+        return true;
+    }
+
+    let text = error_text(e);
+    REMOTE_UNAVAILABLE_MARKERS
+        .iter()
+        .any(|marker| text.contains(marker))
+}
+
+/// The remotes to fetch the index from, in order of preference.
+fn index_remotes(index_config: &IndexConfig) -> anyhow::Result<Vec<&str>> {
+    let remotes = index_config.remotes();
+    if remotes.is_empty() {
+        anyhow::bail!("No index remote is configured");
+    }
+    Ok(remotes)
 }
 
+/// Fetch the most recent index among `candidates` from `remote`, returning the keyset that was fetched, or `None` if the remote has none of them.
+fn fetch_from_remote(
+    app: Arc<App>,
+    cache: &RocksDBCache,
+    sparse_repo_path: &Path,
+    remote: &str,
+    candidates: &[KeysetID],
+    verifying_key: Option<VerifyingKey>,
+    retry: &RetryPolicy,
+) -> anyhow::Result<Option<KeysetID>> {
+    let mut remote = IndexRemote::open(remote.to_owned(), sparse_repo_path, app)?;
+    if let Some(verifying_key) = verifying_key {
+        remote = remote.with_verifying_key(verifying_key)?;
+    }
+    let synchronizer = remote.synchronizer();
+
+    let found_keyset = with_retries(retry, "Finding the index for the current commit", || {
+        synchronizer.find_remote_keyset(candidates)
+    })?;
+    let keyset_id = match found_keyset {
+        Some(keyset_id) => keyset_id,
+        None => return Ok(None),
+    };
+
+    let keyset_id_str = keyset_id.to_string();
+    let span = debug_span!("Fetching index");
+    info!(tag = %keyset_id_str, "Fetching index");
+    let _guard = span.enter();
+    with_retries(retry, "Fetching index", || {
+        synchronizer.fetch_and_populate(keyset_id, cache)
+    })
+    .context("Fetching index data")?;
+    Ok(Some(keyset_id))
+}
+
+/// A fetched index that fails checksum verification has already been discarded, so the index will be generated locally as if none had been available. Any other error is returned.
+fn discard_corrupted_index(e: anyhow::Error) -> anyhow::Result<ExitCode> {
+    match e.downcast_ref::<IndexChecksumMismatch>() {
+        Some(mismatch) => {
+            warn!(%mismatch, "Discarded corrupted index; it will be generated locally instead");
+            Ok(ExitCode(0))
+        }
+        None => Err(e),
+    }
+}
+
+fn fetch_snapshot(
+    app: Arc<App>,
+    cache: &RocksDBCache,
+    sparse_repo_path: PathBuf,
+    index_config: &IndexConfig,
+    tag: &str,
+    verifying_key: Option<VerifyingKey>,
+    retry: &RetryPolicy,
+) -> anyhow::Result<ExitCode> {
+    let repo =
+        Repo::open(sparse_repo_path.as_path(), app.clone()).context("Failed to open repo")?;
+
+    let span = debug_span!("Fetching index snapshot");
+    info!(%tag, "Fetching index snapshot");
+    let _guard = span.enter();
+    let mut last_error = None;
+    for remote in index_remotes(index_config)? {
+        let result = IndexRemote::open(remote.to_owned(), &sparse_repo_path, app.clone())
+            .and_then(|index_remote| match verifying_key {
+                Some(verifying_key) => index_remote.with_verifying_key(verifying_key),
+                None => Ok(index_remote),
+            })
+            .and_then(|index_remote| {
+                let synchronizer = index_remote.git("Fetching index snapshots")?;
+                with_retries(retry, "Fetching index snapshot", || {
+                    synchronizer.fetch_and_populate_snapshot(tag, cache)
+                })
+            });
+        match result {
+            Ok(_) => {
+                info!(%remote, %tag, "Fetched index snapshot from {}", remote);
+                if let Err(e) = repo.record_activity(Activity::IndexFetch) {
+                    warn!(?e, "Failed to record index fetch time");
+                }
+                return Ok(ExitCode(0));
+            }
+            Err(e) if is_remote_unavailable(&e) => {
+                warn!(%remote, error = %format!("{:#}", e), "Fetching the index snapshot from this remote failed");
+                last_error = Some(e.context("Fetching index snapshot"));
+            }
+            Err(e) => return discard_corrupted_index(e.context("Fetching index snapshot")),
+        }
+    }
+
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(ExitCode(0)),
+    }
+}
+
+/// Generate the index for all projects and push it to `remote`. With `tag`, the entries are also published as an immutable named snapshot, which may only be replaced when `force` is set. With `sign_key`, the path of an ed25519 private key, the index is signed so that consumers can verify its provenance. With `read_only_check`, a test write is made to the remote before the index is generated so that a read-only remote is reported early.
+#[allow(clippy::too_many_arguments)]
 pub fn push(
     app: Arc<App>,
     sparse_repo_path: PathBuf,
     remote: String,
     dry_run: bool,
     break_on_missing_keys: bool,
+    tag: Option<String>,
+    force: bool,
+    sign_key: Option<PathBuf>,
+    read_only_check: bool,
 ) -> anyhow::Result<ExitCode> {
+    let signing_key = sign_key
+        .map(|path| load_signing_key(&path))
+        .transpose()
+        .context("Loading the index signing key")?;
     let repo = Repo::open(&sparse_repo_path, app.clone())?;
     let selections = repo.selection_manager()?;
     let all_targets = {
@@ -336,23 +849,39 @@ pub fn push(
 
     let index_dir = index_repo_dir(&sparse_repo_path);
     std::fs::create_dir_all(&index_dir).context("creating index directory")?;
-    let synchronizer = GitBackedCacheSynchronizer::create(
-        index_dir,
-        remote,
-        app.clone(),
-        TAG_NAMESPACE.to_string(),
-        COMMIT_USER_EMAIL.to_string(),
-        COMMIT_USER_NAME.to_string(),
-    )?;
+    let mut remote = IndexRemote::open(remote, &sparse_repo_path, app.clone())?;
+    if let Some(signing_key) = signing_key {
+        remote = remote.with_signing_key(signing_key)?;
+    }
+    let snapshot_synchronizer = match &tag {
+        Some(_) => Some(remote.git("Pushing index snapshots")?),
+        None => None,
+    };
+
+    // Fail before doing any work if the index cannot be pushed.
+    if read_only_check && !dry_run {
+        remote.check_remote_is_writable()?;
+    }
+    if let (Some(tag), Some(synchronizer)) = (&tag, snapshot_synchronizer) {
+        if !force && synchronizer.remote_snapshot_exists(tag)? {
+            anyhow::bail!(
+                "Index snapshot '{}' already exists on the remote (use --force to overwrite it)",
+                tag
+            );
+        }
+    }
 
     let head_commit = repo.get_head_commit()?;
     let head_tree = head_commit.tree().context("finding HEAD tree")?;
     let ctx = HashContext::new(repo.underlying(), &head_tree)?;
 
-    let ResolveTargetResult {
-        seen_keys,
-        paths: _,
-    } = match resolve_targets(app, &sparse_repo_path, all_targets, break_on_missing_keys)? {
+    let ResolveTargetResult { seen_keys, .. } = match resolve_targets(
+        app,
+        &sparse_repo_path,
+        all_targets,
+        break_on_missing_keys,
+        &Default::default(),
+    )? {
         Ok(result) => result,
         Err(exit_code) => return Ok(exit_code),
     };
@@ -376,7 +905,9 @@ pub fn push(
                     result.insert((*FUNCTION_ID, git2::Oid::from(hash)));
                 }
 
-                DependencyKey::BazelBuildFile(_) | DependencyKey::Path(_) => {
+                DependencyKey::BazelBuildFile(_)
+                | DependencyKey::Path(_)
+                | DependencyKey::PantsTarget(_) => {
                     // The paths to materialize for these kinds of dependencies
                     // are known statically, so we don't need to insert or
                     // propagate cache entries.
@@ -392,7 +923,13 @@ pub fn push(
 
     if !dry_run {
         info!("Pushing index");
-        synchronizer.share(ctx.head_tree().id(), &keyset, &odb, None)?;
+        remote
+            .synchronizer()
+            .share(ctx.head_tree().id(), &keyset, &odb, None)?;
+        if let (Some(tag), Some(synchronizer)) = (&tag, snapshot_synchronizer) {
+            info!(%tag, "Pushing index snapshot");
+            synchronizer.share_snapshot(tag, ctx.head_tree().id(), &keyset, &odb, force)?;
+        }
     } else {
         info!("This is a dry run, so not pushing index");
     }
@@ -400,6 +937,292 @@ pub fn push(
     Ok(ExitCode(0))
 }
 
+/// The index entries reachable from one project's targets at HEAD.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProjectIndexStats {
+    pub project: String,
+
+    /// The number of stored entries reached from the project's targets.
+    pub entries: usize,
+
+    /// The total size in bytes of those entries.
+    pub size: usize,
+
+    /// The number of paths the project's targets materialize.
+    pub pattern_count: usize,
+
+    /// The number of entries that could not be found in the index. When nonzero, the other counts are incomplete.
+    pub missing_entries: usize,
+}
+
+/// Index statistics for a repository. Entries shared by several projects count once per project, so the totals are the sums of the per-project figures.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IndexStats {
+    pub entries: usize,
+    pub size: usize,
+    pub pattern_count: usize,
+    pub missing_entries: usize,
+
+    /// Per-project figures, sorted by descending size.
+    pub projects: Vec<ProjectIndexStats>,
+}
+
+impl IndexStats {
+    /// Total up `projects`, sorting them by descending size and then by name.
+    pub fn from_projects(mut projects: Vec<ProjectIndexStats>) -> Self {
+        projects.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.project.cmp(&b.project)));
+        Self {
+            entries: projects.iter().map(|p| p.entries).sum(),
+            size: projects.iter().map(|p| p.size).sum(),
+            pattern_count: projects.iter().map(|p| p.pattern_count).sum(),
+            missing_entries: projects.iter().map(|p| p.missing_entries).sum(),
+            projects,
+        }
+    }
+}
+
+fn project_index_stats(
+    ctx: &HashContext,
+    odb: &dyn ObjectDatabase,
+    project: String,
+    dep_keys: HashSet<DependencyKey>,
+) -> anyhow::Result<ProjectIndexStats> {
+    let (seen_keys, pattern_count, missing_entries) =
+        match get_files_to_materialize(ctx, odb, dep_keys)? {
+            PathsToMaterializeResult::Ok { seen_keys, paths } => (seen_keys, paths.len(), 0),
+            PathsToMaterializeResult::MissingKeys {
+                seen_keys,
+                missing_keys,
+            } => (seen_keys, 0, missing_keys.len()),
+        };
+
+    let mut stats = ProjectIndexStats {
+        project,
+        pattern_count,
+        missing_entries,
+        ..Default::default()
+    };
+    for key in seen_keys.iter() {
+        if let (_, Some(value)) = odb.get(ctx, key)? {
+            stats.entries += 1;
+            stats.size += serde_json::to_vec(&value)
+                .context("serializing DependencyValue as JSON")?
+                .len();
+        }
+    }
+    Ok(stats)
+}
+
+/// Collect the index statistics of each project in `repo` at HEAD.
+fn index_stats(repo: &Repo) -> anyhow::Result<IndexStats> {
+    let selections = repo.selection_manager()?;
+    let catalog = selections.project_catalog();
+    let head_tree = repo
+        .get_head_commit()?
+        .tree()
+        .context("finding HEAD tree")?;
+    let ctx = HashContext::new(repo.underlying(), &head_tree)?;
+    let odb = RocksDBCache::new(repo.underlying());
+
+    let mut projects = Vec::new();
+    for project in catalog
+        .mandatory_projects
+        .underlying
+        .values()
+        .chain(catalog.optional_projects.underlying.values())
+    {
+        let dep_keys = TargetSet::try_from(project)?
+            .into_iter()
+            .map(DependencyKey::from)
+            .collect();
+        projects.push(project_index_stats(
+            &ctx,
+            odb.borrow(),
+            project.name.clone(),
+            dep_keys,
+        )?);
+    }
+    Ok(IndexStats::from_projects(projects))
+}
+
+/// Report how many index entries the projects in the repository use at HEAD and how large they are. With `by_project`, the figures for each project are listed as well, largest first.
+pub fn stats(
+    app: Arc<App>,
+    sparse_repo_path: &Path,
+    by_project: bool,
+    json: bool,
+) -> anyhow::Result<ExitCode> {
+    let repo = Repo::open(sparse_repo_path, app)?;
+    let mut stats = index_stats(&repo)?;
+
+    if !by_project {
+        stats.projects.clear();
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        println!(
+            "{:<48} {:>8} {:>12} {:>9}",
+            "PROJECT", "ENTRIES", "BYTES", "PATTERNS"
+        );
+        for project in stats.projects.iter() {
+            println!(
+                "{:<48} {:>8} {:>12} {:>9}",
+                project.project, project.entries, project.size, project.pattern_count
+            );
+        }
+        println!(
+            "{:<48} {:>8} {:>12} {:>9}",
+            "TOTAL", stats.entries, stats.size, stats.pattern_count
+        );
+    }
+    if stats.missing_entries > 0 {
+        eprintln!(
+            "{} entries are missing from the index, so these figures are incomplete. Run `focus index fetch` or `focus index generate` to populate it.",
+            stats.missing_entries
+        );
+    }
+
+    Ok(ExitCode(0))
+}
+
+/// A project target whose index entries are stale or damaged.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StaleTarget {
+    pub target: String,
+
+    /// The keys reachable from the target that have no entry under their current content hash, as `<hash> <target>`.
+    pub missing: Vec<String>,
+
+    /// Why the entries reachable from the target could not be read, if they could not.
+    pub error: Option<String>,
+}
+
+/// The outcome of `verify`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    /// The number of project targets that were checked.
+    pub checked: usize,
+
+    /// The checked targets whose entries are stale or damaged, sorted by target.
+    pub stale: Vec<StaleTarget>,
+}
+
+/// Check the index entries reachable from each of `targets` under their current content hashes.
+fn stale_targets(
+    ctx: &HashContext,
+    odb: &dyn ObjectDatabase,
+    targets: &[Target],
+) -> Vec<StaleTarget> {
+    targets
+        .iter()
+        .filter_map(|target| {
+            let dep_keys = HashSet::from([DependencyKey::from(target.clone())]);
+            let stale = match get_files_to_materialize(ctx, odb, dep_keys) {
+                Ok(PathsToMaterializeResult::Ok { .. }) => return None,
+                Ok(PathsToMaterializeResult::MissingKeys { missing_keys, .. }) => StaleTarget {
+                    target: target.to_string(),
+                    missing: missing_keys
+                        .iter()
+                        .map(|(key, hash)| format!("{} {}", hash, dep_key_to_target(key)))
+                        .collect(),
+                    error: None,
+                },
+                Err(e) => StaleTarget {
+                    target: target.to_string(),
+                    missing: Vec::new(),
+                    error: Some(format!("{:#}", e)),
+                },
+            };
+            Some(stale)
+        })
+        .collect()
+}
+
+/// Recompute the content hashes of the projects' targets at HEAD and report those whose index entries are missing or unreadable, without rebuilding the index. With `sample`, only that many targets chosen at random are checked. With `fix`, the stale targets are resolved again and their entries replaced.
+pub fn verify(
+    app: Arc<App>,
+    sparse_repo_path: &Path,
+    sample: Option<usize>,
+    fix: bool,
+    json: bool,
+) -> anyhow::Result<ExitCode> {
+    let repo = Repo::open(sparse_repo_path, app.clone())?;
+    let selections = repo.selection_manager()?;
+    let catalog = selections.project_catalog();
+    let mut targets = BTreeMap::new();
+    for project in catalog
+        .mandatory_projects
+        .underlying
+        .values()
+        .chain(catalog.optional_projects.underlying.values())
+    {
+        for target in TargetSet::try_from(project)? {
+            targets.insert(target.to_string(), target);
+        }
+    }
+    let mut targets: Vec<Target> = match sample {
+        Some(sample) => targets
+            .into_values()
+            .choose_multiple(&mut rand::thread_rng(), sample),
+        None => targets.into_values().collect(),
+    };
+    targets.sort_by_key(|target| target.to_string());
+
+    let head_commit = repo.get_head_commit()?;
+    let head_tree = head_commit.tree().context("finding HEAD tree")?;
+    let ctx = HashContext::new(repo.underlying(), &head_tree)?;
+    let odb = RocksDBCache::new(repo.underlying());
+    let mut report = VerifyReport {
+        checked: targets.len(),
+        stale: stale_targets(&ctx, &odb, &targets),
+    };
+
+    if fix && !report.stale.is_empty() {
+        let stale_targets_set = report
+            .stale
+            .iter()
+            .map(|stale| Target::try_from(stale.target.as_str()))
+            .collect::<Result<TargetSet, _>>()?;
+        eprintln!(
+            "Regenerating entries for {} targets",
+            stale_targets_set.len()
+        );
+        repo.reindex(
+            head_commit.id(),
+            &stale_targets_set,
+            &odb,
+            &Default::default(),
+            app,
+        )?;
+
+        let fixed: Vec<Target> = stale_targets_set.into_iter().collect();
+        report.stale = stale_targets(&ctx, &odb, &fixed);
+        report.stale.sort_by(|a, b| a.target.cmp(&b.target));
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for stale in report.stale.iter() {
+            println!("{}", stale.target);
+            for missing in stale.missing.iter() {
+                println!("    missing {}", missing);
+            }
+            if let Some(error) = &stale.error {
+                println!("    unreadable: {}", error);
+            }
+        }
+        eprintln!(
+            "Checked {} targets, {} with stale entries",
+            report.checked,
+            report.stale.len()
+        );
+    }
+
+    Ok(ExitCode(if report.stale.is_empty() { 0 } else { 1 }))
+}
+
 pub fn print_churn_stats(
     app: Arc<App>,
     sparse_repo: PathBuf,
@@ -420,6 +1243,141 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let command_failed = |stderr: &str| {
+            anyhow::Error::new(CommandFailed {
+                description: "git fetch".to_owned(),
+                stderr: stderr.to_owned(),
+            })
+            .context("Fetching")
+        };
+        assert!(is_transient_network_error(&command_failed(
+            "fatal: unable to access 'https://git.example.com/focus-index/': Operation timed out after 30000 milliseconds"
+        )));
+        assert!(is_transient_network_error(&command_failed(
+            "fatal: the remote end hung up unexpectedly"
+        )));
+        assert!(!is_transient_network_error(&command_failed(
+            "fatal: Authentication failed for 'https://git.example.com/focus-index/'"
+        )));
+        assert!(!is_transient_network_error(&command_failed(
+            "fatal: repository 'https://git.example.com/focus-index/' not found"
+        )));
+        assert!(!is_transient_network_error(&anyhow::anyhow!(
+            "Index checksum mismatch"
+        )));
+        assert!(is_transient_network_error(
+            &anyhow::Error::new(CommandTimedOut {
+                description: "git fetch".to_owned(),
+                timeout: Duration::from_secs(60),
+            })
+            .context("Fetching")
+        ));
+
+        let policy = RetryPolicy {
+            retries: 2,
+            base_delay: Duration::ZERO,
+        };
+        let mut attempts = 0;
+        let result = with_retries(&policy, "Fetching", || -> anyhow::Result<()> {
+            attempts += 1;
+            Err(command_failed("fatal: early EOF"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result = with_retries(&policy, "Fetching", || {
+            attempts += 1;
+            if attempts < 2 {
+                Err(command_failed(
+                    "error: RPC failed; curl 56 Connection reset by peer",
+                ))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        let mut attempts = 0;
+        let result = with_retries(&policy, "Fetching", || -> anyhow::Result<()> {
+            attempts += 1;
+            Err(command_failed("fatal: Authentication failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn only_unavailable_remotes_are_skipped() {
+        let command_failed = |stderr: &str| {
+            anyhow::Error::new(CommandFailed {
+                description: "git fetch".to_owned(),
+                stderr: stderr.to_owned(),
+            })
+            .context("Fetching")
+        };
+        assert!(is_remote_unavailable(&command_failed(
+            "fatal: '/nonexistent' does not appear to be a git repository\nfatal: Could not read from remote repository."
+        )));
+        assert!(is_remote_unavailable(&command_failed(
+            "fatal: repository 'https://git.example.com/focus-index/' not found"
+        )));
+        assert!(is_remote_unavailable(&command_failed(
+            "fatal: unable to access 'https://git.example.com/focus-index/': Could not resolve host: git.example.com"
+        )));
+        assert!(!is_remote_unavailable(&anyhow::anyhow!(
+            "Failed to parse the index"
+        )));
+
+        let checksum_mismatch = anyhow::Error::new(IndexChecksumMismatch {
+            reference_name: String::from("refs/tags/focus/abc"),
+            expected: String::from("1"),
+            actual: String::from("2"),
+        })
+        .context("Fetching index data");
+        assert!(!is_remote_unavailable(&checksum_mismatch));
+        let signature_invalid = anyhow::Error::new(IndexSignatureInvalid {
+            reference_name: String::from("refs/tags/focus/abc"),
+            reason: String::from("signature not found"),
+        })
+        .context("Fetching index data");
+        assert!(!is_remote_unavailable(&signature_invalid));
+    }
+
+    #[test]
+    fn projects_are_sorted_by_size_and_totaled() {
+        let project = |name: &str, entries, size, pattern_count| ProjectIndexStats {
+            project: name.to_owned(),
+            entries,
+            size,
+            pattern_count,
+            missing_entries: 0,
+        };
+        let stats = IndexStats::from_projects(vec![
+            project("small", 2, 100, 3),
+            project("large", 40, 9000, 25),
+            project("medium", 10, 2500, 12),
+            ProjectIndexStats {
+                missing_entries: 4,
+                ..project("unindexed", 0, 0, 0)
+            },
+        ]);
+
+        let names: Vec<&str> = stats.projects.iter().map(|p| p.project.as_str()).collect();
+        assert_eq!(names, vec!["large", "medium", "small", "unindexed"]);
+        assert_eq!(
+            (
+                stats.entries,
+                stats.size,
+                stats.pattern_count,
+                stats.missing_entries
+            ),
+            (52, 11600, 40, 4)
+        );
+    }
+
     #[test]
     fn test_index_push_and_fetch() -> anyhow::Result<()> {
         let temp_dir = tempfile::tempdir()?;
@@ -439,6 +1397,10 @@ mod tests {
                 remote.clone(),
                 false,
                 false,
+                None,
+                false,
+                None,
+                false,
             )?;
             assert_eq!(exit_code, 0);
         }
@@ -497,7 +1459,15 @@ mod tests {
             "###);
         }
 
-        let ExitCode(exit_code) = fetch(app, fixture.sparse_repo_path.clone(), false, None)?;
+        let ExitCode(exit_code) = fetch(
+            app,
+            fixture.sparse_repo_path.clone(),
+            false,
+            None,
+            None,
+            None,
+            Default::default(),
+        )?;
         assert_eq!(exit_code, 0);
 
         // Try to materialize files again -- this should be a cache hit.
@@ -542,4 +1512,365 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_index_fetch_falls_back_to_the_next_remote() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let remote_index_store = ScratchGitRepo::new_static_fixture(temp_dir.path())?;
+        let remote = format!("file://{}", remote_index_store.path().display());
+        let missing_remote = format!("file://{}", temp_dir.path().join("missing").display());
+
+        let app = Arc::new(App::new_for_testing()?);
+        let label: Label = "//project_a/src/main/java/com/example/cmdline:runner".parse()?;
+
+        {
+            let fixture = RepoPairFixture::new()?;
+            fixture.perform_clone()?;
+            let ExitCode(exit_code) = push(
+                app.clone(),
+                fixture.sparse_repo_path.clone(),
+                remote.clone(),
+                false,
+                false,
+                None,
+                false,
+                None,
+                false,
+            )?;
+            assert_eq!(exit_code, 0);
+        }
+
+        let fixture = RepoPairFixture::new()?;
+        fixture.perform_clone()?;
+        let index_config = IndexConfig {
+            enabled: true,
+            remote: format!("{},{}", missing_remote, remote),
+        };
+        let config_dir = Configuration::config_dir(&fixture.sparse_repo_path);
+        std::fs::create_dir_all(&config_dir)?;
+        store_model(config_dir.join(INDEX_CONFIG_FILENAME), &index_config)?;
+
+        let ExitCode(exit_code) = fetch(
+            app,
+            fixture.sparse_repo_path.clone(),
+            false,
+            None,
+            None,
+            None,
+            Default::default(),
+        )?;
+        assert_eq!(exit_code, 0);
+
+        // The index was served by the second remote.
+        let repo = fixture.sparse_repo()?;
+        let repo = repo.underlying();
+        let head_tree = repo.head()?.peel_to_commit()?.tree()?;
+        let ctx = HashContext::new(repo, &head_tree)?;
+        let odb = RocksDBCache::new(repo);
+        let materialize_result = get_files_to_materialize(
+            &ctx,
+            odb.borrow(),
+            hashset! {DependencyKey::BazelPackage(label)},
+        )?;
+        assert!(matches!(
+            materialize_result,
+            PathsToMaterializeResult::Ok { .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_generate_is_independent_of_jobs() -> anyhow::Result<()> {
+        let fixture = RepoPairFixture::new()?;
+        fixture.perform_clone()?;
+        let repo = Repo::open(&fixture.sparse_repo_path, fixture.app.clone())?;
+        let all_targets: TargetSet = project_targets(&repo)?
+            .into_iter()
+            .map(|(_, target)| target)
+            .collect();
+
+        let mut indexes = Vec::new();
+        for jobs in [1, 4] {
+            clear(fixture.sparse_repo_path.clone())?;
+            let ExitCode(exit_code) = generate(
+                fixture.app.clone(),
+                fixture.sparse_repo_path.clone(),
+                false,
+                None,
+                Some(jobs),
+            )?;
+            assert_eq!(exit_code, 0);
+
+            let seen_keys = match resolve_targets(
+                fixture.app.clone(),
+                &fixture.sparse_repo_path,
+                all_targets.clone(),
+                false,
+                &Default::default(),
+            )? {
+                Ok(ResolveTargetResult { seen_keys, .. }) => seen_keys,
+                Err(ExitCode(exit_code)) => anyhow::bail!("Resolution exited with {}", exit_code),
+            };
+            let git_repo = git2::Repository::open(&fixture.sparse_repo_path)?;
+            let tree = git_helper::get_head_commit(&git_repo)?.tree()?;
+            let ctx = HashContext::new(&git_repo, &tree)?;
+            let odb = RocksDBCache::new(&git_repo);
+            let mut index = BTreeMap::new();
+            for key in seen_keys {
+                if let DependencyKey::BazelPackage(_) = key {
+                    let hash = content_hash(&ctx, &key)?;
+                    let value = Cache::get(&odb, *FUNCTION_ID, git2::Oid::from(hash.clone()))?
+                        .with_context(|| format!("No index entry for {:?}", key))?;
+                    index.insert(hash, value);
+                }
+            }
+            assert!(!index.is_empty());
+            indexes.push(index);
+        }
+        assert_eq!(indexes[0], indexes[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_stats_of_fixture_projects() -> anyhow::Result<()> {
+        let fixture = RepoPairFixture::new()?;
+        fixture.perform_clone()?;
+        let project_stats = |name: &str| -> anyhow::Result<ProjectIndexStats> {
+            let repo = fixture.sparse_repo()?;
+            index_stats(&repo)?
+                .projects
+                .into_iter()
+                .find(|project| project.project == name)
+                .with_context(|| format!("No stats for {}", name))
+        };
+
+        // Without an index, each Bazel target of a project is one missing entry and nothing else is counted.
+        clear(fixture.sparse_repo_path.clone())?;
+        for (name, targets) in [
+            ("mandatory", 1),
+            ("numbered/lib/3", 1),
+            ("numbered/libs_1_to_5", 5),
+            ("team_banzai/project_a", 1),
+        ] {
+            assert_eq!(
+                project_stats(name)?,
+                ProjectIndexStats {
+                    project: name.to_owned(),
+                    missing_entries: targets,
+                    ..Default::default()
+                }
+            );
+        }
+
+        let ExitCode(exit_code) = generate(
+            fixture.app.clone(),
+            fixture.sparse_repo_path.clone(),
+            false,
+            None,
+            None,
+        )?;
+        assert_eq!(exit_code, 0);
+        for name in ["mandatory", "numbered/libs_1_to_5", "team_banzai/project_a"] {
+            let stats = project_stats(name)?;
+            assert_eq!(stats.missing_entries, 0, "{}", name);
+            assert!(stats.entries > 0 && stats.size > 0, "{}", name);
+        }
+        // The numbered libraries do not depend on each other, so each one's package is an entry of its own.
+        assert!(
+            project_stats("numbered/libs_1_to_5")?.entries
+                >= project_stats("numbered/lib/3")?.entries + 4
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_verify_reports_missing_entries() -> anyhow::Result<()> {
+        let fixture = RepoPairFixture::new()?;
+        fixture.perform_clone()?;
+        let ExitCode(exit_code) = generate(
+            fixture.app.clone(),
+            fixture.sparse_repo_path.clone(),
+            false,
+            None,
+            None,
+        )?;
+        assert_eq!(exit_code, 0);
+
+        let ExitCode(exit_code) = verify(
+            fixture.app.clone(),
+            &fixture.sparse_repo_path,
+            None,
+            false,
+            true,
+        )?;
+        assert_eq!(exit_code, 0);
+
+        clear(fixture.sparse_repo_path.clone())?;
+        let repo = fixture.sparse_repo()?;
+        let head_tree = repo.get_head_commit()?.tree()?;
+        let ctx = HashContext::new(repo.underlying(), &head_tree)?;
+        let odb = RocksDBCache::new(repo.underlying());
+        let target = Target::try_from("bazel://library_a/...")?;
+        let stale = stale_targets(&ctx, &odb, std::slice::from_ref(&target));
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].target, target.to_string());
+        assert!(!stale[0].missing.is_empty());
+        assert_eq!(stale[0].error, None);
+        drop(odb);
+
+        let ExitCode(exit_code) = verify(
+            fixture.app.clone(),
+            &fixture.sparse_repo_path,
+            Some(1),
+            true,
+            true,
+        )?;
+        assert_eq!(exit_code, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_generate_writes_manifest() -> anyhow::Result<()> {
+        let fixture = RepoPairFixture::new()?;
+        fixture.perform_clone()?;
+        let manifest_path = fixture.dir.path().join("manifest.json");
+
+        let ExitCode(exit_code) = generate(
+            fixture.app.clone(),
+            fixture.sparse_repo_path.clone(),
+            false,
+            Some(manifest_path.clone()),
+            None,
+        )?;
+        assert_eq!(exit_code, 0);
+
+        let manifest: serde_json::Value = serde_json::from_slice(&std::fs::read(&manifest_path)?)?;
+        let entries = manifest["entries"].as_array().unwrap();
+
+        // The fixture has 25 project targets: two mandatory ones, 20 across the numbered library projects, and one in each of the three team projects.
+        assert_eq!(entries.len(), 25);
+        let entry = |project: &str, target: &str| {
+            entries
+                .iter()
+                .find(|entry| entry["project"] == project && entry["target"] == target)
+                .unwrap_or_else(|| panic!("No entry for {} in {}", target, project))
+        };
+        assert!(entry("mandatory", "directory:mandatory_y/very_important_info")["size"].is_null());
+        assert!(entry("numbered/lib/3", "bazel://library_3/...")["size"].as_u64() > Some(0));
+        assert_eq!(
+            entry("numbered/lib/3", "bazel://library_3/...")["hash"],
+            entry("numbered/libs_1_to_5", "bazel://library_3/...")["hash"]
+        );
+        let listed: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry["project"].as_str().unwrap(),
+                    entry["target"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        let mut sorted = listed.clone();
+        sorted.sort_unstable();
+        assert_eq!(listed, sorted);
+
+        let repo = fixture.sparse_repo()?;
+        let head_tree = repo.get_head_commit()?.tree()?;
+        let ctx = HashContext::new(repo.underlying(), &head_tree)?;
+        for entry in entries {
+            let target = Target::try_from(entry["target"].as_str().unwrap())?;
+            let hash = content_hash(&ctx, &DependencyKey::from(target))?;
+            assert_eq!(entry["hash"], hash.to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_push_and_fetch_tagged_snapshot() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let remote_index_store = ScratchGitRepo::new_static_fixture(temp_dir.path())?;
+        let remote = format!("file://{}", remote_index_store.path().display());
+
+        let app = Arc::new(App::new_for_testing()?);
+        let label: Label = "//project_a/src/main/java/com/example/cmdline:runner".parse()?;
+        let tag = Some(String::from("release-1"));
+
+        {
+            let fixture = RepoPairFixture::new()?;
+            fixture.perform_clone()?;
+            let ExitCode(exit_code) = push(
+                app.clone(),
+                fixture.sparse_repo_path.clone(),
+                remote.clone(),
+                false,
+                false,
+                tag.clone(),
+                false,
+                None,
+                false,
+            )?;
+            assert_eq!(exit_code, 0);
+
+            // Tagged snapshots are immutable unless forced.
+            assert!(push(
+                app.clone(),
+                fixture.sparse_repo_path.clone(),
+                remote.clone(),
+                false,
+                false,
+                tag.clone(),
+                false,
+                None,
+                false,
+            )
+            .is_err());
+            let ExitCode(exit_code) = push(
+                app.clone(),
+                fixture.sparse_repo_path.clone(),
+                remote.clone(),
+                false,
+                false,
+                tag.clone(),
+                true,
+                None,
+                false,
+            )?;
+            assert_eq!(exit_code, 0);
+        }
+
+        let fixture = RepoPairFixture::new()?;
+        fixture.perform_clone()?;
+        let ExitCode(exit_code) = fetch(
+            app.clone(),
+            fixture.sparse_repo_path.clone(),
+            true,
+            Some(remote),
+            tag,
+            None,
+            Default::default(),
+        )?;
+        assert_eq!(exit_code, 0);
+
+        let repo = fixture.sparse_repo()?;
+        let repo = repo.underlying();
+        let head_tree = repo.head()?.peel_to_commit()?.tree()?;
+        let ctx = HashContext::new(repo, &head_tree)?;
+        let odb = RocksDBCache::new(repo);
+        let materialize_result = get_files_to_materialize(
+            &ctx,
+            odb.borrow(),
+            hashset! {DependencyKey::BazelPackage(label)},
+        )?;
+        assert!(matches!(
+            materialize_result,
+            PathsToMaterializeResult::Ok { .. }
+        ));
+
+        Ok(())
+    }
 }
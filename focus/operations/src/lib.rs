@@ -6,7 +6,9 @@
 pub mod background;
 pub mod branch;
 pub mod clone;
+pub mod config;
 pub mod detect_build_graph_changes;
+pub mod doctor;
 pub mod ensure_clean;
 pub mod event;
 pub mod filter;
@@ -0,0 +1,293 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scheduling of maintenance jobs with cron, for Linux hosts that do not run systemd.
+
+use super::{scheduling::*, *};
+use std::{io::Write, process::Stdio};
+use strum::IntoEnumIterator;
+
+const CRONTAB_BIN: &str = "crontab";
+const SYSTEMD_RUNTIME_DIR: &str = "/run/systemd/system";
+
+/// Every crontab entry we write ends with this marker followed by the time period, so that we can find (and remove) exactly the entries we added.
+const CRON_MARKER_PREFIX: &str = "# focus-maintenance:";
+
+/// Whether this host was booted with systemd. This is the same check as `sd_booted(3)`.
+pub fn systemd_available() -> bool {
+    Path::new(SYSTEMD_RUNTIME_DIR).is_dir()
+}
+
+fn marker(time_period: TimePeriod) -> String {
+    format!("{}{}", CRON_MARKER_PREFIX, time_period)
+}
+
+fn is_entry_for(line: &str, time_period: TimePeriod) -> bool {
+    line.trim_end().ends_with(&marker(time_period))
+}
+
+fn is_focus_entry(line: &str) -> bool {
+    TimePeriod::iter().any(|time_period| is_entry_for(line, time_period))
+}
+
+/// Quote `arg` for the shell that cron runs commands with. `%` is special to cron, so it is escaped as well.
+fn quote(arg: &str) -> String {
+    let quoted = if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@".contains(c))
+    {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r#"'\''"#))
+    };
+    quoted.replace('%', r"\%")
+}
+
+/// The schedule fields of the crontab entry for `opts`. As with launchd, hourly jobs run every five minutes.
+fn schedule(opts: &ScheduledJobOpts) -> String {
+    let CalendarInterval {
+        hour,
+        minute,
+        weekday,
+        every_n_minutes,
+        ..
+    } = opts.schedule_defaults.clone().unwrap_or_default();
+    let minute = minute.unwrap_or_else(|| rand::random::<u32>() % 60);
+    let hour = hour.unwrap_or(DEFAULT_DAILY_HOUR);
+    match opts.time_period {
+        TimePeriod::Hourly => match every_n_minutes {
+            Some(n) => format!("{}-59/{} * * * *", minute % n, n),
+            None => format!("{} * * * *", minute),
+        },
+        TimePeriod::Daily => format!("{} {} * * *", minute, hour),
+        TimePeriod::Weekly => format!(
+            "{} {} * * {}",
+            minute,
+            hour,
+            weekday.unwrap_or(DEFAULT_WEEKLY_WEEKDAY)
+        ),
+    }
+}
+
+/// Render the crontab line for `opts`, including its marker.
+pub(crate) fn entry(opts: &ScheduledJobOpts) -> String {
+    let command = ProgramArguments::from(opts.clone())
+        .to_args()
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "{} {} {}",
+        schedule(opts),
+        command,
+        marker(opts.time_period)
+    )
+}
+
+/// Return `crontab` with `entries` added, replacing any entries we previously added for the same time periods. Other lines are preserved.
+pub(crate) fn add_entries(crontab: &str, entries: &[(TimePeriod, String)]) -> String {
+    let mut lines: Vec<&str> = crontab
+        .lines()
+        .filter(|line| {
+            !entries
+                .iter()
+                .any(|(time_period, _)| is_entry_for(line, *time_period))
+        })
+        .collect();
+    lines.extend(entries.iter().map(|(_, entry)| entry.as_str()));
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// Return `crontab` without any of the entries we added. Other lines are preserved.
+pub(crate) fn remove_entries(crontab: &str) -> String {
+    let mut result = String::new();
+    for line in crontab.lines().filter(|line| !is_focus_entry(line)) {
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
+
+#[derive(Debug, Clone)]
+pub struct Crontab {
+    pub crontab_bin: PathBuf,
+}
+
+impl Default for Crontab {
+    fn default() -> Self {
+        Self {
+            crontab_bin: CRONTAB_BIN.into(),
+        }
+    }
+}
+
+impl Crontab {
+    /// Read the current user's crontab. A user without a crontab has an empty one.
+    pub fn read(&self) -> Result<String> {
+        let output = Command::new(&self.crontab_bin)
+            .arg("-l")
+            .output()
+            .context("failed to run crontab -l")?;
+        if output.status.success() {
+            return String::from_utf8(output.stdout).context("crontab was not valid UTF-8");
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no crontab for") {
+            Ok(String::new())
+        } else {
+            bail!("crontab -l failed: {}", stderr.trim());
+        }
+    }
+
+    /// Replace the current user's crontab with `content`.
+    pub fn write(&self, content: &str) -> Result<()> {
+        let mut child = Command::new(&self.crontab_bin)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("failed to run crontab -")?;
+        child
+            .stdin
+            .take()
+            .expect("stdin was not captured")
+            .write_all(content.as_bytes())
+            .context("writing crontab")?;
+        let status = child.wait()?;
+        if !status.success() {
+            bail!("crontab - failed: {}", status);
+        }
+        Ok(())
+    }
+}
+
+/// Whether the current user's crontab has an entry for `time_period`.
+pub fn is_scheduled(time_period: TimePeriod) -> Result<bool> {
+    let crontab = Crontab::default().read()?;
+    Ok(crontab.lines().any(|line| is_entry_for(line, time_period)))
+}
+
+/// Add crontab entries for the requested time periods (all of them if `time_period` is None).
+#[tracing::instrument]
+pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
+    let ScheduleOpts {
+        time_period,
+        git_path,
+        focus_path,
+        skip_if_already_scheduled,
+        tracked,
+        on_battery,
+    } = opts;
+
+    let crontab = Crontab::default();
+    let existing = crontab.read()?;
+
+    let time_periods: Vec<TimePeriod> = match time_period {
+        Some(tp) => vec![tp],
+        None => TimePeriod::iter().collect(),
+    };
+
+    let mut entries = Vec::new();
+    for tp in time_periods {
+        if skip_if_already_scheduled && existing.lines().any(|line| is_entry_for(line, tp)) {
+            debug!(time_period = %tp, "Already scheduled in crontab");
+            continue;
+        }
+        let job_opts = ScheduledJobOpts {
+            focus_path: focus_path.to_owned(),
+            git_binary_path: git_path.to_owned(),
+            time_period: tp,
+            schedule_defaults: match tp {
+                TimePeriod::Hourly => Some(CalendarInterval {
+                    every_n_minutes: Some(5),
+                    ..Default::default()
+                }),
+                _ => None,
+            },
+            tracked,
+            skip_on_battery: on_battery == power::OnBattery::Skip,
+            ..Default::default()
+        };
+        entries.push((tp, entry(&job_opts)));
+    }
+
+    if !entries.is_empty() {
+        crontab.write(&add_entries(&existing, &entries))?;
+    }
+    Ok(())
+}
+
+/// Remove all crontab entries added by `schedule_enable`.
+#[tracing::instrument]
+pub fn schedule_disable() -> Result<()> {
+    let crontab = Crontab::default();
+    let existing = crontab.read()?;
+    let updated = remove_entries(&existing);
+    if updated != existing {
+        crontab.write(&updated)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_opts(time_period: TimePeriod) -> ScheduledJobOpts {
+        ScheduledJobOpts {
+            focus_path: "/path/to/focus".into(),
+            git_binary_path: "/usr/local/bin/git".into(),
+            time_period,
+            schedule_defaults: Some(CalendarInterval {
+                minute: Some(12),
+                every_n_minutes: Some(5),
+                ..Default::default()
+            }),
+            tracked: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_entry() {
+        assert_eq!(
+            entry(&job_opts(TimePeriod::Hourly)),
+            "2-59/5 * * * * /path/to/focus maintenance --git-config-key=maintenance.repo run --git-binary-path=/usr/local/bin/git --time-period=hourly --tracked # focus-maintenance:hourly"
+        );
+        assert!(entry(&job_opts(TimePeriod::Daily)).starts_with("12 4 * * * "));
+        assert!(entry(&job_opts(TimePeriod::Weekly)).starts_with("12 4 * * 1 "));
+        assert!(entry(&ScheduledJobOpts {
+            skip_on_battery: true,
+            ..job_opts(TimePeriod::Daily)
+        })
+        .ends_with("--tracked --skip-on-battery # focus-maintenance:daily"));
+        assert_eq!(quote("it's 100%"), r"'it'\''s 100\%'");
+    }
+
+    #[test]
+    fn test_add_and_remove_entries() {
+        let existing = "MAILTO=ops@example.com\n0 0 * * * /usr/bin/backup\n";
+        let hourly = entry(&job_opts(TimePeriod::Hourly));
+        let daily = entry(&job_opts(TimePeriod::Daily));
+
+        let crontab = add_entries(
+            existing,
+            &[
+                (TimePeriod::Hourly, hourly.clone()),
+                (TimePeriod::Daily, daily.clone()),
+            ],
+        );
+        assert_eq!(crontab, format!("{}{}\n{}\n", existing, hourly, daily));
+
+        // Re-adding an entry replaces the previous one.
+        let crontab = add_entries(&crontab, &[(TimePeriod::Hourly, hourly.clone())]);
+        assert_eq!(crontab, format!("{}{}\n{}\n", existing, daily, hourly));
+
+        assert_eq!(remove_entries(&crontab), existing);
+        assert_eq!(remove_entries(existing), existing);
+    }
+}
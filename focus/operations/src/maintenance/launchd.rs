@@ -215,6 +215,7 @@ pub struct ScheduleOpts {
     pub focus_path: PathBuf,
     pub skip_if_already_scheduled: bool,
     pub tracked: bool,
+    pub on_battery: power::OnBattery,
 }
 
 impl Default for ScheduleOpts {
@@ -226,6 +227,7 @@ impl Default for ScheduleOpts {
                 .expect("could not determine current executable path"),
             skip_if_already_scheduled: true,
             tracked: true,
+            on_battery: power::OnBattery::Run,
         }
     }
 }
@@ -233,9 +235,21 @@ impl Default for ScheduleOpts {
 #[tracing::instrument]
 #[cfg(target_os = "linux")]
 pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
+    if !cron::systemd_available() {
+        return cron::schedule_enable(opts);
+    }
+    if opts.on_battery == power::OnBattery::Skip {
+        tracing::warn!("Maintenance is scheduled by systemd on this host, so it cannot be skipped on battery power");
+    }
     Ok(())
 }
 
+#[tracing::instrument]
+#[cfg(windows)]
+pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
+    schtasks::schedule_enable(opts)
+}
+
 #[tracing::instrument]
 #[cfg(target_os = "macos")]
 fn schedule_defaults_for(tp: TimePeriod, plist_opts: ScheduledJobOpts) -> ScheduledJobOpts {
@@ -268,6 +282,7 @@ pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
         focus_path,
         skip_if_already_scheduled,
         tracked,
+        on_battery,
     } = opts;
 
     assert!(
@@ -295,6 +310,7 @@ pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
                 focus_path: focus_path.to_owned(),
                 git_binary_path: git_path.to_owned(),
                 tracked,
+                skip_on_battery: on_battery == power::OnBattery::Skip,
                 ..Default::default()
             },
         );
@@ -320,7 +336,19 @@ pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
 }
 
 #[tracing::instrument]
+#[cfg(windows)]
 pub fn schedule_disable(delete: bool) -> Result<()> {
+    schtasks::schedule_disable(delete)
+}
+
+#[tracing::instrument]
+#[cfg(not(windows))]
+pub fn schedule_disable(delete: bool) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if !cron::systemd_available() {
+        return cron::schedule_disable();
+    }
+
     let launchctl = Launchctl::default();
     let time_periods: Vec<TimePeriod> = TimePeriod::iter().collect();
 
@@ -363,6 +391,7 @@ mod tests {
                 ..Default::default()
             }),
             tracked: true,
+            skip_on_battery: false,
         }
     }
 
@@ -1,8 +1,14 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(target_os = "linux")]
+pub mod cron;
 pub mod launchd;
+pub mod power;
 pub mod scheduling;
+#[cfg(windows)]
+pub mod schtasks;
+pub mod status;
 
 use std::{
     collections::HashMap,
@@ -10,13 +16,18 @@ use std::{
     fmt::Debug,
     ops::Deref,
     path::{Path, PathBuf},
-    process::{Command, ExitStatus},
+    process::{Command, ExitStatus, Stdio},
     sync::Arc,
     time::{Duration, Instant, SystemTime},
 };
 
 use content_addressed_cache::RocksDBCache;
-use focus_internals::{index::RocksDBMemoizationCacheExt, locking, tracker::Tracker};
+use focus_internals::{
+    index::RocksDBMemoizationCacheExt,
+    locking,
+    model::{activity::Activity, repo::Repo},
+    tracker::Tracker,
+};
 
 use anyhow::{bail, Context, Result};
 use focus_util::git_helper::{git_command_with_git_binary, GitBinary};
@@ -54,6 +65,58 @@ impl TimePeriod {
     pub fn name(&self) -> &'static str {
         self.into()
     }
+
+    /// The tasks run in each repo for this time period, in execution order.
+    pub fn tasks(&self) -> Vec<MaintenanceTask> {
+        vec![
+            MaintenanceTask::GitMaintenance,
+            MaintenanceTask::PreemptiveSync,
+            MaintenanceTask::RocksDBCompaction,
+        ]
+    }
+}
+
+/// A unit of work performed in a repo during a maintenance run.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    strum_macros::Display,
+    strum_macros::IntoStaticStr,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum MaintenanceTask {
+    /// `git maintenance run` for the scheduled time period.
+    GitMaintenance,
+
+    /// A preemptive sync of the sparse checkout.
+    PreemptiveSync,
+
+    /// Compaction of the index database.
+    #[serde(rename = "rocksdb-compaction")]
+    #[strum(serialize = "rocksdb-compaction")]
+    RocksDBCompaction,
+}
+
+impl MaintenanceTask {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+
+    /// A human-readable description of what the task does for the given time period.
+    pub fn describe(&self, time_period: TimePeriod) -> String {
+        match self {
+            MaintenanceTask::GitMaintenance => {
+                format!("git maintenance run --schedule={}", time_period.name())
+            }
+            MaintenanceTask::PreemptiveSync => "focus preemptive sync".to_string(),
+            MaintenanceTask::RocksDBCompaction => "index database compaction".to_string(),
+        }
+    }
 }
 
 pub const DEFAULT_CONFIG_KEY: &str = "maintenance.repo";
@@ -179,7 +242,7 @@ fn maint_exit_status_metric_helper(exit_status: ExitStatus) -> String {
     }
 }
 
-fn sync_status_metric_helper(sync_status: crate::sync::SyncStatus) -> String {
+fn sync_status_metric_helper(sync_status: &crate::sync::SyncStatus) -> String {
     match sync_status {
         crate::sync::SyncStatus::Success => "success".to_string(),
         crate::sync::SyncStatus::SkippedSyncPointUnchanged => "skipped_unchanged".to_string(),
@@ -191,9 +254,50 @@ fn sync_status_metric_helper(sync_status: crate::sync::SyncStatus) -> String {
             "skipped_activity".to_string()
         }
         crate::sync::SyncStatus::SkippedUnfilterView => "skipped_unfiltered".to_string(),
+        crate::sync::SyncStatus::DiffOnly => "diff_only".to_string(),
+    }
+}
+
+/// A repo and the tasks that a maintenance run would perform in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRun {
+    pub path: PathBuf,
+    pub tasks: Vec<MaintenanceTask>,
+}
+
+/// How long a task took in one repo during a maintenance run, and how it ended.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TaskTiming {
+    pub path: PathBuf,
+    pub task: MaintenanceTask,
+    pub duration_secs: f64,
+
+    /// The git exit code or sync status if the task completed, or `error` if it failed.
+    pub status: String,
+}
+
+/// Which repos a maintenance run completed in and which it failed in. A failure in one repo does
+/// not stop maintenance from running in the others.
+#[derive(Debug, Default, Clone)]
+pub struct RunSummary {
+    pub succeeded: Vec<PathBuf>,
+
+    /// The repos that failed, with a description of the error.
+    pub failed: Vec<(PathBuf, String)>,
+
+    /// Whether the run was skipped because the host was on battery power.
+    pub skipped_on_battery: bool,
+}
+
+impl RunSummary {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
     }
 }
 
+/// The name of the file in the sandbox that the timings of a maintenance run are written to.
+pub const TIMINGS_FILE_NAME: &str = "maintenance-timings.json";
+
 #[derive(Debug)]
 enum MaintResult {
     Success(ExitStatus),
@@ -209,6 +313,10 @@ pub struct Runner<'a> {
     pub tracker: &'a Tracker,
     /// if true, use the focus Tracker to discover repos
     pub tracked_repos: bool,
+    /// if true, print the time taken by each task when the run finishes
+    pub print_timings: bool,
+    /// if true, let git maintenance write to the terminal rather than capturing its output
+    pub stream_output: bool,
     pub app: Arc<App>,
 }
 
@@ -228,6 +336,9 @@ impl Runner<'_> {
             git_config_key: config_key,
             git_config_path: config_path,
             tracked,
+            timings,
+            skip_on_battery: _,
+            stream_output,
         } = opts;
 
         let git_binary = match git_binary {
@@ -241,6 +352,8 @@ impl Runner<'_> {
             config: use_config_path_or_default_global(config_path.as_deref())?,
             tracker,
             tracked_repos: tracked,
+            print_timings: timings,
+            stream_output,
             app,
         })
     }
@@ -248,17 +361,25 @@ impl Runner<'_> {
     #[tracing::instrument]
     fn run_git_maint(&self, time_period: TimePeriod, repo_path: &Path) -> Result<MaintResult> {
         let (mut cmd, scmd) = git_command_with_git_binary(self.app.clone(), &self.git_binary)?;
+        cmd.arg("maintenance")
+            .arg("run")
+            .arg(format!("--schedule={}", time_period.name()))
+            .current_dir(repo_path);
+
+        if self.stream_output {
+            let status = cmd
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .status()
+                .context("Running git maintenance")?;
+            return Ok(MaintResult::Success(status));
+        }
 
         // TODO: this needs to log and capture output for debugging if necessary
-        Ok(MaintResult::Success(
-            scmd.ensure_success_or_log(
-                cmd.arg("maintenance")
-                    .arg("run")
-                    .arg(format!("--schedule={}", time_period.name()))
-                    .current_dir(repo_path),
-                SandboxCommandOutput::Stderr,
-            )?,
-        ))
+        Ok(MaintResult::Success(scmd.ensure_success_or_log(
+            &mut cmd,
+            SandboxCommandOutput::Stderr,
+        )?))
     }
 
     #[tracing::instrument]
@@ -270,11 +391,7 @@ impl Runner<'_> {
     }
 
     #[tracing::instrument]
-    fn run_internal_maint(
-        &self,
-        time_period: TimePeriod,
-        repo_path: &Path,
-    ) -> Result<crate::sync::SyncStatus> {
+    fn run_preemptive_sync(&self, repo_path: &Path) -> Result<crate::sync::SyncStatus> {
         let sync_result = crate::sync::run(
             &SyncRequest::new(
                 repo_path,
@@ -284,13 +401,48 @@ impl Runner<'_> {
         )
         .with_context(|| format!("Preemptively syncing in {}", repo_path.display()))?;
 
-        self.run_rocksdb_compaction(repo_path)?;
-
         Ok(sync_result.status)
     }
 
-    #[tracing::instrument]
-    fn run_maint(&self, time_period: TimePeriod, repo_path: &Path) -> Result<MaintResult> {
+    /// Run `f` as `task` in `repo_path`, recording how long it took and how it ended in `timings`. Returns the result of `f` along with how long it took.
+    fn time_task<T>(
+        &self,
+        task: MaintenanceTask,
+        repo_path: &Path,
+        timings: &mut Vec<TaskTiming>,
+        f: impl FnOnce() -> Result<T>,
+        status: impl FnOnce(&T) -> String,
+    ) -> Result<(T, Duration)> {
+        let started_at = Instant::now();
+        let result = f();
+        let duration = started_at.elapsed();
+        let timing = TaskTiming {
+            path: repo_path.to_owned(),
+            task,
+            duration_secs: duration.as_secs_f64(),
+            status: match &result {
+                Ok(value) => status(value),
+                Err(_) => "error".to_string(),
+            },
+        };
+        info!(
+            %task,
+            path = ?repo_path,
+            duration_secs = timing.duration_secs,
+            status = %timing.status,
+            "finished maintenance task"
+        );
+        timings.push(timing);
+        result.map(|value| (value, duration))
+    }
+
+    #[tracing::instrument(skip(timings))]
+    fn run_maint(
+        &self,
+        time_period: TimePeriod,
+        repo_path: &Path,
+        timings: &mut Vec<TaskTiming>,
+    ) -> Result<MaintResult> {
         let _lock = match locking::hold_lock(repo_path, Path::new("maint.lock"), self.app.clone()) {
             Ok(lock) => lock,
             Err(e) => {
@@ -299,23 +451,44 @@ impl Runner<'_> {
             }
         };
 
-        let git_maint_started_at = Instant::now();
-        let git_maint_result = self
-            .run_git_maint(time_period, repo_path)
+        let (git_maint_result, git_maint_runtime) = self
+            .time_task(
+                MaintenanceTask::GitMaintenance,
+                repo_path,
+                timings,
+                || self.run_git_maint(time_period, repo_path),
+                |result| match result {
+                    MaintResult::Success(status) => maint_exit_status_metric_helper(*status),
+                    MaintResult::LockFailed => "lock_failed".to_string(),
+                },
+            )
+            .with_context(|| format!("Running internal maintenance in {}", repo_path.display()))?;
+
+        let (sync_result, sync_runtime) = self
+            .time_task(
+                MaintenanceTask::PreemptiveSync,
+                repo_path,
+                timings,
+                || self.run_preemptive_sync(repo_path),
+                sync_status_metric_helper,
+            )
             .with_context(|| format!("Running internal maintenance in {}", repo_path.display()))?;
-        let git_maint_runtime = git_maint_started_at.elapsed();
 
-        let sync_maint_started_at = Instant::now();
-        let sync_result = self
-            .run_internal_maint(time_period, repo_path)
+        let ((), compaction_runtime) = self
+            .time_task(
+                MaintenanceTask::RocksDBCompaction,
+                repo_path,
+                timings,
+                || self.run_rocksdb_compaction(repo_path),
+                |_| "success".to_string(),
+            )
             .with_context(|| format!("Running internal maintenance in {}", repo_path.display()))?;
-        let sync_maint_runtime = sync_maint_started_at.elapsed();
 
         self.add_maint_ti_invocation_message(
             &git_maint_result,
             git_maint_runtime,
             sync_result,
-            sync_maint_runtime,
+            sync_runtime + compaction_runtime,
         );
 
         Ok(git_maint_result)
@@ -332,7 +505,8 @@ impl Runner<'_> {
         if !self.tracked_repos {
             return Ok(());
         }
-        self.tracker.repair(self.app.clone())
+        self.tracker.repair(false, self.app.clone())?;
+        Ok(())
     }
 
     fn get_repo_paths_from_config(&self) -> Result<Vec<PathBuf>> {
@@ -370,26 +544,47 @@ impl Runner<'_> {
         }
     }
 
-    fn run_in_path(&self, time_period: TimePeriod, path: &Path) -> Result<()> {
+    /// Run the maintenance tasks in `path`. If another maintenance run holds the repo's lock, nothing is run, which is an error only if `fail_if_locked` is set.
+    fn run_in_path(
+        &self,
+        time_period: TimePeriod,
+        path: &Path,
+        fail_if_locked: bool,
+        timings: &mut Vec<TaskTiming>,
+    ) -> Result<()> {
         info!(?time_period, ?path, "running tasks");
-        set_default_git_maintenance_config(path)?;
+        set_default_git_maintenance_config(path).context("Setting default maintenance config")?;
 
-        let maint_result = match self.run_maint(time_period, path) {
+        let mut failure = None;
+        let maint_result = match self.run_maint(time_period, path, timings) {
             Ok(MaintResult::Success(status)) => {
                 if status.success() {
                     debug!(?time_period, ?path, "completed maintenance",);
+                    if let Err(e) = Repo::open(path, self.app.clone())
+                        .and_then(|repo| repo.record_activity(Activity::Maintenance))
+                    {
+                        warn!(?path, ?e, "Failed to record maintenance time");
+                    }
                     None
                 } else {
                     warn!(?path, exit_status = ?status, "maintenance failed");
+                    failure = Some(anyhow::anyhow!("git maintenance exited with {}", status));
                     Some(maint_exit_status_metric_helper(status))
                 }
             }
             Ok(MaintResult::LockFailed) => {
                 warn!(?path, "failed to acquire lock");
+                if fail_if_locked {
+                    failure = Some(anyhow::anyhow!(
+                        "Another maintenance run holds the lock in {}",
+                        path.display()
+                    ));
+                }
                 Some("lock_failed".to_string())
             }
             Err(e) => {
                 warn!(?path, ?e, "failed running git-maintenance");
+                failure = Some(e);
                 Some("git_error".to_string())
             }
         };
@@ -397,7 +592,10 @@ impl Runner<'_> {
         if let Some(maint_result) = maint_result {
             self.add_ti_invocation_message(&hashmap! { "maint_result".to_string() => maint_result })
         };
-        Ok(())
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
     fn add_ti_invocation_message(&self, maint_custom_map: &HashMap<String, String>) {
@@ -420,36 +618,117 @@ impl Runner<'_> {
                 MaintResult::Success(status) => maint_exit_status_metric_helper(*status),
                 MaintResult::LockFailed => "lock_failed".to_string(),
             },
-            "sync_return_status".to_string() => sync_status_metric_helper(sync_maint_result),
+            "sync_return_status".to_string() => sync_status_metric_helper(&sync_maint_result),
             "git_maint_duration_sec".to_string() => git_maint_runtime.as_secs_f32().to_string(),
             "sync_maint_duration_sec".to_string() => sync_maint_runtime.as_secs_f32().to_string()
         });
     }
 
+    /// Enumerate the repos and tasks that `run` would act on for the given time period. Nothing is
+    /// executed and the configuration is left untouched, so missing repos are reported rather than
+    /// removed.
     #[tracing::instrument]
-    pub fn run(&mut self, time_period: TimePeriod, app: Arc<App>) -> Result<()> {
+    pub fn plan(&self, time_period: TimePeriod) -> Result<Vec<PlannedRun>> {
+        let mut planned = Vec::new();
+        for path in self.get_repo_paths()? {
+            match does_repo_exist(&path) {
+                Ok(true) => planned.push(PlannedRun {
+                    path,
+                    tasks: time_period.tasks(),
+                }),
+                Ok(false) => {
+                    info!(?path, "repo at path does not exist, it would be skipped");
+                }
+                Err(e) => {
+                    error!(?path, ?e, "error in determining if path is a repo");
+                }
+            }
+        }
+
+        Ok(planned)
+    }
+
+    #[tracing::instrument]
+    pub fn run(&mut self, time_period: TimePeriod, app: Arc<App>) -> Result<RunSummary> {
         if self.tracked_repos {
             self.run_tracked_repo_repair()?;
         }
         let repo_paths = self.get_repo_paths()?;
 
+        let mut summary = RunSummary::default();
+        let mut timings = Vec::new();
         for path in repo_paths {
             let p: &Path = &path;
             match does_repo_exist(p) {
-                Ok(true) => self.run_in_path(time_period, p)?,
+                Ok(true) => match self.run_in_path(time_period, p, false, &mut timings) {
+                    Ok(()) => summary.succeeded.push(path),
+                    Err(e) => {
+                        error!(path = ?p, ?e, "maintenance failed, continuing with other repos");
+                        summary.failed.push((path, format!("{:#}", e)));
+                    }
+                },
                 Ok(false) if self.tracked_repos => {
                     info!(path=?p, "repo at returned path did not exist, continuing");
                 }
-                Ok(false) => self.handle_missing_config_entry(match path.to_str() {
-                    Some(s) => s,
-                    None => bail!("path contains invalid UTF-8: {:?}", path.to_string_lossy()),
-                })?,
+                Ok(false) => {
+                    let result = match path.to_str() {
+                        Some(s) => self.handle_missing_config_entry(s),
+                        None => Err(anyhow::anyhow!(
+                            "path contains invalid UTF-8: {:?}",
+                            path.to_string_lossy()
+                        )),
+                    };
+                    if let Err(e) = result {
+                        warn!(?path, ?e, "failed to remove missing repo from config");
+                    }
+                }
                 Err(e) => {
                     error!(?path, ?e, "error in determining if path is a repo");
+                    summary.failed.push((path, format!("{:#}", e)));
                 }
             }
         }
 
+        self.report_timings(&timings)?;
+        Ok(summary)
+    }
+
+    /// Run the maintenance tasks for `time_period` in the single repo at `path`, regardless of
+    /// whether it is registered for scheduled maintenance. Fails if another maintenance run holds
+    /// the repo's lock.
+    #[tracing::instrument]
+    pub fn run_in_repo(&self, time_period: TimePeriod, path: &Path) -> Result<()> {
+        if !does_repo_exist(path)? {
+            bail!("{} is not a git repository", path.display());
+        }
+
+        let mut timings = Vec::new();
+        let result = self.run_in_path(time_period, path, true, &mut timings);
+        self.report_timings(&timings)?;
+        result
+    }
+
+    /// Write `timings` to the sandbox, and print them if requested.
+    fn report_timings(&self, timings: &[TaskTiming]) -> Result<()> {
+        let path = self.app.sandbox().path().join(TIMINGS_FILE_NAME);
+        let file =
+            std::fs::File::create(&path).with_context(|| format!("Creating {}", path.display()))?;
+        serde_json::to_writer_pretty(file, timings)
+            .with_context(|| format!("Writing maintenance timings to {}", path.display()))?;
+        debug!(?path, "Wrote maintenance timings");
+
+        if self.print_timings {
+            for timing in timings {
+                println!(
+                    "{:>9.2}s  {:<20} {:<18} {}",
+                    timing.duration_secs,
+                    timing.task.name(),
+                    timing.status,
+                    timing.path.display()
+                );
+            }
+        }
+
         Ok(())
     }
 }
@@ -461,6 +740,12 @@ pub struct RunOptions {
     pub git_config_key: String,
     pub git_config_path: Option<PathBuf>,
     pub tracked: bool,
+    /// print the time taken by each task when the run finishes
+    pub timings: bool,
+    /// exit without running any tasks if the host is on battery power
+    pub skip_on_battery: bool,
+    /// let git maintenance write to the terminal rather than capturing its output
+    pub stream_output: bool,
 }
 
 impl Default for RunOptions {
@@ -470,6 +755,9 @@ impl Default for RunOptions {
             git_config_key: DEFAULT_CONFIG_KEY.to_owned(),
             git_config_path: None,
             tracked: false,
+            timings: false,
+            skip_on_battery: false,
+            stream_output: false,
         }
     }
 }
@@ -484,9 +772,56 @@ pub fn run(
     time_period: TimePeriod,
     tracker: &Tracker,
     app: Arc<App>,
+) -> Result<RunSummary> {
+    let on_battery = if cli.skip_on_battery {
+        power::OnBattery::Skip
+    } else {
+        power::OnBattery::Run
+    };
+    if !power::should_run(on_battery, power::PowerSource::current()) {
+        info!(%time_period, "Skipping maintenance because the host is on battery power");
+        if let Err(e) = status::LastRuns::skipped_path().and_then(|path| {
+            status::LastRuns::record(&path, time_period, chrono::Utc::now().timestamp())
+        }) {
+            warn!(?e, "Failed to record skipped maintenance run");
+        }
+        return Ok(RunSummary {
+            skipped_on_battery: true,
+            ..Default::default()
+        });
+    }
+
+    let summary = Runner::new(cli, tracker, app.clone())?.run(time_period, app)?;
+    if let Err(e) = status::LastRuns::default_path().and_then(|path| {
+        status::LastRuns::record(&path, time_period, chrono::Utc::now().timestamp())
+    }) {
+        warn!(?e, "Failed to record maintenance run time");
+    }
+    Ok(summary)
+}
+
+/// Runs the maintenance tasks for `time_period` in the repo at `repo_path` only. Unlike `run`, the
+/// power source is not checked and the run is not recorded as a scheduled one.
+#[tracing::instrument]
+pub fn run_in_repo(
+    cli: RunOptions,
+    time_period: TimePeriod,
+    repo_path: &Path,
+    tracker: &Tracker,
+    app: Arc<App>,
 ) -> Result<()> {
-    Runner::new(cli, tracker, app.clone())?.run(time_period, app)?;
-    Ok(())
+    Runner::new(cli, tracker, app)?.run_in_repo(time_period, repo_path)
+}
+
+/// Returns the repos and tasks that `run` would execute without running any of them.
+#[tracing::instrument]
+pub fn plan(
+    cli: RunOptions,
+    time_period: TimePeriod,
+    tracker: &Tracker,
+    app: Arc<App>,
+) -> Result<Vec<PlannedRun>> {
+    Runner::new(cli, tracker, app)?.plan(time_period)
 }
 
 #[cfg(test)]
@@ -555,6 +890,85 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_run_continues_after_repo_failure() -> Result<()> {
+        let fix = ConfigFixture::new()?;
+        let tracker = Tracker::for_testing()?;
+        let failing = ScratchGitRepo::new_static_fixture(fix.tempdir.path())?;
+        let succeeding = ScratchGitRepo::new_static_fixture(fix.tempdir.path())?;
+
+        // A stale lock on the repo config makes setting the maintenance defaults fail.
+        std::fs::write(failing.path().join(".git").join("config.lock"), "")?;
+
+        {
+            let mut config = fix.config()?;
+            for scratch in [&failing, &succeeding] {
+                let path = scratch.path().to_str().unwrap();
+                config.set_multivar(DEFAULT_CONFIG_KEY, &regex_escape(path), path)?;
+            }
+        }
+
+        let mut runner = Runner::new(
+            RunOptions {
+                git_binary: Some(GitBinary::for_testing()?),
+                git_config_path: Some(fix.config_path.clone()),
+                ..Default::default()
+            },
+            &tracker,
+            fix.app.clone(),
+        )?;
+        let summary = runner.run(TimePeriod::Hourly, fix.app.clone())?;
+
+        assert!(!summary.is_success());
+        assert_eq!(
+            summary
+                .failed
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>(),
+            vec![failing.path().to_owned()]
+        );
+        assert_eq!(summary.succeeded, vec![succeeding.path().to_owned()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_in_repo_only_touches_that_repo() -> Result<()> {
+        let fix = ConfigFixture::new()?;
+        let tracker = Tracker::for_testing()?;
+        let scratch = ScratchGitRepo::new_static_fixture(fix.tempdir.path())?;
+        let not_a_repo = fix.tempdir.path().join("not_a_repo");
+        std::fs::create_dir(&not_a_repo)?;
+
+        let runner = Runner::new(
+            RunOptions {
+                git_binary: Some(GitBinary::for_testing()?),
+                git_config_path: Some(fix.config_path.clone()),
+                stream_output: true,
+                ..Default::default()
+            },
+            &tracker,
+            fix.app.clone(),
+        )?;
+        runner.run_in_repo(TimePeriod::Weekly, scratch.path())?;
+        assert!(runner.run_in_repo(TimePeriod::Weekly, &not_a_repo).is_err());
+
+        // Maintenance that is already running in the repo is reported rather than silently skipped.
+        {
+            let _lock =
+                locking::hold_lock(scratch.path(), Path::new("maint.lock"), fix.app.clone())?;
+            assert!(runner
+                .run_in_repo(TimePeriod::Weekly, scratch.path())
+                .is_err());
+        }
+
+        // The repo is not registered for scheduled maintenance as a side effect.
+        assert!(runner.get_repo_paths_from_config()?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_repo_paths() -> Result<()> {
         let fix = ConfigFixture::new()?;
@@ -586,6 +1000,95 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_plan_lists_repos_without_running_git() -> Result<()> {
+        let fix = ConfigFixture::new()?;
+        let tracker = Tracker::for_testing()?;
+        let scratch = ScratchGitRepo::new_static_fixture(fix.tempdir.path())?;
+        let repo_path = scratch.path().to_owned();
+        let missing_path = fix.tempdir.path().join("does-not-exist");
+
+        {
+            let mut config = fix.config()?;
+            for path in [&repo_path, &missing_path] {
+                let path = path.to_str().unwrap();
+                config.set_multivar(DEFAULT_CONFIG_KEY, &regex_escape(path), path)?;
+            }
+        }
+
+        // A bogus git binary ensures that the test fails if git is actually invoked.
+        let git_binary = GitBinary {
+            home_temp_dir: None,
+            git_binary_path: "/path/to/bin/git".into(),
+            git_exec_path: "/path/to/lib/gitcore".into(),
+            env: Default::default(),
+        };
+
+        let planned = plan(
+            RunOptions {
+                git_binary: Some(git_binary),
+                git_config_path: Some(fix.config_path.clone()),
+                ..Default::default()
+            },
+            TimePeriod::Daily,
+            &tracker,
+            fix.app.clone(),
+        )?;
+
+        assert_eq!(
+            planned,
+            vec![PlannedRun {
+                path: repo_path,
+                tasks: TimePeriod::Daily.tasks(),
+            }]
+        );
+        assert_eq!(
+            MaintenanceTask::GitMaintenance.describe(TimePeriod::Daily),
+            "git maintenance run --schedule=daily"
+        );
+
+        // The missing repo is reported, not removed from the config.
+        let values = fix.config()?.multivar_values(DEFAULT_CONFIG_KEY, None)?;
+        assert_eq!(values.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_timings_writes_to_sandbox() -> Result<()> {
+        let fix = ConfigFixture::new()?;
+        let tracker = Tracker::for_testing()?;
+        let runner = Runner::new(
+            RunOptions {
+                git_config_path: Some(fix.config_path.clone()),
+                ..Default::default()
+            },
+            &tracker,
+            fix.app.clone(),
+        )?;
+
+        runner.report_timings(&[TaskTiming {
+            path: PathBuf::from("/path/to/repo"),
+            task: MaintenanceTask::RocksDBCompaction,
+            duration_secs: 1.5,
+            status: "success".to_string(),
+        }])?;
+
+        let content = std::fs::read(fix.app.sandbox().path().join(TIMINGS_FILE_NAME))?;
+        let value: serde_json::Value = serde_json::from_slice(&content)?;
+        assert_eq!(
+            value,
+            serde_json::json!([{
+                "path": "/path/to/repo",
+                "task": "rocksdb-compaction",
+                "duration_secs": 1.5,
+                "status": "success",
+            }])
+        );
+
+        Ok(())
+    }
+
     fn assert_repo_defaults_set(config: &git2::Config) {
         for (k, v) in CONFIG_DEFAULTS.iter() {
             let val = config.get_string(k).unwrap();
@@ -633,6 +1136,9 @@ mod tests {
             git_config_key: config_key.into(),
             git_config_path: Some(config_path),
             tracked: false,
+            timings: false,
+            skip_on_battery: false,
+            stream_output: false,
         };
 
         let runner = Runner::new(opts, &tracker, fix.app)?;
@@ -0,0 +1,207 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checking whether the host is running on battery power, so that scheduled maintenance can be skipped on laptops away from a charger.
+
+use super::*;
+
+#[cfg(target_os = "macos")]
+const PMSET_BIN: &str = "pmset";
+#[cfg(target_os = "linux")]
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+/// What scheduled maintenance runs should do when the host is on battery power.
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    strum_macros::EnumVariantNames,
+    strum_macros::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OnBattery {
+    /// Run regardless of the power source.
+    #[default]
+    Run,
+
+    /// Exit without running any tasks, recording the skip.
+    Skip,
+}
+
+/// Where the host is drawing power from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+
+    /// The power source could not be determined, as on hosts without a battery.
+    Unknown,
+}
+
+impl PowerSource {
+    /// The power source of this host.
+    pub fn current() -> Self {
+        match Self::detect() {
+            Ok(source) => source,
+            Err(e) => {
+                warn!(?e, "Could not determine the power source");
+                PowerSource::Unknown
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn detect() -> Result<Self> {
+        let out = Command::new(PMSET_BIN)
+            .args(["-g", "batt"])
+            .output()
+            .context("failed to run pmset")?;
+        if !out.status.success() {
+            bail!("pmset exited with {}", out.status);
+        }
+        Ok(Self::from_pmset_output(&String::from_utf8_lossy(
+            &out.stdout,
+        )))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn detect() -> Result<Self> {
+        Self::from_power_supply_dir(Path::new(POWER_SUPPLY_DIR))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn detect() -> Result<Self> {
+        Ok(PowerSource::Unknown)
+    }
+
+    /// Parse the output of `pmset -g batt`, whose first line reads like `Now drawing from 'AC Power'`.
+    #[cfg(any(target_os = "macos", test))]
+    fn from_pmset_output(output: &str) -> Self {
+        match output.lines().next() {
+            Some(line) if line.contains("'AC Power'") => PowerSource::Ac,
+            Some(line) if line.contains("'Battery Power'") => PowerSource::Battery,
+            _ => PowerSource::Unknown,
+        }
+    }
+
+    /// Inspect the supplies under `dir` (normally `/sys/class/power_supply`). The host is on AC power if any mains supply is online, and on battery if none is but a battery is present.
+    #[cfg(any(target_os = "linux", test))]
+    fn from_power_supply_dir(dir: &Path) -> Result<Self> {
+        let read = |path: PathBuf| -> Option<String> {
+            std::fs::read_to_string(path)
+                .ok()
+                .map(|content| content.trim().to_owned())
+        };
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(PowerSource::Unknown),
+            Err(e) => return Err(e).with_context(|| format!("Listing {}", dir.display())),
+        };
+
+        let mut has_battery = false;
+        for entry in entries {
+            let supply = entry?.path();
+            match read(supply.join("type")).as_deref() {
+                Some("Mains") | Some("USB")
+                    if read(supply.join("online")).as_deref() == Some("1") =>
+                {
+                    return Ok(PowerSource::Ac);
+                }
+                Some("Battery") => has_battery = true,
+                _ => {}
+            }
+        }
+
+        Ok(if has_battery {
+            PowerSource::Battery
+        } else {
+            PowerSource::Unknown
+        })
+    }
+}
+
+/// Whether a maintenance run should proceed given the `on_battery` policy and the power `source`. Runs proceed unless the host is known to be on battery and the policy is to skip.
+pub fn should_run(on_battery: OnBattery, source: PowerSource) -> bool {
+    !(on_battery == OnBattery::Skip && source == PowerSource::Battery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_supply(dir: &Path, name: &str, kind: &str, online: Option<&str>) -> Result<()> {
+        let supply = dir.join(name);
+        std::fs::create_dir_all(&supply)?;
+        std::fs::write(supply.join("type"), format!("{}\n", kind))?;
+        if let Some(online) = online {
+            std::fs::write(supply.join("online"), format!("{}\n", online))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn skipping_only_happens_on_battery() {
+        for source in [PowerSource::Ac, PowerSource::Battery, PowerSource::Unknown] {
+            assert!(should_run(OnBattery::Run, source));
+        }
+        assert!(should_run(OnBattery::Skip, PowerSource::Ac));
+        assert!(!should_run(OnBattery::Skip, PowerSource::Battery));
+        assert!(should_run(OnBattery::Skip, PowerSource::Unknown));
+    }
+
+    #[test]
+    fn power_source_from_pmset_output() {
+        assert_eq!(
+            PowerSource::from_pmset_output(
+                "Now drawing from 'AC Power'\n -InternalBattery-0 (id=123)\t100%; charged; 0:00 remaining present: true\n"
+            ),
+            PowerSource::Ac
+        );
+        assert_eq!(
+            PowerSource::from_pmset_output(
+                "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=123)\t87%; discharging; 5:12 remaining present: true\n"
+            ),
+            PowerSource::Battery
+        );
+        assert_eq!(PowerSource::from_pmset_output(""), PowerSource::Unknown);
+    }
+
+    #[test]
+    fn power_source_from_power_supply_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let supplies = dir.path().join("power_supply");
+        assert_eq!(
+            PowerSource::from_power_supply_dir(&supplies)?,
+            PowerSource::Unknown
+        );
+
+        write_supply(&supplies, "BAT0", "Battery", None)?;
+        write_supply(&supplies, "AC", "Mains", Some("0"))?;
+        assert_eq!(
+            PowerSource::from_power_supply_dir(&supplies)?,
+            PowerSource::Battery
+        );
+
+        write_supply(&supplies, "AC", "Mains", Some("1"))?;
+        assert_eq!(
+            PowerSource::from_power_supply_dir(&supplies)?,
+            PowerSource::Ac
+        );
+
+        // Desktops without a battery report only mains supplies.
+        std::fs::remove_dir_all(&supplies)?;
+        write_supply(&supplies, "AC", "Mains", Some("0"))?;
+        assert_eq!(
+            PowerSource::from_power_supply_dir(&supplies)?,
+            PowerSource::Unknown
+        );
+
+        Ok(())
+    }
+}
@@ -18,6 +18,8 @@ pub struct ScheduledJobOpts {
     pub schedule_defaults: Option<CalendarInterval>,
     /// run maintenance on all tracked repos
     pub tracked: bool,
+    /// have the job exit without running any tasks if the host is on battery power
+    pub skip_on_battery: bool,
 }
 
 impl ScheduledJobOpts {
@@ -36,6 +38,7 @@ impl Default for ScheduledJobOpts {
             config_path: None,
             schedule_defaults: None,
             tracked: false,
+            skip_on_battery: false,
         }
     }
 }
@@ -49,8 +52,9 @@ impl From<ScheduledJobOpts> for ProgramArguments {
     }
 }
 
-impl From<ProgramArguments> for PlistValue {
-    fn from(args: ProgramArguments) -> Self {
+impl ProgramArguments {
+    /// The command line of the scheduled job, starting with the path to focus.
+    pub(crate) fn to_args(&self) -> Vec<String> {
         let ProgramArguments(ScheduledJobOpts {
             focus_path,
             git_binary_path,
@@ -58,8 +62,9 @@ impl From<ProgramArguments> for PlistValue {
             config_key,
             config_path,
             tracked,
+            skip_on_battery,
             schedule_defaults: _,
-        }) = args;
+        }) = self.clone();
 
         assert!(
             !git_binary_path.as_os_str().is_empty(),
@@ -87,7 +92,17 @@ impl From<ProgramArguments> for PlistValue {
             args.push("--tracked".into());
         }
 
-        PlistValue::Array(args.into_iter().map(|a| a.into()).collect())
+        if skip_on_battery {
+            args.push("--skip-on-battery".into());
+        }
+
+        args
+    }
+}
+
+impl From<ProgramArguments> for PlistValue {
+    fn from(args: ProgramArguments) -> Self {
+        PlistValue::Array(args.to_args().into_iter().map(|a| a.into()).collect())
     }
 }
 
@@ -113,8 +128,8 @@ pub struct CalendarInterval {
 }
 
 #[allow(dead_code)]
-const DEFAULT_DAILY_HOUR: u32 = 4;
-const DEFAULT_WEEKLY_WEEKDAY: u32 = 1;
+pub(crate) const DEFAULT_DAILY_HOUR: u32 = 4;
+pub(crate) const DEFAULT_WEEKLY_WEEKDAY: u32 = 1;
 
 fn random_minute() -> u32 {
     rand::random::<u32>() % 60
@@ -0,0 +1,345 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scheduling of maintenance jobs with the Windows Task Scheduler. Tasks are defined by XML documents that mirror the launchd plists.
+
+use super::{scheduling::*, *};
+use anyhow::anyhow;
+use std::io::ErrorKind;
+use strum::IntoEnumIterator;
+
+const SCHTASKS_BIN: &str = "schtasks";
+const TASK_FOLDER: &str = r"\focus\";
+const TASK_XML_RELPATH: &str = r"focus\tasks";
+
+/// Escape `s` for inclusion in XML text.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Quote `arg` as a single argument on a Windows command line.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        arg.to_owned()
+    } else {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    }
+}
+
+/// The trigger element for `opts`. As with launchd, hourly jobs run every five minutes.
+fn trigger(opts: &ScheduledJobOpts) -> String {
+    let CalendarInterval {
+        hour,
+        minute,
+        weekday,
+        every_n_minutes,
+        ..
+    } = opts.schedule_defaults.clone().unwrap_or_default();
+    let minute = minute.unwrap_or_else(|| rand::random::<u32>() % 60);
+    let hour = hour.unwrap_or(DEFAULT_DAILY_HOUR);
+    // The date is arbitrary; only the time of day matters.
+    let start = |hour: u32, minute: u32| format!("2022-01-01T{:02}:{:02}:00", hour, minute);
+    match opts.time_period {
+        TimePeriod::Hourly => {
+            let interval = every_n_minutes.unwrap_or(60);
+            format!(
+                "<TimeTrigger><StartBoundary>{}</StartBoundary><Repetition><Interval>PT{}M</Interval></Repetition></TimeTrigger>",
+                start(0, minute % interval),
+                interval
+            )
+        }
+        TimePeriod::Daily => format!(
+            "<CalendarTrigger><StartBoundary>{}</StartBoundary><ScheduleByDay><DaysInterval>1</DaysInterval></ScheduleByDay></CalendarTrigger>",
+            start(hour, minute)
+        ),
+        TimePeriod::Weekly => {
+            let day = match weekday.unwrap_or(DEFAULT_WEEKLY_WEEKDAY) % 7 {
+                0 => "Sunday",
+                1 => "Monday",
+                2 => "Tuesday",
+                3 => "Wednesday",
+                4 => "Thursday",
+                5 => "Friday",
+                _ => "Saturday",
+            };
+            format!(
+                "<CalendarTrigger><StartBoundary>{}</StartBoundary><ScheduleByWeek><WeeksInterval>1</WeeksInterval><DaysOfWeek><{}/></DaysOfWeek></ScheduleByWeek></CalendarTrigger>",
+                start(hour, minute),
+                day
+            )
+        }
+    }
+}
+
+/// Render the task definition for `opts`.
+pub(crate) fn task_xml(opts: &ScheduledJobOpts) -> String {
+    let args = ProgramArguments::from(opts.clone()).to_args();
+    let (command, arguments) = args.split_first().expect("program arguments are empty");
+    let arguments = arguments
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo><URI>{uri}</URI></RegistrationInfo>
+  <Triggers>{trigger}</Triggers>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <Enabled>true</Enabled>
+  </Settings>
+  <Actions><Exec><Command>{command}</Command><Arguments>{arguments}</Arguments></Exec></Actions>
+</Task>
+"#,
+        uri = xml_escape(&Schtasks::task_name(&opts.label())),
+        trigger = trigger(opts),
+        command = xml_escape(command),
+        arguments = xml_escape(&arguments),
+    )
+}
+
+/// Extract the next run time from the output of `schtasks /Query /FO LIST`.
+fn parse_next_run_time(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Next Run Time:"))
+        .map(str::trim)
+        .filter(|time| !time.is_empty() && *time != "N/A")
+        .map(str::to_owned)
+}
+
+#[derive(Debug, Clone)]
+pub struct Schtasks {
+    pub schtasks_bin: PathBuf,
+    pub task_xml_path: PathBuf,
+}
+
+impl Default for Schtasks {
+    fn default() -> Self {
+        let data_dir = dirs::data_local_dir().expect("could not determine local data dir");
+
+        Self {
+            schtasks_bin: SCHTASKS_BIN.into(),
+            task_xml_path: data_dir.join(TASK_XML_RELPATH),
+        }
+    }
+}
+
+impl Schtasks {
+    fn task_name(label: &str) -> String {
+        format!("{}{}", TASK_FOLDER, label)
+    }
+
+    #[tracing::instrument]
+    fn exec_cmd(&self, args: &[&str]) -> Result<()> {
+        debug!("running schtasks {:?}", args);
+        let res = Command::new(&self.schtasks_bin)
+            .args(args)
+            .status()
+            .with_context(|| format!("failed to run schtasks {:?}", args))?;
+        if !res.success() {
+            let fail_msg = format!("schtasks error: {:?}: result {}", args, res);
+            error!("{}", fail_msg);
+            bail!(fail_msg)
+        }
+        Ok(())
+    }
+
+    /// Returns true if a task with the given label is registered.
+    pub fn is_task_registered<S: AsRef<str>>(&self, label: S) -> Result<bool> {
+        let out = Command::new(&self.schtasks_bin)
+            .args(["/Query", "/TN", &Self::task_name(label.as_ref())])
+            .output()?;
+        debug!(label = ?label.as_ref(), success = ?out.status.success(), "is task registered");
+        Ok(out.status.success())
+    }
+
+    /// The next time the task with the given label will run, as reported by the Task Scheduler.
+    pub fn next_run_time<S: AsRef<str>>(&self, label: S) -> Result<Option<String>> {
+        let out = Command::new(&self.schtasks_bin)
+            .args([
+                "/Query",
+                "/TN",
+                &Self::task_name(label.as_ref()),
+                "/FO",
+                "LIST",
+            ])
+            .output()?;
+        if !out.status.success() {
+            return Ok(None);
+        }
+        Ok(parse_next_run_time(&String::from_utf8_lossy(&out.stdout)))
+    }
+
+    /// Register (or replace) the task with the given label from its XML definition.
+    pub fn create<S: AsRef<str>>(&self, label: S) -> Result<()> {
+        let label = label.as_ref();
+        let xml_path = self.xml_path(label);
+        self.exec_cmd(&[
+            "/Create",
+            "/TN",
+            &Self::task_name(label),
+            "/XML",
+            xml_path
+                .to_str()
+                .expect("task XML path was not valid UTF-8"),
+            "/F",
+        ])
+    }
+
+    pub fn delete<S: AsRef<str>>(&self, label: S) -> Result<()> {
+        self.exec_cmd(&["/Delete", "/TN", &Self::task_name(label.as_ref()), "/F"])
+    }
+
+    pub fn xml_path(&self, label: &str) -> PathBuf {
+        self.task_xml_path.join(format!("{}.xml", label))
+    }
+
+    /// Write the task XML, which the Task Scheduler expects to be UTF-16.
+    pub fn write_xml(&self, opts: &ScheduledJobOpts) -> Result<PathBuf> {
+        let output_path = self.xml_path(&opts.label());
+        std::fs::create_dir_all(&self.task_xml_path)?;
+
+        let mut bytes = vec![0xff, 0xfe];
+        for unit in task_xml(opts).encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        let temp = tempfile::NamedTempFile::new_in(&self.task_xml_path)?;
+        std::fs::write(temp.path(), bytes)?;
+        temp.persist(&output_path)?;
+
+        Ok(output_path)
+    }
+
+    pub fn delete_xml(&self, opts: &ScheduledJobOpts) -> Result<()> {
+        let path = self.xml_path(&opts.label());
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(anyhow!(e)),
+            _ => Ok(()),
+        }
+        .with_context(|| format!("failed to remove path {:?}", path))
+    }
+}
+
+/// Register tasks for the requested time periods (all of them if `time_period` is None).
+#[tracing::instrument]
+pub fn schedule_enable(opts: ScheduleOpts) -> Result<()> {
+    let ScheduleOpts {
+        time_period,
+        git_path,
+        focus_path,
+        skip_if_already_scheduled,
+        tracked,
+        on_battery,
+    } = opts;
+
+    let schtasks = Schtasks::default();
+
+    let time_periods: Vec<TimePeriod> = match time_period {
+        Some(tp) => vec![tp],
+        None => TimePeriod::iter().collect(),
+    };
+
+    for tp in time_periods {
+        let task_opts = ScheduledJobOpts {
+            focus_path: focus_path.to_owned(),
+            git_binary_path: git_path.to_owned(),
+            time_period: tp,
+            schedule_defaults: match tp {
+                TimePeriod::Hourly => Some(CalendarInterval {
+                    every_n_minutes: Some(5),
+                    ..Default::default()
+                }),
+                _ => None,
+            },
+            tracked,
+            skip_on_battery: on_battery == power::OnBattery::Skip,
+            ..Default::default()
+        };
+        let label = task_opts.label();
+
+        if skip_if_already_scheduled && schtasks.is_task_registered(&label)? {
+            continue;
+        }
+
+        schtasks.write_xml(&task_opts)?;
+        schtasks.create(&label)?;
+    }
+
+    Ok(())
+}
+
+/// Delete the registered tasks, and their XML definitions if `delete` is set.
+#[tracing::instrument]
+pub fn schedule_disable(delete: bool) -> Result<()> {
+    let schtasks = Schtasks::default();
+
+    for tp in TimePeriod::iter() {
+        let task_opts = ScheduledJobOpts {
+            time_period: tp,
+            ..Default::default()
+        };
+        let label = task_opts.label();
+
+        if schtasks.is_task_registered(&label)? {
+            schtasks.delete(&label)?;
+        }
+
+        if delete {
+            schtasks.delete_xml(&task_opts)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_opts(time_period: TimePeriod) -> ScheduledJobOpts {
+        ScheduledJobOpts {
+            focus_path: r"C:\Program Files\focus\focus.exe".into(),
+            git_binary_path: r"C:\Program Files\Git\bin\git.exe".into(),
+            time_period,
+            schedule_defaults: Some(CalendarInterval {
+                minute: Some(12),
+                every_n_minutes: Some(5),
+                ..Default::default()
+            }),
+            tracked: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_task_xml() {
+        let xml = task_xml(&task_opts(TimePeriod::Hourly));
+        assert!(xml.contains(r"<URI>\focus\com.twitter.git-maintenance.hourly</URI>"));
+        assert!(xml.contains("<StartBoundary>2022-01-01T00:02:00</StartBoundary><Repetition><Interval>PT5M</Interval>"));
+        assert!(xml.contains(r"<Command>C:\Program Files\focus\focus.exe</Command>"));
+        assert!(xml.contains(r#"&quot;--git-binary-path=C:\Program Files\Git\bin\git.exe&quot;"#));
+        assert!(xml.contains("--time-period=hourly --tracked</Arguments>"));
+
+        let xml = task_xml(&task_opts(TimePeriod::Weekly));
+        assert!(xml.contains("<StartBoundary>2022-01-01T04:12:00</StartBoundary>"));
+        assert!(xml.contains("<DaysOfWeek><Monday/></DaysOfWeek>"));
+    }
+
+    #[test]
+    fn test_parse_next_run_time() {
+        let output = "\r\nFolder: \\focus\r\nHostName:      HOST\r\nTaskName:      \\focus\\com.twitter.git-maintenance.daily\r\nNext Run Time: 10/17/2026 4:12:00 AM\r\nStatus:        Ready\r\n";
+        assert_eq!(
+            parse_next_run_time(output).as_deref(),
+            Some("10/17/2026 4:12:00 AM")
+        );
+        assert_eq!(parse_next_run_time("Next Run Time: N/A\r\n"), None);
+    }
+}
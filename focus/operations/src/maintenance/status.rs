@@ -0,0 +1,247 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reporting on whether maintenance jobs are scheduled with the host's scheduler and when they last ran.
+
+use super::{scheduling::*, *};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+const SYSTEMCTL_BIN: &str = "systemctl";
+const LAST_RUNS_RELPATH: &str = "focus/maintenance-runs.json";
+const SKIPPED_RUNS_RELPATH: &str = "focus/maintenance-skipped-runs.json";
+
+/// The scheduler that maintenance jobs are registered with on this host.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum_macros::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SchedulerBackend {
+    Launchd,
+
+    /// Timers installed by `git maintenance start`.
+    Systemd,
+
+    Cron,
+
+    TaskScheduler,
+}
+
+impl SchedulerBackend {
+    /// The backend that `schedule_enable` uses on this host.
+    pub fn current() -> Self {
+        #[cfg(target_os = "linux")]
+        if !cron::systemd_available() {
+            return SchedulerBackend::Cron;
+        }
+
+        if cfg!(windows) {
+            SchedulerBackend::TaskScheduler
+        } else if cfg!(target_os = "macos") {
+            SchedulerBackend::Launchd
+        } else {
+            SchedulerBackend::Systemd
+        }
+    }
+}
+
+fn data_local_dir() -> Result<PathBuf> {
+    dirs::data_local_dir().context("Could not determine the local data directory")
+}
+
+/// When each time period's maintenance run last completed, in seconds since the Unix epoch. Missing values mean no run has been recorded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastRuns {
+    #[serde(default)]
+    pub hourly: Option<i64>,
+
+    #[serde(default)]
+    pub daily: Option<i64>,
+
+    #[serde(default)]
+    pub weekly: Option<i64>,
+}
+
+impl LastRuns {
+    /// The file that `maintenance run` records its completion in.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(data_local_dir()?.join(LAST_RUNS_RELPATH))
+    }
+
+    /// The file that `maintenance run --skip-on-battery` records runs it skipped in.
+    pub fn skipped_path() -> Result<PathBuf> {
+        Ok(data_local_dir()?.join(SKIPPED_RUNS_RELPATH))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content)
+                .with_context(|| format!("Parsing maintenance runs from {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Reading maintenance runs from {}", path.display()))
+            }
+        }
+    }
+
+    /// Record that the run for `time_period` completed at `timestamp`, updating the file at `path`.
+    pub fn record(path: &Path, time_period: TimePeriod, timestamp: i64) -> Result<()> {
+        let mut last_runs = Self::load(path)?;
+        *last_runs.get_mut(time_period) = Some(timestamp);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating directory {}", parent.display()))?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(&last_runs)?)
+            .with_context(|| format!("Storing maintenance runs to {}", path.display()))
+    }
+
+    pub fn get(&self, time_period: TimePeriod) -> Option<i64> {
+        match time_period {
+            TimePeriod::Hourly => self.hourly,
+            TimePeriod::Daily => self.daily,
+            TimePeriod::Weekly => self.weekly,
+        }
+    }
+
+    fn get_mut(&mut self, time_period: TimePeriod) -> &mut Option<i64> {
+        match time_period {
+            TimePeriod::Hourly => &mut self.hourly,
+            TimePeriod::Daily => &mut self.daily,
+            TimePeriod::Weekly => &mut self.weekly,
+        }
+    }
+}
+
+/// The state of the maintenance job for one time period.
+#[derive(Debug, Clone)]
+pub struct JobStatus {
+    pub time_period: TimePeriod,
+
+    /// Whether the scheduler knows about the job.
+    pub registered: bool,
+
+    /// When the scheduler will next run the job, as the scheduler describes it. None if the scheduler does not say.
+    pub next_run: Option<String>,
+
+    /// When a run for this time period last completed, in seconds since the Unix epoch.
+    pub last_run: Option<i64>,
+
+    /// When a run for this time period was last skipped because the host was on battery power, in seconds since the Unix epoch.
+    pub last_skipped: Option<i64>,
+}
+
+/// The scheduler in use and the state of each time period's job.
+#[derive(Debug, Clone)]
+pub struct MaintenanceStatus {
+    pub backend: SchedulerBackend,
+    pub jobs: Vec<JobStatus>,
+}
+
+fn systemd_timer(time_period: TimePeriod) -> String {
+    format!("git-maintenance@{}.timer", time_period)
+}
+
+fn systemd_job_status(time_period: TimePeriod) -> Result<(bool, Option<String>)> {
+    let timer = systemd_timer(time_period);
+    let enabled = Command::new(SYSTEMCTL_BIN)
+        .args(["--user", "is-enabled", "--quiet", &timer])
+        .status()
+        .context("failed to run systemctl is-enabled")?
+        .success();
+    if !enabled {
+        return Ok((false, None));
+    }
+
+    let out = Command::new(SYSTEMCTL_BIN)
+        .args([
+            "--user",
+            "show",
+            &timer,
+            "--property=NextElapseUSecRealtime",
+            "--value",
+        ])
+        .output()
+        .context("failed to run systemctl show")?;
+    let next_run = String::from_utf8_lossy(&out.stdout).trim().to_owned();
+    Ok((
+        true,
+        Some(next_run).filter(|s| out.status.success() && !s.is_empty()),
+    ))
+}
+
+fn job_status(
+    backend: SchedulerBackend,
+    time_period: TimePeriod,
+) -> Result<(bool, Option<String>)> {
+    let label = ScheduledJobOpts {
+        time_period,
+        ..Default::default()
+    }
+    .label();
+
+    match backend {
+        SchedulerBackend::Launchd => Ok((Launchctl::default().is_service_loaded(&label)?, None)),
+        SchedulerBackend::Systemd => systemd_job_status(time_period),
+        #[cfg(target_os = "linux")]
+        SchedulerBackend::Cron => Ok((cron::is_scheduled(time_period)?, None)),
+        #[cfg(windows)]
+        SchedulerBackend::TaskScheduler => {
+            let schtasks = schtasks::Schtasks::default();
+            if schtasks.is_task_registered(&label)? {
+                Ok((true, schtasks.next_run_time(&label)?))
+            } else {
+                Ok((false, None))
+            }
+        }
+        #[allow(unreachable_patterns)]
+        _ => bail!(
+            "The {} scheduler is not supported on this platform",
+            backend
+        ),
+    }
+}
+
+/// Inspect the current scheduler for each time period's job and combine the results with the run times recorded at `last_runs_path` and the skipped runs recorded at `skipped_runs_path`.
+#[tracing::instrument]
+pub fn status(last_runs_path: &Path, skipped_runs_path: &Path) -> Result<MaintenanceStatus> {
+    let backend = SchedulerBackend::current();
+    let last_runs = LastRuns::load(last_runs_path)?;
+    let skipped_runs = LastRuns::load(skipped_runs_path)?;
+
+    let mut jobs = Vec::new();
+    for time_period in TimePeriod::iter() {
+        let (registered, next_run) = job_status(backend, time_period)
+            .with_context(|| format!("Inspecting the {} job", time_period))?;
+        jobs.push(JobStatus {
+            time_period,
+            registered,
+            next_run,
+            last_run: last_runs.get(time_period),
+            last_skipped: skipped_runs.get(time_period),
+        });
+    }
+
+    Ok(MaintenanceStatus { backend, jobs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_preserves_other_time_periods() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("focus").join("maintenance-runs.json");
+        assert_eq!(LastRuns::load(&path)?, Default::default());
+
+        LastRuns::record(&path, TimePeriod::Hourly, 100)?;
+        LastRuns::record(&path, TimePeriod::Weekly, 200)?;
+        LastRuns::record(&path, TimePeriod::Hourly, 300)?;
+
+        let last_runs = LastRuns::load(&path)?;
+        assert_eq!(last_runs.get(TimePeriod::Hourly), Some(300));
+        assert_eq!(last_runs.get(TimePeriod::Daily), None);
+        assert_eq!(last_runs.get(TimePeriod::Weekly), Some(200));
+
+        Ok(())
+    }
+}
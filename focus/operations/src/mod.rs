@@ -4,6 +4,7 @@
 pub mod branch;
 pub mod clone;
 pub mod detect_build_graph_changes;
+pub mod doctor;
 pub mod ensure_clean;
 pub mod event;
 pub mod filter;
@@ -11,6 +11,7 @@ use focus_util::{
 
 use anyhow::{Context, Result};
 use git2::Repository;
+use serde::Serialize;
 use tracing::debug;
 
 /// Vec of names that should never be expired via this process
@@ -30,12 +31,14 @@ mod partition {
     use focus_util::time::{FocusTime, GitTime};
     use git2::{Commit, Oid, Repository};
 
-    use super::PartitionedRefNames;
+    use super::{PartitionedRef, PartitionedRefs};
 
     #[derive(Debug, Clone)]
     pub(super) struct RefInfo {
         pub name: String,
+        pub oid: Oid,
         pub author_time: FocusTime,
+        pub committer_time: FocusTime,
         pub merge_base_auth_time: Option<FocusTime>,
     }
 
@@ -77,12 +80,15 @@ mod partition {
             let commit = r.peel_to_commit().context("peeling ref to commit")?;
             let name = r.name().unwrap().to_string();
             let author_time = FocusTime::from(GitTime::from(commit.author().when()));
+            let committer_time = FocusTime::from(GitTime::from(commit.committer().when()));
             let merge_base_auth_time = find_merge_base_commit(repo, head.id(), commit.id())
                 .map(|mbc| FocusTime::from(mbc.author().when()))
                 .ok();
             refs.push(RefInfo {
                 name,
+                oid: commit.id(),
                 author_time,
+                committer_time,
                 merge_base_auth_time,
             })
         }
@@ -90,54 +96,108 @@ mod partition {
         Ok(refs)
     }
 
-    pub(super) fn partitioned_ref_names(
+    pub(super) fn partitioned_refs(
         ref_infos: Vec<RefInfo>,
         cutoff: FocusTime,
         check_merge_base: bool,
-    ) -> Result<PartitionedRefNames> {
-        let (cur_info, expired_info): (Vec<RefInfo>, Vec<RefInfo>) =
-            ref_infos.into_iter().partition(|ref_info| {
-                let RefInfo {
-                    name: _,
-                    author_time,
-                    merge_base_auth_time,
-                } = ref_info;
-                let auth_time = author_time;
-                if *auth_time < cutoff {
-                    false
-                } else if check_merge_base {
-                    match merge_base_auth_time {
-                        Some(t) if *t < cutoff => false,
-                        None => true, // merge base not found, assume its current or an orphan branch
-                        _ => true,
-                    }
-                } else {
-                    true
-                }
-            });
+    ) -> Result<PartitionedRefs> {
+        let mut partitioned = PartitionedRefs::default();
+        for ref_info in ref_infos {
+            let RefInfo {
+                name,
+                oid,
+                author_time,
+                committer_time,
+                merge_base_auth_time,
+            } = ref_info;
+            let passed_merge_base_check = if author_time < cutoff || !check_merge_base {
+                None
+            } else {
+                // merge base not found, assume its current or an orphan branch
+                merge_base_auth_time.map(|t| t >= cutoff)
+            };
+            let is_current = author_time >= cutoff && passed_merge_base_check != Some(false);
+
+            let partitioned_ref = PartitionedRef {
+                name,
+                oid: oid.to_string(),
+                committer_timestamp: committer_time.into_inner().timestamp(),
+                passed_merge_base_check,
+            };
+            if is_current {
+                partitioned.current.push(partitioned_ref);
+            } else {
+                partitioned.expired.push(partitioned_ref);
+            }
+        }
 
-        Ok(PartitionedRefNames {
-            current: cur_info.into_iter().map(|i| i.name).collect(),
-            expired: expired_info.into_iter().map(|i| i.name).collect(),
-        })
+        Ok(partitioned)
     }
 }
 
+/// A ref along with the details used to decide whether it has expired.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartitionedRef {
+    /// The full name of the ref, like `refs/heads/main`.
+    pub name: String,
+
+    /// The commit the ref resolves to.
+    pub oid: String,
+
+    /// The committer time of that commit in seconds since the Unix epoch.
+    pub committer_timestamp: i64,
+
+    /// Whether the merge base with `HEAD` falls after the cutoff. This is `None` if the check was not requested, was not needed because the ref is already older than the cutoff, or no merge base was found.
+    pub passed_merge_base_check: Option<bool>,
+}
+
+/// The refs of a repo, split by whether they are newer than a cutoff.
 #[derive(Debug, Default, PartialEq, Eq)]
-pub struct PartitionedRefNames {
-    pub current: Vec<String>,
-    pub expired: Vec<String>,
+pub struct PartitionedRefs {
+    pub current: Vec<PartitionedRef>,
+    pub expired: Vec<PartitionedRef>,
+}
+
+/// How `focus refs list-current` presents its output.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    strum_macros::EnumVariantNames,
+    strum_macros::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RefListFormat {
+    /// One ref name per line.
+    Text,
+
+    /// A JSON array of `PartitionedRef` objects.
+    Json,
 }
 
-impl PartitionedRefNames {
+/// Print `refs` in the given format.
+pub fn print_refs(refs: &[PartitionedRef], format: RefListFormat) -> Result<()> {
+    match format {
+        RefListFormat::Text => {
+            let names: Vec<&str> = refs.iter().map(|r| r.name.as_str()).collect();
+            println!("{}", names.join("\n"));
+        }
+        RefListFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(refs)?);
+        }
+    }
+    Ok(())
+}
+
+impl PartitionedRefs {
     /// convenience constructor, given a repo, the cutoff time, and the check_merge_base option,
-    /// create a PartitionedRefNames instance for the repo's references.
+    /// create a PartitionedRefs instance for the repo's references.
     pub fn for_repo(repo: &Repository, cutoff: FocusTime, check_merge_base: bool) -> Result<Self> {
-        partition::partitioned_ref_names(
-            partition::collect_ref_info(repo)?,
-            cutoff,
-            check_merge_base,
-        )
+        partition::partitioned_refs(partition::collect_ref_info(repo)?, cutoff, check_merge_base)
     }
 }
 
@@ -172,6 +232,67 @@ pub fn expire_old_refs(
     check_merge_base: bool,
     use_transaction: bool,
     app: Arc<App>,
+) -> Result<()> {
+    let expired = {
+        let PartitionedRefs {
+            current: _,
+            expired,
+        } = PartitionedRefs::for_repo(repo, cutoff, check_merge_base)
+            .context("collecting expired ref names")?;
+        delete_case_conflict_refs(repo, expired.into_iter().map(|r| r.name).collect())?
+    };
+
+    delete_refs(repo, &expired, use_transaction, app)
+}
+
+/// Find refs whose target (or, for annotated tags, the tagged object) is missing from the object database, for instance because it was garbage collected. Symbolic refs are not considered.
+pub fn find_empty_refs(repo: &Repository) -> Result<Vec<String>> {
+    let mut empty = Vec::new();
+    for reference in repo.references().context("opening repo.references")? {
+        let reference = match reference {
+            Ok(reference) => reference,
+            Err(e) => {
+                debug!(?e, "skipping unreadable ref");
+                continue;
+            }
+        };
+        if reference.kind() != Some(git2::ReferenceType::Direct) {
+            continue;
+        }
+        let name = match reference.name() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        // Peeling fails if any object along the way is missing.
+        if reference.peel(git2::ObjectType::Any).is_err() {
+            debug!(%name, target = ?reference.target(), "ref target is missing");
+            empty.push(name);
+        }
+    }
+    empty.sort();
+    Ok(empty)
+}
+
+/// Delete the refs found by `find_empty_refs`, returning their names. With `dry_run`, nothing is deleted.
+pub fn prune_empty_refs(
+    repo: &Repository,
+    dry_run: bool,
+    use_transaction: bool,
+    app: Arc<App>,
+) -> Result<Vec<String>> {
+    let empty = find_empty_refs(repo).context("finding empty refs")?;
+    if !dry_run && !empty.is_empty() {
+        delete_refs(repo, &empty, use_transaction, app)?;
+    }
+    Ok(empty)
+}
+
+/// Delete the named refs with a single `git update-ref --stdin` invocation, optionally within a transaction.
+fn delete_refs(
+    repo: &Repository,
+    names: &[String],
+    use_transaction: bool,
+    app: Arc<App>,
 ) -> Result<()> {
     let sandbox = app.sandbox();
 
@@ -179,16 +300,7 @@ pub fn expire_old_refs(
         let (mut ref_file, ref_file_path, _) =
             sandbox.create_file(Some("update-refs"), None, None)?;
 
-        let xs = {
-            let PartitionedRefNames {
-                current: _,
-                expired,
-            } = PartitionedRefNames::for_repo(repo, cutoff, check_merge_base)
-                .context("collecting expired ref names")?;
-            delete_case_conflict_refs(repo, expired)?
-        };
-
-        let mut content: Vec<String> = xs
+        let mut content: Vec<String> = names
             .iter()
             .map(|ref_name| format!("delete {}\x00\x00", ref_name))
             .collect();
@@ -338,6 +450,83 @@ mod testing {
         Ok(())
     }
 
+    #[test]
+    fn test_partitioned_refs_carry_commit_details() -> Result<()> {
+        let mut fix = Fixture::new()?;
+        let ident = old_ident();
+        {
+            setup_ref_repo(&mut fix, &ident)?;
+        }
+
+        let repo = fix.repo();
+        let cutoff = FocusTime::now() - chrono::Duration::days(90);
+        let find = |refs: &[super::PartitionedRef], name: &str| -> super::PartitionedRef {
+            refs.iter().find(|r| r.name == name).unwrap().clone()
+        };
+
+        let checked = super::PartitionedRefs::for_repo(repo, cutoff.clone(), true)?;
+        let old_merge_base = find(&checked.expired, OLD_MERGE_BASE_BRANCH_NAME);
+        let commit = repo
+            .find_reference(OLD_MERGE_BASE_BRANCH_NAME)?
+            .peel_to_commit()?;
+        assert_eq!(old_merge_base.oid, commit.id().to_string());
+        assert_eq!(
+            old_merge_base.committer_timestamp,
+            commit.committer().when().seconds()
+        );
+        assert_eq!(old_merge_base.passed_merge_base_check, Some(false));
+        assert_eq!(
+            find(&checked.expired, OLD_TIP_BRANCH_NAME).passed_merge_base_check,
+            None
+        );
+
+        let unchecked = super::PartitionedRefs::for_repo(repo, cutoff, false)?;
+        let old_merge_base = find(&unchecked.current, OLD_MERGE_BASE_BRANCH_NAME);
+        assert_eq!(old_merge_base.passed_merge_base_check, None);
+
+        let json = serde_json::to_value(&old_merge_base)?;
+        assert_eq!(json["name"], OLD_MERGE_BASE_BRANCH_NAME);
+        assert_eq!(json["oid"], commit.id().to_string());
+        assert!(json["passed_merge_base_check"].is_null());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_empty_refs() -> Result<()> {
+        let mut fix = Fixture::new()?;
+        let ident = old_ident();
+        {
+            setup_ref_repo(&mut fix, &ident)?;
+        }
+
+        // A loose ref pointing at a commit that does not exist.
+        const GHOST_BRANCH_NAME: &str = "refs/heads/ghost";
+        fs::write(
+            fix.repo_path().join(".git").join(GHOST_BRANCH_NAME),
+            "deadbeefdeadbeefdeadbeefdeadbeefdeadbeef\n",
+        )?;
+
+        let repo = fix.repo();
+        assert_eq!(
+            super::find_empty_refs(repo)?,
+            vec![GHOST_BRANCH_NAME.to_string()]
+        );
+
+        let pruned = super::prune_empty_refs(repo, true, false, fix.app())?;
+        assert_eq!(pruned, vec![GHOST_BRANCH_NAME.to_string()]);
+        assert!(repo.find_reference(GHOST_BRANCH_NAME).is_ok());
+
+        let pruned = super::prune_empty_refs(repo, false, false, fix.app())?;
+        assert_eq!(pruned, vec![GHOST_BRANCH_NAME.to_string()]);
+        assert!(repo.find_reference(GHOST_BRANCH_NAME).is_err());
+        assert!(repo.find_reference(REFS_HEADS_MAIN).is_ok());
+        assert!(repo.find_reference(OLD_TIP_BRANCH_NAME).is_ok());
+        assert!(super::find_empty_refs(repo)?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_expire_leaves_repod_master_untouched() -> Result<()> {
         let mut fix = Fixture::new()?;
@@ -1,29 +1,180 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-use focus_internals::tracker::Tracker;
-use focus_util::app::App;
+use focus_internals::{
+    model::{activity::Activity, repo::Repo},
+    tracker::Tracker,
+};
+use focus_util::{app::App, files, paths};
+use serde::Serialize;
+use tracing::debug;
 
-pub fn list(tracker: &Tracker) -> Result<()> {
+use crate::maintenance::{self, RunOptions, TimePeriod};
+
+/// How `focus repo list` prints the registered repos.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    strum_macros::EnumVariantNames,
+    strum_macros::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RepoListFormat {
+    /// One repo per line.
+    Text,
+
+    /// A JSON array of `RepoSummary` objects.
+    Json,
+}
+
+/// A registered repo along with what is needed to decide whether it is still in use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoSummary {
+    pub path: PathBuf,
+    pub identifier: String,
+
+    /// The total size of the files in the repo, in bytes.
+    pub size: u64,
+
+    /// When the repo was last synced successfully, in seconds since the Unix epoch. None if no sync has been recorded.
+    pub last_sync: Option<i64>,
+
+    /// The most recent migration applied to the repo, or None if it could not be read.
+    pub migration_version: Option<String>,
+}
+
+/// Summarize the registered repos. The migration version of each is determined by `migration_version`, which is supplied by the caller since the migrations live outside this crate. Details that cannot be read from a repo are left empty rather than failing the listing.
+pub fn list(
+    tracker: &Tracker,
+    app: Arc<App>,
+    migration_version: impl Fn(&Path, Arc<App>) -> Result<String>,
+) -> Result<Vec<RepoSummary>> {
     let snapshot = tracker.scan().context("scanning repositories")?;
-    for repo in snapshot.repos() {
-        println!("{}", repo)
-    }
+    Ok(snapshot
+        .repos()
+        .iter()
+        .map(|tracked| {
+            let path = tracked.location();
+            let last_sync = Repo::open(path, app.clone())
+                .and_then(|repo| repo.activity_timestamps())
+                .map(|timestamps| timestamps.get(Activity::Sync))
+                .unwrap_or_else(|e| {
+                    debug!(?path, ?e, "Could not read activity timestamps");
+                    None
+                });
+            let migration_version = migration_version(path, app.clone())
+                .map_err(|e| debug!(?path, ?e, "Could not read migration version"))
+                .ok();
+            RepoSummary {
+                path: path.to_owned(),
+                identifier: tracked.identifier().to_string(),
+                size: files::size_of(path),
+                last_sync,
+                migration_version,
+            }
+        })
+        .collect())
+}
 
+/// Repair the registry, reporting registered repos that no longer exist on disk and removing them unless `keep_missing` is set, as well as repos missing their `.focus` directory.
+pub fn repair(tracker: &Tracker, keep_missing: bool, app: Arc<App>) -> Result<()> {
+    let report = tracker
+        .repair(keep_missing, app)
+        .context("Failed to repair repository registry")?;
+    for path in report.missing.iter() {
+        if report.pruned_missing {
+            eprintln!("Removed missing repository {}", path.display());
+        } else {
+            eprintln!(
+                "Repository {} no longer exists; run `focus repo repair` without --keep-missing to remove it",
+                path.display()
+            );
+        }
+    }
+    for path in report.without_focus_dir.iter() {
+        eprintln!(
+            "Repository {} has no .focus directory; run `focus repo remove` if it is no longer a focused repo",
+            path.display()
+        );
+    }
     Ok(())
 }
 
-pub fn repair(tracker: &Tracker, app: Arc<App>) -> Result<()> {
-    tracker
-        .repair(app)
-        .context("Failed to repair repository registry")
+/// Discard the registry and reconstruct it from the focused repos found under `root`.
+pub fn rebuild_from_disk(
+    root: impl AsRef<Path>,
+    max_depth: usize,
+    tracker: &Tracker,
+    app: Arc<App>,
+) -> Result<()> {
+    let repos = tracker
+        .rebuild_from_disk(root.as_ref(), max_depth, app)
+        .context("Failed to rebuild repository registry")?;
+    for repo in repos.iter() {
+        println!("{}", repo.display());
+    }
+    eprintln!("Registered {} repositories", repos.len());
+    Ok(())
 }
 
 pub fn register(repo_directory: impl AsRef<Path>, tracker: &Tracker, app: Arc<App>) -> Result<()> {
     tracker.ensure_registered(repo_directory.as_ref(), app)?;
     Ok(())
 }
+
+/// Run the maintenance tasks for `time_period` in the repo at `repo_directory` now, letting git print its output.
+pub fn gc(
+    repo_directory: impl AsRef<Path>,
+    time_period: TimePeriod,
+    timings: bool,
+    tracker: &Tracker,
+    app: Arc<App>,
+) -> Result<()> {
+    let repo_directory = repo_directory.as_ref();
+    let options = RunOptions {
+        git_binary: Some(app.git_binary().clone()),
+        timings,
+        stream_output: true,
+        ..Default::default()
+    };
+    maintenance::run_in_repo(options, time_period, repo_directory, tracker, app)
+        .with_context(|| format!("Running maintenance in {}", repo_directory.display()))
+}
+
+/// Remove the repo at `repo_directory` from the registry so that it is no longer maintained, also deleting the checkout if `delete` is set.
+pub fn remove(repo_directory: impl AsRef<Path>, delete: bool, tracker: &Tracker) -> Result<()> {
+    let repo_directory = repo_directory.as_ref();
+    // The repo may already be gone from disk, in which case it cannot be canonicalized, so `..` components are resolved lexically to match the registered path.
+    let repo_directory = match repo_directory.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) => paths::normalize_path(&std::env::current_dir()?.join(repo_directory)),
+    };
+
+    if !tracker.deregister(&repo_directory)? {
+        bail!(
+            "{} is not a registered repository; run `focus repo list` to see the registered repositories",
+            repo_directory.display()
+        );
+    }
+    eprintln!("Deregistered {}", repo_directory.display());
+
+    if delete && repo_directory.exists() {
+        std::fs::remove_dir_all(&repo_directory)
+            .with_context(|| format!("Deleting {}", repo_directory.display()))?;
+        eprintln!("Deleted {}", repo_directory.display());
+    }
+
+    Ok(())
+}
@@ -3,17 +3,18 @@
 
 use std::{
     borrow::Cow,
-    collections::HashSet,
-    fmt::Display,
+    collections::{BTreeSet, HashSet},
+    fmt::{Display, Write},
     path::{Path, PathBuf},
     sync::Arc,
     thread,
 };
 
 use anyhow::{bail, Context, Result};
+use chrono::TimeZone;
 use console::style;
 use focus_util::{
-    app::App,
+    app::{App, ExitCode},
     git_helper::{get_changed_paths_between_trees, get_head_commit},
     paths::is_relevant_to_build_graph,
 };
@@ -85,6 +86,7 @@ fn mutate(
     sync_if_changed: bool,
     action: OperationAction,
     projects_and_targets: Vec<String>,
+    interpretation: Interpretation,
     app: Arc<focus_util::app::App>,
 ) -> Result<bool> {
     let mut synced = false;
@@ -100,14 +102,68 @@ fn mutate(
         None
     };
 
-    let mut projects_and_targets = projects_and_targets;
+    let mut interpretation = interpretation;
+
+    // Directory targets written relative to the current directory are stored relative to the repository root.
+    let cwd = std::env::current_dir().context("Determining the current directory")?;
+    let mut projects_and_targets = projects_and_targets
+        .into_iter()
+        .map(|name| match interpretation.target(&name)? {
+            Some(target @ Target::Directory(_)) => {
+                let normalized = target
+                    .clone()
+                    .relative_to_repo_root(repo.path(), &cwd)
+                    .with_context(|| format!("Interpreting {:?}", name))?;
+                Ok(if normalized == target {
+                    name
+                } else {
+                    String::from(&normalized)
+                })
+            }
+            _ => Ok(name),
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    if let OperationAction::Add(AddOptions {
+        project_from_label: true,
+        ..
+    }) = action
+    {
+        let catalog = selections.project_catalog();
+        let mut mapped = Vec::with_capacity(projects_and_targets.len());
+        for name in projects_and_targets {
+            let target = match interpretation.target(&name)? {
+                Some(target) => target,
+                None => {
+                    mapped.push(name);
+                    continue;
+                }
+            };
+            match catalog
+                .optional_projects
+                .smallest_project_containing(&target)
+            {
+                Some(project) => {
+                    eprintln!("Adding project {} for {}", project.name, name);
+                    mapped.push(project.name.clone());
+                }
+                None => {
+                    eprintln!("No project contains {}; adding it as a target", name);
+                    mapped.push(name);
+                }
+            }
+        }
+        projects_and_targets = mapped;
+        // The list now mixes project names and targets, which are told apart as usual.
+        interpretation = Interpretation::Inferred;
+    }
 
     match action {
-        OperationAction::Add(AddOptions { unroll: true }) => {
+        OperationAction::Add(AddOptions { unroll: true, .. }) => {
             let mut projects = vec![];
             let mut targets = vec![];
             for i in projects_and_targets.clone() {
-                if Target::try_from(i.as_str()).is_ok() {
+                if interpretation.target(&i)?.is_some() {
                     targets.push(i);
                 } else {
                     projects.extend(
@@ -138,6 +194,7 @@ fn mutate(
             }
             projects_and_targets = targets;
             projects_and_targets.extend(projects);
+            interpretation = Interpretation::Inferred;
         }
         OperationAction::Remove(RemoveOptions { all: true }) => {
             let projects: Vec<String> = selections
@@ -153,15 +210,34 @@ fn mutate(
                 .map(|x| match x {
                     Target::Bazel(c) => format!("bazel:{}", c),
                     Target::Directory(c) => format!("bazel:{}", c),
+                    Target::Pants(c) => format!("pants:{}", c),
                 })
                 .collect();
             projects_and_targets = targets;
             projects_and_targets.extend(projects);
+            interpretation = Interpretation::Inferred;
         }
         _ => (),
     }
+    if let OperationAction::Add(AddOptions {
+        allow_full_repo, ..
+    }) = action
+    {
+        let mut targets: Vec<Target> = Vec::new();
+        for name in projects_and_targets.iter() {
+            targets.extend(interpretation.target(name)?);
+        }
+        check_selection_size(&repo, &targets, allow_full_repo)?;
+    }
+    let dedupe_with_gitignore = matches!(
+        action,
+        OperationAction::Add(AddOptions {
+            dedupe_with_gitignore: true,
+            ..
+        })
+    );
     if selections
-        .mutate(action, &projects_and_targets)
+        .mutate(action, interpretation, &projects_and_targets)
         .context("Updating the selection")?
     {
         selections.save().context("Saving selection")?;
@@ -169,7 +245,8 @@ fn mutate(
             info!("Synchronizing after selection changed");
             // TODO: Use the correct sync mode here. Sync will override for SyncMode::Incremental, but that feels janky.
             let result = super::sync::run(
-                &SyncRequest::new(sparse_repo.as_ref(), SyncMode::Incremental),
+                &SyncRequest::new(sparse_repo.as_ref(), SyncMode::Incremental)
+                    .with_dedupe_with_gitignore(dedupe_with_gitignore),
                 app,
             )
             .context("Synchronizing changes")?;
@@ -181,26 +258,111 @@ fn mutate(
     Ok(synced)
 }
 
+/// The directory, relative to the repository root, that `target` selects in its entirety. The root ellipsis `bazel://...` selects the empty path.
+fn subtree_of(target: &Target) -> Option<PathBuf> {
+    match target {
+        Target::Bazel(label) => label.ellipsis_path(),
+        Target::Directory(path) => Some(PathBuf::from(path.trim_matches('/'))),
+        Target::Pants(_) => None,
+    }
+}
+
+/// Count the files in `tree`, returning how many lie under any of `subtrees` along with the total.
+fn estimate_path_count(tree: &git2::Tree, subtrees: &[PathBuf]) -> Result<(usize, usize)> {
+    let mut selected = 0;
+    let mut total = 0;
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+            total += 1;
+            if subtrees
+                .iter()
+                .any(|subtree| Path::new(root).starts_with(subtree))
+            {
+                selected += 1;
+            }
+        }
+        TreeWalkResult::Ok
+    })
+    .context("Walking the HEAD tree")?;
+    Ok((selected, total))
+}
+
+/// Refuse to add targets that would materialize more than the configured percentage of the repository unless `allow_full_repo` is set. Only targets that select whole directories, like `bazel://...`, can be estimated without resolving them.
+fn check_selection_size(repo: &Repo, targets: &[Target], allow_full_repo: bool) -> Result<()> {
+    let subtrees: Vec<PathBuf> = targets.iter().filter_map(subtree_of).collect();
+    if subtrees.is_empty() {
+        return Ok(());
+    }
+
+    let head_tree = get_head_commit(repo.underlying())?
+        .tree()
+        .context("Reading the HEAD tree")?;
+    let (selected, total) = estimate_path_count(&head_tree, &subtrees)?;
+    let max_percentage = repo.get_max_selection_percentage()? as usize;
+    if total == 0 || selected * 100 <= total * max_percentage {
+        return Ok(());
+    }
+
+    eprintln!(
+        "These targets would materialize approximately {} of the {} paths in the repository ({}%)",
+        selected,
+        total,
+        selected * 100 / total
+    );
+    if !allow_full_repo {
+        bail!(
+            "Refusing to materialize more than {}% of the repository; pass --allow-full-repo to proceed anyway",
+            max_percentage
+        );
+    }
+    Ok(())
+}
+
 pub fn add(
     sparse_repo: impl AsRef<Path>,
     sync_if_changed: bool,
     projects_and_targets: Vec<String>,
     unroll: bool,
     app: Arc<App>,
+) -> Result<bool> {
+    add_with_options(
+        sparse_repo,
+        sync_if_changed,
+        projects_and_targets,
+        Interpretation::Inferred,
+        AddOptions {
+            unroll,
+            ..Default::default()
+        },
+        app,
+    )
+}
+
+/// Like `add`, but with all of the add options (see `AddOptions`), interpreting `projects_and_targets` according to `interpretation`.
+pub fn add_with_options(
+    sparse_repo: impl AsRef<Path>,
+    sync_if_changed: bool,
+    projects_and_targets: Vec<String>,
+    interpretation: Interpretation,
+    options: AddOptions,
+    app: Arc<App>,
 ) -> Result<bool> {
     mutate(
         sparse_repo,
         sync_if_changed,
-        OperationAction::Add(AddOptions { unroll }),
+        OperationAction::Add(options),
         projects_and_targets,
+        interpretation,
         app,
     )
 }
 
+/// Remove `projects_and_targets`, interpreted according to `interpretation`, from the selection, or everything if `all` is set.
 pub fn remove(
     sparse_repo: impl AsRef<Path>,
     sync_if_changed: bool,
     projects_and_targets: Vec<String>,
+    interpretation: Interpretation,
     all: bool,
     app: Arc<App>,
 ) -> Result<bool> {
@@ -209,6 +371,7 @@ pub fn remove(
         sync_if_changed,
         OperationAction::Remove(RemoveOptions { all }),
         projects_and_targets,
+        interpretation,
         app,
     )
 }
@@ -220,6 +383,176 @@ pub fn list_projects(sparse_repo: impl AsRef<Path>, app: Arc<App>) -> Result<()>
     Ok(())
 }
 
+/// Describe the project called `name`: its description, the projects it includes, and the targets it expands to once included projects are resolved.
+fn describe_project(repo: &Repo, name: &str) -> Result<String> {
+    let selections = repo.selection_manager()?;
+    let catalog = selections.project_catalog();
+    let project = match catalog.find_project(name) {
+        Some(project) => project,
+        None => bail!("Project {} is not defined", name),
+    };
+    let expanded_targets =
+        resolve_targets_for_project(vec![project.clone()], &catalog.optional_projects.underlying)
+            .with_context(|| format!("Resolving the targets of project {}", name))?;
+    let expanded_targets: BTreeSet<String> = expanded_targets
+        .iter()
+        .map(|target| target.to_string())
+        .collect();
+
+    let mut description = String::new();
+    writeln!(
+        description,
+        "{}{}",
+        project.name,
+        if project.mandatory {
+            " <mandatory>"
+        } else {
+            ""
+        }
+    )?;
+    writeln!(description, "{}", project.description)?;
+    writeln!(description)?;
+    writeln!(description, "--- Included projects ---")?;
+    if project.projects.is_empty() {
+        writeln!(description, "None.")?;
+    }
+    for included in project.projects.iter() {
+        writeln!(description, "{}", included)?;
+    }
+    writeln!(description)?;
+    writeln!(description, "--- Targets ---")?;
+    if expanded_targets.is_empty() {
+        writeln!(description, "None.")?;
+    }
+    for target in expanded_targets.iter() {
+        if project.targets.contains(target) {
+            writeln!(description, "{}", target)?;
+        } else {
+            writeln!(description, "{} (from an included project)", target)?;
+        }
+    }
+    Ok(description)
+}
+
+/// Print the definition of the project called `name` and the targets it expands to.
+pub fn show_project(sparse_repo: impl AsRef<Path>, name: &str, app: Arc<App>) -> Result<()> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    print!("{}", describe_project(&repo, name)?);
+    Ok(())
+}
+
+/// Check the stored selection for unparseable targets, undefined projects, and cycles among included projects, printing any problems found. Nothing is modified.
+pub fn validate(sparse_repo: impl AsRef<Path>, app: Arc<App>) -> Result<ExitCode> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    let problems = repo
+        .validate_selection()
+        .context("Validating the selection")?;
+    if problems.is_empty() {
+        println!("Pass");
+        return Ok(ExitCode(0));
+    }
+
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+    Ok(ExitCode(1))
+}
+
+/// Print the most recent changes to the selection, newest first, up to `limit` if given. Nothing is modified.
+pub fn history(
+    sparse_repo: impl AsRef<Path>,
+    limit: Option<usize>,
+    json: bool,
+    app: Arc<App>,
+) -> Result<ExitCode> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    let history = repo
+        .selection_manager()?
+        .history()
+        .context("Loading the selection history")?;
+    let changes = history.recent(limit);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+        return Ok(ExitCode(0));
+    }
+
+    if changes.is_empty() {
+        println!("No changes to the selection have been recorded.");
+    }
+    for change in changes {
+        let time = chrono::Local
+            .timestamp_opt(change.timestamp, 0)
+            .single()
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_else(|| change.timestamp.to_string());
+        println!("{}", time);
+        for project in change.added_projects.iter() {
+            println!("  + project {}", project);
+        }
+        for project in change.removed_projects.iter() {
+            println!("  - project {}", project);
+        }
+        for target in change.added_targets.iter() {
+            println!("  + target {}", target);
+        }
+        for target in change.removed_targets.iter() {
+            println!("  - target {}", target);
+        }
+    }
+    Ok(ExitCode(0))
+}
+
+/// Store the current selection as the profile `name` so that it can be switched back to later.
+pub fn save_profile(sparse_repo: impl AsRef<Path>, name: &str, app: Arc<App>) -> Result<()> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    repo.selection_manager()?
+        .save_profile(name)
+        .context("Saving the selection profile")?;
+    println!("Saved the selection as profile {}", name);
+    Ok(())
+}
+
+/// Replace the selection with the profile `name` and sync if it changed. Returns whether a sync was performed. The previous selection is restored if the sync fails.
+pub fn switch_profile(sparse_repo: impl AsRef<Path>, name: &str, app: Arc<App>) -> Result<bool> {
+    let repo = Repo::open(sparse_repo.as_ref(), app.clone())?;
+    let mut selections = repo.selection_manager().context("Loading the selection")?;
+    let previous_selection = selections.selection()?;
+    selections
+        .switch_to_profile(name)
+        .context("Switching the selection profile")?;
+    if selections.selection()? == previous_selection {
+        println!("The selection already matches profile {}", name);
+        return Ok(false);
+    }
+
+    let backup = selections
+        .create_backup()
+        .context("Creating a backup of the current selection")?;
+    selections.save().context("Saving selection")?;
+    info!("Synchronizing after switching the selection profile");
+    let result = super::sync::run(
+        &SyncRequest::new(sparse_repo.as_ref(), SyncMode::Incremental),
+        app,
+    )
+    .context("Synchronizing changes")?;
+    backup.discard();
+    println!("Switched to selection profile {}", name);
+    Ok(result.status == super::sync::SyncStatus::Success)
+}
+
+/// Print the names of the stored selection profiles.
+pub fn list_profiles(sparse_repo: impl AsRef<Path>, app: Arc<App>) -> Result<()> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    let profiles = repo.selection_manager()?.profiles()?;
+    if profiles.is_empty() {
+        println!("No selection profiles have been saved.");
+    }
+    for name in profiles {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 enum SkimSource {
     Project,
@@ -589,6 +922,7 @@ pub fn add_interactive(
     app: Arc<App>,
     search_all_targets: bool,
     unroll: bool,
+    dedupe_with_gitignore: bool,
 ) -> Result<()> {
     let sparse_repo_path = sparse_repo.as_ref();
     let repo = Repo::open(sparse_repo_path, app.clone())?;
@@ -653,7 +987,18 @@ pub fn add_interactive(
                 } => format!("bazel://{name}/..."),
             })
             .collect();
-        add(sparse_repo, true, selected_projects, unroll, app)?;
+        add_with_options(
+            sparse_repo,
+            true,
+            selected_projects,
+            Interpretation::Inferred,
+            AddOptions {
+                unroll,
+                dedupe_with_gitignore,
+                ..Default::default()
+            },
+            app,
+        )?;
     }
 
     Ok(())
@@ -662,8 +1007,15 @@ pub fn add_interactive(
 mod tests {
     use std::collections::HashSet;
     use std::fs;
+    use std::sync::Arc;
 
     use anyhow::Result;
+    use focus_internals::model::{
+        repo::Repo,
+        selection::{AddOptions, Interpretation},
+    };
+    use focus_testing::ScratchGitRepo;
+    use focus_util::app::App;
 
     use crate::testing::integration::RepoPairFixture;
 
@@ -728,6 +1080,7 @@ mod tests {
             &fixture.sparse_repo_path,
             true,
             Vec::new(),
+            Interpretation::Inferred,
             true,
             fixture.app.clone(),
         )?;
@@ -958,4 +1311,82 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn projects_are_described_with_included_targets() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let app = Arc::new(App::new_for_testing()?);
+        let fix = ScratchGitRepo::new_copied_fixture(
+            app.git_binary().clone(),
+            std::path::Path::new("bazel_java_example"),
+            &temp.path().join("dense"),
+            "main",
+        )?;
+        let repo = Repo::open(fix.path(), app)?;
+
+        assert_eq!(
+            super::describe_project(&repo, "team_zissou/project_c")?,
+            "team_zissou/project_c
+Stuff relating to project C
+
+--- Included projects ---
+team_banzai/project_a
+
+--- Targets ---
+bazel://project_a/... (from an included project)
+bazel://project_b/...
+"
+        );
+        assert!(super::describe_project(&repo, "blofeld/moonbase").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn selection_add_full_repo_requires_confirmation() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let fix = ScratchGitRepo::new_static_fixture(temp.path())?;
+        let app = Arc::new(App::new_for_testing()?);
+        let selected_targets = || -> Result<HashSet<String>> {
+            Ok(Repo::open(fix.path(), app.clone())?
+                .selection_manager()?
+                .selection()?
+                .targets
+                .into_iter()
+                .map(|t| t.to_string())
+                .collect())
+        };
+
+        let result = crate::selection::add_with_options(
+            fix.path(),
+            false,
+            vec![String::from("bazel://...")],
+            Interpretation::Inferred,
+            AddOptions::default(),
+            app.clone(),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--allow-full-repo"));
+        assert_eq!(selected_targets()?, HashSet::new());
+
+        crate::selection::add_with_options(
+            fix.path(),
+            false,
+            vec![String::from("bazel://...")],
+            Interpretation::Inferred,
+            AddOptions {
+                allow_full_repo: true,
+                ..Default::default()
+            },
+            app.clone(),
+        )?;
+        assert_eq!(
+            selected_targets()?,
+            HashSet::from(["bazel://...".to_string()])
+        );
+
+        Ok(())
+    }
 }
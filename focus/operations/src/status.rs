@@ -1,17 +1,193 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
-use focus_internals::{model::repo::Repo, target::TargetTypes};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use focus_internals::{
+    model::{
+        activity::{Activity, ActivityTimestamps},
+        repo::Repo,
+    },
+    target::TargetTypes,
+};
 use focus_util::app::{App, ExitCode};
-use std::{collections::HashSet, path::Path, sync::Arc};
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The version of the JSON document emitted by `focus status --format json`. Bump this whenever a field is removed or changes meaning; adding fields does not require a bump.
+pub const STATUS_SCHEMA_VERSION: u32 = 1;
+
+/// How `focus status` presents its output.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    strum_macros::EnumVariantNames,
+    strum_macros::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum StatusFormat {
+    /// Human-readable text.
+    Text,
+
+    /// A `StatusReport` serialized as JSON.
+    Json,
+}
+
+/// The machine-readable status of a sparse repo.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StatusReport {
+    /// Always `STATUS_SCHEMA_VERSION`.
+    pub schema_version: u32,
+
+    /// Names of the user-selected projects, sorted. Mandatory projects are not included.
+    pub projects: Vec<String>,
+
+    /// Ad-hoc targets in the selection, sorted.
+    pub targets: Vec<String>,
+
+    /// Whether only the selection is present in the working tree, as opposed to all repo contents.
+    pub filtered: bool,
+
+    /// Whether the working tree has no uncommitted changes.
+    pub working_tree_clean: bool,
+
+    /// The number of patterns in the current sparse checkout profile.
+    pub sparse_pattern_count: usize,
+
+    /// Selected projects that the last sync left out because of `focus sync --exclude-project`, sorted.
+    pub temporarily_excluded_projects: Vec<String>,
+}
+
+/// Gather the status of the sparse repo at `sparse_repo`.
+pub fn report(sparse_repo: impl AsRef<Path>, app: Arc<App>) -> Result<StatusReport> {
+    let repo = Repo::open(sparse_repo.as_ref(), app.clone())?;
+    let selection = repo.selection_manager()?.selection()?;
+    let working_tree = repo.working_tree()?;
+
+    let mut projects: Vec<String> = selection
+        .projects
+        .iter()
+        .filter(|project| project.is_selectable())
+        .map(|project| project.name.clone())
+        .collect();
+    projects.sort();
+    let mut targets: Vec<String> = selection.targets.iter().map(|t| t.to_string()).collect();
+    targets.sort();
+
+    let sparse_checkout_path = working_tree.sparse_checkout_path();
+    let sparse_pattern_count = if sparse_checkout_path.is_file() {
+        std::fs::read_to_string(&sparse_checkout_path)
+            .with_context(|| format!("Reading {}", sparse_checkout_path.display()))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .count()
+    } else {
+        0
+    };
+
+    Ok(StatusReport {
+        schema_version: STATUS_SCHEMA_VERSION,
+        projects,
+        targets,
+        filtered: working_tree.get_filter_config()?,
+        working_tree_clean: working_tree.is_clean(app)?,
+        sparse_pattern_count,
+        temporarily_excluded_projects: repo.temporarily_excluded_projects()?,
+    })
+}
+
+/// How long ago activities in a sparse repo last completed, for scraping by monitoring systems. Ages are `null` for activities that have not been recorded.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct MachineAge {
+    /// Seconds since the index was last fetched.
+    pub index_fetch_age_seconds: Option<i64>,
+
+    /// Seconds since the working tree was last synchronized.
+    pub sync_age_seconds: Option<i64>,
+
+    /// Seconds since maintenance last ran.
+    pub maintenance_age_seconds: Option<i64>,
+}
+
+impl MachineAge {
+    /// Compute ages relative to `now` (in seconds since the Unix epoch). Timestamps in the future count as zero seconds old.
+    pub fn from_timestamps(timestamps: &ActivityTimestamps, now: i64) -> Self {
+        let age = |activity| {
+            timestamps
+                .get(activity)
+                .map(|timestamp| (now - timestamp).max(0))
+        };
+        Self {
+            index_fetch_age_seconds: age(Activity::IndexFetch),
+            sync_age_seconds: age(Activity::Sync),
+            maintenance_age_seconds: age(Activity::Maintenance),
+        }
+    }
+}
+
+/// Compute the `MachineAge` of the sparse repo at `sparse_repo`.
+pub fn machine_age(sparse_repo: impl AsRef<Path>, app: Arc<App>) -> Result<MachineAge> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    let timestamps = repo
+        .activity_timestamps()
+        .context("Reading activity timestamps")?;
+    Ok(MachineAge::from_timestamps(
+        &timestamps,
+        Utc::now().timestamp(),
+    ))
+}
+
+/// Check that each of the projects named in `names` is selected, explaining on stderr which are not. Exits non-zero if any is missing and prints nothing otherwise. Mandatory projects are always selected.
+pub fn assert_projects_selected(
+    sparse_repo: impl AsRef<Path>,
+    names: &[String],
+    app: Arc<App>,
+) -> Result<ExitCode> {
+    let repo = Repo::open(sparse_repo.as_ref(), app)?;
+    let selection = repo.computed_selection()?;
+    let selected: HashSet<&str> = selection
+        .projects
+        .iter()
+        .map(|project| project.name.as_str())
+        .collect();
+    let missing: Vec<&String> = names
+        .iter()
+        .filter(|name| !selected.contains(name.as_str()))
+        .collect();
+    for name in missing.iter() {
+        eprintln!("Project {} is not selected", name);
+    }
+    Ok(ExitCode(if missing.is_empty() { 0 } else { 1 }))
+}
 
 pub fn run(
     sparse_repo: impl AsRef<Path>,
     app: Arc<App>,
     targets_flag: bool,
     target_types: Vec<TargetTypes>,
+    format: StatusFormat,
 ) -> Result<ExitCode> {
+    if format == StatusFormat::Json {
+        let report = report(sparse_repo, app)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(ExitCode(0));
+    }
+
     let target_types = HashSet::<TargetTypes>::from_iter(target_types.iter().cloned());
     let repo = Repo::open(sparse_repo.as_ref(), app)?;
     let selections = repo.selection_manager()?;
@@ -25,6 +201,13 @@ pub fn run(
     } else {
         eprintln!("All repo contents are present in the worktree.")
     }
+    let excluded_projects = repo.temporarily_excluded_projects()?;
+    if !excluded_projects.is_empty() {
+        eprintln!(
+            "These projects were temporarily excluded from the last sync: {}",
+            excluded_projects.join(", ")
+        );
+    }
     eprintln!();
 
     if target_types.is_empty() && !targets_flag {
@@ -51,9 +234,297 @@ pub fn run(
                         println!("{}", target);
                     }
                 }
+                focus_internals::target::Target::Pants(_) => {
+                    if target_types.contains(&TargetTypes::Pants) {
+                        println!("{}", target);
+                    }
+                }
             }
         }
     }
 
     Ok(ExitCode(0))
 }
+
+/// How often a watch waiting out its interval checks whether it was interrupted.
+const WATCH_STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Set when `focus status --watch-interval` receives SIGINT.
+static WATCH_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_watch_sigint(_signal: i32) {
+    WATCH_INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// A source of time for polling the status, which tests replace with a fake.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/// Remembers the last status report seen while watching so that unchanged reports are not printed again.
+#[derive(Debug, Default)]
+struct ChangeDetector {
+    last: Option<StatusReport>,
+}
+
+impl ChangeDetector {
+    /// Record `report`, returning whether it differs from the previously recorded one.
+    fn observe(&mut self, report: StatusReport) -> bool {
+        if self.last.as_ref() == Some(&report) {
+            return false;
+        }
+        self.last = Some(report);
+        true
+    }
+}
+
+/// Call `poll` every `interval` until `stop` is set, passing each report that differs from the last to `emit`.
+fn watch_with(
+    clock: &dyn Clock,
+    interval: Duration,
+    stop: &AtomicBool,
+    mut poll: impl FnMut() -> Result<StatusReport>,
+    mut emit: impl FnMut(&StatusReport) -> Result<()>,
+) -> Result<()> {
+    let mut detector = ChangeDetector::default();
+    while !stop.load(Ordering::SeqCst) {
+        let deadline = clock.now() + interval;
+        let report = poll()?;
+        if detector.observe(report) {
+            emit(detector.last.as_ref().unwrap())?;
+        }
+
+        // Wait in short slices so that an interrupt is noticed promptly.
+        while !stop.load(Ordering::SeqCst) {
+            let now = clock.now();
+            if now >= deadline {
+                break;
+            }
+            clock.sleep((deadline - now).min(WATCH_STOP_CHECK_INTERVAL));
+        }
+    }
+    Ok(())
+}
+
+/// Print the status every `interval` seconds, but only when it has changed, until interrupted. This polls rather than relying on filesystem events, which are unreliable on network filesystems and in containers.
+pub fn watch(
+    sparse_repo: impl AsRef<Path>,
+    app: Arc<App>,
+    targets_flag: bool,
+    target_types: Vec<TargetTypes>,
+    format: StatusFormat,
+    interval: Duration,
+) -> Result<ExitCode> {
+    if interval.is_zero() {
+        bail!("The watch interval must be at least one second");
+    }
+
+    let sparse_repo = sparse_repo.as_ref();
+    let handler = SigAction::new(
+        SigHandler::Handler(handle_watch_sigint),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // Safety: the handler only stores to an atomic.
+    unsafe { sigaction(Signal::SIGINT, &handler) }.context("Installing a SIGINT handler")?;
+
+    watch_with(
+        &SystemClock,
+        interval,
+        &WATCH_INTERRUPTED,
+        || report(sparse_repo, app.clone()),
+        |report| {
+            if format == StatusFormat::Json {
+                println!("{}", serde_json::to_string_pretty(report)?);
+            } else {
+                let _ = run(
+                    sparse_repo,
+                    app.clone(),
+                    targets_flag,
+                    target_types.clone(),
+                    format,
+                )?;
+            }
+            Ok(())
+        },
+    )?;
+    Ok(ExitCode(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+    use crate::testing::integration::RepoPairFixture;
+    use focus_internals::model::selection::{Interpretation, OperationAction};
+    use focus_testing::ScratchGitRepo;
+
+    struct FakeClock {
+        now: Cell<Instant>,
+        slept: Cell<Duration>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+            self.slept.set(self.slept.get() + duration);
+        }
+    }
+
+    fn report_with_pattern_count(sparse_pattern_count: usize) -> StatusReport {
+        StatusReport {
+            schema_version: STATUS_SCHEMA_VERSION,
+            projects: Vec::new(),
+            targets: Vec::new(),
+            filtered: true,
+            working_tree_clean: true,
+            sparse_pattern_count,
+            temporarily_excluded_projects: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn watching_prints_only_changed_reports() -> Result<()> {
+        let clock = FakeClock {
+            now: Cell::new(Instant::now()),
+            slept: Cell::new(Duration::ZERO),
+        };
+        let stop = AtomicBool::new(false);
+        let mut polled = vec![1, 1, 2, 2, 2, 1].into_iter();
+        let emitted = RefCell::new(Vec::new());
+
+        watch_with(
+            &clock,
+            Duration::from_secs(5),
+            &stop,
+            || {
+                let count = polled.next().unwrap();
+                if polled.len() == 0 {
+                    stop.store(true, Ordering::SeqCst);
+                }
+                Ok(report_with_pattern_count(count))
+            },
+            |report| {
+                emitted.borrow_mut().push(report.sparse_pattern_count);
+                Ok(())
+            },
+        )?;
+
+        assert_eq!(emitted.into_inner(), vec![1, 2, 1]);
+        // Every poll but the interrupted last one waited out the interval.
+        assert_eq!(clock.slept.get(), Duration::from_secs(5 * 5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn machine_age_is_computed_from_recorded_timestamps() {
+        let timestamps = ActivityTimestamps {
+            index_fetch: Some(1_000),
+            sync: Some(1_900),
+            maintenance: None,
+        };
+        let age = MachineAge::from_timestamps(&timestamps, 2_000);
+        assert_eq!(
+            age,
+            MachineAge {
+                index_fetch_age_seconds: Some(1_000),
+                sync_age_seconds: Some(100),
+                maintenance_age_seconds: None,
+            }
+        );
+
+        let value = serde_json::to_value(&age).unwrap();
+        assert_eq!(value["sync_age_seconds"], 100);
+        assert!(value["maintenance_age_seconds"].is_null());
+
+        // Clock skew does not produce negative ages.
+        let age = MachineAge::from_timestamps(&timestamps, 1_500);
+        assert_eq!(age.sync_age_seconds, Some(0));
+    }
+
+    #[test]
+    fn asserting_projects_are_selected() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let app = Arc::new(App::new_for_testing()?);
+        let fix = ScratchGitRepo::new_copied_fixture(
+            app.git_binary().clone(),
+            Path::new("bazel_java_example"),
+            &temp.path().join("dense"),
+            "main",
+        )?;
+        let repo = Repo::open(fix.path(), app.clone())?;
+        let mut selections = repo.selection_manager()?;
+        selections.mutate(
+            OperationAction::default_add(),
+            Interpretation::Inferred,
+            &[String::from("team_banzai/project_a")],
+        )?;
+        selections.save()?;
+
+        let assert_selected = |names: &[&str]| -> Result<ExitCode> {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            assert_projects_selected(fix.path(), &names, app.clone())
+        };
+        assert_eq!(assert_selected(&["team_banzai/project_a"])?, ExitCode(0));
+        assert_eq!(
+            assert_selected(&["team_banzai/project_a", "mandatory"])?,
+            ExitCode(0)
+        );
+        assert_eq!(
+            assert_selected(&["team_banzai/project_a", "team_zissou/project_b"])?,
+            ExitCode(1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn status_report_serializes_with_schema_version() -> Result<()> {
+        let mut fixture = RepoPairFixture::new()?;
+        fixture
+            .projects_and_targets
+            .push(String::from("team_zissou/project_b"));
+        fixture
+            .projects_and_targets
+            .push(String::from("bazel://library_a/..."));
+        fixture.perform_clone()?;
+
+        let report = report(&fixture.sparse_repo_path, fixture.app.clone())?;
+        assert_eq!(report.schema_version, STATUS_SCHEMA_VERSION);
+        assert_eq!(report.projects, vec![String::from("team_zissou/project_b")]);
+        assert_eq!(report.targets, vec![String::from("bazel://library_a/...")]);
+        assert!(report.filtered);
+        assert!(report.working_tree_clean);
+        assert!(report.sparse_pattern_count > 0);
+        assert!(report.temporarily_excluded_projects.is_empty());
+
+        let value = serde_json::to_value(&report)?;
+        assert_eq!(value["schema_version"], STATUS_SCHEMA_VERSION);
+        assert_eq!(value["projects"][0], "team_zissou/project_b");
+
+        Ok(())
+    }
+}
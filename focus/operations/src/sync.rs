@@ -4,21 +4,35 @@
 use core::fmt;
 use core::sync::atomic::AtomicBool;
 use focus_internals::index::RocksDBMemoizationCacheExt;
-use focus_internals::{locking, model::repo::Repo};
-use focus_util::git;
+use focus_internals::{
+    locking,
+    model::{
+        activity::Activity,
+        repo::{NarrowingConflict, Repo, SyncOptions},
+    },
+    target::Target,
+};
+use focus_util::git::{self, model::Kind};
+use focus_util::git_helper;
 
 use crate::util::perform;
 use content_addressed_cache::RocksDBCache;
 use focus_util::app::App;
 use focus_util::backed_up_file::BackedUpFile;
+use focus_util::sandbox_command::{SandboxCommand, SandboxCommandOutput};
 use tracing::{debug, info, warn};
 
-use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
 
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 use anyhow::{bail, Context, Result};
+use focus_internals::target_resolver::BazelOptions;
 use lazy_static::lazy_static;
 
 const PREEMPTIVE_SYNC_MAX_WAIT_MILLIS: u64 = 30000;
@@ -26,6 +40,14 @@ const TEST_ONLY_PREEMPTIVE_SYNC_MAX_WAIT_MILLIS_UNDER_TEST: u64 = 300;
 const PREEMPTIVE_SYNC_POLL_INTERVAL_MILLIS: u64 = 100;
 pub(crate) const SYNC_FROM_PROJECT_CACHE_REQUIRED_ERROR_MESSAGE: &str =
     "Sync from project cache was required but not possible";
+#[cfg(not(target_os = "windows"))]
+const POST_SYNC_HOOK_SHELL: &str = "sh";
+#[cfg(not(target_os = "windows"))]
+const POST_SYNC_HOOK_SHELL_FLAG: &str = "-c";
+#[cfg(target_os = "windows")]
+const POST_SYNC_HOOK_SHELL: &str = "cmd";
+#[cfg(target_os = "windows")]
+const POST_SYNC_HOOK_SHELL_FLAG: &str = "/C";
 
 lazy_static! {
     static ref TEST_ONLY_PREEMPTIVE_SYNC_MACHINE_IS_ACTIVE: AtomicBool = AtomicBool::new(false);
@@ -81,6 +103,9 @@ pub enum SyncStatus {
 
     /// Syncing was cancelled because repo unfiltered view
     SkippedUnfilterView,
+
+    /// Only the changes the sync would make were written to the diff file; the working tree was not changed.
+    DiffOnly,
 }
 
 /// An enumeration capturing which mechanism was used to perform the sync.
@@ -106,12 +131,80 @@ impl fmt::Display for SyncMechanism {
     }
 }
 
+/// What a sync does when it would remove files with local changes from the working tree.
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    strum_macros::EnumVariantNames,
+    strum_macros::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ConflictResolution {
+    /// Fail, leaving the working tree and the local changes as they were.
+    #[default]
+    Abort,
+
+    /// Stash the local changes to those files, then sync.
+    Stash,
+
+    /// Discard the local changes to those files, then sync.
+    Discard,
+}
+
 pub struct SyncRequest {
     /// The path to the sparse repo.
     sparse_repo: PathBuf,
 
     /// Which sync mechanism to use.
     mode: SyncMode,
+
+    /// Additional options passed to Bazel when resolving targets.
+    bazel_options: BazelOptions,
+
+    /// Whether to leave out selected paths that match ignore rules.
+    dedupe_with_gitignore: bool,
+
+    /// Whether to fail when a directory target does not exist rather than warn.
+    strict_directories: bool,
+
+    /// The number of parallel workers Git uses to check out the working tree, if not Git's default.
+    checkout_workers: Option<usize>,
+
+    /// Whether a failed checkout should restore the previous sparse checkout rather than leave the working tree partially updated.
+    atomic: bool,
+
+    /// Selected projects to leave out of this sync only. The stored selection is not changed.
+    excluded_projects: Vec<String>,
+
+    /// A file to write the paths the sync would add to and remove from the working tree to instead of syncing.
+    diff_path: Option<PathBuf>,
+
+    /// Directories that every resolved path must be within. Empty means no restriction.
+    allowed_roots: Vec<PathBuf>,
+
+    /// What to do with local changes to files the sync would remove from the working tree.
+    conflict_resolution: ConflictResolution,
+
+    /// A commit from which, if the build graph has not changed since, the patterns resolved by the last sync are reused.
+    since: Option<String>,
+
+    /// Whether to resolve targets afresh rather than use results cached by earlier resolutions.
+    no_cache: bool,
+
+    /// A shell command to run after a successful sync, overriding the one configured in the repo.
+    post_sync_hook: Option<String>,
+
+    /// Directories to leave out of this and every later sync, in addition to those already recorded.
+    excluded_paths: Vec<PathBuf>,
+
+    /// Whether to forget the directories recorded by earlier syncs with `excluded_paths`.
+    clear_excluded_paths: bool,
 }
 
 impl SyncRequest {
@@ -119,9 +212,93 @@ impl SyncRequest {
         Self {
             sparse_repo: sparse_repo.as_ref().to_owned(),
             mode,
+            bazel_options: Default::default(),
+            dedupe_with_gitignore: false,
+            strict_directories: false,
+            checkout_workers: None,
+            atomic: false,
+            excluded_projects: Vec::new(),
+            diff_path: None,
+            allowed_roots: Vec::new(),
+            conflict_resolution: ConflictResolution::default(),
+            since: None,
+            no_cache: false,
+            post_sync_hook: None,
+            excluded_paths: Vec::new(),
+            clear_excluded_paths: false,
         }
     }
 
+    pub fn with_bazel_options(mut self, bazel_options: BazelOptions) -> Self {
+        self.bazel_options = bazel_options;
+        self
+    }
+
+    pub fn with_dedupe_with_gitignore(mut self, dedupe_with_gitignore: bool) -> Self {
+        self.dedupe_with_gitignore = dedupe_with_gitignore;
+        self
+    }
+
+    pub fn with_strict_directories(mut self, strict_directories: bool) -> Self {
+        self.strict_directories = strict_directories;
+        self
+    }
+
+    pub fn with_checkout_workers(mut self, checkout_workers: Option<usize>) -> Self {
+        self.checkout_workers = checkout_workers;
+        self
+    }
+
+    pub fn with_atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    pub fn with_excluded_projects(mut self, excluded_projects: Vec<String>) -> Self {
+        self.excluded_projects = excluded_projects;
+        self
+    }
+
+    pub fn with_diff_path(mut self, diff_path: Option<PathBuf>) -> Self {
+        self.diff_path = diff_path;
+        self
+    }
+
+    pub fn with_allowed_roots(mut self, allowed_roots: Vec<PathBuf>) -> Self {
+        self.allowed_roots = allowed_roots;
+        self
+    }
+
+    pub fn with_conflict_resolution(mut self, conflict_resolution: ConflictResolution) -> Self {
+        self.conflict_resolution = conflict_resolution;
+        self
+    }
+
+    pub fn with_since(mut self, since: Option<String>) -> Self {
+        self.since = since;
+        self
+    }
+
+    pub fn with_no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+
+    pub fn with_post_sync_hook(mut self, post_sync_hook: Option<String>) -> Self {
+        self.post_sync_hook = post_sync_hook;
+        self
+    }
+
+    pub fn with_excluded_paths(mut self, excluded_paths: Vec<PathBuf>) -> Self {
+        self.excluded_paths = excluded_paths;
+        self
+    }
+
+    pub fn with_clear_excluded_paths(mut self, clear_excluded_paths: bool) -> Self {
+        self.clear_excluded_paths = clear_excluded_paths;
+        self
+    }
+
     pub fn sparse_repo_path(&self) -> &Path {
         self.sparse_repo.as_path()
     }
@@ -129,6 +306,83 @@ impl SyncRequest {
     pub fn mode(&self) -> SyncMode {
         self.mode
     }
+
+    pub fn bazel_options(&self) -> &BazelOptions {
+        &self.bazel_options
+    }
+
+    pub fn dedupe_with_gitignore(&self) -> bool {
+        self.dedupe_with_gitignore
+    }
+
+    pub fn strict_directories(&self) -> bool {
+        self.strict_directories
+    }
+
+    pub fn checkout_workers(&self) -> Option<usize> {
+        self.checkout_workers
+    }
+
+    pub fn atomic(&self) -> bool {
+        self.atomic
+    }
+
+    pub fn excluded_projects(&self) -> &[String] {
+        &self.excluded_projects
+    }
+
+    pub fn diff_path(&self) -> Option<&Path> {
+        self.diff_path.as_deref()
+    }
+
+    pub fn allowed_roots(&self) -> &[PathBuf] {
+        &self.allowed_roots
+    }
+
+    pub fn conflict_resolution(&self) -> ConflictResolution {
+        self.conflict_resolution
+    }
+
+    pub fn since(&self) -> Option<&str> {
+        self.since.as_deref()
+    }
+
+    pub fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+
+    pub fn post_sync_hook(&self) -> Option<&str> {
+        self.post_sync_hook.as_deref()
+    }
+
+    pub fn excluded_paths(&self) -> &[PathBuf] {
+        &self.excluded_paths
+    }
+
+    pub fn clear_excluded_paths(&self) -> bool {
+        self.clear_excluded_paths
+    }
+}
+
+/// Parse the `directory:` target given to `--exclude` into the path of the directory to leave out of syncs, relative to the root of the repo.
+pub fn parse_excluded_path(value: &str) -> Result<PathBuf> {
+    let target = Target::try_from(value).with_context(|| format!("Parsing {:?}", value))?;
+    let directory = match target.as_directory() {
+        Some(directory) => directory.trim_matches('/'),
+        None => bail!("Only directory targets can be excluded (got {:?})", value),
+    };
+    let path = PathBuf::from(directory);
+    if directory.is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!(
+            "{:?} does not name a directory within the repository",
+            value
+        );
+    }
+    Ok(path)
 }
 
 /// State describing the outcome of a sync.
@@ -148,6 +402,67 @@ pub struct SyncResult {
 
 /// Synchronize the sparse repo's contents with the build graph. Returns a SyncResult indicating what happened.
 pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
+    let error = match run_once(request, app.clone()) {
+        Err(error) if request.conflict_resolution() != ConflictResolution::Abort => error,
+        result => return result,
+    };
+    let conflict = match error.downcast_ref::<NarrowingConflict>() {
+        Some(conflict) => conflict,
+        None => return Err(error),
+    };
+
+    // The working tree was restored along with the local changes, so set aside the conflicting changes and try again.
+    match request.conflict_resolution() {
+        ConflictResolution::Abort => unreachable!(),
+        ConflictResolution::Stash => {
+            let stash = git_helper::stash_paths(
+                request.sparse_repo_path(),
+                &conflict.paths,
+                "focus sync: changes outside the new sparse checkout",
+                app.clone(),
+            )?;
+            println!(
+                "Stashed local changes to {} file(s) outside the new sparse checkout as {}",
+                conflict.paths.len(),
+                stash
+            );
+        }
+        ConflictResolution::Discard => {
+            git_helper::discard_changes(request.sparse_repo_path(), &conflict.paths, app.clone())?;
+            println!(
+                "Discarded local changes to {} file(s) outside the new sparse checkout",
+                conflict.paths.len()
+            );
+        }
+    }
+
+    run_once(request, app)
+}
+
+fn run_once(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
+    if request.mode() == SyncMode::RequireProjectCache {
+        app.require_network("Syncing from the project cache")?;
+    }
+    if let Some(diff_path) = request.diff_path() {
+        // A delta written by an earlier run must not be mistaken for this one's if it stops early.
+        match std::fs::remove_file(diff_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| {
+                    format!("Removing the previous sync diff {}", diff_path.display())
+                })
+            }
+            _ => {}
+        }
+        if request.clear_excluded_paths() || !request.excluded_paths().is_empty() {
+            bail!("Writing the sync diff cannot be combined with recording excluded paths");
+        }
+        if matches!(request.mode(), SyncMode::Preemptive { .. }) {
+            bail!(
+                "Writing the sync diff requires a sync of the working tree, not a preemptive one"
+            );
+        }
+    }
+
     let repo =
         Repo::open(request.sparse_repo_path(), app.clone()).context("Failed to open the repo")?;
     let working_tree = repo.working_tree()?;
@@ -211,11 +526,24 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
     )
     .context("Failed to obtain synchronization lock")?;
 
+    // The profile is backed up below, so restore one left behind by an interrupted atomic sync first.
+    if !preemptive {
+        working_tree.recover_interrupted_update(app.clone())?;
+    }
+
     let sparse_profile_path = repo.git_dir().join("info").join("sparse-checkout");
     if !sparse_profile_path.is_file() {
         bail!("This does not appear to be a focused repo -- it is missing a sparse checkout file");
     }
 
+    // The snapshot's changes to tracked files can only be reapplied if those files stay checked out.
+    let preserved_paths = if preemptive {
+        BTreeSet::new()
+    } else {
+        locally_changed_paths(request.sparse_repo_path(), app.clone())
+            .context("Finding files with local changes")?
+    };
+
     // Take a snapshot of the sparse repo state.
     let snapshot =
         git::snapshot::create(request.sparse_repo_path(), app.clone()).with_context(|| {
@@ -228,8 +556,38 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
         git::snapshot::ReapplyGuard::new(request.sparse_repo_path(), snapshot.clone(), app.clone());
 
     let selections = repo.selection_manager()?;
-    let selection = selections.computed_selection()?;
-    let targets = selections.compute_complete_target_set()?;
+    let mut selection = selections.computed_selection()?;
+    let excluded_projects = request.excluded_projects();
+    if let Some(name) = excluded_projects.iter().find(|name| {
+        !selection
+            .projects
+            .iter()
+            .any(|project| &project.name == *name)
+    }) {
+        bail!(
+            "Cannot exclude project '{}' because it is not selected",
+            name
+        );
+    }
+    selection
+        .projects
+        .retain(|project| !excluded_projects.contains(&project.name));
+    let targets = selections.compute_target_set(selection.clone())?;
+
+    // The new excluded paths are only recorded once the sync succeeds, so that a failed sync leaves them as they were.
+    let excluded_paths = if !preemptive
+        && (request.clear_excluded_paths() || !request.excluded_paths().is_empty())
+    {
+        let mut excluded_paths = if request.clear_excluded_paths() {
+            Vec::new()
+        } else {
+            repo.excluded_paths()?
+        };
+        excluded_paths.extend_from_slice(request.excluded_paths());
+        Some(excluded_paths)
+    } else {
+        None
+    };
 
     let mut mechanism = SyncMechanism::IncrementalOutline;
 
@@ -326,11 +684,102 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
         // TODO: Skip outlining if there are no changes to the build graph between the last and new prospective sync point
     }
 
+    let reuse_since = match request.since() {
+        Some(since) => Some(
+            repo.underlying()
+                .revparse_single(since)
+                .and_then(|object| object.peel_to_commit())
+                .with_context(|| format!("Resolving {}", since))?
+                .id(),
+        ),
+        None => None,
+    };
+
+    let post_sync_hook = match request.post_sync_hook() {
+        Some(hook) => Some(hook.to_owned()),
+        None if !preemptive => repo.get_post_sync_hook()?,
+        None => None,
+    };
+
+    let paths_before = if request.diff_path().is_some() {
+        Some(
+            working_tree
+                .checked_out_paths(app.clone())
+                .context("Listing paths before syncing")?,
+        )
+    } else if post_sync_hook.is_some() && !preemptive {
+        // The hook is best-effort, so failing to list the paths it is given skips it rather than the sync.
+        match working_tree.checked_out_paths(app.clone()) {
+            Ok(paths) => Some(paths),
+            Err(e) => {
+                warn!(
+                    ?e,
+                    "Failed to list paths before syncing; the post-sync hook will not run"
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if let (Some(diff_path), Some(paths_before)) = (request.diff_path(), &paths_before) {
+        let one_shot = match request.mode() {
+            SyncMode::OneShot => true,
+            SyncMode::Preemptive { .. } => false,
+            SyncMode::Incremental | SyncMode::RequireProjectCache => {
+                repo.get_bazel_oneshot_resolution()?
+            }
+        };
+        let cache = if one_shot {
+            None
+        } else {
+            Some(RocksDBCache::new(repo.underlying()))
+        };
+        let (patterns, _) = perform("Computing the new sparse profile", || {
+            repo.sparse_patterns(
+                commit.id(),
+                &targets,
+                app.clone(),
+                cache.as_ref(),
+                snapshot.clone(),
+                &sync_options(
+                    request,
+                    preemptive,
+                    &preserved_paths,
+                    reuse_since,
+                    excluded_paths.as_deref(),
+                ),
+            )
+        })?;
+        let paths_after = working_tree
+            .paths_included_by(&patterns, app.clone())
+            .context("Listing the paths the sync would check out")?;
+        write_path_delta(diff_path, paths_before, &paths_after)?;
+        return Ok(SyncResult {
+            checked_out: false,
+            commit_id: Some(commit.id()),
+            status: SyncStatus::DiffOnly,
+            mechanism: if one_shot {
+                SyncMechanism::OneShotOutline
+            } else {
+                SyncMechanism::IncrementalOutline
+            },
+        });
+    }
+
     // If only projects are selected (no ad-hoc targets) we try to use the project cache to sync. Otherwise we fall back to regular syncing.
     let (pattern_count, checked_out) = perform("Computing the new sparse profile", || {
         // Try to use the project cache
         let project_cache_result = repo
-            .sync_using_project_cache(commit.id(), &selection, snapshot.clone())
+            .sync_using_project_cache(
+                commit.id(),
+                &selection,
+                snapshot.clone(),
+                request.atomic(),
+                &preserved_paths,
+                excluded_paths.as_deref(),
+            )
             .context("Syncing from project cache failed");
 
         match project_cache_result {
@@ -339,6 +788,8 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
                 mechanism = SyncMechanism::ProjectCache;
                 Ok(inner)
             }
+            // Any other mechanism would remove the same files
+            Err(e) if e.downcast_ref::<NarrowingConflict>().is_some() => Err(e),
             // No answer from project cache when one was required
             _ if request.mode() == SyncMode::RequireProjectCache => Err(anyhow::anyhow!(
                 SYNC_FROM_PROJECT_CACHE_REQUIRED_ERROR_MESSAGE,
@@ -370,10 +821,16 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
                 repo.sync(
                     commit.id(),
                     &targets,
-                    preemptive,
                     app.clone(),
                     cache.as_ref(),
                     snapshot.clone(),
+                    &sync_options(
+                        request,
+                        preemptive,
+                        &preserved_paths,
+                        reuse_since,
+                        excluded_paths.as_deref(),
+                    ),
                 )
                 .context("Sync failed")
             }
@@ -398,8 +855,39 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
             working_tree.write_sync_point_ref()
         })?;
 
+        if let Some(excluded_paths) = &excluded_paths {
+            repo.set_excluded_paths(excluded_paths)
+                .context("Recording excluded paths")?;
+        }
+
         // The profile was successfully applied, so do not restore the backup.
         backed_up_sparse_profile.unwrap().set_restore(false);
+
+        if let Err(e) = repo.record_activity(Activity::Sync) {
+            warn!(?e, "Failed to record sync time");
+        }
+        if let Err(e) = repo.set_temporarily_excluded_projects(excluded_projects) {
+            warn!(?e, "Failed to record temporarily excluded projects");
+        }
+
+        // The sync is committed at this point, so nothing below may fail it.
+        if let (Some(hook), Some(paths_before)) = (post_sync_hook.as_deref(), paths_before) {
+            let result = working_tree
+                .checked_out_paths(app.clone())
+                .context("Listing paths after syncing")
+                .and_then(|paths_after| {
+                    run_post_sync_hook(
+                        request.sparse_repo_path(),
+                        hook,
+                        &paths_before,
+                        &paths_after,
+                        app.clone(),
+                    )
+                });
+            if let Err(e) = result {
+                warn!(?e, %hook, "Post-sync hook failed");
+            }
+        }
     }
 
     Ok(SyncResult {
@@ -410,6 +898,102 @@ pub fn run(request: &SyncRequest, app: Arc<App>) -> Result<SyncResult> {
     })
 }
 
+/// The options for resolving and applying the sparse profile of `request`.
+fn sync_options(
+    request: &SyncRequest,
+    preemptive: bool,
+    preserved_paths: &BTreeSet<PathBuf>,
+    reuse_since: Option<git2::Oid>,
+    excluded_paths: Option<&[PathBuf]>,
+) -> SyncOptions {
+    SyncOptions::default()
+        .with_skip_pattern_application(preemptive)
+        .with_bazel_options(request.bazel_options().clone())
+        .with_dedupe_with_gitignore(request.dedupe_with_gitignore())
+        .with_strict_directories(request.strict_directories())
+        .with_accept_cached_resolution(!request.no_cache())
+        .with_checkout_workers(request.checkout_workers())
+        .with_atomic(request.atomic())
+        .with_preserved_paths(preserved_paths.clone())
+        .with_reuse_since(reuse_since)
+        .with_allowed_roots(request.allowed_roots().to_vec())
+        .with_excluded_paths(excluded_paths.map(<[PathBuf]>::to_vec))
+}
+
+/// The tracked paths with local changes, staged or not.
+fn locally_changed_paths(sparse_repo: &Path, app: Arc<App>) -> Result<BTreeSet<PathBuf>> {
+    let state = git::working_tree::status(sparse_repo, app)?;
+    Ok(state
+        .entries()
+        .iter()
+        .filter(|entry| matches!(entry.kind, Kind::Ordinary | Kind::RenameOrCopy))
+        .map(|entry| entry.path.clone())
+        .collect())
+}
+
+/// The paths in `after` but not `before` prefixed with `+`, and those in `before` but not `after` prefixed with `-`, one per line in path order.
+fn path_delta(before: &BTreeSet<PathBuf>, after: &BTreeSet<PathBuf>) -> String {
+    let mut entries: Vec<(&PathBuf, char)> = after
+        .difference(before)
+        .map(|p| (p, '+'))
+        .chain(before.difference(after).map(|p| (p, '-')))
+        .collect();
+    entries.sort();
+
+    let mut content = String::new();
+    for (p, marker) in entries {
+        content.push_str(&format!("{}{}\n", marker, p.display()));
+    }
+    content
+}
+
+fn write_path_delta(
+    path: &Path,
+    before: &BTreeSet<PathBuf>,
+    after: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    std::fs::write(path, path_delta(before, after))
+        .with_context(|| format!("Writing the sync diff to {}", path.display()))
+}
+
+/// Run the shell command `hook` in `sparse_repo` after a sync that changed the working tree from the paths `before` to those `after`. The command finds the repo and the numbers of paths added and removed in the `FOCUS_SPARSE_REPO`, `FOCUS_SYNC_PATHS_ADDED`, and `FOCUS_SYNC_PATHS_REMOVED` environment variables. The changed paths themselves are written to its standard input in the format of `focus sync --diff-only`, and to the file named by `FOCUS_SYNC_CHANGED_PATHS_FILE`.
+pub(crate) fn run_post_sync_hook(
+    sparse_repo: &Path,
+    hook: &str,
+    before: &BTreeSet<PathBuf>,
+    after: &BTreeSet<PathBuf>,
+    app: Arc<App>,
+) -> Result<()> {
+    let changed_paths_file = {
+        let (mut file, path, _) =
+            app.sandbox()
+                .create_file(Some("post-sync-changes"), None, None)?;
+        file.write_all(path_delta(before, after).as_bytes())
+            .context("Writing the changed paths for the post-sync hook")?;
+        path
+    };
+    let added = after.difference(before).count();
+    let removed = before.difference(after).count();
+
+    let (mut cmd, scmd) = SandboxCommand::new_with_handles(
+        POST_SYNC_HOOK_SHELL,
+        Some(Stdio::from(File::open(&changed_paths_file)?)),
+        None,
+        None,
+        app,
+    )?;
+    cmd.arg(POST_SYNC_HOOK_SHELL_FLAG)
+        .arg(hook)
+        .current_dir(sparse_repo)
+        .env("FOCUS_SPARSE_REPO", sparse_repo)
+        .env("FOCUS_SYNC_PATHS_ADDED", added.to_string())
+        .env("FOCUS_SYNC_PATHS_REMOVED", removed.to_string())
+        .env("FOCUS_SYNC_CHANGED_PATHS_FILE", &changed_paths_file);
+    scmd.ensure_success_or_log(&mut cmd, SandboxCommandOutput::Stderr)
+        .with_context(|| format!("Running post-sync hook '{}'", hook))?;
+    Ok(())
+}
+
 /// Wait for the machine to be idle for a given time period, waiting up to some maximum, and polling at a given interval.
 fn wait_for_machine_to_be_idle(
     idle_duration: Duration,
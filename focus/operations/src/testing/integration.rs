@@ -113,8 +113,19 @@ impl RepoPairFixture {
             projects_and_targets: self.projects_and_targets.clone(),
             copy_branches: true,
             days_of_history: 90,
+            shallow_since: None,
+            depth: None,
             do_post_clone_fetch: false,
             sync_mode: self.sync_mode.get(),
+            use_index: true,
+            offline: false,
+            resume: false,
+            resolution_cache_from: None,
+            excluded_paths: Vec::new(),
+            mirror_from: None,
+            reference: None,
+            dissociate: false,
+            additional_branches: Vec::new(),
         };
 
         crate::clone::run(
@@ -1,7 +1,7 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use focus_internals::tracker::Tracker;
+use focus_internals::{model::activity::Activity, tracker::Tracker};
 
 use anyhow::Result;
 
@@ -53,3 +53,68 @@ fn repo_register_after_move() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn repo_rebuild_from_disk() -> Result<()> {
+    init_logging();
+
+    let tracker = Tracker::for_testing()?;
+    tracker.ensure_directories_exist()?;
+
+    let fixture = RepoPairFixture::new()?;
+    fixture.perform_clone()?;
+
+    // Nothing is registered, as if the registry had been lost.
+    assert!(tracker.scan()?.repos().is_empty());
+
+    let root = fixture.sparse_repo_path.parent().unwrap();
+    crate::repo::rebuild_from_disk(root, 2, &tracker, fixture.app.clone())?;
+
+    let id = fixture.sparse_repo()?.working_tree()?.read_uuid()?.unwrap();
+    let snapshot = tracker.scan()?;
+    let tracked = snapshot
+        .find_repo_by_id(id.as_bytes())
+        .expect("The sparse repo should be registered");
+    assert_eq!(
+        tracked.location(),
+        fixture.sparse_repo_path.canonicalize()?.as_path()
+    );
+    // The dense repo is not a focused repo.
+    assert_eq!(snapshot.repos().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn repo_list_summarizes_registered_repos() -> Result<()> {
+    init_logging();
+
+    let tracker = Tracker::for_testing()?;
+    tracker.ensure_directories_exist()?;
+
+    let fixture = RepoPairFixture::new()?;
+    fixture.perform_clone()?;
+    tracker.ensure_registered(&fixture.sparse_repo_path, fixture.app.clone())?;
+    fixture.sparse_repo()?.record_activity(Activity::Sync)?;
+
+    let summaries = crate::repo::list(&tracker, fixture.app.clone(), |_path, _app| {
+        Ok("#2".to_string())
+    })?;
+    assert_eq!(summaries.len(), 1);
+    let summary = &summaries[0];
+    assert_eq!(
+        summary.path,
+        fixture.sparse_repo_path.canonicalize()?.as_path()
+    );
+    assert!(summary.size > 0);
+    assert!(summary.last_sync.is_some());
+    assert_eq!(summary.migration_version.as_deref(), Some("#2"));
+
+    // Details that cannot be determined are left out rather than failing the listing.
+    let summaries = crate::repo::list(&tracker, fixture.app.clone(), |_path, _app| {
+        anyhow::bail!("no manifest")
+    })?;
+    assert_eq!(summaries[0].migration_version, None);
+
+    Ok(())
+}
@@ -1,12 +1,18 @@
 // Copyright 2022 Twitter, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use focus_internals::{model::repo::Repo, target::Target};
+use focus_internals::{
+    model::{
+        repo::{NarrowingConflict, Repo},
+        selection::{AddOptions, Interpretation},
+    },
+    target::Target,
+};
 use focus_testing::ScratchGitRepo;
 use insta::assert_snapshot;
 use std::{
-    collections::HashSet,
-    path::Path,
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
     sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
@@ -15,10 +21,10 @@ use anyhow::Result;
 use maplit::hashset;
 
 use focus_testing::init_logging;
-use focus_util::app;
+use focus_util::{app, git_helper};
 
 use crate::{
-    sync::{SyncMechanism, SyncMode, SyncRequest, SyncStatus},
+    sync::{ConflictResolution, SyncMechanism, SyncMode, SyncRequest, SyncResult, SyncStatus},
     testing::integration::{RepoDisposition, RepoPairFixture},
 };
 
@@ -295,6 +301,7 @@ fn sync_layer_manipulation_internal(sync_mode: SyncMode) -> Result<()> {
         &path,
         true,
         vec![project_a_label],
+        Interpretation::Inferred,
         false,
         fixture.app.clone(),
     )?;
@@ -313,6 +320,7 @@ fn sync_layer_manipulation_internal(sync_mode: SyncMode) -> Result<()> {
         &path,
         true,
         vec![project_b_label],
+        Interpretation::Inferred,
         false,
         fixture.app.clone(),
     )?;
@@ -364,6 +372,7 @@ fn sync_adhoc_manipulation_internal(sync_mode: SyncMode) -> Result<()> {
         &fixture.sparse_repo_path,
         true,
         targets,
+        Interpretation::Inferred,
         false,
         fixture.app.clone(),
     )?;
@@ -396,6 +405,335 @@ fn failed_selection_mutations_are_reverted() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn sync_with_excluded_project_leaves_selection_unchanged() -> Result<()> {
+    init_logging();
+
+    let project_a_label = String::from("team_banzai/project_a");
+    let project_b_label = String::from("team_zissou/project_b");
+
+    let mut fixture = RepoPairFixture::new()?;
+    fixture.projects_and_targets.push(project_a_label.clone());
+    fixture.projects_and_targets.push(project_b_label.clone());
+    fixture.perform_clone()?;
+
+    let path = fixture.sparse_repo_path.clone();
+    let project_a_dir = path.join("project_a");
+    let project_b_dir = path.join("project_b");
+    assert!(project_a_dir.is_dir());
+    assert!(project_b_dir.is_dir());
+
+    crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental)
+            .with_excluded_projects(vec![project_a_label.clone()]),
+        fixture.app.clone(),
+    )?;
+    assert!(!project_a_dir.is_dir());
+    assert!(project_b_dir.is_dir());
+
+    let repo = fixture.sparse_repo()?;
+    let selected_names: HashSet<String> = repo
+        .selection_manager()?
+        .selection()?
+        .projects
+        .iter()
+        .map(|project| project.name.clone())
+        .collect();
+    assert_eq!(
+        selected_names,
+        hashset! { project_a_label.clone(), project_b_label }
+    );
+    assert_eq!(
+        repo.temporarily_excluded_projects()?,
+        vec![project_a_label.clone()]
+    );
+
+    // Projects that are not selected cannot be excluded.
+    assert!(crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental)
+            .with_excluded_projects(vec![String::from("team_zissou/project_z")]),
+        fixture.app.clone(),
+    )
+    .is_err());
+
+    // A sync without exclusions brings the project back.
+    crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental),
+        fixture.app.clone(),
+    )?;
+    assert!(project_a_dir.is_dir());
+    assert!(repo.temporarily_excluded_projects()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn sync_diff_only_lists_paths_without_changing_the_working_tree() -> Result<()> {
+    init_logging();
+
+    let project_a_label = String::from("team_banzai/project_a");
+    let project_b_label = String::from("team_zissou/project_b");
+
+    let mut fixture = RepoPairFixture::new()?;
+    fixture.projects_and_targets.push(project_a_label.clone());
+    fixture.projects_and_targets.push(project_b_label);
+    fixture.perform_clone()?;
+
+    let path = fixture.sparse_repo_path.clone();
+    let diff_dir = tempfile::tempdir()?;
+    let diff_path = diff_dir.path().join("sync.diff");
+    let project_a_paths = [
+        "project_a/src/main/java/com/example/cmdline/BUILD",
+        "project_a/src/main/java/com/example/cmdline/Runner.java",
+    ];
+
+    let result = crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental)
+            .with_excluded_projects(vec![project_a_label])
+            .with_diff_path(Some(diff_path.clone())),
+        fixture.app.clone(),
+    )?;
+    assert_eq!(result.status, SyncStatus::DiffOnly);
+    assert!(!result.checked_out);
+    let diff = std::fs::read_to_string(&diff_path)?;
+    let lines: Vec<&str> = diff.lines().collect();
+    for project_a_path in project_a_paths {
+        assert!(lines.contains(&format!("-{}", project_a_path).as_str()));
+        assert!(path.join(project_a_path).is_file());
+    }
+    assert!(lines.iter().all(|line| line.starts_with('-')));
+    assert!(lines.windows(2).all(|pair| pair[0][1..] < pair[1][1..]));
+
+    // The delta from the previous run is replaced, not appended to.
+    std::fs::write(&diff_path, "+stale\n")?;
+    crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental).with_diff_path(Some(diff_path.clone())),
+        fixture.app.clone(),
+    )?;
+    assert_eq!(std::fs::read_to_string(&diff_path)?, "");
+
+    Ok(())
+}
+
+#[test]
+fn excluded_paths_must_be_directories_within_the_repo() -> Result<()> {
+    assert_eq!(
+        crate::sync::parse_excluded_path("directory:project_a/generated/")?,
+        PathBuf::from("project_a/generated")
+    );
+    for value in [
+        "bazel://project_a/...",
+        "project_a",
+        "directory:",
+        "directory:/",
+        "directory:../elsewhere",
+    ] {
+        assert!(
+            crate::sync::parse_excluded_path(value).is_err(),
+            "{} was accepted",
+            value
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn post_sync_hook_runs_with_sync_environment() -> Result<()> {
+    init_logging();
+
+    let app = std::sync::Arc::new(app::App::new_for_testing()?);
+    let dir = tempfile::tempdir()?;
+    let sparse_repo = dir.path().join("sparse");
+    std::fs::create_dir(&sparse_repo)?;
+
+    let before: BTreeSet<PathBuf> = ["a", "b"].iter().map(PathBuf::from).collect();
+    let after: BTreeSet<PathBuf> = ["b", "c", "d/e", "f"].iter().map(PathBuf::from).collect();
+    crate::sync::run_post_sync_hook(
+        &sparse_repo,
+        "printf '%s %s %s' \"$FOCUS_SPARSE_REPO\" \"$FOCUS_SYNC_PATHS_ADDED\" \"$FOCUS_SYNC_PATHS_REMOVED\" > hook.out && cat > hook.stdin && cp \"$FOCUS_SYNC_CHANGED_PATHS_FILE\" hook.file",
+        &before,
+        &after,
+        app.clone(),
+    )?;
+    assert_eq!(
+        std::fs::read_to_string(sparse_repo.join("hook.out"))?,
+        format!("{} 3 1", sparse_repo.display())
+    );
+    let expected_changes = "-a\n+c\n+d/e\n+f\n";
+    assert_eq!(
+        std::fs::read_to_string(sparse_repo.join("hook.stdin"))?,
+        expected_changes
+    );
+    assert_eq!(
+        std::fs::read_to_string(sparse_repo.join("hook.file"))?,
+        expected_changes
+    );
+
+    let none = BTreeSet::new();
+    assert!(crate::sync::run_post_sync_hook(&sparse_repo, "exit 1", &none, &none, app).is_err());
+
+    Ok(())
+}
+
+/// Clone with two projects, modify a file in one of them, then sync without that project using `conflict_resolution`. Returns the fixture, the modified file's path relative to the repo, and the result of the sync.
+fn sync_narrowing_modified_file(
+    conflict_resolution: ConflictResolution,
+) -> Result<(RepoPairFixture, PathBuf, Result<SyncResult>)> {
+    let project_a_label = String::from("team_banzai/project_a");
+    let project_b_label = String::from("team_zissou/project_b");
+
+    let mut fixture = RepoPairFixture::new()?;
+    fixture.projects_and_targets.push(project_a_label.clone());
+    fixture.projects_and_targets.push(project_b_label);
+    fixture.perform_clone()?;
+
+    let modified_path = PathBuf::from("project_a/src/main/java/com/example/cmdline/Runner.java");
+    std::fs::write(
+        fixture.sparse_repo_path.join(&modified_path),
+        "// Modified locally\n",
+    )?;
+
+    let result = crate::sync::run(
+        &SyncRequest::new(&fixture.sparse_repo_path, SyncMode::Incremental)
+            .with_excluded_projects(vec![project_a_label])
+            .with_conflict_resolution(conflict_resolution),
+        fixture.app.clone(),
+    );
+    Ok((fixture, modified_path, result))
+}
+
+#[test]
+fn sync_aborts_when_narrowing_modified_file() -> Result<()> {
+    init_logging();
+
+    let (fixture, modified_path, result) = sync_narrowing_modified_file(ConflictResolution::Abort)?;
+    let error = result.err().unwrap();
+    let conflict = error.downcast_ref::<NarrowingConflict>().unwrap();
+    assert_eq!(conflict.paths, vec![modified_path.clone()]);
+
+    // The working tree and the local change are left as they were.
+    assert_eq!(
+        std::fs::read_to_string(fixture.sparse_repo_path.join(&modified_path))?,
+        "// Modified locally\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn failed_sync_leaves_excluded_paths_unchanged() -> Result<()> {
+    init_logging();
+
+    let mut fixture = RepoPairFixture::new()?;
+    fixture
+        .projects_and_targets
+        .push(String::from("team_banzai/project_a"));
+    fixture
+        .projects_and_targets
+        .push(String::from("team_zissou/project_b"));
+    fixture.perform_clone()?;
+    let path = fixture.sparse_repo_path.clone();
+    let repo = fixture.sparse_repo()?;
+
+    crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental)
+            .with_excluded_paths(vec![PathBuf::from("project_b/src")]),
+        fixture.app.clone(),
+    )?;
+    assert_eq!(repo.excluded_paths()?, vec![PathBuf::from("project_b/src")]);
+
+    // Excluding a directory with local changes fails, and the exclusion is not recorded.
+    let modified_path = path.join("project_a/src/main/java/com/example/cmdline/Runner.java");
+    std::fs::write(&modified_path, "// Modified locally\n")?;
+    let error = crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental)
+            .with_excluded_paths(vec![PathBuf::from("project_a")]),
+        fixture.app.clone(),
+    )
+    .err()
+    .unwrap();
+    assert!(error.downcast_ref::<NarrowingConflict>().is_some());
+    assert_eq!(repo.excluded_paths()?, vec![PathBuf::from("project_b/src")]);
+    assert!(modified_path.is_file());
+
+    Ok(())
+}
+
+#[test]
+fn sync_stashes_changes_when_narrowing_modified_file() -> Result<()> {
+    init_logging();
+
+    let (fixture, modified_path, result) = sync_narrowing_modified_file(ConflictResolution::Stash)?;
+    result?;
+    assert!(!fixture.sparse_repo_path.join(&modified_path).exists());
+
+    let stashed_paths = git_helper::run_consuming_stdout(
+        &fixture.sparse_repo_path,
+        ["stash", "show", "--name-only", "refs/stash"],
+        fixture.app.clone(),
+    )?;
+    assert_eq!(stashed_paths, modified_path.display().to_string());
+
+    Ok(())
+}
+
+#[test]
+fn sync_discards_changes_when_narrowing_modified_file() -> Result<()> {
+    init_logging();
+
+    let (fixture, modified_path, result) =
+        sync_narrowing_modified_file(ConflictResolution::Discard)?;
+    result?;
+    assert!(!fixture.sparse_repo_path.join(&modified_path).exists());
+
+    // Nothing was stashed, and syncing the project again restores the committed file.
+    let stashes = git_helper::run_consuming_stdout(
+        &fixture.sparse_repo_path,
+        ["stash", "list"],
+        fixture.app.clone(),
+    )?;
+    assert!(stashes.is_empty());
+    crate::sync::run(
+        &SyncRequest::new(&fixture.sparse_repo_path, SyncMode::Incremental),
+        fixture.app.clone(),
+    )?;
+    assert_ne!(
+        std::fs::read_to_string(fixture.sparse_repo_path.join(&modified_path))?,
+        "// Modified locally\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn sync_fails_on_paths_outside_allowed_roots() -> Result<()> {
+    init_logging();
+
+    let mut fixture = RepoPairFixture::new()?;
+    fixture
+        .projects_and_targets
+        .push(String::from("team_banzai/project_a"));
+    fixture
+        .projects_and_targets
+        .push(String::from("team_zissou/project_b"));
+    fixture.perform_clone()?;
+    let path = fixture.sparse_repo_path.clone();
+
+    let error = crate::sync::run(
+        &SyncRequest::new(&path, SyncMode::Incremental)
+            .with_allowed_roots(vec![PathBuf::from("project_a")]),
+        fixture.app.clone(),
+    )
+    .err()
+    .expect("Sync should fail when a path is outside the allowed roots");
+    let message = format!("{:#}", error);
+    assert!(message.contains("outside the allowed roots"), "{}", message);
+    assert!(message.contains("project_b"), "{}", message);
+    assert!(!message.contains("project_a/"), "{}", message);
+
+    Ok(())
+}
+
 #[test]
 fn clone_contains_top_level_with_incremental_sync() -> Result<()> {
     clone_contains_top_level_internal(SyncMode::Incremental)
@@ -688,6 +1026,43 @@ fn regression_adding_deep_directory_target_materializes_correctly_internal(
     Ok(())
 }
 
+#[test]
+fn sync_dedupe_with_gitignore_skips_ignored_paths() -> Result<()> {
+    init_logging();
+
+    let fixture = RepoPairFixture::new()?;
+    // Commit the artifact before the rule that ignores it, as if it had been committed by mistake.
+    fixture.dense_repo.write_and_commit_file(
+        Path::new("w_dir/x_dir/artifact.o"),
+        b"not source",
+        "Add artifact",
+    )?;
+    fixture.dense_repo.write_and_commit_file(
+        Path::new(".gitignore"),
+        b"*.o\n",
+        "Ignore artifacts",
+    )?;
+    fixture.perform_clone()?;
+
+    let x_dir = fixture.sparse_repo_path.join("w_dir").join("x_dir");
+    crate::selection::add_with_options(
+        &fixture.sparse_repo_path,
+        true,
+        vec![String::from("directory:w_dir/x_dir")],
+        Interpretation::Inferred,
+        AddOptions {
+            dedupe_with_gitignore: true,
+            ..Default::default()
+        },
+        fixture.app.clone(),
+    )?;
+
+    assert!(x_dir.join("x.txt").is_file());
+    assert!(!x_dir.join("artifact.o").exists());
+
+    Ok(())
+}
+
 struct PreemptiveSyncFixture {
     pub underlying: RepoPairFixture,
     pub repo: Repo,
@@ -747,6 +747,45 @@ impl Event {
     }
 }
 
+/// Restricts which events are converted. An event must satisfy every criterion that is set; by
+/// default, every event matches.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    thread: Option<String>,
+    name_substrings: Vec<String>,
+}
+
+impl EventFilter {
+    /// Keep only the events git recorded on `thread`, such as `main` or `th02:preload_thread`.
+    /// Spans that git opens and closes are recorded on a single thread, so they stay paired.
+    pub fn with_thread(mut self, thread: Option<String>) -> Self {
+        self.thread = thread;
+        self
+    }
+
+    /// Keep only the events whose names, as shown in the trace viewer, contain each of
+    /// `name_substrings`. Names are matched after conversion since git names the events opening
+    /// and closing a span differently.
+    pub fn with_name_substrings(mut self, name_substrings: Vec<String>) -> Self {
+        self.name_substrings = name_substrings;
+        self
+    }
+
+    fn matches_git_event(&self, gev: &GitEvent) -> bool {
+        match &self.thread {
+            Some(thread) => &gev.common().thread == thread,
+            None => true,
+        }
+    }
+
+    fn matches_event(&self, event: &Event) -> bool {
+        let name = &event.common().name;
+        self.name_substrings
+            .iter()
+            .all(|substring| name.contains(substring.as_str()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Trace {
@@ -774,6 +813,80 @@ impl Trace {
         Ok(())
     }
 
+    /// Convert the `GIT_TRACE2_EVENT` output at `input`, a file or directory as for
+    /// [`Trace::git_trace_from`], to a trace written as JSON to `out` without holding either in
+    /// memory. The input is read twice: first to find the earliest timestamp, which written
+    /// timestamps are relative to, then to convert it. Unlike in a built trace, events are written
+    /// in the order they complete rather than sorted. Only events that match `filter` are written.
+    pub fn stream_git_trace_to<P: AsRef<Path>, Q: AsRef<Path>>(
+        input: P,
+        out: Q,
+        filter: &EventFilter,
+    ) -> Result<()> {
+        let input = input.as_ref();
+        let p = out.as_ref();
+
+        let mut min_ts: Option<i64> = None;
+        Self::convert_git_events(input, filter, |event| {
+            min_ts = Some(min_ts.map_or(event.ts(), |ts| ts.min(event.ts())));
+            Ok(())
+        })?;
+        let min_ts = min_ts.unwrap_or_default();
+
+        let mut temp = NamedTempFile::new_in(p.parent().ok_or_else(|| {
+            anyhow::anyhow!("could not determine parent directoy of output path {:?}", p)
+        })?)?;
+        {
+            let mut writer = BufWriter::new(temp.as_file_mut());
+            writer.write_all(b"{\"traceEvents\":[")?;
+            let mut first = true;
+            Self::convert_git_events(input, filter, |mut event| {
+                event.common_mut().ts -= min_ts;
+                if !first {
+                    writer.write_all(b",")?;
+                }
+                first = false;
+                serde_json::to_writer(&mut writer, &event)?;
+                Ok(())
+            })?;
+            // The remaining fields as `Trace` serializes them by default.
+            writer.write_all(
+                b"],\"displayTimeUnit\":null,\"systemTraceEvents\":null,\"otherData\":null}",
+            )?;
+            writer.flush()?;
+        }
+        temp.as_file_mut().sync_all()?;
+        temp.persist(p)?;
+
+        Ok(())
+    }
+
+    /// Call `f` with each trace event converted from the git events at `input` as they are read,
+    /// leaving out those that do not match `filter`.
+    fn convert_git_events(
+        input: &Path,
+        filter: &EventFilter,
+        mut f: impl FnMut(Event) -> Result<()>,
+    ) -> Result<()> {
+        let mut converter = trace::Converter::default();
+        let mut converted: Vec<Event> = Vec::new();
+        let mut emit = |event: Event| {
+            if filter.matches_event(&event) {
+                f(event)
+            } else {
+                Ok(())
+            }
+        };
+        git_trace2::Events::for_each_in_path(input, |gev| {
+            if filter.matches_git_event(&gev) {
+                converter.add_event(gev, &mut converted);
+            }
+            converted.drain(..).try_for_each(&mut emit)
+        })?;
+        converter.finish(&mut converted);
+        converted.into_iter().try_for_each(emit)
+    }
+
     fn git_trace_from_file<P: AsRef<Path>>(path: P) -> Result<Trace> {
         let git_events = git_trace2::Events::from_file(path.as_ref())?;
         trace::Builder::from(git_events.into_inner()).build()
@@ -784,6 +897,13 @@ impl Trace {
         trace::Builder::from_iter(events.into_iter().flat_map(|ev| ev.into_inner())).build()
     }
 
+    /// Convert the `GIT_TRACE2_EVENT` output read from `reader` to a trace. Events may be newline-delimited or a JSON array (see [`git_trace2::Events::from_slice`]).
+    pub fn git_trace_from_reader<R: Read>(reader: R) -> Result<Trace> {
+        let git_events = git_trace2::Events::from_reader(reader)?;
+        trace::Builder::from(git_events.into_inner()).build()
+    }
+
+    /// Convert the `GIT_TRACE2_EVENT` output at `path` to a trace. If `path` is a directory, as when `GIT_TRACE2_EVENT` names one, the events in every file within it are combined.
     pub fn git_trace_from<P: AsRef<Path>>(path: P) -> Result<Trace> {
         let p = path.as_ref();
         if p.is_file() {
@@ -46,3 +46,153 @@ fn test_rebase_trace() -> Result<()> {
 fn test_branch_update_trace() -> Result<()> {
     assert_trace_snapshot("branch-update.json")
 }
+
+#[test]
+fn test_array_input_matches_ndjson_input() -> Result<()> {
+    let ndjson = std::fs::read_to_string(fixture_path("status.json")?)?;
+    let array = format!("[{}]", ndjson.lines().collect::<Vec<_>>().join(","));
+
+    let from_ndjson = Trace::git_trace_from_file(&fixture_path("status.json")?)?;
+    let from_array = Trace::git_trace_from_reader(array.as_bytes())?;
+    assert_eq!(
+        serde_json::to_value(&from_ndjson)?,
+        serde_json::to_value(&from_array)?
+    );
+    Ok(())
+}
+
+fn sorted_event_json(trace: &Trace) -> Result<Vec<String>> {
+    let mut events = trace
+        .trace_events
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<serde_json::Result<Vec<String>>>()?;
+    events.sort();
+    Ok(events)
+}
+
+#[test]
+fn test_streamed_trace_matches_built_trace() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    for input in ["clone-perf.json", "status.json", "rebase.json"] {
+        let out = dir.path().join(input);
+        Trace::stream_git_trace_to(fixture_path(input)?, &out, &EventFilter::default())?;
+        let streamed: Trace = serde_json::from_reader(std::fs::File::open(&out)?)?;
+        let built = Trace::git_trace_from(fixture_path(input)?)?;
+        assert_eq!(sorted_event_json(&streamed)?, sorted_event_json(&built)?);
+    }
+    Ok(())
+}
+
+/// A trace of a process with a worker thread, as git writes them to GIT_TRACE2_EVENT.
+const THREADED_TRACE: &str = r#"
+{"event":"version","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000000Z","file":"common-main.c","line":49,"evt":"3","exe":"2.35.1"}
+{"event":"start","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000100Z","file":"common-main.c","line":50,"t_abs":0.0001,"argv":["git","status"]}
+{"event":"region_enter","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000200Z","file":"read-cache.c","line":2,"nesting":1,"category":"index","label":"do_read_index"}
+{"event":"thread_start","sid":"s1","thread":"th02:preload_thread","time":"2022-03-03T19:33:40.000300Z","file":"preload-index.c","line":3,"thread_name":"th02:preload_thread"}
+{"event":"region_enter","sid":"s1","thread":"th02:preload_thread","time":"2022-03-03T19:33:40.000400Z","file":"preload-index.c","line":4,"nesting":1,"category":"index","label":"preload"}
+{"event":"region_leave","sid":"s1","thread":"th02:preload_thread","time":"2022-03-03T19:33:40.000500Z","file":"preload-index.c","line":5,"t_rel":0.0001,"nesting":1,"category":"index","label":"preload"}
+{"event":"thread_exit","sid":"s1","thread":"th02:preload_thread","time":"2022-03-03T19:33:40.000600Z","file":"preload-index.c","line":6,"thread_name":"th02:preload_thread","t_rel":0.0003}
+{"event":"region_leave","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000700Z","file":"read-cache.c","line":7,"t_rel":0.0005,"nesting":1,"category":"index","label":"do_read_index"}
+{"event":"exit","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000800Z","file":"git.c","line":8,"t_abs":0.0008,"code":0}
+{"event":"atexit","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000900Z","file":"trace2.c","line":9,"t_abs":0.0009,"code":0}
+"#;
+
+fn event_names(trace: &Trace) -> Vec<String> {
+    let mut names: Vec<String> = trace
+        .trace_events
+        .iter()
+        .map(|event| event.common().name.clone())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_streamed_trace_is_filtered() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("threaded.json");
+    std::fs::write(&input, THREADED_TRACE)?;
+    let out = dir.path().join("trace.json");
+    let stream = |filter: EventFilter| -> Result<Trace> {
+        Trace::stream_git_trace_to(&input, &out, &filter)?;
+        Ok(serde_json::from_reader(std::fs::File::open(&out)?)?)
+    };
+
+    assert_eq!(stream(EventFilter::default())?.trace_events.len(), 5);
+
+    let preload_thread = Some(String::from("th02:preload_thread"));
+    let on_thread = stream(EventFilter::default().with_thread(preload_thread.clone()))?;
+    assert_eq!(
+        event_names(&on_thread),
+        vec!["preload", "preload-index.c:3:th02:preload_thread"]
+    );
+
+    let named = stream(EventFilter::default().with_name_substrings(vec![String::from("read")]))?;
+    assert_eq!(
+        event_names(&named),
+        vec!["do_read_index", "preload-index.c:3:th02:preload_thread"]
+    );
+
+    // Filters combine: both must match.
+    let both = stream(
+        EventFilter::default()
+            .with_thread(preload_thread)
+            .with_name_substrings(vec![String::from("read"), String::from("thread")]),
+    )?;
+    assert_eq!(
+        event_names(&both),
+        vec!["preload-index.c:3:th02:preload_thread"]
+    );
+    let neither = stream(
+        EventFilter::default()
+            .with_thread(Some(String::from("main")))
+            .with_name_substrings(vec![String::from("preload")]),
+    )?;
+    assert!(neither.trace_events.is_empty());
+
+    Ok(())
+}
+
+/// A child process that runs while a worker thread starts and exits, so their spans overlap.
+const CHILD_AND_THREAD_TRACE: &str = r#"
+{"event":"version","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000000Z","file":"common-main.c","line":49,"evt":"3","exe":"2.35.1"}
+{"event":"start","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000000Z","file":"common-main.c","line":50,"t_abs":0.0,"argv":["git","fetch"]}
+{"event":"child_start","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000100Z","file":"run-command.c","line":1,"child_id":0,"child_class":"?","use_shell":false,"argv":["git","index-pack"]}
+{"event":"thread_start","sid":"s1","thread":"th01:fetch_thread","time":"2022-03-03T19:33:40.000200Z","file":"fetch-pack.c","line":2,"thread_name":"th01:fetch_thread"}
+{"event":"thread_exit","sid":"s1","thread":"th01:fetch_thread","time":"2022-03-03T19:33:40.000300Z","file":"fetch-pack.c","line":3,"thread_name":"th01:fetch_thread","t_rel":0.0001}
+{"event":"child_exit","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000400Z","file":"run-command.c","line":4,"child_id":0,"pid":2,"code":0,"t_rel":0.0003}
+{"event":"exit","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000500Z","file":"git.c","line":5,"t_abs":0.0005,"code":0}
+{"event":"atexit","sid":"s1","thread":"main","time":"2022-03-03T19:33:40.000500Z","file":"trace2.c","line":6,"t_abs":0.0005,"code":0}
+"#;
+
+#[test]
+fn test_thread_and_child_spans_are_paired_separately() -> Result<()> {
+    let trace = Trace::git_trace_from_reader(CHILD_AND_THREAD_TRACE.trim().as_bytes())?;
+    let mut spans: Vec<(String, i64, i64)> = trace
+        .trace_events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Complete(c) => Some((c.common.name.clone(), c.common.ts, c.dur)),
+            Event::Instant(_) => None,
+        })
+        .filter(|(name, _, _)| !name.starts_with("common-main.c"))
+        .collect();
+    spans.sort();
+
+    // A thread_exit closes the span opened by its thread_start, not the child's child_start.
+    let child_ts = spans[1].1;
+    assert_eq!(
+        spans,
+        vec![
+            (
+                String::from("fetch-pack.c:2:th01:fetch_thread"),
+                child_ts + 100,
+                100
+            ),
+            (String::from("run-command.c:1:main"), child_ts, 300),
+        ]
+    );
+
+    Ok(())
+}
@@ -106,119 +106,131 @@ impl Session {
 
         let mut res: Vec<Event> = Vec::new();
 
-        for gev in self.git_events.into_iter() {
-            match gev {
-                GitEvent::Version(_) => {
-                    let prev = self.version.replace(Some(Instant::from(gev)));
-                    assert!(prev.is_none(), "[BUG] expected only one Version event");
-                }
-                GitEvent::Atexit(_) => {
-                    Self::push_complete(
-                        self.version
-                            .replace(None)
-                            .expect("[BUG] expected got AtExit before Version"),
-                        Instant::from(gev),
-                        self.pid,
-                        None,
-                        &mut res,
-                    );
-                }
-                GitEvent::Start(_) => {
-                    let prev = self.start.replace(Some(Instant::from(gev)));
-                    assert!(prev.is_none(), "[BUG] expected to see only one Start event");
-                }
-                GitEvent::Exit(_) => {
-                    Self::push_complete(
-                        self.start
-                            .replace(None)
-                            .expect("[BUG] Exit encountered before Start event"),
-                        Instant::from(gev),
-                        self.pid,
-                        None,
-                        &mut res,
-                    );
-                }
+        for gev in std::mem::take(&mut self.git_events) {
+            self.convert(gev, &mut res);
+        }
+        self.finish(&mut res);
 
-                GitEvent::RegionEnter(_) => self.region_enter.push(Instant::from(gev)),
-                GitEvent::RegionLeave(_) => {
-                    let thread_id = match &gev {
-                        GitEvent::RegionLeave(rleave) => rleave.nesting,
-                        v => panic!("[BUG] expected RegionLeave event, got: {:?}", v),
-                    };
-
-                    Self::push_complete(
-                        self.region_enter
-                            .pop()
-                            .expect("[BUG] expected Some(RegionEnter)"),
-                        Instant::from(gev),
-                        self.pid,
-                        Some(thread_id),
-                        &mut res,
-                    );
-                }
+        res
+    }
 
-                GitEvent::ChildStart(_) => self.child_start.push(Instant::from(gev)),
-                GitEvent::ChildExit(_) => {
-                    Self::push_complete(
-                        self.child_start
-                            .pop()
-                            .expect("[BUG] expected Some(ChildStart)"),
-                        Instant::from(gev),
-                        self.pid,
-                        None,
-                        &mut res,
-                    );
-                }
+    /// Convert a single git event, pushing the trace events it completes onto `res`. Events that
+    /// open a span are held until the event closing it arrives.
+    fn convert(&mut self, gev: GitEvent, res: &mut Vec<Event>) {
+        match gev {
+            GitEvent::Version(_) => {
+                let prev = self.version.replace(Some(Instant::from(gev)));
+                assert!(prev.is_none(), "[BUG] expected only one Version event");
+            }
+            GitEvent::Atexit(_) => {
+                Self::push_complete(
+                    self.version
+                        .replace(None)
+                        .expect("[BUG] expected got AtExit before Version"),
+                    Instant::from(gev),
+                    self.pid,
+                    None,
+                    res,
+                );
+            }
+            GitEvent::Start(_) => {
+                let prev = self.start.replace(Some(Instant::from(gev)));
+                assert!(prev.is_none(), "[BUG] expected to see only one Start event");
+            }
+            GitEvent::Exit(_) => {
+                Self::push_complete(
+                    self.start
+                        .replace(None)
+                        .expect("[BUG] Exit encountered before Start event"),
+                    Instant::from(gev),
+                    self.pid,
+                    None,
+                    res,
+                );
+            }
 
-                GitEvent::ThreadStart(_) => self.child_start.push(Instant::from(gev)),
-                GitEvent::ThreadExit(_) => {
-                    Self::push_complete(
-                        self.thread_start
-                            .pop()
-                            .expect("[BUG] expected Some(ThreadStart)"),
-                        Instant::from(gev),
-                        self.pid,
-                        None,
-                        &mut res,
-                    );
-                }
-                ref gev @ GitEvent::Data(gevent::Data { t_rel, nesting, .. })
-                | ref gev @ GitEvent::DataJson(gevent::DataJson { t_rel, nesting, .. }) => {
-                    let mut c = Complete::from(gev.clone());
-                    // calculates the duration of this event from the t_rel field
-                    c.dur = (t_rel * MICROS_PER_SEC as f64) as i64;
-
-                    // ok this is super super gross, but "data" seems to be emitted at
-                    // the end of the work done (so its time is at the end of work) but
-                    // it really started at (time - t_rel). WE make the adjustment here
-                    // because it "looks right"
-                    c.common.ts -= (t_rel * MICROS_PER_SEC as f64) as i64;
-
-                    // using the thread id like this allows us to have another "lane"
-                    // where the supplemental thread and region specific info can be displayed
-                    c.common.tid = nesting;
-
-                    Self::push_event(self.pid, c.into(), &mut res);
-                }
+            GitEvent::RegionEnter(_) => self.region_enter.push(Instant::from(gev)),
+            GitEvent::RegionLeave(_) => {
+                let thread_id = match &gev {
+                    GitEvent::RegionLeave(rleave) => rleave.nesting,
+                    v => panic!("[BUG] expected RegionLeave event, got: {:?}", v),
+                };
+
+                Self::push_complete(
+                    self.region_enter
+                        .pop()
+                        .expect("[BUG] expected Some(RegionEnter)"),
+                    Instant::from(gev),
+                    self.pid,
+                    Some(thread_id),
+                    res,
+                );
+            }
 
-                GitEvent::Signal(_)
-                | GitEvent::Error(_)
-                | GitEvent::CmdPath(_)
-                | GitEvent::TooManyFiles(_)
-                | GitEvent::CmdAncestry(_)
-                | GitEvent::CmdName(_)
-                | GitEvent::CmdMode(_)
-                | GitEvent::Alias(_)
-                | GitEvent::ChildReady(_)
-                | GitEvent::Exec(_)
-                | GitEvent::ExecResult(_)
-                | GitEvent::DefParam(_)
-                | GitEvent::DefRepo(_) => {
-                    Self::push_event(self.pid, Instant::from(gev).into(), &mut res);
-                }
+            GitEvent::ChildStart(_) => self.child_start.push(Instant::from(gev)),
+            GitEvent::ChildExit(_) => {
+                Self::push_complete(
+                    self.child_start
+                        .pop()
+                        .expect("[BUG] expected Some(ChildStart)"),
+                    Instant::from(gev),
+                    self.pid,
+                    None,
+                    res,
+                );
+            }
+
+            GitEvent::ThreadStart(_) => self.thread_start.push(Instant::from(gev)),
+            GitEvent::ThreadExit(_) => {
+                Self::push_complete(
+                    self.thread_start
+                        .pop()
+                        .expect("[BUG] expected Some(ThreadStart)"),
+                    Instant::from(gev),
+                    self.pid,
+                    None,
+                    res,
+                );
+            }
+            ref gev @ GitEvent::Data(gevent::Data { t_rel, nesting, .. })
+            | ref gev @ GitEvent::DataJson(gevent::DataJson { t_rel, nesting, .. }) => {
+                let mut c = Complete::from(gev.clone());
+                // calculates the duration of this event from the t_rel field
+                c.dur = (t_rel * MICROS_PER_SEC as f64) as i64;
+
+                // ok this is super super gross, but "data" seems to be emitted at
+                // the end of the work done (so its time is at the end of work) but
+                // it really started at (time - t_rel). WE make the adjustment here
+                // because it "looks right"
+                c.common.ts -= (t_rel * MICROS_PER_SEC as f64) as i64;
+
+                // using the thread id like this allows us to have another "lane"
+                // where the supplemental thread and region specific info can be displayed
+                c.common.tid = nesting;
+
+                Self::push_event(self.pid, c.into(), res);
+            }
+
+            GitEvent::Signal(_)
+            | GitEvent::Error(_)
+            | GitEvent::CmdPath(_)
+            | GitEvent::TooManyFiles(_)
+            | GitEvent::CmdAncestry(_)
+            | GitEvent::CmdName(_)
+            | GitEvent::CmdMode(_)
+            | GitEvent::Alias(_)
+            | GitEvent::ChildReady(_)
+            | GitEvent::Exec(_)
+            | GitEvent::ExecResult(_)
+            | GitEvent::DefParam(_)
+            | GitEvent::DefRepo(_) => {
+                Self::push_event(self.pid, Instant::from(gev).into(), res);
             }
         }
+    }
 
+    /// Push the events that were never closed onto `res`.
+    fn finish(self, res: &mut Vec<Event>) {
         // ok, now we clean up all the stupid things that git didn't actually
         // emit as pairs because it's totally awesome
 
@@ -231,10 +243,8 @@ impl Session {
         .concat();
 
         for v in vs {
-            Self::push_event(self.pid, v.clone().into(), &mut res);
+            Self::push_event(self.pid, v.clone().into(), res);
         }
-
-        res
     }
 }
 
@@ -335,6 +345,43 @@ impl Builder {
     }
 }
 
+/// Converts git events to trace events as they are read rather than collecting them first like
+/// [`Builder`]. Only the spans still open in each git session are held in memory, and a session
+/// is dropped once it exits. Timestamps are left absolute and events are produced in the order
+/// they complete.
+#[derive(Default)]
+pub struct Converter {
+    spmap: SidPidMapper,
+    sessions: HashMap<Sid, Session>,
+}
+
+impl Converter {
+    /// Convert `gev`, pushing the trace events it completes onto `res`.
+    pub fn add_event(&mut self, gev: GitEvent, res: &mut Vec<Event>) {
+        let sid = gev.sid().clone();
+        let exiting = matches!(gev, GitEvent::Atexit(_));
+        let spmap = &mut self.spmap;
+        let session = self.sessions.entry(sid.clone()).or_insert_with(|| Session {
+            pid: spmap.get(&sid),
+            sid: sid.clone(),
+            ..Session::default()
+        });
+        session.convert(gev, res);
+        if exiting {
+            if let Some(session) = self.sessions.remove(&sid) {
+                session.finish(res);
+            }
+        }
+    }
+
+    /// Push the events of the sessions that never exited onto `res`.
+    pub fn finish(self, res: &mut Vec<Event>) {
+        for session in self.sessions.into_values() {
+            session.finish(res);
+        }
+    }
+}
+
 impl From<Vec<GitEvent>> for Builder {
     fn from(events: Vec<GitEvent>) -> Self {
         let mut builder = Builder::default();
@@ -10,10 +10,13 @@ use std::path::PathBuf;
 use anyhow::{Context, Result};
 use tracing::dispatcher::DefaultGuard;
 use tracing::metadata::LevelFilter;
+use tracing::{info_span, Span, Subscriber};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::{self, util::SubscriberInitExt, EnvFilter};
 
 #[derive(Debug)]
@@ -28,11 +31,39 @@ pub struct Guard {
     _inner: Vec<GuardWrapper>,
 }
 
+/// How records are written to the log file. Console output is always human-readable.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    strum_macros::EnumVariantNames,
+    strum_macros::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum LogFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+
+    /// One JSON object per line.
+    Json,
+}
+
 #[derive(Debug, Default)]
 pub struct TracingOpts {
     pub is_tty: bool,
     pub no_color: bool,
     pub log_dir: Option<PathBuf>,
+
+    /// Overrides `RUST_LOG` for console output. The log file still records at least `info`.
+    pub console_level: Option<LevelFilter>,
+
+    pub log_format: LogFormat,
 }
 
 const LOG_FILE_NAME: &str = "focus.log";
@@ -42,6 +73,8 @@ pub fn init_tracing(opts: TracingOpts) -> Result<Guard> {
         is_tty,
         no_color,
         log_dir,
+        console_level,
+        log_format,
     } = opts;
 
     let use_color = is_tty && !no_color;
@@ -76,9 +109,12 @@ pub fn init_tracing(opts: TracingOpts) -> Result<Guard> {
 
     tracing_subscriber::registry()
         .with(ErrorLayer::default())
-        .with(EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()),
-        ))
+        .with(match console_level {
+            Some(level) => EnvFilter::new(level.max(LevelFilter::INFO).to_string()),
+            None => {
+                EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()))
+            }
+        })
         .with(
             Targets::new()
                 .with_targets(vec![
@@ -95,15 +131,10 @@ pub fn init_tracing(opts: TracingOpts) -> Result<Guard> {
                 .with_target(true)
                 .with_writer(stderr_writer)
                 .with_ansi(use_color)
-                .event_format(console_format),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_ansi(false)
-                .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
-                .with_target(false)
-                .with_writer(log_file_writer),
+                .event_format(console_format)
+                .with_filter(console_level.unwrap_or(LevelFilter::TRACE)),
         )
+        .with(file_layer(log_format, log_file_writer))
         .try_init()?;
 
     Ok(Guard {
@@ -113,3 +144,77 @@ pub fn init_tracing(opts: TracingOpts) -> Result<Guard> {
         ],
     })
 }
+
+/// The root span of a focus invocation running the subcommand `feature_name`. JSON log records list the spans they were recorded in, so each one carries the `feature` of this span.
+pub fn feature_span(feature_name: &str) -> Span {
+    info_span!("focus", feature = %feature_name)
+}
+
+/// Build the layer that writes to the log file in the requested format.
+fn file_layer<S, W>(
+    log_format: LogFormat,
+    writer: W,
+) -> Box<dyn tracing_subscriber::Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(writer);
+    match log_format {
+        LogFormat::Text => Box::new(layer.with_target(false)),
+        LogFormat::Json => Box::new(layer.json().with_current_span(true).with_span_list(true)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_file_layer_writes_one_record_per_line() -> Result<()> {
+        let buffer = SharedBuffer::default();
+        let subscriber = tracing_subscriber::registry().with(file_layer(LogFormat::Json, {
+            let buffer = buffer.clone();
+            move || buffer.clone()
+        }));
+        tracing::subscriber::with_default(subscriber, || {
+            feature_span("sync").in_scope(|| {
+                tracing::info_span!("syncing", attempt = 1).in_scope(|| {
+                    tracing::info!(count = 3, path = ?PathBuf::from("a/b"), "Synced");
+                });
+            });
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone())?;
+        let event: Value = serde_json::from_str(output.lines().next().unwrap())?;
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["span"]["name"], "syncing");
+        assert_eq!(event["span"]["attempt"], 1);
+        assert_eq!(event["spans"][0]["name"], "focus");
+        assert_eq!(event["spans"][0]["feature"], "sync");
+        assert_eq!(event["fields"]["message"], "Synced");
+        assert_eq!(event["fields"]["count"], 3);
+        assert_eq!(event["fields"]["path"], "\"a/b\"");
+        Ok(())
+    }
+}
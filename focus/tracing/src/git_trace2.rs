@@ -2,12 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
     ops::{Deref, DerefMut},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use serde::de::{Error as _, SeqAccess, Visitor};
+use serde::Deserializer as _;
 use serde_derive::{Deserialize, Serialize};
 use walkdir::{DirEntry, WalkDir};
 
@@ -233,17 +235,91 @@ impl Events {
             bail!("path {:?} was not a file", path);
         }
 
-        let mut result: Vec<Event> = Vec::new();
-
         let bytes = std::fs::read(path)?;
         debug!("parsing: {:?}", &path);
-        for line in BufReader::new(bytes.as_slice()).lines() {
-            let s = line?;
-            let event: Event = serde_json::from_str(&s)?;
+        Self::from_slice(&bytes).with_context(|| format!("parsing {:?}", path))
+    }
+
+    /// Read events from `reader`. See [`Events::from_slice`] for the accepted formats.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Events> {
+        let mut result: Vec<Event> = Vec::new();
+        Self::for_each_in_reader(reader, |event| {
             result.push(event);
+            Ok(())
+        })?;
+        Ok(Events(result))
+    }
+
+    /// Parse events in either of two formats, detected from the first non-whitespace byte: newline-delimited JSON, one event per line, as written by `GIT_TRACE2_EVENT`; or a JSON array of events. Blank lines are skipped.
+    pub fn from_slice(bytes: &[u8]) -> Result<Events> {
+        Self::from_reader(bytes)
+    }
+
+    /// Call `f` with each event read from `reader` as it is parsed, without holding the events in memory. See [`Events::from_slice`] for the accepted formats.
+    pub fn for_each_in_reader<R: Read>(
+        reader: R,
+        mut f: impl FnMut(Event) -> Result<()>,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut skipped_lines = 0;
+        let first = loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            let skipped = buf
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .unwrap_or(buf.len());
+            skipped_lines += buf[..skipped].iter().filter(|b| **b == b'\n').count();
+            let first = buf.get(skipped).copied();
+            reader.consume(skipped);
+            if let Some(first) = first {
+                break first;
+            }
+        };
+
+        if first == b'[' {
+            let mut deserializer = serde_json::Deserializer::from_reader(reader);
+            deserializer
+                .deserialize_seq(EventSeqVisitor(&mut f))
+                .context("parsing a JSON array of events")?;
+            return deserializer.end().context("parsing a JSON array of events");
         }
 
-        Ok(Events(result))
+        for (index, line) in reader.lines().enumerate() {
+            let s = line?;
+            if s.trim().is_empty() {
+                continue;
+            }
+            let event: Event = serde_json::from_str(&s).with_context(|| {
+                format!("parsing the event on line {}", skipped_lines + index + 1)
+            })?;
+            f(event)?;
+        }
+        Ok(())
+    }
+
+    /// Call `f` with each event in the file at `path`, or in every file within it if it is a directory, as it is parsed.
+    pub fn for_each_in_path(path: &Path, mut f: impl FnMut(Event) -> Result<()>) -> Result<()> {
+        let files: Vec<PathBuf> = if path.is_file() {
+            vec![path.to_owned()]
+        } else if path.is_dir() {
+            Self::file_iter(path)
+                .filter(|dirent| dirent.path().is_file())
+                .map(|dirent| dirent.into_path())
+                .collect()
+        } else {
+            bail!("path {:?} was neither a file or directory", path);
+        };
+
+        for file in files {
+            debug!("parsing: {:?}", &file);
+            let reader = std::fs::File::open(&file)?;
+            Self::for_each_in_reader(reader, &mut f)
+                .with_context(|| format!("parsing {:?}", file))?;
+        }
+        Ok(())
     }
 
     fn is_hidden(entry: &DirEntry) -> bool {
@@ -275,6 +351,24 @@ impl Events {
     }
 }
 
+/// Passes each element of a JSON array of events to a callback as it is deserialized.
+struct EventSeqVisitor<'a, F>(&'a mut F);
+
+impl<'de, 'a, F: FnMut(Event) -> Result<()>> Visitor<'de> for EventSeqVisitor<'a, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of git trace2 events")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(event) = seq.next_element::<Event>()? {
+            (self.0)(event).map_err(|e| A::Error::custom(format!("{:#}", e)))?;
+        }
+        Ok(())
+    }
+}
+
 impl Deref for Events {
     type Target = Vec<Event>;
     fn deref(&self) -> &Self::Target {
@@ -689,6 +783,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_ndjson_and_array_inputs_are_equivalent() -> Result<()> {
+        let hm = data::hashmap();
+        let lines = [
+            hm.get("version").unwrap().as_str(),
+            hm.get("start").unwrap().as_str(),
+            hm.get("exit").unwrap().as_str(),
+        ];
+
+        let ndjson = format!("{}\n\n{}\n{}\n", lines[0], lines[1], lines[2]);
+        let array = format!("  \n[{}]\n", lines.join(",\n"));
+        let from_ndjson = Events::from_reader(ndjson.as_bytes())?;
+        let from_array = Events::from_reader(array.as_bytes())?;
+        assert_eq!(from_ndjson.len(), 3);
+        assert_eq!(from_ndjson.into_inner(), from_array.into_inner());
+
+        assert!(Events::from_slice(b"[{\"event\":\"version\"}").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_from_definition_macro_works() -> Result<()> {
         let v = event::Version::default();
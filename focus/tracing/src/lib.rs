@@ -7,7 +7,7 @@ pub mod git_trace2;
 
 use std::path::{Path, PathBuf};
 
-pub use crate::focus::{init_tracing, Guard, TracingOpts};
+pub use crate::focus::{feature_span, init_tracing, Guard, LogFormat, TracingOpts};
 pub use chrome::Trace;
 
 use anyhow::Result;
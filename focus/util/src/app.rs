@@ -4,10 +4,11 @@
 use std::sync::Arc;
 use std::{borrow::Borrow, fmt::Debug};
 
+use crate::progress::{Progress, ProgressMode};
 use crate::sandbox::Sandbox;
 use anyhow::{Context, Result};
 use focus_testing::GitBinary;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 #[cfg(not(feature = "twttr"))]
 use crate::stubs::tool_insights_client::Client;
@@ -23,6 +24,9 @@ pub struct App {
     git_binary: GitBinary,
     sandbox: Arc<Sandbox>,
     tool_insights_client: Client,
+    offline: bool,
+    git_timeout: Option<Duration>,
+    progress_mode: ProgressMode,
 }
 
 impl Debug for App {
@@ -57,9 +61,59 @@ impl App {
             git_binary,
             sandbox,
             tool_insights_client,
+            offline: false,
+            git_timeout: None,
+            progress_mode: ProgressMode::Hidden,
         })
     }
 
+    /// Forbid operations that would contact a remote.
+    pub fn with_offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    /// Whether operations that would contact a remote are forbidden.
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Fail if the app is offline, naming the `operation` that would have needed the network.
+    pub fn require_network(&self, operation: &str) -> Result<()> {
+        if self.offline {
+            anyhow::bail!(
+                "{} requires network, but --offline was specified",
+                operation
+            );
+        }
+        Ok(())
+    }
+
+    /// Kill Git commands that run longer than `git_timeout`.
+    pub fn with_git_timeout(self, git_timeout: Option<Duration>) -> Self {
+        Self {
+            git_timeout,
+            ..self
+        }
+    }
+
+    /// How long Git commands may run before they are killed, if limited.
+    pub fn git_timeout(&self) -> Option<Duration> {
+        self.git_timeout
+    }
+
+    /// Report the progress of long-running operations in the given `mode`.
+    pub fn with_progress_mode(self, progress_mode: ProgressMode) -> Self {
+        Self {
+            progress_mode,
+            ..self
+        }
+    }
+
+    /// Start reporting the progress of the phase described by `label`, which has `total` steps if known. Nothing is reported unless a progress mode was set.
+    pub fn progress(&self, label: &str, total: Option<u64>) -> Progress {
+        Progress::new(self.progress_mode, label, total)
+    }
+
     /// Get a reference to the Git binary that this app is using.
     pub fn git_binary(&self) -> &GitBinary {
         &self.git_binary
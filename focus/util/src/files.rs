@@ -5,6 +5,7 @@ use anyhow::{Context, Result};
 use std::{fs::File, io::Read, path::Path};
 
 use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
 
 const BUFFER_SIZE: usize = 4096;
 
@@ -26,3 +27,15 @@ pub fn hash(path: impl AsRef<Path>) -> Result<Vec<u8>> {
 
     Ok(hasher.finalize().to_vec())
 }
+
+/// The total size of the files under `path`, in bytes. Symbolic links are not followed and unreadable entries are skipped.
+pub fn size_of(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
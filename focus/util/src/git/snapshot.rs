@@ -195,7 +195,14 @@ pub fn apply(
         .to_str()
         .ok_or_else(|| anyhow::anyhow!("Failed to convert patch path to a string"))?;
 
-    let _ = git_helper::run_consuming_stdout(repo_path, vec!["apply", "-v", patch_path_str], app)?;
+    // A snapshot of only untracked changes has an empty patch, which `git apply` rejects.
+    let patch_len = std::fs::metadata(&patch_path)
+        .with_context(|| format!("Failed to read metadata of {}", patch_path.display()))?
+        .len();
+    if patch_len > 0 {
+        let _ =
+            git_helper::run_consuming_stdout(repo_path, vec!["apply", "-v", patch_path_str], app)?;
+    }
 
     // Remove the patch file
     std::fs::remove_file(&patch_path)
@@ -314,6 +321,37 @@ mod testing {
         Ok(())
     }
 
+    #[test]
+    fn create_and_apply_untracked_changes_only() -> Result<()> {
+        init_logging();
+
+        let app = Arc::new(App::new_for_testing()?);
+        let repo_dir = app.sandbox().create_subdirectory("repo")?;
+
+        let repo = ScratchGitRepo::new_static_fixture(&repo_dir)?;
+
+        let untracked_file_path = repo.path().join("an-untracked-file.txt");
+        std::fs::write(&untracked_file_path, b"This file is untracked.\n")?;
+        let initial_status = git::working_tree::status(repo.path(), app.clone())?;
+
+        let snapshot = git::snapshot::create(repo.path(), app.clone())
+            .context("Creating the snapshot failed")?
+            .ok_or_else(|| anyhow::anyhow!("Expected a snapshot to be created"))?;
+        assert!(!untracked_file_path.exists());
+
+        git::snapshot::apply(snapshot.as_path(), repo.path(), true, app.clone())
+            .context("Applying snapshot failed")?;
+        assert!(untracked_file_path.is_file());
+        assert_eq!(
+            git::working_tree::status(repo.path(), app.clone())?,
+            initial_status
+        );
+
+        drop(app);
+
+        Ok(())
+    }
+
     #[test]
     fn reapply_guard() -> Result<()> {
         init_logging();
@@ -4,6 +4,9 @@
 use std::{
     collections::{HashMap, HashSet},
     ffi::OsStr,
+    fs::File,
+    io::{Read, Write},
+    os::unix::ffi::OsStrExt,
     path::PathBuf,
     process::Stdio,
     str::FromStr,
@@ -20,8 +23,7 @@ use tracing::{error, warn};
 
 use crate::{
     app::App,
-    process,
-    sandbox_command::{SandboxCommand, SandboxCommandOutput},
+    sandbox_command::{CommandFailed, SandboxCommand, SandboxCommandOutput},
     time::GitIdentTime,
 };
 
@@ -39,12 +41,24 @@ pub fn git_dir(path: &Path, app: Arc<App>) -> Result<PathBuf> {
     }
 }
 
+/// Resolve the path Git uses for `name` (like `hooks`) in the repository at `path`, as `git rev-parse --git-path` does. This honors settings like `core.hooksPath` and works in worktrees, where `.git` is a file.
+pub fn git_path(path: &Path, name: &str, app: Arc<App>) -> Result<PathBuf> {
+    let found_path = run_consuming_stdout(path, vec!["rev-parse", "--git-path", name], app)?;
+    let found_path = PathBuf::from(&found_path);
+    if found_path.is_absolute() {
+        Ok(found_path)
+    } else {
+        Ok(path.join(found_path.as_path()))
+    }
+}
+
 pub fn git_command_with_git_binary(
     app: Arc<App>,
     git_binary: &GitBinary,
 ) -> Result<(Command, SandboxCommand)> {
     let mut cmd = git_binary.command();
-    let scmd = SandboxCommand::with_command(&mut cmd, app)?;
+    let git_timeout = app.git_timeout();
+    let scmd = SandboxCommand::with_command(&mut cmd, app)?.with_timeout(git_timeout);
     Ok((cmd, scmd))
 }
 
@@ -77,6 +91,7 @@ pub fn fetch_refs<P: AsRef<Path>>(
     app: Arc<App>,
     depth: Option<u64>,
 ) -> Result<()> {
+    app.require_network(&format!("Fetching from {}", remote))?;
     let (mut cmd, scmd) = git_command(app)?;
     cmd.current_dir(repo_path).arg("fetch").arg("--force");
     if let Some(d) = depth {
@@ -98,6 +113,7 @@ pub fn pull<P: AsRef<Path>>(
     depth: Option<u64>,
     rebase: Option<bool>,
 ) -> Result<()> {
+    app.require_network(&format!("Pulling from {}", remote))?;
     let (mut cmd, scmd) = git_command(app)?;
     cmd.current_dir(repo_path).arg("pull").arg("--force");
     if let Some(d) = depth {
@@ -126,6 +142,7 @@ pub fn fetch_all_tags<P: AsRef<Path>>(
     app: Arc<App>,
     depth: Option<u64>,
 ) -> Result<String> {
+    app.require_network(&format!("Fetching tags from {}", remote))?;
     let mut args = vec![
         String::from("fetch"),
         remote.to_owned(),
@@ -142,11 +159,14 @@ pub fn fetch_all_tags<P: AsRef<Path>>(
 }
 
 pub fn ls_remote(remote: &str, app: Arc<App>) -> Result<String> {
-    run_consuming_stdout(
-        std::env::current_dir().unwrap(),
-        vec!["ls-remote", "--tags", remote],
-        app,
-    )
+    app.require_network(&format!("Listing refs in {}", remote))?;
+    let (mut cmd, scmd) = git_command(app)?;
+    cmd.current_dir(std::env::current_dir()?)
+        .args(["ls-remote", "--tags", remote]);
+    scmd.ensure_success_or_log(&mut cmd, SandboxCommandOutput::Stderr)?;
+    let mut stdout_contents = String::new();
+    scmd.read_to_string(SandboxCommandOutput::Stdout, &mut stdout_contents)?;
+    Ok(stdout_contents.trim().to_owned())
 }
 
 pub fn push_refs<P: AsRef<Path>>(
@@ -155,6 +175,7 @@ pub fn push_refs<P: AsRef<Path>>(
     remote: &str,
     app: Arc<App>,
 ) -> Result<()> {
+    app.require_network(&format!("Pushing to {}", remote))?;
     let mut args = vec![String::from("push"), remote.to_owned()];
     args.extend(refspecs);
     let (mut cmd, scmd) = git_command(app)?;
@@ -179,16 +200,56 @@ pub fn write_config<P: AsRef<Path>>(
     .map(|_| ())
 }
 
+/// Read the value of the config `key` in the repo at `repo_path`, or `None` if the key is not set. A key that is set to the empty string yields `Some("")`.
 pub fn read_config<P: AsRef<Path>>(
     repo_path: P,
     key: &str,
     app: Arc<App>,
 ) -> Result<Option<String>> {
-    if let Ok(result) = run_consuming_stdout(repo_path, ["config", key], app) {
-        return Ok(Some(result));
+    let (mut cmd, scmd) = git_command(app)?;
+    // `git config --get` exits with status 1 when the key is not set.
+    let status = scmd
+        .ensure_exit_with_status_or_log(
+            cmd.current_dir(repo_path).args(["config", "--get", key]),
+            SandboxCommandOutput::Stderr,
+            &[0, 1],
+        )
+        .with_context(|| format!("Reading config key {}", key))?;
+    if status.code() == Some(1) {
+        return Ok(None);
     }
 
-    Ok(None)
+    let mut value = String::new();
+    scmd.read_to_string(SandboxCommandOutput::Stdout, &mut value)?;
+    Ok(Some(value.trim().to_owned()))
+}
+
+/// Read every value of the multi-valued config `key` in the repo at `repo_path`, in the order git reports them. The result is empty if the key is not set.
+pub fn read_config_all<P: AsRef<Path>>(
+    repo_path: P,
+    key: &str,
+    app: Arc<App>,
+) -> Result<Vec<String>> {
+    let (mut cmd, scmd) = git_command(app)?;
+    // `git config --get-all` exits with status 1 when the key is not set.
+    let status = scmd
+        .ensure_exit_with_status_or_log(
+            cmd.current_dir(repo_path)
+                .args(["config", "--null", "--get-all", key]),
+            SandboxCommandOutput::Stderr,
+            &[0, 1],
+        )
+        .with_context(|| format!("Reading all values of config key {}", key))?;
+    if status.code() == Some(1) {
+        return Ok(Vec::new());
+    }
+
+    let mut output = String::new();
+    scmd.read_to_string(SandboxCommandOutput::Stdout, &mut output)?;
+    Ok(output
+        .split_terminator('\0')
+        .map(|value| value.to_owned())
+        .collect())
 }
 
 pub fn unset_config<P: AsRef<Path>>(repo_path: P, key: &str, app: Arc<App>) -> Result<()> {
@@ -209,11 +270,20 @@ where
     O: AsRef<OsStr>,
 {
     let (mut cmd, scmd) = git_command(app)?;
-    if let Err(e) = cmd.current_dir(repo).args(args).status() {
-        let cmd_description = process::pretty_print_command(&mut cmd);
-        scmd.log(SandboxCommandOutput::Stderr, cmd_description.as_str())?;
-        bail!("Git command '{}' failed: {}", cmd_description, e);
-    }
+    cmd.current_dir(repo).args(args);
+    scmd.ensure_success_or_log(&mut cmd, SandboxCommandOutput::Stderr)
+        .map_err(|e| {
+            // The error already names the command, so only add what git had to say about it.
+            let stderr = e
+                .downcast_ref::<CommandFailed>()
+                .map(|failed| failed.stderr.trim().to_owned())
+                .unwrap_or_default();
+            if stderr.is_empty() {
+                e
+            } else {
+                e.context(stderr)
+            }
+        })?;
     let mut stdout_contents = String::new();
     scmd.read_to_string(SandboxCommandOutput::Stdout, &mut stdout_contents)?;
     Ok(stdout_contents.trim().to_owned())
@@ -234,6 +304,83 @@ pub fn find_top_level(app: Arc<App>, path: impl AsRef<Path>) -> Result<PathBuf>
     }
 }
 
+/// Returns the git directory shared by all working trees of the repository containing `path`. For a linked worktree this is the main repository's git directory rather than the worktree's own.
+pub fn find_common_git_dir(app: Arc<App>, path: impl AsRef<Path>) -> Result<PathBuf> {
+    let path = path.as_ref();
+    let common_dir = run_consuming_stdout(path, ["rev-parse", "--git-common-dir"], app)
+        .context("Finding the repo's common git directory failed")?;
+    if common_dir.is_empty() {
+        bail!(
+            "Could not determine the common git directory of '{}'",
+            path.display()
+        );
+    }
+    // The output is relative to `path` unless it is already absolute.
+    Ok(path.join(common_dir))
+}
+
+/// The number of paths passed to each invocation of `git check-ignore`.
+const CHECK_IGNORE_BATCH_SIZE: usize = 16_384;
+
+/// Returns the members of `paths` (relative to `repo_path`) that match the ignore rules of the repository. Tracked paths are considered too. Paths are sent over standard input in batches so that checking many paths only takes a few invocations of Git.
+pub fn check_ignore<P: AsRef<Path>>(
+    repo_path: P,
+    paths: &[PathBuf],
+    app: Arc<App>,
+) -> Result<HashSet<PathBuf>> {
+    let mut ignored = HashSet::new();
+    for batch in paths.chunks(CHECK_IGNORE_BATCH_SIZE) {
+        let input_path = {
+            let (mut input_file, input_path, _) =
+                app.sandbox()
+                    .create_file(Some("check-ignore"), Some("input"), None)?;
+            for path in batch {
+                input_file
+                    .write_all(path.as_os_str().as_bytes())
+                    .and_then(|_| input_file.write_all(b"\x00"))
+                    .context("Writing paths to check")?;
+            }
+            input_file.sync_data().context("Syncing paths to check")?;
+            input_path
+        };
+
+        let input_file = File::open(&input_path).context("Re-opening paths to check")?;
+        let mut cmd = app.git_binary().command();
+        let scmd = SandboxCommand::with_command_and_handles(
+            &mut cmd,
+            Some(Stdio::from(input_file)),
+            None,
+            None,
+            app.clone(),
+        )?;
+        // Exit status 1 means that none of the paths are ignored.
+        scmd.ensure_exit_with_status_or_log(
+            cmd.current_dir(repo_path.as_ref()).args([
+                "check-ignore",
+                "--no-index",
+                "--stdin",
+                "-z",
+            ]),
+            SandboxCommandOutput::Stderr,
+            &[0, 1],
+        )
+        .context("Running `git check-ignore` failed")?;
+
+        let mut output = Vec::new();
+        scmd.read_buffered(SandboxCommandOutput::Stdout)?
+            .read_to_end(&mut output)
+            .context("Reading output of `git check-ignore`")?;
+        ignored.extend(
+            output
+                .split(|b| *b == 0)
+                .filter(|path| !path.is_empty())
+                .map(|path| PathBuf::from(OsStr::from_bytes(path))),
+        );
+    }
+
+    Ok(ignored)
+}
+
 pub fn get_current_revision(app: Arc<App>, repo: &Path) -> Result<String> {
     run_consuming_stdout(repo, ["rev-parse", "HEAD"], app)
 }
@@ -262,6 +409,47 @@ pub fn get_merge_base(
     run_consuming_stdout(repo, args, app)
 }
 
+/// Stash the local changes to `paths` with `message`, returning the ID of the stash commit created.
+pub fn stash_paths<P: AsRef<Path>>(
+    repo_path: P,
+    paths: &[PathBuf],
+    message: &str,
+    app: Arc<App>,
+) -> Result<String> {
+    let previous_stash = parse_ref(app.clone(), repo_path.as_ref(), "refs/stash")?;
+    let (mut cmd, scmd) = git_command(app.clone())?;
+    scmd.ensure_success_or_log(
+        cmd.current_dir(repo_path.as_ref())
+            .args(["stash", "push", "--message", message, "--"])
+            .args(paths),
+        SandboxCommandOutput::Stderr,
+    )
+    .context("Stashing local changes")?;
+    // `git stash push` succeeds without creating a stash when there is nothing to save, leaving `refs/stash` as it was.
+    let stash = parse_ref(app, repo_path.as_ref(), "refs/stash")?;
+    if stash.is_empty() || stash == previous_stash {
+        bail!("There were no local changes to stash");
+    }
+    Ok(stash)
+}
+
+/// Discard the local changes to `paths`, restoring them in the index and working tree as they are in `HEAD`.
+pub fn discard_changes<P: AsRef<Path>>(
+    repo_path: P,
+    paths: &[PathBuf],
+    app: Arc<App>,
+) -> Result<()> {
+    let (mut cmd, scmd) = git_command(app)?;
+    scmd.ensure_success_or_log(
+        cmd.current_dir(repo_path)
+            .args(["checkout", "HEAD", "--"])
+            .args(paths),
+        SandboxCommandOutput::Stderr,
+    )
+    .context("Discarding local changes")
+    .map(|_| ())
+}
+
 // Switches to a branch in a given repository, switching back to the previous branch afterwards
 pub struct BranchSwitch {
     app: Arc<App>,
@@ -813,6 +1001,124 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_check_ignore() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        git2::Repository::init(dir.path())?;
+        std::fs::write(dir.path().join(".gitignore"), "*.o\n")?;
+
+        let paths = vec![
+            PathBuf::from("a.o"),
+            PathBuf::from("b.txt"),
+            PathBuf::from("dir/c.o"),
+        ];
+        let ignored = check_ignore(dir.path(), &paths, app.clone())?;
+        assert_eq!(
+            ignored,
+            HashSet::from([PathBuf::from("a.o"), PathBuf::from("dir/c.o")])
+        );
+
+        // Nothing ignored is not an error.
+        assert!(check_ignore(dir.path(), &paths[1..2], app)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_consuming_stdout_reports_stderr_on_failure() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        git2::Repository::init(dir.path())?;
+
+        let e =
+            run_consuming_stdout(dir.path(), ["rev-parse", "refs/heads/missing"], app).unwrap_err();
+        let message = format!("{:#}", e);
+        assert!(
+            message.contains("rev-parse refs/heads/missing"),
+            "{}",
+            message
+        );
+        assert!(message.contains("unknown revision"), "{}", message);
+        assert!(e.downcast_ref::<CommandFailed>().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stash_paths_fails_when_nothing_was_stashed() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let repo = focus_testing::ScratchGitRepo::new_static_fixture(dir.path())?;
+        let file = PathBuf::from("stashed.txt");
+        repo.write_and_commit_file(&file, b"committed\n", "Add stashed.txt")?;
+
+        std::fs::write(repo.path().join(&file), b"changed\n")?;
+        let stash = stash_paths(repo.path(), &[file.clone()], "first", app.clone())?;
+        assert_eq!(parse_ref(app.clone(), repo.path(), "refs/stash")?, stash);
+
+        // The change was stashed away, so stashing again must not report the previous stash.
+        assert!(stash_paths(repo.path(), &[file], "second", app).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_config() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        git2::Repository::init(dir.path())?;
+
+        assert_eq!(
+            read_config(dir.path(), "focus.test-key", app.clone())?,
+            None
+        );
+
+        write_config(dir.path(), "focus.test-key", "some value", app.clone())?;
+        assert_eq!(
+            read_config(dir.path(), "focus.test-key", app.clone())?,
+            Some(String::from("some value"))
+        );
+
+        write_config(dir.path(), "focus.test-key", "", app.clone())?;
+        assert_eq!(
+            read_config(dir.path(), "focus.test-key", app)?,
+            Some(String::new())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_config_all() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let repo = git2::Repository::init(dir.path())?;
+        let key = "focus.test-key";
+
+        assert!(read_config_all(dir.path(), key, app.clone())?.is_empty());
+
+        let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+        config.set_multivar(key, "^$", "/path/to/foo")?;
+        assert_eq!(
+            read_config_all(dir.path(), key, app.clone())?,
+            vec![String::from("/path/to/foo")]
+        );
+
+        config.set_multivar(key, "^$", "/path/to/bar")?;
+        config.set_multivar(key, "^$", "/path/with\nnewline")?;
+        assert_eq!(
+            read_config_all(dir.path(), key, app)?,
+            vec![
+                String::from("/path/to/foo"),
+                String::from("/path/to/bar"),
+                String::from("/path/with\nnewline"),
+            ]
+        );
+
+        Ok(())
+    }
+
     fn mk_temp_config(content: &str) -> Result<(tempfile::NamedTempFile, git2::Config)> {
         use std::io::prelude::*;
 
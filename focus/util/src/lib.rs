@@ -10,6 +10,7 @@ pub mod lock_file;
 pub mod paths;
 pub mod periodic;
 pub mod process;
+pub mod progress;
 pub mod sandbox;
 pub mod sandbox_command;
 pub mod stubs;
@@ -5,7 +5,7 @@ use anyhow::{bail, Result};
 use lazy_static::lazy_static;
 use std::{
     ffi::OsString,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
 
@@ -25,9 +25,27 @@ pub fn assert_focused_repo(path: &Path) -> Result<()> {
 }
 
 pub fn find_repo_root_from(app: Arc<App>, path: PathBuf) -> Result<PathBuf> {
-    let path = git_helper::find_top_level(app, &path)?;
-    assert_focused_repo(&path)?;
-    Ok(path)
+    let top_level = git_helper::find_top_level(app.clone(), &path)?;
+    if top_level.join(".focus").is_dir() {
+        return Ok(top_level);
+    }
+
+    // Linked worktrees share focus state with the main working tree, which sits above the common git directory.
+    let common_git_dir = git_helper::find_common_git_dir(app, &top_level)?;
+    if let Some(main_working_tree) = common_git_dir
+        .canonicalize()
+        .ok()
+        .filter(|dir| dir.ends_with(".git"))
+        .and_then(|dir| dir.parent().map(Path::to_path_buf))
+    {
+        if main_working_tree != top_level {
+            assert_focused_repo(&main_working_tree)?;
+            return Ok(main_working_tree);
+        }
+    }
+
+    assert_focused_repo(&top_level)?;
+    Ok(top_level)
 }
 
 pub fn focus_config_dir() -> PathBuf {
@@ -115,6 +133,25 @@ pub fn expand_tilde<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
     }
 }
 
+/// Resolve the `.` and `..` components of `path` without consulting the filesystem, so that paths that no longer exist can be compared. A `..` at the root or at the start of a relative path is kept.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(Component::ParentDir),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
 /// Determine if the `subject` is under `ancestor`.
 pub fn has_ancestor<P: AsRef<Path>>(subject: P, ancestor: P) -> Result<bool> {
     let subject = subject.as_ref();
@@ -136,6 +173,32 @@ pub fn has_ancestor<P: AsRef<Path>>(subject: P, ancestor: P) -> Result<bool> {
     Ok(false)
 }
 
+/// The paths among `paths` that are not within any of the directories in `roots`, in order. Paths and roots are compared component by component, so `foo` contains `foo/bar` but not `foobar`. No roots means no restriction.
+pub fn paths_outside_roots<'a>(
+    paths: impl IntoIterator<Item = &'a Path>,
+    roots: &[PathBuf],
+) -> Vec<PathBuf> {
+    if roots.is_empty() {
+        return Vec::new();
+    }
+
+    // Ignore `.` components so that `./foo` and `foo/` are the same root.
+    let normalize = |path: &Path| -> PathBuf {
+        path.components()
+            .filter(|component| *component != std::path::Component::CurDir)
+            .collect()
+    };
+    let roots: Vec<PathBuf> = roots.iter().map(|root| normalize(root)).collect();
+    paths
+        .into_iter()
+        .filter(|path| {
+            let path = normalize(path);
+            !roots.iter().any(|root| path.starts_with(root))
+        })
+        .map(Path::to_path_buf)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use focus_testing::ScratchGitRepo;
@@ -152,6 +215,38 @@ mod tests {
         assert!(!is_relevant_to_build_graph(Path::new("foo.c")));
     }
 
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("/a/./b/../c/")),
+            PathBuf::from("/a/c")
+        );
+        assert_eq!(normalize_path(Path::new("/../a")), PathBuf::from("/a"));
+        assert_eq!(
+            normalize_path(Path::new("a/../../b")),
+            PathBuf::from("../b")
+        );
+        assert_eq!(normalize_path(Path::new("./a/..")), PathBuf::new());
+    }
+
+    #[test]
+    fn test_paths_outside_roots() {
+        let paths = [
+            Path::new("project_a/src"),
+            Path::new("project_ab"),
+            Path::new("library_b"),
+            Path::new("project_b/lib"),
+        ];
+        assert_eq!(
+            paths_outside_roots(
+                paths,
+                &[PathBuf::from("./project_a/"), PathBuf::from("project_b")]
+            ),
+            vec![PathBuf::from("project_ab"), PathBuf::from("library_b")]
+        );
+        assert!(paths_outside_roots(paths, &[]).is_empty());
+    }
+
     #[test]
     fn test_is_involved_in_build() {
         assert!(is_build_definition(Path::new("BUILD.funky")));
@@ -186,4 +281,64 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_find_repo_root_from_linked_worktree() -> Result<()> {
+        let app = Arc::new(App::new_for_testing()?);
+
+        let temp_dir = tempfile::tempdir()?.into_path();
+        let focus_repo = ScratchGitRepo::new_static_fixture(&temp_dir)?;
+        std::fs::create_dir_all(focus_repo.path().join(".focus"))?;
+
+        let worktree_path = temp_dir.join("linked_worktree");
+        let output = app
+            .git_binary()
+            .command()
+            .arg("worktree")
+            .arg("add")
+            .arg("--detach")
+            .arg(&worktree_path)
+            .current_dir(focus_repo.path())
+            .output()?;
+        assert!(output.status.success());
+
+        assert_eq!(
+            find_repo_root_from(app.clone(), worktree_path.clone())?,
+            focus_repo.path().canonicalize()?
+        );
+
+        // Directories nested in either working tree resolve to the main working tree too.
+        for working_tree in [focus_repo.path(), worktree_path.as_path()] {
+            let nested_path = working_tree.join("nested").join("dir");
+            std::fs::create_dir_all(&nested_path)?;
+            assert_eq!(
+                find_repo_root_from(app.clone(), nested_path)?,
+                focus_repo.path().canonicalize()?
+            );
+        }
+
+        // A path that does not exist has no repo root.
+        if find_repo_root_from(app.clone(), worktree_path.join("missing")).is_ok() {
+            bail!("Should not have found a focus repo root for a missing path")
+        };
+
+        // A linked worktree of a repo without focus state is not a focused repo.
+        let non_focus_repo = ScratchGitRepo::new_static_fixture(&temp_dir)?;
+        let non_focus_worktree_path = temp_dir.join("non_focus_linked_worktree");
+        let output = app
+            .git_binary()
+            .command()
+            .arg("worktree")
+            .arg("add")
+            .arg("--detach")
+            .arg(&non_focus_worktree_path)
+            .current_dir(non_focus_repo.path())
+            .output()?;
+        assert!(output.status.success());
+        if find_repo_root_from(app, non_focus_worktree_path).is_ok() {
+            bail!("Should not have found a focus repo root in a worktree of a non-focus repo")
+        };
+
+        Ok(())
+    }
 }
@@ -0,0 +1,259 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+const BAR_REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+const BAR_WIDTH: usize = 30;
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+
+/// How the progress of long-running operations is reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Progress is not reported.
+    #[default]
+    Hidden,
+
+    /// A progress bar is redrawn on stderr, for interactive terminals.
+    Bar,
+
+    /// A log line is emitted periodically, for when output is not a terminal.
+    Log,
+}
+
+struct State {
+    label: String,
+    total: Option<u64>,
+    position: AtomicU64,
+    started_at: Instant,
+}
+
+impl State {
+    fn position(&self) -> u64 {
+        self.position.load(Ordering::SeqCst)
+    }
+}
+
+/// Reports the progress of one phase of an operation until it is finished or dropped.
+pub struct Progress {
+    mode: ProgressMode,
+    state: Arc<State>,
+    stop_tx: Option<mpsc::Sender<()>>,
+    ticker: Option<JoinHandle<()>>,
+}
+
+impl Progress {
+    /// Start reporting progress of the phase described by `label`, which has `total` steps if known.
+    pub fn new(mode: ProgressMode, label: impl Into<String>, total: Option<u64>) -> Self {
+        let state = Arc::new(State {
+            label: label.into(),
+            total,
+            position: AtomicU64::new(0),
+            started_at: Instant::now(),
+        });
+        let interval = match mode {
+            ProgressMode::Hidden => {
+                return Self {
+                    mode,
+                    state,
+                    stop_tx: None,
+                    ticker: None,
+                }
+            }
+            ProgressMode::Bar => BAR_REFRESH_INTERVAL,
+            ProgressMode::Log => LOG_INTERVAL,
+        };
+
+        // Redraw on a timer so that long steps visibly make progress.
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let ticker = std::thread::spawn({
+            let state = state.clone();
+            move || {
+                let mut tick = 0;
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                    match mode {
+                        ProgressMode::Bar => draw_bar(&state, tick),
+                        ProgressMode::Log => log_progress(&state),
+                        ProgressMode::Hidden => {}
+                    }
+                    tick += 1;
+                }
+            }
+        });
+        Self {
+            mode,
+            state,
+            stop_tx: Some(stop_tx),
+            ticker: Some(ticker),
+        }
+    }
+
+    /// Record that `steps` more steps completed.
+    pub fn inc(&self, steps: u64) {
+        self.state.position.fetch_add(steps, Ordering::SeqCst);
+    }
+
+    /// Record that `position` steps have completed in total.
+    pub fn set_position(&self, position: u64) {
+        self.state.position.store(position, Ordering::SeqCst);
+    }
+
+    /// Mark every step as completed and report the time taken.
+    pub fn finish(mut self) {
+        if let Some(total) = self.state.total {
+            self.set_position(total);
+        }
+        self.stop();
+        match self.mode {
+            ProgressMode::Bar => {
+                draw_bar(&self.state, 0);
+                eprintln!();
+            }
+            ProgressMode::Log => info!(
+                phase = %self.state.label,
+                elapsed_secs = self.state.started_at.elapsed().as_secs(),
+                "Finished"
+            ),
+            ProgressMode::Hidden => {}
+        }
+    }
+
+    fn stop(&mut self) {
+        self.stop_tx.take();
+        if let Some(ticker) = self.ticker.take() {
+            let _ = ticker.join();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        let was_running = self.ticker.is_some();
+        self.stop();
+        if was_running && self.mode == ProgressMode::Bar {
+            // Leave the partially drawn bar on its own line.
+            eprintln!();
+        }
+    }
+}
+
+fn draw_bar(state: &State, tick: usize) {
+    let line = render_bar(
+        &state.label,
+        state.position(),
+        state.total,
+        state.started_at.elapsed(),
+        tick,
+    );
+    let mut stderr = std::io::stderr();
+    let _ = write!(stderr, "\r{}", line);
+    let _ = stderr.flush();
+}
+
+fn log_progress(state: &State) {
+    let elapsed_secs = state.started_at.elapsed().as_secs();
+    match state.total {
+        Some(total) => info!(
+            phase = %state.label,
+            position = state.position(),
+            total,
+            elapsed_secs,
+            "In progress"
+        ),
+        None => info!(
+            phase = %state.label,
+            position = state.position(),
+            elapsed_secs,
+            "In progress"
+        ),
+    }
+}
+
+/// Render a progress bar line. Phases with an unknown total show a spinner instead of a bar.
+fn render_bar(
+    label: &str,
+    position: u64,
+    total: Option<u64>,
+    elapsed: Duration,
+    tick: usize,
+) -> String {
+    let elapsed = format!("{}s", elapsed.as_secs());
+    match total {
+        Some(total) => {
+            let position = position.min(total);
+            let filled = if total == 0 {
+                BAR_WIDTH
+            } else {
+                (position as usize * BAR_WIDTH) / total as usize
+            };
+            format!(
+                "{} [{}{}] {}/{} ({})",
+                label,
+                "=".repeat(filled),
+                " ".repeat(BAR_WIDTH - filled),
+                position,
+                total,
+                elapsed
+            )
+        }
+        None => format!(
+            "{} {} {} ({})",
+            label,
+            SPINNER[tick % SPINNER.len()],
+            position,
+            elapsed
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bars_are_rendered_in_proportion() {
+        let elapsed = Duration::from_secs(42);
+        assert_eq!(
+            render_bar("Resolving", 0, Some(4), elapsed, 0),
+            format!("Resolving [{}] 0/4 (42s)", " ".repeat(BAR_WIDTH))
+        );
+        assert_eq!(
+            render_bar("Resolving", 2, Some(4), elapsed, 0),
+            format!(
+                "Resolving [{}{}] 2/4 (42s)",
+                "=".repeat(BAR_WIDTH / 2),
+                " ".repeat(BAR_WIDTH / 2)
+            )
+        );
+        assert_eq!(
+            render_bar("Resolving", 9, Some(4), elapsed, 0),
+            format!("Resolving [{}] 4/4 (42s)", "=".repeat(BAR_WIDTH))
+        );
+        assert_eq!(
+            render_bar("Checking out", 0, Some(0), elapsed, 0),
+            format!("Checking out [{}] 0/0 (42s)", "=".repeat(BAR_WIDTH))
+        );
+        assert_eq!(
+            render_bar("Checking out", 3, None, elapsed, 1),
+            "Checking out / 3 (42s)"
+        );
+    }
+
+    #[test]
+    fn progress_tracks_position() {
+        let progress = Progress::new(ProgressMode::Hidden, "Testing", Some(10));
+        progress.inc(3);
+        progress.inc(2);
+        assert_eq!(progress.state.position(), 5);
+        progress.set_position(8);
+        assert_eq!(progress.state.position(), 8);
+        progress.finish();
+    }
+}
@@ -2,9 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
-use crate::{git_helper::ConfigExt, paths};
+use crate::{files::size_of, git_helper::ConfigExt, paths};
 use chrono::{DateTime, Utc};
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 use walkdir::{DirEntry, WalkDir};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -21,6 +21,13 @@ pub struct Config {
     pub max_num_sandboxes: u32,
     /// the directory to search for sandboxes. If None, then use system TMPDIR
     pub sandbox_root: Option<PathBuf>,
+    /// Names of sandbox directories that should never be deleted. These are
+    /// left out of both the time based and the count based cleanup, so they
+    /// do not count towards max_num_sandboxes.
+    pub keep: Vec<String>,
+    /// If set, log the sandboxes that would be deleted and the space that
+    /// would be reclaimed without deleting anything.
+    pub dry_run: bool,
 }
 
 impl Default for Config {
@@ -30,6 +37,8 @@ impl Default for Config {
             preserve_hours: Self::DEFAULT_HOURS,
             max_num_sandboxes: Self::DEFAULT_MAX_NUM_SANDBOXES,
             sandbox_root: Some(paths::focus_sandbox_dir()),
+            keep: Vec::new(),
+            dry_run: false,
         }
     }
 }
@@ -37,9 +46,9 @@ impl Default for Config {
 impl Config {
     pub const DEFAULT_HOURS: u32 = 24;
     pub const DEFAULT_MAX_NUM_SANDBOXES: u32 = 500;
-    const MAX_NUM_SANDBOXES_KEY: &'static str = "focus.sandbox.maxnumsandboxes";
-    const PRESERVE_HOURS_KEY: &'static str = "focus.sandbox.preservehours";
-    const CLEANUP_KEY: &'static str = "focus.sandbox.cleanup";
+    pub const MAX_NUM_SANDBOXES_KEY: &'static str = "focus.sandbox.maxnumsandboxes";
+    pub const PRESERVE_HOURS_KEY: &'static str = "focus.sandbox.preservehours";
+    pub const CLEANUP_KEY: &'static str = "focus.sandbox.cleanup";
 
     /// Try to load the config from the global git config, falling back to defaults
     /// if the sandbox cleanup isn't configured.
@@ -90,8 +99,26 @@ impl Config {
     }
 }
 
+/// Why cleanup removes a sandbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The sandbox is older than preserve_hours.
+    Age,
+    /// The sandbox is among the oldest beyond max_num_sandboxes.
+    Count,
+}
+
+impl std::fmt::Display for RemovalReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemovalReason::Age => write!(f, "age"),
+            RemovalReason::Count => write!(f, "count"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-struct DirEnt {
+pub(super) struct DirEnt {
     pub entry: DirEntry,
     pub mtime: DateTime<Utc>,
 }
@@ -120,35 +147,24 @@ pub fn run_with_default() -> Result<()> {
     Config::try_from_git_default().and_then(|config| run(&config))
 }
 
-pub fn run(config: &Config) -> Result<()> {
-    let Config {
-        cleanup_enabled,
-        preserve_hours,
-        max_num_sandboxes,
-        sandbox_root,
-    } = config.clone();
-
-    if !cleanup_enabled {
-        return Ok(());
-    }
-
-    let sb_root = match sandbox_root {
-        Some(sandbox_root) => {
-            std::fs::create_dir_all(&sandbox_root)
-                .with_context(|| format!("creating sandbox root at {}", sandbox_root.display()))?;
-            sandbox_root
-        }
-        None => std::env::temp_dir(),
-    };
+/// The directory that sandboxes are searched for in.
+pub(super) fn sandbox_root(config: &Config) -> PathBuf {
+    config
+        .sandbox_root
+        .clone()
+        .unwrap_or_else(std::env::temp_dir)
+}
 
-    let walker = WalkDir::new(&sb_root)
+/// The sandbox directories directly under `sb_root`.
+pub(super) fn find_sandboxes(sb_root: &Path) -> Vec<DirEnt> {
+    let walker = WalkDir::new(sb_root)
         .follow_links(false)
         .max_depth(1)
         .min_depth(1)
         .same_file_system(true)
         .sort_by_file_name();
 
-    let dirents: Vec<DirEnt> = walker
+    walker
         .into_iter()
         .filter_entry(|dirent| {
             !dirent.path_is_symlink()
@@ -161,8 +177,45 @@ pub fn run(config: &Config) -> Result<()> {
         })
         .filter_map(|d| d.ok())
         .filter_map(|d| DirEnt::try_from(d).ok())
+        .collect()
+}
+
+pub fn run(config: &Config) -> Result<()> {
+    let Config {
+        cleanup_enabled,
+        preserve_hours,
+        max_num_sandboxes,
+        sandbox_root: _,
+        keep,
+        dry_run,
+    } = config.clone();
+
+    if !cleanup_enabled {
+        return Ok(());
+    }
+
+    let sb_root = sandbox_root(config);
+    std::fs::create_dir_all(&sb_root)
+        .with_context(|| format!("creating sandbox root at {}", sb_root.display()))?;
+
+    let dirents: Vec<DirEnt> = find_sandboxes(&sb_root)
+        .into_iter()
+        .filter(|d| {
+            let kept = d
+                .entry
+                .file_name()
+                .to_str()
+                .map(|name| keep.iter().any(|k| k == name))
+                .unwrap_or(false);
+            if kept {
+                debug!(dirent = ?d.entry.path(), "keeping pinned sandbox");
+            }
+            !kept
+        })
         .collect();
 
+    let mut removals: Vec<(DirEnt, RemovalReason)> = Vec::new();
+
     let (time_expired, mut unexpired): (Vec<DirEnt>, Vec<DirEnt>) = if preserve_hours == 0 {
         (vec![], dirents)
     } else {
@@ -170,9 +223,11 @@ pub fn run(config: &Config) -> Result<()> {
         dirents.into_iter().partition(|d| d.mtime < cutoff)
     };
 
-    for dirent in time_expired.into_iter() {
-        safe_delete_all(&sb_root, &dirent);
-    }
+    removals.extend(
+        time_expired
+            .into_iter()
+            .map(|dirent| (dirent, RemovalReason::Age)),
+    );
 
     // if we still have too many sandbox directories left over after expiring the
     // ones that are older than preserve_hours, we sort by time and delete the oldest
@@ -181,9 +236,37 @@ pub fn run(config: &Config) -> Result<()> {
         unexpired.sort_unstable_by_key(|d| d.mtime);
         let upper_bound = unexpired.len() - max_num_sandboxes as usize;
 
-        for dirent in unexpired.into_iter().take(upper_bound) {
-            safe_delete_all(&sb_root, &dirent)
+        removals.extend(
+            unexpired
+                .into_iter()
+                .take(upper_bound)
+                .map(|dirent| (dirent, RemovalReason::Count)),
+        );
+    }
+
+    if dry_run {
+        let now = Utc::now();
+        let mut reclaimable_bytes = 0;
+        for (dirent, reason) in removals.iter() {
+            let size = size_of(dirent.entry.path());
+            reclaimable_bytes += size;
+            info!(
+                path = ?dirent.entry.path(),
+                age_hours = (now - dirent.mtime).num_hours(),
+                %reason,
+                size,
+                "Would remove sandbox"
+            );
         }
+        info!(
+            count = removals.len(),
+            reclaimable_bytes, "Dry run: would reclaim {} bytes", reclaimable_bytes
+        );
+        return Ok(());
+    }
+
+    for (dirent, _reason) in removals.iter() {
+        safe_delete_all(&sb_root, dirent);
     }
 
     Ok(())
@@ -368,6 +451,87 @@ cleanup = 72
         Ok(())
     }
 
+    #[test]
+    fn kept_sandbox_survives_time_based_cleanup() -> Result<()> {
+        focus_testing::init_logging();
+
+        let SandboxFixture {
+            sb_root: _sb_root,
+            sandboxes,
+        } = SandboxFixture::run(Config {
+            preserve_hours: 1,
+            keep: vec![format!("{}0", DEFAULT_NAME_PREFIX)],
+            ..Default::default()
+        })?;
+
+        // sandboxes[0] is the oldest
+        assert!(
+            sandboxes[0].exists(),
+            "kept sandbox {:?} was expected to exist",
+            sandboxes[0]
+        );
+
+        for sb in sandboxes.iter().skip(1).take(5) {
+            assert!(!sb.exists(), "sandbox {:?} was expected to *not* exist", sb)
+        }
+
+        assert!(sandboxes[6].exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn kept_sandbox_survives_count_based_cleanup() -> Result<()> {
+        focus_testing::init_logging();
+
+        let SandboxFixture {
+            sb_root: _sb_root,
+            sandboxes,
+        } = SandboxFixture::run(Config {
+            preserve_hours: 0,
+            max_num_sandboxes: 3,
+            keep: vec![
+                format!("{}0", DEFAULT_NAME_PREFIX),
+                String::from("not_a_sandbox"),
+            ],
+            ..Default::default()
+        })?;
+
+        // the kept sandbox doesn't count, so the next 3 oldest are deleted
+        assert!(sandboxes[0].exists());
+
+        for sb in sandboxes.iter().skip(1).take(3) {
+            assert!(!sb.exists(), "sandbox {:?} was expected to *not* exist", sb)
+        }
+
+        for sb in sandboxes.iter().skip(4) {
+            assert!(sb.exists(), "expected path {:?} to exist", sb)
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_deletes_nothing() -> Result<()> {
+        focus_testing::init_logging();
+
+        let SandboxFixture {
+            sb_root: _sb_root,
+            sandboxes,
+        } = SandboxFixture::run(Config {
+            preserve_hours: 1,
+            max_num_sandboxes: 0,
+            dry_run: true,
+            ..Default::default()
+        })?;
+
+        for sb in sandboxes.iter() {
+            assert!(sb.exists(), "expected path {:?} to exist", sb)
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn disabled_does_nothing() -> Result<()> {
         focus_testing::init_logging();
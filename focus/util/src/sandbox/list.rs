@@ -0,0 +1,97 @@
+// Copyright 2022 Twitter, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    cleanup::{find_sandboxes, sandbox_root, Config},
+    *,
+};
+use crate::files::size_of;
+use chrono::{DateTime, Utc};
+
+/// A sandbox found on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxSummary {
+    pub path: PathBuf,
+
+    /// The prefix the sandbox was created with, which names the command that created it. None for sandboxes created without one.
+    pub name_prefix: Option<String>,
+
+    /// When the sandbox directory was last modified.
+    pub modified: DateTime<Utc>,
+
+    /// The total size of the files in the sandbox, in bytes.
+    pub size: u64,
+}
+
+/// Recover the prefix given to `Sandbox::new` from a sandbox directory name, which is the prefix surrounded by `DEFAULT_NAME_PREFIX` and a random suffix.
+fn name_prefix_of(file_name: &str) -> Option<String> {
+    let rest = file_name.strip_prefix(DEFAULT_NAME_PREFIX)?;
+    let (prefix, _random) = rest.rsplit_once('_')?;
+    Some(prefix.to_owned()).filter(|prefix| !prefix.is_empty())
+}
+
+/// List the sandboxes that `cleanup::run` would consider, oldest first.
+pub fn list(config: &Config) -> Result<Vec<SandboxSummary>> {
+    let sb_root = sandbox_root(config);
+    if !sb_root.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut sandboxes: Vec<SandboxSummary> = find_sandboxes(&sb_root)
+        .into_iter()
+        .map(|dirent| SandboxSummary {
+            path: dirent.entry.path().to_owned(),
+            name_prefix: dirent.entry.file_name().to_str().and_then(name_prefix_of),
+            modified: dirent.mtime,
+            size: size_of(dirent.entry.path()),
+        })
+        .collect();
+    sandboxes.sort_by_key(|sandbox| sandbox.modified);
+    Ok(sandboxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn name_prefixes_are_recovered() {
+        assert_eq!(
+            name_prefix_of("focus_sandbox_refs-delete_a1B2c3").as_deref(),
+            Some("refs-delete")
+        );
+        assert_eq!(name_prefix_of("focus_sandbox_a1B2c3"), None);
+        assert_eq!(name_prefix_of("something_else"), None);
+    }
+
+    #[test]
+    fn sandboxes_are_listed_oldest_first() -> Result<()> {
+        let sb_root = tempfile::tempdir()?;
+        let config = Config {
+            sandbox_root: Some(sb_root.path().to_owned()),
+            ..Default::default()
+        };
+
+        let newer = sb_root.path().join("focus_sandbox_sync_aaaaaa");
+        let older = sb_root.path().join("focus_sandbox_zzzzzz");
+        std::fs::create_dir(&newer)?;
+        std::fs::create_dir(&older)?;
+        std::fs::create_dir(sb_root.path().join("unrelated"))?;
+        std::fs::write(newer.join("cmd"), "focus sync")?;
+        filetime::set_file_mtime(&older, filetime::FileTime::from_unix_time(1_000_000, 0))?;
+
+        let sandboxes = list(&config)?;
+        assert_eq!(
+            sandboxes
+                .iter()
+                .map(|sandbox| (sandbox.path.clone(), sandbox.name_prefix.clone()))
+                .collect::<Vec<_>>(),
+            vec![(older, None), (newer, Some(String::from("sync")))]
+        );
+        assert_eq!(sandboxes[0].size, 0);
+        assert_eq!(sandboxes[1].size, "focus sync".len() as u64);
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod cleanup;
+pub mod list;
 
 use anyhow::{Context, Result};
 use std::fs;
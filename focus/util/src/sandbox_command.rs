@@ -7,13 +7,14 @@ use std::{
     ffi::OsStr,
     fs::{File, OpenOptions},
     io::{BufRead, BufReader, Write},
+    os::unix::process::CommandExt,
     path::{Path, PathBuf},
-    process::{Command, ExitStatus, Stdio},
+    process::{Child, Command, ExitStatus, Stdio},
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc, Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tracing::{debug, debug_span, error, info, info_span, warn};
 
@@ -41,8 +42,50 @@ pub struct SandboxCommand {
     stderr_path: PathBuf,
     git_trace2_path: PathBuf,
     description_path: PathBuf,
+    timeout: Option<Duration>,
 }
 
+/// How often a command with a timeout is checked for having exited.
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a command that timed out is given to clean up after `SIGTERM` before it is sent `SIGKILL`. Git removes its lock files when terminated, but not when killed.
+const TIMEOUT_KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// A command exited unsuccessfully. The standard error it wrote is kept so that callers can tell why it failed.
+#[derive(Debug, Clone)]
+pub struct CommandFailed {
+    pub description: String,
+    pub stderr: String,
+}
+
+impl std::fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Command failed: {}", self.description)
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+/// A command ran longer than its timeout and was terminated. The operation may succeed if retried.
+#[derive(Debug, Clone)]
+pub struct CommandTimedOut {
+    pub description: String,
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for CommandTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Command timed out after {}s: {}",
+            self.timeout.as_secs_f32(),
+            self.description
+        )
+    }
+}
+
+impl std::error::Error for CommandTimedOut {}
+
 #[derive(Debug)]
 pub enum SandboxCommandOutput {
     All,
@@ -123,9 +166,15 @@ impl SandboxCommand {
             stderr_path,
             git_trace2_path,
             description_path,
+            timeout: None,
         })
     }
 
+    /// Terminate the command, along with any processes it started, if it runs longer than `timeout`. So that they can be terminated together, a command with a timeout runs in a session of its own, which has no controlling terminal; it cannot prompt for input, so prompts fail rather than hang until the deadline. Git is also told not to prompt for credentials (`GIT_TERMINAL_PROMPT=0`), and SSH fails, much as in `BatchMode`, when it would need to ask for a passphrase or to confirm a host key on the terminal. Signals sent from the terminal, such as `SIGINT` from Ctrl-C, are not delivered to the command.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> Self {
+        Self { timeout, ..self }
+    }
+
     pub fn log(&self, output: SandboxCommandOutput, description: &str) -> Result<()> {
         let title = |o: SandboxCommandOutput| format!("{:?} from {}", o, description);
         let items: Vec<(String, &Path)> = match output {
@@ -233,6 +282,18 @@ impl SandboxCommand {
             .open(&self.description_path)?;
         writeln!(file, "{}", process::pretty_print_command(cmd))?;
 
+        if self.timeout.is_some() {
+            // Run the command in a session of its own, which is also its own process group, so that everything it starts can be terminated together. Without a controlling terminal, nothing it starts can prompt on the terminal and stop waiting for input.
+            cmd.env("GIT_TERMINAL_PROMPT", "0");
+            unsafe {
+                cmd.pre_exec(|| {
+                    nix::unistd::setsid()
+                        .map(|_| ())
+                        .map_err(|e| std::io::Error::from_raw_os_error(e as i32))
+                });
+            }
+        }
+
         let mut launch = cmd
             .spawn()
             .with_context(|| format!("Failed to spawn command {}", &command_description))?;
@@ -240,12 +301,25 @@ impl SandboxCommand {
         let tailer = Self::tail(&command_description, &self.stderr_path)
             .context("Could not create log tailer");
 
-        let status = launch
-            .wait()
-            .with_context(|| format!("Failed to wait for command {}", &command_description))?;
+        let status = match self.timeout {
+            Some(timeout) => Self::wait_with_timeout(&mut launch, timeout),
+            None => launch.wait().map(Some),
+        }
+        .with_context(|| format!("Failed to wait for command {}", &command_description));
         if let Ok(tailer) = tailer {
             tailer.stop();
         }
+        let status = match status? {
+            Some(status) => status,
+            None => {
+                let timeout = self.timeout.unwrap_or_default();
+                error!(command = %command_description, ?timeout, "Command timed out");
+                return Err(anyhow::Error::new(CommandTimedOut {
+                    description: command_description,
+                    timeout,
+                }));
+            }
+        };
         debug!(command = %command_description, %status, "Command exited");
 
         let exit_code = status
@@ -255,12 +329,58 @@ impl SandboxCommand {
         if !success {
             self.log(output, &command_description)
                 .context("logging output")?;
-            bail!("Command failed: {}", command_description);
+            let mut stderr = String::new();
+            if let Err(e) = self.read_to_string(SandboxCommandOutput::Stderr, &mut stderr) {
+                debug!(
+                    ?e,
+                    "Could not read the standard error of the failed command"
+                );
+            }
+            return Err(anyhow::Error::new(CommandFailed {
+                description: command_description,
+                stderr,
+            }));
         }
 
         Ok(status)
     }
 
+    /// Wait for `child` to exit, returning `None` if it had to be terminated because it ran longer than `timeout`. The child's whole process group is sent `SIGTERM`, then `SIGKILL` if it has not exited after a grace period.
+    fn wait_with_timeout(
+        child: &mut Child,
+        timeout: Duration,
+    ) -> std::io::Result<Option<ExitStatus>> {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Some(status));
+            }
+            std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+        }
+
+        let process_group = Pid::from_raw(child.id() as i32);
+        if let Err(e) = killpg(process_group, Signal::SIGTERM) {
+            warn!(?e, "Failed to terminate timed out command");
+        }
+        let kill_deadline = Instant::now() + TIMEOUT_KILL_GRACE_PERIOD;
+        while Instant::now() < kill_deadline {
+            if child.try_wait()?.is_some() {
+                // Make sure nothing the command started outlives it.
+                let _ = killpg(process_group, Signal::SIGKILL);
+                return Ok(None);
+            }
+            std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+        }
+        if let Err(e) = killpg(process_group, Signal::SIGKILL) {
+            warn!(?e, "Failed to kill timed out command");
+        }
+        child.wait()?;
+        Ok(None)
+    }
+
     fn tail(description: &str, path: &Path) -> Result<Tailer> {
         Ok(match File::options().read(true).open(path) {
             Ok(f) => Tailer::new(description, f),
@@ -359,6 +479,70 @@ mod tests {
         Ok(())
     }
 
+    /// Whether the process `pid` is running: it exists and has not exited. A process that exited but has not been reaped yet counts as not running.
+    fn is_running(pid: &str) -> Result<bool> {
+        let output = Command::new("ps")
+            .args(["-o", "stat=", "-p", pid])
+            .output()?;
+        let state = String::from_utf8(output.stdout)?;
+        let state = state.trim();
+        Ok(!state.is_empty() && !state.starts_with('Z'))
+    }
+
+    #[test]
+    fn sandboxed_command_times_out() -> Result<()> {
+        init_logging();
+
+        let app = Arc::from(App::new_for_testing()?);
+        let dir = tempfile::tempdir()?;
+        let pid_path = dir.path().join("pid");
+        let (mut cmd, scmd) = SandboxCommand::new("sh", app)?;
+        let scmd = scmd.with_timeout(Some(Duration::from_millis(500)));
+        let started_at = Instant::now();
+        let e = scmd
+            .ensure_success_or_log(
+                cmd.arg("-c").arg(format!(
+                    "sleep 30 & echo $! > {}; sleep 30",
+                    pid_path.display()
+                )),
+                SandboxCommandOutput::Stderr,
+            )
+            .unwrap_err();
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+        let timed_out = e.downcast_ref::<CommandTimedOut>().unwrap();
+        assert_eq!(timed_out.timeout, Duration::from_millis(500));
+
+        // The command started in the background was terminated along with the command.
+        let pid = std::fs::read_to_string(&pid_path)?;
+        let pid = pid.trim();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while is_running(pid)? && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert!(!is_running(pid)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sandboxed_command_with_timeout_cannot_prompt() -> Result<()> {
+        init_logging();
+
+        let app = Arc::from(App::new_for_testing()?);
+        let (mut cmd, scmd) = SandboxCommand::new("sh", app)?;
+        let scmd = scmd.with_timeout(Some(Duration::from_secs(10)));
+        scmd.ensure_success_or_log(
+            cmd.arg("-c")
+                .arg("echo \"$GIT_TERMINAL_PROMPT\"; if (: < /dev/tty) 2> /dev/null; then echo tty; else echo no tty; fi"),
+            SandboxCommandOutput::Stderr,
+        )?;
+        let mut output_string = String::new();
+        scmd.read_to_string(SandboxCommandOutput::Stdout, &mut output_string)?;
+        assert_eq!(output_string, "0\nno tty\n");
+
+        Ok(())
+    }
+
     #[test]
     fn sandboxed_command_specific_stdin() -> Result<()> {
         init_logging();
@@ -113,6 +113,39 @@ impl FocusTime {
         ))
     }
 
+    /// Parse either an RFC3339 timestamp like `2022-01-02T03:04:05-05:00` or a date accepted by `parse_date`, which is taken to mean midnight UTC.
+    pub fn parse_datetime<S: AsRef<str>>(s: S) -> Result<FocusTime> {
+        let s = s.as_ref().trim();
+        if let Ok(time) = Self::parse_from_rfc3339(s) {
+            return Ok(time);
+        }
+        Self::parse_date(s).with_context(|| {
+            format!(
+                "'{}' is neither a date like 2021-01-01 nor an RFC3339 timestamp like 2021-01-01T12:00:00Z",
+                s
+            )
+        })
+    }
+
+    /// Parse a cutoff given either as a date or timestamp accepted by `parse_datetime` or as a duration before now such as `90d`, `12w`, or `6m` (days, weeks, or 30-day months).
+    pub fn parse_cutoff<S: AsRef<str>>(s: S) -> Result<FocusTime> {
+        Self::parse_cutoff_relative_to(s, FocusTime::now())
+    }
+
+    /// Like `parse_cutoff`, but relative durations are measured back from `now`.
+    pub fn parse_cutoff_relative_to<S: AsRef<str>>(s: S, now: FocusTime) -> Result<FocusTime> {
+        let s = s.as_ref().trim();
+        match parse_relative_duration(s) {
+            Some(duration) => Ok(now - duration),
+            None => Self::parse_datetime(s).with_context(|| {
+                format!(
+                    "Cutoff '{}' is neither a date like 2021-01-01, an RFC3339 timestamp like 2021-01-01T12:00:00Z, nor a duration like 90d, 12w, or 6m",
+                    s
+                )
+            }),
+        }
+    }
+
     pub fn parse_from_rfc3339<S: AsRef<str>>(s: S) -> Result<FocusTime> {
         Ok(FocusTime(DateTime::parse_from_rfc3339(s.as_ref())?))
     }
@@ -126,6 +159,30 @@ impl FocusTime {
     }
 }
 
+/// Parse a duration like `90d`, `12w`, or `6m`. Returns `None` if `s` is not of that form.
+fn parse_relative_duration(s: &str) -> Option<Duration> {
+    let unit_index = s.len().checked_sub(1)?;
+    if !s.is_char_boundary(unit_index) {
+        return None;
+    }
+    let (count, unit) = s.split_at(unit_index);
+    if count.is_empty() || !count.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let count: i64 = count.parse().ok()?;
+    let days = match unit {
+        "d" => count,
+        "w" => count.checked_mul(7)?,
+        "m" => count.checked_mul(30)?,
+        _ => return None,
+    };
+    // Bound the value so that `Duration::days` cannot overflow.
+    if days > 1_000_000 {
+        return None;
+    }
+    Some(Duration::days(days))
+}
+
 impl From<FileTime> for FocusTime {
     fn from(ft: FileTime) -> Self {
         Self(FixedOffset::east(0).timestamp(ft.seconds(), ft.nanoseconds()))
@@ -241,6 +298,59 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_datetime() -> Result<()> {
+        assert_eq!(
+            FocusTime::parse_datetime("2022-01-02")?,
+            FocusTime::parse_date("2022-01-02")?
+        );
+        assert_eq!(
+            *FocusTime::parse_datetime("2022-01-02T03:04:05-05:00")?,
+            DateTime::parse_from_rfc3339("2022-01-02T08:04:05Z")?
+        );
+
+        let err = FocusTime::parse_datetime("yesterday").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'yesterday' is neither a date like 2021-01-01 nor an RFC3339 timestamp"),
+            "unexpected error: {}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_cutoff() -> Result<()> {
+        let now = FocusTime::parse_from_rfc3339("2022-06-30T12:00:00-00:00")?;
+        let cutoff = |s: &str| FocusTime::parse_cutoff_relative_to(s, now.clone());
+
+        assert_eq!(cutoff("2021-01-01")?, FocusTime::parse_date("2021-01-01")?);
+        assert_eq!(
+            cutoff("2021-01-01T12:30:00Z")?,
+            FocusTime::parse_from_rfc3339("2021-01-01T12:30:00Z")?
+        );
+        assert_eq!(cutoff("90d")?, now.clone() - Duration::days(90));
+        assert_eq!(cutoff("12w")?, now.clone() - Duration::days(84));
+        assert_eq!(cutoff("6m")?, now.clone() - Duration::days(180));
+        assert_eq!(cutoff("0d")?, now);
+
+        for bad in [
+            "",
+            "d",
+            "90",
+            "-5d",
+            "5y",
+            "1.5w",
+            "2021-13-01",
+            "99999999999999w",
+        ] {
+            assert!(cutoff(bad).is_err(), "{:?} should not parse", bad);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_git_time_to_date_time() -> Result<()> {
         let expected_git_offset_mins = -5 * 60;